@@ -0,0 +1,286 @@
+//! Static HTML dataset browser.
+
+use crate::{
+    dataset::{Dataset, SampleDataRef, SampleRef, SceneRef},
+    error::Result,
+    report::html_escape,
+    serializable::Modality,
+};
+use rayon::prelude::*;
+use std::{
+    fmt::Write,
+    fs,
+    path::{Component, Path, PathBuf},
+};
+
+/// The display width of camera images on sample pages, which is also
+/// the maximum width and height of thumbnails.
+pub const THUMBNAIL_SIZE: u32 = 400;
+
+type ThumbnailFn<'a> = dyn Fn(&SampleDataRef, &Path) -> Result<()> + Sync + 'a;
+
+/// The source of camera images on sample pages.
+enum CameraImages<'a> {
+    /// Full-size files under the dataset directory at the path.
+    Files(PathBuf),
+    /// Thumbnails written to `thumbnails/` by the function.
+    Thumbnails(&'a ThumbnailFn<'a>),
+}
+
+impl Dataset {
+    /// Generate a static HTML site in the output directory.
+    ///
+    /// The site consists of an `index.html` scene list, a page per
+    /// scene in `scenes/` and a page per sample in `samples/`. Camera
+    /// images are full-size previews linked to the files in the
+    /// dataset directory. See [Dataset::generate_browser_with] to
+    /// embed downscaled thumbnails instead.
+    pub fn generate_browser<P>(&self, out_dir: P) -> Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        let out_dir = out_dir.as_ref();
+        fs::create_dir_all(out_dir.join("samples"))?;
+
+        // Link to data files relative to the sample pages if possible
+        let data_root = {
+            let samples_dir = out_dir.join("samples").canonicalize()?;
+            let dataset_dir = self.dataset_dir.canonicalize()?;
+            relative_path(&samples_dir, &dataset_dir).unwrap_or(dataset_dir)
+        };
+        self.write_browser(out_dir, CameraImages::Files(data_root))
+    }
+
+    /// Generate the static HTML site with camera thumbnails in
+    /// `thumbnails/`.
+    ///
+    /// The function is called with each camera key frame and the path
+    /// to the `.jpg` thumbnail, which should fit in [THUMBNAIL_SIZE]
+    /// pixels. The image crate provides one as
+    /// `nuscenes_data_image::thumbnail::generate_browser`.
+    pub fn generate_browser_with<P, F>(&self, out_dir: P, write_thumbnail: F) -> Result<()>
+    where
+        P: AsRef<Path>,
+        F: Fn(&SampleDataRef, &Path) -> Result<()> + Sync,
+    {
+        let out_dir = out_dir.as_ref();
+        fs::create_dir_all(out_dir.join("thumbnails"))?;
+        self.write_browser(out_dir, CameraImages::Thumbnails(&write_thumbnail))
+    }
+
+    fn write_browser(&self, out_dir: &Path, images: CameraImages) -> Result<()> {
+        fs::create_dir_all(out_dir.join("scenes"))?;
+        fs::create_dir_all(out_dir.join("samples"))?;
+
+        let scenes: Vec<_> = self
+            .sorted_scene_tokens
+            .iter()
+            .filter_map(|token| self.scene(*token))
+            .collect();
+
+        fs::write(out_dir.join("index.html"), render_index(self, &scenes))?;
+
+        scenes.par_iter().try_for_each(|scene| -> Result<()> {
+            let path = out_dir.join("scenes").join(format!("{}.html", scene.token));
+            fs::write(path, render_scene(scene))?;
+
+            for sample in scene.sample_iter() {
+                let path = out_dir
+                    .join("samples")
+                    .join(format!("{}.html", sample.token));
+                fs::write(path, render_sample(&sample, out_dir, &images)?)?;
+            }
+            Ok(())
+        })?;
+
+        Ok(())
+    }
+}
+
+fn page_header(html: &mut String, title: &str) {
+    let title = html_escape(title);
+    writeln!(html, "<!DOCTYPE html>").unwrap();
+    writeln!(html, "<html>").unwrap();
+    writeln!(
+        html,
+        "<head><meta charset=\"utf-8\"><title>{title}</title></head>"
+    )
+    .unwrap();
+    writeln!(html, "<body>").unwrap();
+    writeln!(html, "<h1>{title}</h1>").unwrap();
+}
+
+fn page_footer(html: &mut String) {
+    writeln!(html, "</body>").unwrap();
+    writeln!(html, "</html>").unwrap();
+}
+
+fn render_index(dataset: &Dataset, scenes: &[SceneRef]) -> String {
+    let mut html = String::new();
+    page_header(&mut html, &format!("nuScenes {}", dataset.version));
+
+    writeln!(html, "<table>").unwrap();
+    writeln!(
+        html,
+        "<tr><th>Scene</th><th>Description</th><th>Location</th><th>Samples</th></tr>"
+    )
+    .unwrap();
+    for scene in scenes {
+        writeln!(
+            html,
+            "<tr><td><a href=\"scenes/{}.html\">{}</a></td><td>{}</td><td>{}</td><td>{}</td></tr>",
+            scene.token,
+            html_escape(&scene.name),
+            html_escape(&scene.description),
+            html_escape(&scene.log().location),
+            scene.sample_tokens.len()
+        )
+        .unwrap();
+    }
+    writeln!(html, "</table>").unwrap();
+
+    page_footer(&mut html);
+    html
+}
+
+fn render_scene(scene: &SceneRef) -> String {
+    let mut html = String::new();
+    page_header(&mut html, &scene.name);
+
+    writeln!(html, "<p><a href=\"../index.html\">Back to scenes</a></p>").unwrap();
+    writeln!(html, "<p>{}</p>", html_escape(&scene.description)).unwrap();
+
+    writeln!(html, "<table>").unwrap();
+    writeln!(
+        html,
+        "<tr><th>#</th><th>Sample</th><th>Timestamp</th><th>Annotations</th></tr>"
+    )
+    .unwrap();
+    for (index, sample) in scene.sample_iter().enumerate() {
+        writeln!(
+            html,
+            "<tr><td>{index}</td><td><a href=\"../samples/{token}.html\">{token}</a></td><td>{}</td><td>{}</td></tr>",
+            sample.timestamp,
+            sample.annotation_tokens.len(),
+            token = sample.token,
+        )
+        .unwrap();
+    }
+    writeln!(html, "</table>").unwrap();
+
+    page_footer(&mut html);
+    html
+}
+
+fn render_sample(sample: &SampleRef, out_dir: &Path, images: &CameraImages) -> Result<String> {
+    let scene = sample.scene();
+    let mut html = String::new();
+    page_header(&mut html, &format!("Sample {}", sample.token));
+
+    // Navigation links
+    write!(
+        html,
+        "<p><a href=\"../scenes/{}.html\">{}</a>",
+        scene.token,
+        html_escape(&scene.name)
+    )
+    .unwrap();
+    if let Some(prev) = sample.prev {
+        write!(html, " | <a href=\"{prev}.html\">prev</a>").unwrap();
+    }
+    if let Some(next) = sample.next {
+        write!(html, " | <a href=\"{next}.html\">next</a>").unwrap();
+    }
+    writeln!(html, "</p>").unwrap();
+    writeln!(html, "<p>Timestamp: {}</p>", sample.timestamp).unwrap();
+
+    // Camera images
+    let mut cameras: Vec<_> = sample
+        .keyframe_data_iter()
+        .filter_map(|data| {
+            let sensor = data.calibrated_sensor().sensor();
+            (sensor.modality == Modality::Camera).then_some((sensor.channel, data))
+        })
+        .collect();
+    cameras.sort_by_key(|(channel, _)| channel.as_str());
+
+    writeln!(html, "<h2>Cameras</h2>").unwrap();
+    for (channel, data) in &cameras {
+        let src = match images {
+            CameraImages::Files(data_root) => data_root.join(&data.filename),
+            CameraImages::Thumbnails(write_thumbnail) => {
+                let file_name = format!("{}.jpg", data.token);
+                write_thumbnail(data, &out_dir.join("thumbnails").join(&file_name))?;
+                Path::new("../thumbnails").join(file_name)
+            }
+        };
+        writeln!(
+            html,
+            "<figure style=\"display:inline-block\"><img src=\"{}\" width=\"{THUMBNAIL_SIZE}\"><figcaption>{channel}</figcaption></figure>",
+            html_escape(&src.to_string_lossy())
+        )
+        .unwrap();
+    }
+
+    // Annotation table
+    writeln!(html, "<h2>Annotations</h2>").unwrap();
+    writeln!(html, "<table>").unwrap();
+    writeln!(
+        html,
+        "<tr><th>Token</th><th>Category</th><th>Attributes</th><th>Translation</th><th>Size</th><th>Lidar points</th><th>Radar points</th><th>Visibility</th></tr>"
+    )
+    .unwrap();
+    for annotation in sample.annotation_iter() {
+        let category = annotation.instance().category().name.clone();
+        let attributes: Vec<_> = annotation
+            .attribute_iter()
+            .map(|attribute| attribute.name.clone())
+            .collect();
+        let visibility = annotation
+            .visibility()
+            .map(|visibility| visibility.description.clone())
+            .unwrap_or_default();
+        let [x, y, z] = annotation.translation;
+        let [w, l, h] = annotation.size;
+
+        writeln!(
+            html,
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{x:.2}, {y:.2}, {z:.2}</td><td>{w:.2}, {l:.2}, {h:.2}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+            annotation.token,
+            html_escape(&category),
+            html_escape(&attributes.join(", ")),
+            annotation.num_lidar_pts,
+            annotation.num_radar_pts,
+            html_escape(&visibility),
+        )
+        .unwrap();
+    }
+    writeln!(html, "</table>").unwrap();
+
+    page_footer(&mut html);
+    Ok(html)
+}
+
+/// Compute the path to `to` relative to the `from` directory. Both
+/// paths must be absolute.
+fn relative_path(from: &Path, to: &Path) -> Option<PathBuf> {
+    let from: Vec<_> = from.components().collect();
+    let to: Vec<_> = to.components().collect();
+
+    // The paths must share the same root
+    if from.first() != to.first() {
+        return None;
+    }
+
+    let common = from
+        .iter()
+        .zip(&to)
+        .take_while(|(lhs, rhs)| lhs == rhs)
+        .count();
+
+    let path = (common..from.len())
+        .map(|_| Component::ParentDir)
+        .chain(to[common..].iter().cloned())
+        .collect();
+    Some(path)
+}
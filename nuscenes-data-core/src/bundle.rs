@@ -0,0 +1,199 @@
+//! Assembly of multi-modal model inputs of a sample.
+//!
+//! [SampleRef::bundle] resolves the requested modalities of a sample
+//! and decodes them in parallel. Camera and radar files are decoded
+//! by the loaders registered in [data_loader](crate::data_loader), so
+//! the payload types depend on the enabled extension crates. Lidar
+//! points are aggregated over sweeps in the sensor frame of the key
+//! frame, and boxes are expressed in the global frame.
+
+use crate::{
+    data_loader::Payload,
+    dataset::{SampleDataRef, SampleRef},
+    error::Result,
+    features::{FeatureLayout, PointFeatures},
+    geometry::Box3D,
+    serializable::{Channel, Token},
+};
+use rayon::prelude::*;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BundleConfig {
+    /// The camera channels to decode.
+    pub cameras: Vec<Channel>,
+    /// The lidar points to aggregate.
+    pub lidar: Option<LidarBundleConfig>,
+    /// The radar channels to decode.
+    pub radars: Vec<Channel>,
+    /// Include annotation boxes.
+    pub boxes: bool,
+    /// The side length in meters of the map region centered at the
+    /// ego position. The map is not included if it is `None`.
+    pub map_crop_size: Option<f64>,
+}
+
+impl Default for BundleConfig {
+    /// All cameras and radars, top lidar points with 10 sweeps and
+    /// boxes, without the map.
+    fn default() -> Self {
+        Self {
+            cameras: vec![
+                Channel::CamFront,
+                Channel::CamFrontRight,
+                Channel::CamBackRight,
+                Channel::CamBack,
+                Channel::CamBackLeft,
+                Channel::CamFrontLeft,
+            ],
+            lidar: Some(LidarBundleConfig {
+                channel: Channel::LidarTop,
+                layout: FeatureLayout::xyzi_time(),
+                max_sweeps: 10,
+            }),
+            radars: vec![
+                Channel::RadarFront,
+                Channel::RadarFrontLeft,
+                Channel::RadarFrontRight,
+                Channel::RadarBackLeft,
+                Channel::RadarBackRight,
+            ],
+            boxes: true,
+            map_crop_size: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LidarBundleConfig {
+    pub channel: Channel,
+    pub layout: FeatureLayout,
+    /// The maximum number of sweeps aggregated into the key frame.
+    pub max_sweeps: usize,
+}
+
+/// A decoded key frame file.
+#[derive(Debug)]
+pub struct DecodedData {
+    pub channel: Channel,
+    pub sample_data_token: Token,
+    pub payload: Payload,
+}
+
+/// An annotation box in the global frame.
+#[derive(Debug, Clone)]
+pub struct BundleBox {
+    pub annotation_token: Token,
+    pub instance_token: Token,
+    pub category: String,
+    pub bbox: Box3D,
+}
+
+/// The map region around the ego vehicle. The map mask is decoded by
+/// extension crates.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MapCrop {
+    pub map_token: Token,
+    /// The ego position in the global frame.
+    pub center: [f64; 2],
+    /// The side length in meters.
+    pub size: f64,
+}
+
+/// The decoded inputs of a sample. Requested channels missing in the
+/// sample are omitted.
+#[derive(Debug)]
+pub struct SampleBundle {
+    pub sample_token: Token,
+    pub cameras: Vec<DecodedData>,
+    /// The lidar points in the sensor frame of the key frame.
+    pub lidar: Option<PointFeatures>,
+    pub radars: Vec<DecodedData>,
+    pub boxes: Vec<BundleBox>,
+    pub map_crop: Option<MapCrop>,
+}
+
+impl SampleRef {
+    /// Decode the requested modalities of the sample in parallel.
+    pub fn bundle(&self, config: &BundleConfig) -> Result<SampleBundle> {
+        let key_frames: Vec<SampleDataRef> = self.keyframe_data_iter().collect();
+        let find = |channels: &[Channel]| -> Vec<SampleDataRef> {
+            channels
+                .iter()
+                .filter_map(|&channel| {
+                    key_frames
+                        .iter()
+                        .find(|data| data.channel() == channel)
+                        .cloned()
+                })
+                .collect()
+        };
+        let cameras = find(&config.cameras);
+        let radars = find(&config.radars);
+
+        let (decoded, lidar) = rayon::join(
+            || -> Result<Vec<DecodedData>> {
+                cameras
+                    .par_iter()
+                    .chain(radars.par_iter())
+                    .map(|data| {
+                        Ok(DecodedData {
+                            channel: data.channel(),
+                            sample_data_token: data.token,
+                            payload: data.load_any()?,
+                        })
+                    })
+                    .collect()
+            },
+            || -> Result<Option<PointFeatures>> {
+                let Some(lidar) = &config.lidar else {
+                    return Ok(None);
+                };
+                let Some(group) = self.sweep_group(lidar.channel) else {
+                    return Ok(None);
+                };
+                Ok(Some(
+                    group.load_lidar_features(&lidar.layout, lidar.max_sweeps)?,
+                ))
+            },
+        );
+        let mut decoded = decoded?;
+        let radars = decoded.split_off(cameras.len());
+
+        let boxes = if config.boxes {
+            self.annotation_iter()
+                .map(|annotation| BundleBox {
+                    annotation_token: annotation.token,
+                    instance_token: annotation.instance_token,
+                    category: annotation.instance().category().name.clone(),
+                    bbox: Box3D::from(&*annotation),
+                })
+                .collect()
+        } else {
+            vec![]
+        };
+
+        let map_crop = config.map_crop_size.and_then(|size| {
+            let map = self.scene().log().map()?;
+            let data = key_frames
+                .iter()
+                .find(|data| data.is_lidar())
+                .or(key_frames.first())?;
+            let ego_to_global = data.ego_to_global();
+            let [x, y, _] = ego_to_global.translation;
+            Some(MapCrop {
+                map_token: map.token,
+                center: [x, y],
+                size,
+            })
+        });
+
+        Ok(SampleBundle {
+            sample_token: self.token,
+            cameras: decoded,
+            lidar: lidar?,
+            radars,
+            boxes,
+            map_crop,
+        })
+    }
+}
@@ -0,0 +1,465 @@
+//! Caching of artifacts derived from datasets.
+//!
+//! Exporters and preprocessing steps, such as ground truth databases,
+//! projected 2D boxes or aggregated sweeps, store their outputs in a
+//! [DerivedCache]. Each entry is a directory keyed by the artifact
+//! name, the artifact version and the [Fingerprint] of the dataset:
+//!
+//! ```text
+//! <root>/<artifact>/v<version>-<fingerprint>/
+//!     manifest.json
+//!     ...
+//! ```
+//!
+//! Artifacts derived with options, such as export specs, are also
+//! keyed by a hash of the options given to [ArtifactKey::with_params],
+//! and stored in `v<version>-<params>-<fingerprint>` instead.
+//!
+//! An entry is invalidated by changing the dataset, which changes the
+//! fingerprint, or by bumping the version of the [ArtifactKey] when
+//! the output format or the algorithm changes. Entries are built in a
+//! temporary directory and renamed into place when complete, so that
+//! interrupted builds never appear as valid entries.
+
+use crate::{
+    dataset::Dataset,
+    error::{Error, Result},
+    utils::splitmix64_bytes,
+};
+use chrono::NaiveDateTime;
+use rayon::prelude::*;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fmt::{self, Display, Formatter},
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+    process,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// The name of the cache directory under the dataset directory used by
+/// [DerivedCache::in_dataset_dir].
+pub const DEFAULT_CACHE_DIR_NAME: &str = ".nuscenes-cache";
+
+/// The file describing a complete cache entry.
+pub const MANIFEST_FILE_NAME: &str = "manifest.json";
+
+/// Bumped whenever the fingerprint computation changes.
+const FINGERPRINT_FORMAT: u32 = 2;
+
+/// The file of values stored by [DerivedCache::load_or_compute_json].
+const JSON_DATA_FILE_NAME: &str = "data.json";
+
+const TEMP_PREFIX: &str = ".tmp-";
+
+/// A digest of the records and load options of a dataset. It changes
+/// if any record is added, removed or modified.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Fingerprint(pub u64);
+
+impl Display for Fingerprint {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+        write!(formatter, "{:016x}", self.0)
+    }
+}
+
+/// The name and version of a kind of derived artifact.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ArtifactKey {
+    /// The name, which consists of lowercase ASCII letters, digits,
+    /// `_` and `-`.
+    pub name: &'static str,
+    /// The version of the artifact format, which should be bumped
+    /// whenever the output changes for the same dataset.
+    pub version: u32,
+    /// The hash of the options the artifact is derived with, if any.
+    pub params: Option<u64>,
+}
+
+impl ArtifactKey {
+    pub const fn new(name: &'static str, version: u32) -> Self {
+        let bytes = name.as_bytes();
+        assert!(!bytes.is_empty(), "artifact names must not be empty");
+        let mut index = 0;
+        while index < bytes.len() {
+            let byte = bytes[index];
+            assert!(
+                byte.is_ascii_lowercase() || byte.is_ascii_digit() || byte == b'_' || byte == b'-',
+                "artifact names must consist of lowercase letters, digits, '_' and '-'"
+            );
+            index += 1;
+        }
+        Self {
+            name,
+            version,
+            params: None,
+        }
+    }
+
+    /// Key the artifact by the options it is derived with as well, so
+    /// that outputs of different options are cached apart.
+    pub fn with_params<P>(mut self, params: &P) -> Self
+    where
+        P: Serialize,
+    {
+        let bytes = serde_json::to_vec(params).expect("parameters are serializable");
+        self.params = Some(splitmix64_bytes(0, &bytes));
+        self
+    }
+}
+
+/// The description of a complete cache entry.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CacheManifest {
+    pub artifact: String,
+    pub version: u32,
+    /// The hash of the options of [ArtifactKey::params].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub params: Option<String>,
+    pub fingerprint: String,
+    /// The version name of the dataset.
+    pub dataset_version: String,
+    /// The UTC time the entry is created.
+    pub created_at: NaiveDateTime,
+}
+
+/// A complete entry of a cache.
+#[derive(Debug, Clone)]
+pub struct CacheEntry {
+    pub path: PathBuf,
+    pub manifest: CacheManifest,
+}
+
+/// A directory of artifacts derived from a dataset.
+#[derive(Debug, Clone)]
+pub struct DerivedCache {
+    root: PathBuf,
+    fingerprint: Fingerprint,
+    dataset_version: String,
+}
+
+impl DerivedCache {
+    /// Create a cache of the dataset under the root directory. The
+    /// fingerprint of the dataset is computed once here. The root may
+    /// be shared by several datasets.
+    pub fn new<P>(dataset: &Dataset, root: P) -> Self
+    where
+        P: AsRef<Path>,
+    {
+        Self {
+            root: root.as_ref().to_owned(),
+            fingerprint: dataset.fingerprint(),
+            dataset_version: dataset.version.clone(),
+        }
+    }
+
+    /// Create a cache under [DEFAULT_CACHE_DIR_NAME] in the dataset
+    /// directory.
+    pub fn in_dataset_dir(dataset: &Dataset) -> Self {
+        Self::new(dataset, dataset.dataset_dir.join(DEFAULT_CACHE_DIR_NAME))
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn fingerprint(&self) -> Fingerprint {
+        self.fingerprint
+    }
+
+    /// Get the directory of the entry, which may not exist.
+    pub fn entry_path(&self, key: ArtifactKey) -> PathBuf {
+        self.root.join(key.name).join(self.entry_name(key))
+    }
+
+    /// Get the directory of the entry if it is complete.
+    pub fn get(&self, key: ArtifactKey) -> Option<PathBuf> {
+        let path = self.entry_path(key);
+        path.join(MANIFEST_FILE_NAME).is_file().then_some(path)
+    }
+
+    /// Get the directory of the entry, or build it if it is missing.
+    /// `build` writes the artifact into the given empty directory.
+    /// Entries of older versions of the artifact for the same dataset
+    /// are removed after the build.
+    pub fn get_or_build<F>(&self, key: ArtifactKey, build: F) -> Result<PathBuf>
+    where
+        F: FnOnce(&Path) -> Result<()>,
+    {
+        if let Some(path) = self.get(key) {
+            return Ok(path);
+        }
+
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let artifact_dir = self.root.join(key.name);
+        let temp_dir = artifact_dir.join(format!(
+            "{TEMP_PREFIX}{}-{}-{}",
+            self.entry_name(key),
+            process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::create_dir_all(&temp_dir)?;
+
+        let result = build(&temp_dir).and_then(|()| {
+            let manifest = CacheManifest {
+                artifact: key.name.to_string(),
+                version: key.version,
+                params: key.params.map(|params| format!("{params:016x}")),
+                fingerprint: self.fingerprint.to_string(),
+                dataset_version: self.dataset_version.clone(),
+                created_at: chrono::Utc::now().naive_utc(),
+            };
+            write_json(&temp_dir.join(MANIFEST_FILE_NAME), &manifest)
+        });
+        if let Err(err) = result {
+            let _ = fs::remove_dir_all(&temp_dir);
+            return Err(err);
+        }
+
+        let path = self.entry_path(key);
+        if fs::rename(&temp_dir, &path).is_err() {
+            // Another build of the same entry finished first
+            fs::remove_dir_all(&temp_dir)?;
+            if self.get(key).is_none() {
+                return Err(Error::CorruptedFile(path));
+            }
+        }
+
+        for entry in self.entries()? {
+            let CacheManifest {
+                artifact,
+                version,
+                fingerprint,
+                ..
+            } = &entry.manifest;
+            if *artifact == key.name
+                && *version < key.version
+                && *fingerprint == self.fingerprint.to_string()
+            {
+                fs::remove_dir_all(&entry.path)?;
+            }
+        }
+
+        Ok(path)
+    }
+
+    /// Load a value stored in the entry as JSON, or compute and store
+    /// it if the entry is missing.
+    pub fn load_or_compute_json<T, F>(&self, key: ArtifactKey, compute: F) -> Result<T>
+    where
+        T: Serialize + DeserializeOwned,
+        F: FnOnce() -> Result<T>,
+    {
+        if let Some(path) = self.get(key) {
+            return read_json(&path.join(JSON_DATA_FILE_NAME));
+        }
+
+        let mut computed = None;
+        self.get_or_build(key, |dir| {
+            let value = compute()?;
+            write_json(&dir.join(JSON_DATA_FILE_NAME), &value)?;
+            computed = Some(value);
+            Ok(())
+        })?;
+
+        match computed {
+            Some(value) => Ok(value),
+            // The entry was built concurrently by someone else
+            None => read_json(&self.entry_path(key).join(JSON_DATA_FILE_NAME)),
+        }
+    }
+
+    /// Remove the entry of the dataset. It returns `false` if the
+    /// entry does not exist.
+    pub fn invalidate(&self, key: ArtifactKey) -> Result<bool> {
+        let path = self.entry_path(key);
+        if !path.exists() {
+            return Ok(false);
+        }
+        fs::remove_dir_all(path)?;
+        Ok(true)
+    }
+
+    /// List the complete entries under the root, including those of
+    /// other datasets.
+    pub fn entries(&self) -> Result<Vec<CacheEntry>> {
+        let mut entries = vec![];
+        for artifact_dir in read_dirs(&self.root)? {
+            for path in read_dirs(&artifact_dir)? {
+                let manifest_path = path.join(MANIFEST_FILE_NAME);
+                if !manifest_path.is_file() {
+                    continue;
+                }
+                let manifest = read_json(&manifest_path)?;
+                entries.push(CacheEntry { path, manifest });
+            }
+        }
+        entries.sort_by(|lhs, rhs| lhs.path.cmp(&rhs.path));
+        Ok(entries)
+    }
+
+    /// Remove entries whose fingerprint differs from this dataset, such
+    /// as those of modified datasets or of other datasets sharing the
+    /// root, and leftovers of interrupted builds. It returns the number
+    /// of removed directories.
+    pub fn prune(&self) -> Result<usize> {
+        let fingerprint = self.fingerprint.to_string();
+        let mut count = 0;
+        for artifact_dir in read_dirs(&self.root)? {
+            for path in read_dirs(&artifact_dir)? {
+                let is_temp = path
+                    .file_name()
+                    .and_then(|name| name.to_str())
+                    .is_some_and(|name| name.starts_with(TEMP_PREFIX));
+                let manifest_path = path.join(MANIFEST_FILE_NAME);
+                let is_stale = manifest_path.is_file()
+                    && read_json::<CacheManifest>(&manifest_path)?.fingerprint != fingerprint;
+                if is_temp || is_stale {
+                    fs::remove_dir_all(&path)?;
+                    count += 1;
+                }
+            }
+        }
+        Ok(count)
+    }
+
+    fn entry_name(&self, key: ArtifactKey) -> String {
+        match key.params {
+            Some(params) => format!("v{}-{params:016x}-{}", key.version, self.fingerprint),
+            None => format!("v{}-{}", key.version, self.fingerprint),
+        }
+    }
+}
+
+impl Dataset {
+    /// Compute the fingerprint of the records and of the load options
+    /// changing them, such as time offsets. It serializes every record,
+    /// so it is best computed once, as [DerivedCache] does.
+    pub fn fingerprint(&self) -> Fingerprint {
+        let table_hashes: Vec<u64> = (0..NUM_FINGERPRINT_TABLES)
+            .into_par_iter()
+            .map(|table| match table {
+                0 => hash_table(&self.attribute_map),
+                1 => hash_table(&self.calibrated_sensor_map),
+                2 => hash_table(&self.category_map),
+                3 => hash_table(&self.ego_pose_map),
+                4 => hash_table_with(&self.instance_map, |instance| instance.to_instance()),
+                5 => hash_table(&self.log_map),
+                6 => hash_table(&self.map_map),
+                7 => hash_table_with(&self.sample_map, |sample| sample.to_sample()),
+                8 => hash_table(&self.sample_annotation_map),
+                9 => hash_table(&self.sample_data_map),
+                10 => hash_table_with(&self.scene_map, |scene| scene.to_scene()),
+                11 => hash_table(&self.sensor_map),
+                12 => hash_table(&self.visibility_map),
+                13 => hash_table(&self.panoptic_map),
+                _ => unreachable!(),
+            })
+            .collect();
+
+        let mut time_offsets: Vec<(String, i64)> = self
+            .time_offsets
+            .iter()
+            .map(|(channel, offset)| {
+                let channel = serde_json::to_string(channel).unwrap();
+                (channel, offset.num_microseconds().unwrap_or(i64::MAX))
+            })
+            .collect();
+        time_offsets.sort();
+
+        let mut bytes = FINGERPRINT_FORMAT.to_le_bytes().to_vec();
+        serde_json::to_writer(
+            &mut bytes,
+            &(&self.versions, self.drop_sweeps, time_offsets),
+        )
+        .unwrap();
+        for hash in table_hashes {
+            bytes.extend_from_slice(&hash.to_le_bytes());
+        }
+        Fingerprint(splitmix64_bytes(0, &bytes))
+    }
+}
+
+/// The number of tables hashed by [Dataset::fingerprint].
+const NUM_FINGERPRINT_TABLES: usize = 14;
+
+/// Hash the records of a table in the order of their tokens.
+fn hash_table<K, V>(map: &HashMap<K, V>) -> u64
+where
+    K: Ord,
+    V: Serialize,
+{
+    hash_sorted(map, |buffer, value| serde_json::to_writer(buffer, value))
+}
+
+/// Hash the records converted from the indexed values of a table in
+/// the order of their tokens.
+fn hash_table_with<K, V, R, F>(map: &HashMap<K, V>, to_record: F) -> u64
+where
+    K: Ord,
+    R: Serialize,
+    F: Fn(&V) -> R,
+{
+    hash_sorted(map, |buffer, value| {
+        serde_json::to_writer(buffer, &to_record(value))
+    })
+}
+
+/// Hash the serialized records one by one with [splitmix64_bytes],
+/// which is stable across platforms and compiler versions unlike the
+/// hashers of the standard library.
+fn hash_sorted<K, V, F>(map: &HashMap<K, V>, mut write: F) -> u64
+where
+    K: Ord,
+    F: FnMut(&mut Vec<u8>, &V) -> serde_json::Result<()>,
+{
+    let mut records: Vec<(&K, &V)> = map.iter().collect();
+    records.sort_unstable_by(|lhs, rhs| lhs.0.cmp(rhs.0));
+
+    let mut buffer = vec![];
+    let mut hash = 0;
+    for (_, value) in records {
+        buffer.clear();
+        write(&mut buffer, value).expect("records are serializable");
+        hash = splitmix64_bytes(hash, &buffer);
+    }
+    hash
+}
+
+/// List the subdirectories, which are empty if the directory does not
+/// exist.
+fn read_dirs(dir: &Path) -> Result<Vec<PathBuf>> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(err) => return Err(err.into()),
+    };
+    let mut dirs = vec![];
+    for entry in entries {
+        let entry = entry?;
+        if entry.file_type()?.is_dir() {
+            dirs.push(entry.path());
+        }
+    }
+    Ok(dirs)
+}
+
+pub(crate) fn write_json<T>(path: &Path, value: &T) -> Result<()>
+where
+    T: Serialize,
+{
+    let mut writer = BufWriter::new(File::create(path)?);
+    serde_json::to_writer(&mut writer, value).map_err(io::Error::from)?;
+    writer.flush()?;
+    Ok(())
+}
+
+pub(crate) fn read_json<T>(path: &Path) -> Result<T>
+where
+    T: DeserializeOwned,
+{
+    let reader = BufReader::new(File::open(path)?);
+    serde_json::from_reader(reader).map_err(|_| Error::CorruptedFile(path.to_owned()))
+}
@@ -0,0 +1,215 @@
+//! Calibration drift analysis across logs.
+//!
+//! Calibrated sensors of the same vehicle and channel are compared
+//! in the order of log capture dates. Drifts are measured against the
+//! earliest calibration.
+
+use crate::{
+    camera::CameraIntrinsic,
+    dataset::Dataset,
+    geometry::{quat_conj, quat_mul, Transform},
+    serializable::{Channel, Token},
+};
+use chrono::NaiveDate;
+use itertools::Itertools;
+use std::{
+    collections::{BTreeMap, HashSet},
+    fmt::Write,
+};
+
+/// The calibration of a sensor in a log.
+#[derive(Debug, Clone)]
+pub struct CalibrationEntry {
+    pub log_token: Token,
+    pub logfile: Option<String>,
+    pub date_captured: NaiveDate,
+    pub calibrated_sensor_token: Token,
+    /// The transform from the sensor frame to the ego frame.
+    pub extrinsic: Transform,
+    pub intrinsic: Option<CameraIntrinsic>,
+    /// The translation difference in meters from the earliest
+    /// calibration.
+    pub translation_drift: f64,
+    /// The rotation angle in radians from the earliest calibration.
+    pub rotation_drift: f64,
+    /// The largest difference of focal lengths in pixels from the
+    /// earliest calibration.
+    pub focal_drift: Option<f64>,
+    /// The principal point distance in pixels from the earliest
+    /// calibration.
+    pub principal_point_drift: Option<f64>,
+}
+
+/// The calibration history of a channel on a vehicle.
+#[derive(Debug, Clone)]
+pub struct ChannelDrift {
+    pub vehicle: String,
+    pub channel: Channel,
+    /// Calibrations sorted by capture dates.
+    pub entries: Vec<CalibrationEntry>,
+}
+
+impl ChannelDrift {
+    pub fn max_translation_drift(&self) -> f64 {
+        self.entries
+            .iter()
+            .map(|entry| entry.translation_drift)
+            .fold(0.0, f64::max)
+    }
+
+    pub fn max_rotation_drift(&self) -> f64 {
+        self.entries
+            .iter()
+            .map(|entry| entry.rotation_drift)
+            .fold(0.0, f64::max)
+    }
+
+    pub fn max_focal_drift(&self) -> Option<f64> {
+        self.entries
+            .iter()
+            .filter_map(|entry| entry.focal_drift)
+            .reduce(f64::max)
+    }
+
+    pub fn max_principal_point_drift(&self) -> Option<f64> {
+        self.entries
+            .iter()
+            .filter_map(|entry| entry.principal_point_drift)
+            .reduce(f64::max)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CalibrationDriftReport {
+    /// Drifts sorted by vehicles and channels.
+    pub channels: Vec<ChannelDrift>,
+}
+
+impl CalibrationDriftReport {
+    /// Get the channels drifting more than the thresholds in meters
+    /// and radians.
+    pub fn exceeding(
+        &self,
+        translation_threshold: f64,
+        rotation_threshold: f64,
+    ) -> impl Iterator<Item = &ChannelDrift> + '_ {
+        self.channels.iter().filter(move |drift| {
+            drift.max_translation_drift() > translation_threshold
+                || drift.max_rotation_drift() > rotation_threshold
+        })
+    }
+
+    /// Render the report as CSV with one row per calibration.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from(
+            "vehicle,channel,date_captured,log_token,calibrated_sensor_token,\
+             translation_drift,rotation_drift,focal_drift,principal_point_drift\n",
+        );
+
+        let format_opt = |value: Option<f64>| value.map(|v| v.to_string()).unwrap_or_default();
+        for drift in &self.channels {
+            for entry in &drift.entries {
+                writeln!(
+                    csv,
+                    "{},{},{},{},{},{},{},{},{}",
+                    drift.vehicle,
+                    drift.channel,
+                    entry.date_captured,
+                    entry.log_token,
+                    entry.calibrated_sensor_token,
+                    entry.translation_drift,
+                    entry.rotation_drift,
+                    format_opt(entry.focal_drift),
+                    format_opt(entry.principal_point_drift),
+                )
+                .unwrap();
+            }
+        }
+        csv
+    }
+}
+
+impl Dataset {
+    /// Compare calibrated sensors of the same vehicle and channel
+    /// across logs.
+    pub fn calibration_drift_report(&self) -> CalibrationDriftReport {
+        // Collect calibrated sensors used in each log
+        let mut pairs: HashSet<(Token, Token)> = HashSet::new();
+        for scene in self.scene_iter() {
+            let Some(sample) = scene.sample_iter().next() else {
+                continue;
+            };
+            for data in sample.sample_data_iter() {
+                pairs.insert((scene.log_token, data.calibrated_sensor_token));
+            }
+        }
+
+        let mut groups: BTreeMap<(String, &'static str), (Channel, Vec<CalibrationEntry>)> =
+            BTreeMap::new();
+        for (log_token, calibrated_sensor_token) in pairs {
+            let log = self.log(log_token).unwrap();
+            let calibrated_sensor = self.calibrated_sensor(calibrated_sensor_token).unwrap();
+            let channel = calibrated_sensor.sensor().channel;
+
+            let entry = CalibrationEntry {
+                log_token,
+                logfile: log.logfile.as_ref().map(|path| path.display().to_string()),
+                date_captured: log.date_captured,
+                calibrated_sensor_token,
+                extrinsic: Transform::new(
+                    calibrated_sensor.rotation,
+                    calibrated_sensor.translation,
+                ),
+                intrinsic: calibrated_sensor.intrinsic(),
+                translation_drift: 0.0,
+                rotation_drift: 0.0,
+                focal_drift: None,
+                principal_point_drift: None,
+            };
+            groups
+                .entry((log.vehicle.clone(), channel.as_str()))
+                .or_insert_with(|| (channel, vec![]))
+                .1
+                .push(entry);
+        }
+
+        let channels = groups
+            .into_iter()
+            .map(|((vehicle, _), (channel, entries))| {
+                let mut entries: Vec<_> = entries
+                    .into_iter()
+                    .sorted_by_key(|entry| (entry.date_captured, entry.logfile.clone()))
+                    .collect();
+                let reference = entries[0].clone();
+                entries
+                    .iter_mut()
+                    .for_each(|entry| compute_drift(entry, &reference));
+
+                ChannelDrift {
+                    vehicle,
+                    channel,
+                    entries,
+                }
+            })
+            .collect();
+
+        CalibrationDriftReport { channels }
+    }
+}
+
+fn compute_drift(entry: &mut CalibrationEntry, reference: &CalibrationEntry) {
+    let [x1, y1, z1] = entry.extrinsic.translation;
+    let [x2, y2, z2] = reference.extrinsic.translation;
+    entry.translation_drift = ((x1 - x2).powi(2) + (y1 - y2).powi(2) + (z1 - z2).powi(2)).sqrt();
+
+    let [w, ..] = quat_mul(
+        entry.extrinsic.rotation,
+        quat_conj(reference.extrinsic.rotation),
+    );
+    entry.rotation_drift = 2.0 * w.abs().min(1.0).acos();
+
+    if let (Some(lhs), Some(rhs)) = (entry.intrinsic, reference.intrinsic) {
+        entry.focal_drift = Some((lhs.fx - rhs.fx).abs().max((lhs.fy - rhs.fy).abs()));
+        entry.principal_point_drift = Some((lhs.cx - rhs.cx).hypot(lhs.cy - rhs.cy));
+    }
+}
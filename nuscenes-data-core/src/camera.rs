@@ -0,0 +1,93 @@
+//! Pinhole camera intrinsics.
+
+use crate::serializable::CalibratedSensor;
+use serde::{Deserialize, Serialize};
+
+/// Intrinsic parameters of a pinhole camera in pixels.
+///
+/// They correspond to the matrix
+///
+/// ```text
+/// | fx  skew  cx |
+/// |  0   fy   cy |
+/// |  0    0    1 |
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct CameraIntrinsic {
+    pub fx: f64,
+    pub fy: f64,
+    pub cx: f64,
+    pub cy: f64,
+    pub skew: f64,
+}
+
+impl CameraIntrinsic {
+    /// Read the parameters from a row-major intrinsic matrix.
+    pub fn from_matrix(matrix: &[[f64; 3]; 3]) -> Self {
+        Self {
+            fx: matrix[0][0],
+            fy: matrix[1][1],
+            cx: matrix[0][2],
+            cy: matrix[1][2],
+            skew: matrix[0][1],
+        }
+    }
+
+    /// Get the row-major intrinsic matrix.
+    pub fn to_matrix(&self) -> [[f64; 3]; 3] {
+        [
+            [self.fx, self.skew, self.cx],
+            [0.0, self.fy, self.cy],
+            [0.0, 0.0, 1.0],
+        ]
+    }
+
+    /// Project a point in the camera frame to pixel coordinates. It
+    /// returns `None` if the point is not in front of the camera.
+    pub fn project(&self, point: [f64; 3]) -> Option<[f64; 2]> {
+        let [x, y, z] = point;
+        if z <= 0.0 {
+            return None;
+        }
+        let u = (self.fx * x + self.skew * y) / z + self.cx;
+        let v = self.fy * y / z + self.cy;
+        Some([u, v])
+    }
+
+    /// Get the point in the camera frame at the pixel and the depth.
+    pub fn unproject(&self, pixel: [f64; 2], depth: f64) -> [f64; 3] {
+        let [u, v] = pixel;
+        let y = (v - self.cy) / self.fy;
+        let x = (u - self.cx - self.skew * y) / self.fx;
+        [x * depth, y * depth, depth]
+    }
+
+    /// Get the horizontal field of view in radians for an image of
+    /// the width in pixels.
+    pub fn horizontal_fov(&self, width: u32) -> f64 {
+        let width = width as f64;
+        (self.cx / self.fx).atan() + ((width - self.cx) / self.fx).atan()
+    }
+
+    /// Get the vertical field of view in radians for an image of the
+    /// height in pixels.
+    pub fn vertical_fov(&self, height: u32) -> f64 {
+        let height = height as f64;
+        (self.cy / self.fy).atan() + ((height - self.cy) / self.fy).atan()
+    }
+}
+
+impl From<&[[f64; 3]; 3]> for CameraIntrinsic {
+    fn from(matrix: &[[f64; 3]; 3]) -> Self {
+        Self::from_matrix(matrix)
+    }
+}
+
+impl CalibratedSensor {
+    /// Get the typed intrinsics, which only exist for cameras.
+    pub fn intrinsic(&self) -> Option<CameraIntrinsic> {
+        self.camera_intrinsic
+            .as_ref()
+            .map(CameraIntrinsic::from_matrix)
+    }
+}
@@ -0,0 +1,234 @@
+//! The CAN bus expansion.
+//!
+//! The expansion stores the vehicle messages of each scene in
+//! `can_bus/<scene_name>_<message>.json` files under the dataset
+//! directory. Each file is a list of messages stamped by the `utime`
+//! field in microseconds. [SceneRef::can_bus] loads the messages into
+//! time-indexed series.
+
+use crate::{
+    dataset::SceneRef,
+    error::{Error, Result},
+    odometry::OdometrySample,
+    serializable::serde_utils,
+    storage::read_with_options,
+};
+use chrono::NaiveDateTime;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{
+    io,
+    ops::{Bound, RangeBounds},
+};
+
+/// The name of the expansion directory under the dataset directory.
+pub const CAN_BUS_DIR: &str = "can_bus";
+
+/// Messages sorted by timestamps.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimeSeries<T> {
+    timestamps: Vec<NaiveDateTime>,
+    values: Vec<T>,
+}
+
+impl<T> TimeSeries<T> {
+    pub fn new(mut entries: Vec<(NaiveDateTime, T)>) -> Self {
+        entries.sort_by_key(|(timestamp, _)| *timestamp);
+        let (timestamps, values) = entries.into_iter().unzip();
+        Self { timestamps, values }
+    }
+
+    pub fn len(&self) -> usize {
+        self.timestamps.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.timestamps.is_empty()
+    }
+
+    pub fn timestamps(&self) -> &[NaiveDateTime] {
+        &self.timestamps
+    }
+
+    pub fn values(&self) -> &[T] {
+        &self.values
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (NaiveDateTime, &T)> + '_ {
+        self.timestamps.iter().copied().zip(&self.values)
+    }
+
+    /// Get the message closest in time to the timestamp.
+    pub fn nearest(&self, timestamp: NaiveDateTime) -> Option<(NaiveDateTime, &T)> {
+        let index = self.timestamps.partition_point(|&time| time < timestamp);
+        let candidates = [index.checked_sub(1), Some(index)];
+        let index = candidates
+            .into_iter()
+            .flatten()
+            .filter(|&index| index < self.len())
+            .min_by_key(|&index| (self.timestamps[index] - timestamp).abs())?;
+        Some((self.timestamps[index], &self.values[index]))
+    }
+
+    /// Iterate over the messages within the time range.
+    pub fn range<R>(&self, range: R) -> impl Iterator<Item = (NaiveDateTime, &T)> + '_
+    where
+        R: RangeBounds<NaiveDateTime> + 'static,
+    {
+        let start = match range.start_bound() {
+            Bound::Included(start) => self.timestamps.partition_point(|time| time < start),
+            Bound::Excluded(start) => self.timestamps.partition_point(|time| time <= start),
+            Bound::Unbounded => 0,
+        };
+        self.iter()
+            .skip(start)
+            .take_while(move |(time, _)| range.contains(time))
+    }
+}
+
+impl<T> Default for TimeSeries<T> {
+    fn default() -> Self {
+        Self {
+            timestamps: vec![],
+            values: vec![],
+        }
+    }
+}
+
+/// The `pose` message at 50Hz.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct CanBusPose {
+    /// The position in meters relative to the start of the log.
+    pub pos: [f64; 3],
+    /// The orientation quaternion in w, x, y, z order.
+    pub orientation: [f64; 4],
+    /// The velocity in m/s in the ego frame.
+    pub vel: [f64; 3],
+    /// The acceleration in m/s² in the ego frame.
+    pub accel: [f64; 3],
+    /// The angular velocity in rad/s in the ego frame.
+    pub rotation_rate: [f64; 3],
+}
+
+/// The `steeranglefeedback` message at 100Hz.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SteerAngleFeedback {
+    /// The steering angle in radians.
+    pub value: f64,
+}
+
+/// The `vehicle_monitor` message at 2Hz.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct VehicleMonitor {
+    pub available_distance: f64,
+    pub battery_level: f64,
+    pub brake: f64,
+    pub brake_switch: f64,
+    pub gear_position: f64,
+    pub left_signal: f64,
+    pub rear_left_rpm: f64,
+    pub rear_right_rpm: f64,
+    pub right_signal: f64,
+    /// The steering angle in degrees.
+    pub steering: f64,
+    pub steering_speed: f64,
+    pub throttle: f64,
+    /// The speed in km/h.
+    pub vehicle_speed: f64,
+    /// The yaw rate in degrees per second.
+    pub yaw_rate: f64,
+}
+
+/// The `zoesensors` message at 794Hz.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ZoeSensors {
+    pub brake_sensor: f64,
+    pub steering_sensor: f64,
+    pub throttle_sensor: f64,
+}
+
+/// The CAN bus messages of a scene. Messages missing in the expansion
+/// are left empty.
+#[derive(Debug, Clone, Default)]
+pub struct SceneCanBus {
+    pub pose: TimeSeries<CanBusPose>,
+    pub steer_angle_feedback: TimeSeries<SteerAngleFeedback>,
+    pub vehicle_monitor: TimeSeries<VehicleMonitor>,
+    pub zoe_sensors: TimeSeries<ZoeSensors>,
+}
+
+impl SceneCanBus {
+    /// Convert the speeds of pose messages into odometry samples for
+    /// [SceneRef::check_ego_odometry].
+    pub fn odometry(&self) -> Vec<OdometrySample> {
+        self.pose
+            .iter()
+            .map(|(timestamp, pose)| {
+                let [vx, vy, vz] = pose.vel;
+                OdometrySample {
+                    timestamp,
+                    speed: (vx * vx + vy * vy + vz * vz).sqrt(),
+                }
+            })
+            .collect()
+    }
+}
+
+#[derive(Deserialize)]
+struct Stamped<T> {
+    #[serde(with = "serde_utils::timestamp")]
+    utime: NaiveDateTime,
+    #[serde(flatten)]
+    value: T,
+}
+
+impl SceneRef {
+    /// Load the CAN bus messages of the scene. It returns `None` if
+    /// the expansion has no messages of the scene, which is the case
+    /// for a few scenes with corrupted recordings.
+    pub fn can_bus(&self) -> Result<Option<SceneCanBus>> {
+        let pose = self.load_can_bus_messages("pose")?;
+        let steer_angle_feedback = self.load_can_bus_messages("steeranglefeedback")?;
+        let vehicle_monitor = self.load_can_bus_messages("vehicle_monitor")?;
+        let zoe_sensors = self.load_can_bus_messages("zoesensors")?;
+
+        if pose.is_none()
+            && steer_angle_feedback.is_none()
+            && vehicle_monitor.is_none()
+            && zoe_sensors.is_none()
+        {
+            return Ok(None);
+        }
+
+        Ok(Some(SceneCanBus {
+            pose: pose.unwrap_or_default(),
+            steer_angle_feedback: steer_angle_feedback.unwrap_or_default(),
+            vehicle_monitor: vehicle_monitor.unwrap_or_default(),
+            zoe_sensors: zoe_sensors.unwrap_or_default(),
+        }))
+    }
+
+    fn load_can_bus_messages<T>(&self, message: &str) -> Result<Option<TimeSeries<T>>>
+    where
+        T: DeserializeOwned,
+    {
+        let dataset = self.dataset();
+        let path = dataset
+            .dataset_dir
+            .join(CAN_BUS_DIR)
+            .join(format!("{}_{message}.json", self.name));
+        let bytes = match read_with_options(&dataset.storage, &path, &dataset.read_options) {
+            Ok(bytes) => bytes,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+
+        let messages: Vec<Stamped<T>> = serde_json::from_slice(&bytes).map_err(|err| {
+            Error::ParseError(format!("failed to load {}: {err}", path.display()))
+        })?;
+        let entries = messages
+            .into_iter()
+            .map(|message| (message.utime, message.value))
+            .collect();
+        Ok(Some(TimeSeries::new(entries)))
+    }
+}
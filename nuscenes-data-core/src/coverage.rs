@@ -0,0 +1,241 @@
+//! Ego-centric polar occupancy of lidar returns and annotations.
+//!
+//! Lidar returns and annotation centers of a sample are counted on a
+//! polar grid around the ego vehicle at the top lidar sample data.
+//! Summed over many samples, the histograms show where the sensor sees
+//! and where objects are annotated, which can be compared between
+//! locations to study domain gaps.
+
+use crate::{
+    dataset::{Dataset, SampleRef},
+    error::Result,
+    serializable::{Channel, Token},
+};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, f64::consts::PI};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PolarGridConfig {
+    /// Positions beyond this distance in meters from the ego origin
+    /// are ignored.
+    pub max_distance: f64,
+    /// The number of equal distance bins over `[0, max_distance)`.
+    pub distance_bins: usize,
+    /// The number of equal azimuth bins over `[-π, π)`.
+    pub azimuth_bins: usize,
+}
+
+impl Default for PolarGridConfig {
+    fn default() -> Self {
+        Self {
+            max_distance: 80.0,
+            distance_bins: 16,
+            azimuth_bins: 36,
+        }
+    }
+}
+
+/// Counts on a polar grid in the ego frame. Cells are stored by
+/// distance bin, then by azimuth bin. The azimuth is measured
+/// counterclockwise from the forward x-axis.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PolarHistogram {
+    pub config: PolarGridConfig,
+    pub counts: Vec<u32>,
+}
+
+impl PolarHistogram {
+    pub fn new(config: PolarGridConfig) -> Self {
+        assert!(
+            config.max_distance > 0.0 && config.max_distance.is_finite(),
+            "max_distance must be positive and finite"
+        );
+        assert!(config.distance_bins > 0, "distance_bins must be positive");
+        assert!(config.azimuth_bins > 0, "azimuth_bins must be positive");
+        let len = config.distance_bins * config.azimuth_bins;
+        Self {
+            config,
+            counts: vec![0; len],
+        }
+    }
+
+    pub fn get(&self, distance_bin: usize, azimuth_bin: usize) -> Option<u32> {
+        let PolarGridConfig {
+            distance_bins,
+            azimuth_bins,
+            ..
+        } = self.config;
+        (distance_bin < distance_bins && azimuth_bin < azimuth_bins)
+            .then(|| self.counts[distance_bin * azimuth_bins + azimuth_bin])
+    }
+
+    /// Get the bins `(distance_bin, azimuth_bin)` of a position in the
+    /// ego frame.
+    pub fn bin_of(&self, x: f64, y: f64) -> Option<(usize, usize)> {
+        let PolarGridConfig {
+            max_distance,
+            distance_bins,
+            azimuth_bins,
+        } = self.config;
+        let distance = x.hypot(y);
+        if distance.is_nan() || distance >= max_distance {
+            return None;
+        }
+        let distance_bin = (distance / max_distance * distance_bins as f64) as usize;
+        let ratio = (y.atan2(x) + PI) / (2.0 * PI);
+        let azimuth_bin = ((ratio * azimuth_bins as f64) as usize).min(azimuth_bins - 1);
+        Some((distance_bin.min(distance_bins - 1), azimuth_bin))
+    }
+
+    /// Count a position in the ego frame. Positions beyond the maximum
+    /// distance are ignored.
+    pub fn add(&mut self, x: f64, y: f64) {
+        if let Some((distance_bin, azimuth_bin)) = self.bin_of(x, y) {
+            self.counts[distance_bin * self.config.azimuth_bins + azimuth_bin] += 1;
+        }
+    }
+
+    /// Add the counts of another histogram on the same grid.
+    pub fn merge(&mut self, other: &PolarHistogram) {
+        assert_eq!(self.config, other.config, "the grids must be the same");
+        self.counts
+            .iter_mut()
+            .zip(&other.counts)
+            .for_each(|(count, other)| *count += other);
+    }
+
+    pub fn total(&self) -> u64 {
+        self.counts.iter().map(|&count| count as u64).sum()
+    }
+
+    /// Get the fraction of cells with at least one count.
+    pub fn occupancy(&self) -> f64 {
+        let occupied = self.counts.iter().filter(|&&count| count > 0).count();
+        occupied as f64 / self.counts.len().max(1) as f64
+    }
+
+    /// Get the counts divided by the total, which sum to one unless
+    /// the histogram is empty.
+    pub fn normalized(&self) -> Vec<f64> {
+        let total = self.total().max(1) as f64;
+        self.counts
+            .iter()
+            .map(|&count| count as f64 / total)
+            .collect()
+    }
+
+    /// Get the counts of each distance bin.
+    pub fn rows(&self) -> Vec<Vec<u32>> {
+        self.counts
+            .chunks(self.config.azimuth_bins.max(1))
+            .map(|row| row.to_vec())
+            .collect()
+    }
+}
+
+/// The polar histograms of one or more samples.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PolarOccupancy {
+    /// The number of accumulated samples.
+    pub num_samples: usize,
+    pub lidar: PolarHistogram,
+    pub annotations: PolarHistogram,
+}
+
+impl PolarOccupancy {
+    pub fn new(config: PolarGridConfig) -> Self {
+        Self {
+            num_samples: 0,
+            lidar: PolarHistogram::new(config.clone()),
+            annotations: PolarHistogram::new(config),
+        }
+    }
+
+    /// Accumulate the histograms of another sample or set of samples.
+    pub fn merge(&mut self, other: &PolarOccupancy) {
+        self.num_samples += other.num_samples;
+        self.lidar.merge(&other.lidar);
+        self.annotations.merge(&other.annotations);
+    }
+}
+
+impl SampleRef {
+    /// Count the top lidar returns and the annotation centers of the
+    /// sample around the ego vehicle. It returns `None` if the sample
+    /// has no top lidar data.
+    pub fn polar_occupancy(&self, config: &PolarGridConfig) -> Result<Option<PolarOccupancy>> {
+        let Some(data) = self.sample_data_by_channel(Channel::LidarTop) else {
+            return Ok(None);
+        };
+        let mut occupancy = PolarOccupancy::new(config.clone());
+        occupancy.num_samples = 1;
+
+        let sensor_to_ego = data.sensor_to_ego();
+        for point in data.load_lidar_bin_points()?.iter() {
+            let [x, y, _] =
+                sensor_to_ego.apply([point[0] as f64, point[1] as f64, point[2] as f64]);
+            occupancy.lidar.add(x, y);
+        }
+
+        let global_to_ego = data.ego_to_global().inverse();
+        for annotation in self.annotation_iter() {
+            let [x, y, _] = global_to_ego.apply(annotation.translation);
+            occupancy.annotations.add(x, y);
+        }
+
+        Ok(Some(occupancy))
+    }
+}
+
+impl Dataset {
+    /// Accumulate the polar occupancy of the samples by the locations
+    /// of their scenes. Samples without top lidar data are skipped.
+    pub fn polar_occupancy_by_location<I>(
+        &self,
+        samples: I,
+        config: &PolarGridConfig,
+    ) -> Result<BTreeMap<String, PolarOccupancy>>
+    where
+        I: IntoIterator<Item = SampleRef>,
+    {
+        let samples: Vec<SampleRef> = samples.into_iter().collect();
+        let per_sample: Vec<(String, PolarOccupancy)> = samples
+            .par_iter()
+            .filter_map(|sample| {
+                let occupancy = sample.polar_occupancy(config).transpose()?;
+                let location = sample.scene().log().location.clone();
+                Some(occupancy.map(|occupancy| (location, occupancy)))
+            })
+            .collect::<Result<_>>()?;
+
+        let mut locations: BTreeMap<String, PolarOccupancy> = BTreeMap::new();
+        for (location, occupancy) in per_sample {
+            locations
+                .entry(location)
+                .or_insert_with(|| PolarOccupancy::new(config.clone()))
+                .merge(&occupancy);
+        }
+        Ok(locations)
+    }
+
+    /// Get the polar occupancy of each sample by sample token. Samples
+    /// without top lidar data are absent.
+    pub fn polar_occupancy_by_sample<I>(
+        &self,
+        samples: I,
+        config: &PolarGridConfig,
+    ) -> Result<BTreeMap<Token, PolarOccupancy>>
+    where
+        I: IntoIterator<Item = SampleRef>,
+    {
+        let samples: Vec<SampleRef> = samples.into_iter().collect();
+        samples
+            .par_iter()
+            .filter_map(|sample| {
+                let occupancy = sample.polar_occupancy(config).transpose()?;
+                Some(occupancy.map(|occupancy| (sample.token, occupancy)))
+            })
+            .collect()
+    }
+}
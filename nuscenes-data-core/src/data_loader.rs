@@ -0,0 +1,150 @@
+//! Pluggable loaders of sample data files.
+//!
+//! Extension crates implement [SampleDataLoader] and register it with
+//! [register_loader]. Then [SampleDataRef::load_any] dispatches to the
+//! most recently registered loader supporting the sample data.
+//!
+//! ```ignore
+//! nuscenes_data_image::register_loaders();
+//!
+//! let payload = sample_data.load_any()?;
+//! if let Some(image) = payload.downcast_ref::<image::DynamicImage>() {
+//!     /* omit */
+//! }
+//! ```
+//!
+//! The core crate registers a loader of lidar `.bin` files producing
+//! [PointCloud].
+
+use crate::{
+    dataset::SampleDataRef,
+    error::{Result, UnsupportedFormat},
+    pointcloud::PointCloud,
+    serializable::{FileFormat, SampleData},
+};
+use std::{
+    any::Any,
+    fmt::{self, Debug},
+    sync::{Arc, OnceLock, RwLock},
+};
+
+/// A loader decoding sample data files of certain formats.
+pub trait SampleDataLoader: Send + Sync {
+    /// The name to identify the loader.
+    fn name(&self) -> &str;
+
+    /// Check if the loader is able to decode the sample data.
+    fn supports(&self, data: &SampleData) -> bool;
+
+    fn load(&self, data: &SampleDataRef) -> Result<Payload>;
+}
+
+/// The decoded content of a sample data file. The concrete type
+/// depends on the loader.
+pub struct Payload {
+    loader: String,
+    value: Box<dyn Any + Send>,
+}
+
+impl Payload {
+    pub fn new<T>(loader: impl Into<String>, value: T) -> Self
+    where
+        T: Any + Send,
+    {
+        Self {
+            loader: loader.into(),
+            value: Box::new(value),
+        }
+    }
+
+    /// Get the name of the loader producing the payload.
+    pub fn loader(&self) -> &str {
+        &self.loader
+    }
+
+    pub fn is<T: Any>(&self) -> bool {
+        self.value.is::<T>()
+    }
+
+    pub fn downcast_ref<T: Any>(&self) -> Option<&T> {
+        self.value.downcast_ref()
+    }
+
+    /// Take the content if it has type `T`. Otherwise, the payload is
+    /// returned back.
+    pub fn downcast<T: Any>(self) -> std::result::Result<T, Self> {
+        let Self { loader, value } = self;
+        match value.downcast() {
+            Ok(value) => Ok(*value),
+            Err(value) => Err(Self { loader, value }),
+        }
+    }
+}
+
+impl Debug for Payload {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Payload")
+            .field("loader", &self.loader)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Register a loader. It takes precedence over previously registered
+/// loaders supporting the same sample data.
+pub fn register_loader<L>(loader: L)
+where
+    L: SampleDataLoader + 'static,
+{
+    registry().write().unwrap().push(Arc::new(loader));
+}
+
+/// Get the registered loaders in the order of registration.
+pub fn registered_loaders() -> Vec<Arc<dyn SampleDataLoader>> {
+    registry().read().unwrap().clone()
+}
+
+/// Find the loader for the sample data.
+pub fn find_loader(data: &SampleData) -> Option<Arc<dyn SampleDataLoader>> {
+    registry()
+        .read()
+        .unwrap()
+        .iter()
+        .rev()
+        .find(|loader| loader.supports(data))
+        .cloned()
+}
+
+fn registry() -> &'static RwLock<Vec<Arc<dyn SampleDataLoader>>> {
+    static REGISTRY: OnceLock<RwLock<Vec<Arc<dyn SampleDataLoader>>>> = OnceLock::new();
+    REGISTRY.get_or_init(|| RwLock::new(vec![Arc::new(LidarBinLoader)]))
+}
+
+/// Load lidar `.bin` files as [PointCloud].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LidarBinLoader;
+
+impl SampleDataLoader for LidarBinLoader {
+    fn name(&self) -> &str {
+        "lidar-bin"
+    }
+
+    fn supports(&self, data: &SampleData) -> bool {
+        data.fileformat == FileFormat::Pcd
+            && data.filename.extension().is_some_and(|ext| ext == "bin")
+    }
+
+    fn load(&self, data: &SampleDataRef) -> Result<Payload> {
+        let points = PointCloud::from_lidar_bin_bytes(&data.read_bytes()?)?;
+        Ok(Payload::new(self.name(), points))
+    }
+}
+
+impl SampleDataRef {
+    /// Load the file with the registered loader supporting it.
+    pub fn load_any(&self) -> Result<Payload> {
+        let loader = find_loader(self).ok_or_else(|| {
+            UnsupportedFormat::of_sample_data(self, "a format with a registered loader")
+        })?;
+        loader.load(self)
+    }
+}
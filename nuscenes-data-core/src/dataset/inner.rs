@@ -1,34 +1,77 @@
 use crate::{
     error::{Error, Result},
+    loader::{LoadWarnings, RecordFields, RecordHook},
     serializable::{
-        Attribute, CalibratedSensor, Category, EgoPose, Instance, Log, Map, Sample,
-        SampleAnnotation, SampleData, Scene, Sensor, Token, Visibility, VisibilityToken,
+        Attribute, CalibratedSensor, Category, Channel, EgoPose, Instance, Log, Map, Modality,
+        Panoptic, Sample, SampleAnnotation, SampleData, Scene, Sensor, Token, Visibility,
+        VisibilityToken,
     },
+    storage::{ReadOptions, Storage},
+};
+use chrono::{Duration, NaiveDate, NaiveDateTime};
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::PathBuf,
+    sync::Arc,
 };
-use chrono::NaiveDateTime;
-use std::{collections::HashMap, path::PathBuf};
 
 #[derive(Debug, Clone)]
 pub struct DatasetInner {
+    /// The version name, or the names of all versions joined by `+`
+    /// if multiple versions are loaded.
     pub version: String,
+    pub versions: Vec<String>,
+    /// The index into `versions` of each scene.
+    pub scene_versions: Arc<HashMap<Token, usize>>,
     pub dataset_dir: PathBuf,
-    pub attribute_map: HashMap<Token, Attribute>,
-    pub calibrated_sensor_map: HashMap<Token, CalibratedSensor>,
-    pub category_map: HashMap<Token, Category>,
-    pub ego_pose_map: HashMap<Token, EgoPose>,
-    pub instance_map: HashMap<Token, InstanceInner>,
-    pub log_map: HashMap<Token, Log>,
-    pub map_map: HashMap<Token, Map>,
-    pub scene_map: HashMap<Token, SceneInner>,
-    pub sample_map: HashMap<Token, SampleInner>,
-    pub sample_annotation_map: HashMap<Token, SampleAnnotation>,
-    pub sample_data_map: HashMap<Token, SampleData>,
-    pub sensor_map: HashMap<Token, Sensor>,
-    pub visibility_map: HashMap<VisibilityToken, Visibility>,
+    pub storage: Arc<dyn Storage>,
+    pub read_options: ReadOptions,
+    /// The time offsets applied to sample data timestamps.
+    pub time_offsets: HashMap<Channel, Duration>,
+    /// Whether the sample data of sweeps are dropped.
+    pub drop_sweeps: bool,
+    /// The hooks run on records at load time.
+    pub record_hooks: Vec<RecordHook>,
+    /// The fields attached to records by the hooks.
+    pub record_fields: Arc<HashMap<Token, RecordFields>>,
+    pub attribute_map: Arc<HashMap<Token, Attribute>>,
+    pub calibrated_sensor_map: Arc<HashMap<Token, CalibratedSensor>>,
+    pub category_map: Arc<HashMap<Token, Category>>,
+    pub ego_pose_map: Arc<HashMap<Token, EgoPose>>,
+    pub instance_map: Arc<HashMap<Token, InstanceInner>>,
+    pub log_map: Arc<HashMap<Token, Log>>,
+    pub map_map: Arc<HashMap<Token, Map>>,
+    pub scene_map: Arc<HashMap<Token, SceneInner>>,
+    pub sample_map: Arc<HashMap<Token, SampleInner>>,
+    pub sample_annotation_map: Arc<HashMap<Token, SampleAnnotation>>,
+    pub sample_data_map: Arc<HashMap<Token, SampleData>>,
+    pub sensor_map: Arc<HashMap<Token, Sensor>>,
+    pub visibility_map: Arc<HashMap<VisibilityToken, Visibility>>,
+    /// The panoptic records, which are empty if `panoptic.json` does
+    /// not exist.
+    pub panoptic_map: Arc<HashMap<Token, Panoptic>>,
+    /// The channel and modality of each calibrated sensor.
+    pub calibrated_sensor_channel_map: Arc<HashMap<Token, (Channel, Modality)>>,
+    /// The scene tokens of each scene name in the order of versions.
+    /// Merged versions may contain different scenes of the same name.
+    pub scene_name_map: Arc<HashMap<String, Vec<Token>>>,
+    /// The scene tokens of each log sorted by time.
+    pub log_scene_map: Arc<HashMap<Token, Vec<Token>>>,
+    /// The sample data tokens of each scene, including sweeps, sorted
+    /// by timestamp.
+    pub scene_sample_data_map: Arc<HashMap<Token, Vec<Token>>>,
+    /// The log tokens of each capture date.
+    pub log_date_map: Arc<BTreeMap<NaiveDate, Vec<Token>>>,
+    /// The log tokens of each vehicle sorted by capture date.
+    pub log_vehicle_map: Arc<HashMap<String, Vec<Token>>>,
+    /// The panoptic token of each sample data.
+    pub sample_data_panoptic_map: Arc<HashMap<Token, Token>>,
     pub sorted_ego_pose_tokens: Vec<Token>,
     pub sorted_sample_tokens: Vec<Token>,
     pub sorted_sample_data_tokens: Vec<Token>,
     pub sorted_scene_tokens: Vec<Token>,
+    /// The records dropped by lenient loading.
+    pub load_warnings: Arc<LoadWarnings>,
 }
 
 #[derive(Debug, Clone)]
@@ -66,6 +109,17 @@ impl SampleInner {
             sample_data_tokens,
         }
     }
+
+    /// Convert back to the serialized record.
+    pub fn to_sample(&self) -> Sample {
+        Sample {
+            token: self.token,
+            next: self.next,
+            prev: self.prev,
+            scene_token: self.scene_token,
+            timestamp: self.timestamp,
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -129,6 +183,17 @@ impl InstanceInner {
         };
         Ok(ret)
     }
+
+    /// Convert back to the serialized record.
+    pub fn to_instance(&self) -> Instance {
+        Instance {
+            token: self.token,
+            nbr_annotations: self.annotation_tokens.len(),
+            category_token: self.category_token,
+            first_annotation_token: self.annotation_tokens[0],
+            last_annotation_token: *self.annotation_tokens.last().unwrap(),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -193,4 +258,17 @@ impl SceneInner {
         };
         Ok(ret)
     }
+
+    /// Convert back to the serialized record.
+    pub fn to_scene(&self) -> Scene {
+        Scene {
+            token: self.token,
+            name: self.name.clone(),
+            description: self.description.clone(),
+            log_token: self.log_token,
+            nbr_samples: self.sample_tokens.len(),
+            first_sample_token: self.sample_tokens[0],
+            last_sample_token: *self.sample_tokens.last().unwrap(),
+        }
+    }
 }
@@ -1,5 +1,7 @@
 mod inner;
 mod types;
+mod window;
 
 pub use inner::*;
 pub use types::*;
+pub use window::*;
@@ -0,0 +1,865 @@
+use super::inner::{DatasetInner, InstanceInner, SampleInner, SceneInner};
+use crate::{
+    error::Result,
+    loader::{self, LoadWarnings, RecordFields},
+    serializable::{
+        Attribute, CalibratedSensor, Category, Channel, EgoPose, Log, Map, Modality, Panoptic,
+        SampleAnnotation, SampleData, Sensor, Visibility, VisibilityToken,
+    },
+    storage::{read_data_with_options, DataPath, ReadOptions},
+    table::Table,
+    DatasetLoader, Token,
+};
+use chrono::{NaiveDate, NaiveDateTime};
+use ownref::ArcRefC;
+use std::{
+    io,
+    ops::{Deref, Range, RangeBounds},
+    path::Path,
+};
+
+type ARef<T> = ArcRefC<'static, DatasetInner, T>;
+
+macro_rules! make_ref {
+    ($name:ident, $ty:ty) => {
+        #[derive(Clone)]
+        pub struct $name {
+            #[allow(dead_code)]
+            owner: ARef<DatasetInner>,
+            ref_: ARef<$ty>,
+        }
+
+        impl $name {
+            #[allow(dead_code)]
+            fn new(owner: ARef<DatasetInner>, ref_: ARef<$ty>) -> Self {
+                Self { owner, ref_ }
+            }
+
+            pub fn dataset(&self) -> Dataset {
+                Dataset::new(self.owner.clone(), self.owner.clone())
+            }
+        }
+
+        impl Deref for $name {
+            type Target = $ty;
+
+            fn deref(&self) -> &Self::Target {
+                self.ref_.deref()
+            }
+        }
+    };
+}
+
+make_ref!(Dataset, DatasetInner);
+make_ref!(AttributeRef, Attribute);
+make_ref!(CalibratedSensorRef, CalibratedSensor);
+make_ref!(CategoryRef, Category);
+make_ref!(EgoPoseRef, EgoPose);
+make_ref!(InstanceRef, InstanceInner);
+make_ref!(LogRef, Log);
+make_ref!(MapRef, Map);
+make_ref!(SceneRef, SceneInner);
+make_ref!(SampleRef, SampleInner);
+make_ref!(SampleAnnotationRef, SampleAnnotation);
+make_ref!(SampleDataRef, SampleData);
+make_ref!(SensorRef, Sensor);
+make_ref!(VisibilityRef, Visibility);
+
+impl Dataset {
+    pub(crate) fn from_inner(inner: DatasetInner) -> Self {
+        let owner = ARef::new(inner);
+        Self {
+            owner: owner.clone(),
+            ref_: owner.clone(),
+        }
+    }
+
+    pub fn load<P>(version: &str, dataset_dir: P) -> Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        DatasetLoader::default().load(version, dataset_dir)
+    }
+
+    /// Load multiple versions into one dataset. See
+    /// [DatasetLoader::load_many].
+    pub fn load_many<P>(versions: &[&str], dataset_dir: P) -> Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        DatasetLoader::default().load_many(versions, dataset_dir)
+    }
+
+    /// Load a version without blocking the async executor. See
+    /// [DatasetLoader::load_async].
+    #[cfg(feature = "async")]
+    pub async fn load_async<P>(version: &str, dataset_dir: P) -> Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        DatasetLoader::default()
+            .load_async(version, dataset_dir)
+            .await
+    }
+
+    /// Get the names of loaded versions.
+    pub fn versions(&self) -> &[String] {
+        &self.owner.versions
+    }
+
+    /// Get the fields attached to the record by the
+    /// [RecordHook](crate::loader::RecordHook)s at load time.
+    pub fn record_fields(&self, token: Token) -> Option<&RecordFields> {
+        self.owner.record_fields.get(&token)
+    }
+
+    /// Get the records dropped by
+    /// [DatasetLoader::lenient](crate::loader::DatasetLoader::lenient)
+    /// loading, which is empty otherwise.
+    pub fn load_warnings(&self) -> &LoadWarnings {
+        &self.owner.load_warnings
+    }
+
+    /// Get the version defining the scene, log, sample, sample data,
+    /// annotation or instance with the token.
+    pub fn version_of(&self, token: Token) -> Option<&str> {
+        let owner = &self.owner;
+        let scene_token = if owner.scene_map.contains_key(&token) {
+            token
+        } else if let Some(sample) = owner.sample_map.get(&token) {
+            sample.scene_token
+        } else if let Some(data) = owner.sample_data_map.get(&token) {
+            owner.sample_map[&data.sample_token].scene_token
+        } else if let Some(annotation) = owner.sample_annotation_map.get(&token) {
+            owner.sample_map[&annotation.sample_token].scene_token
+        } else if let Some(instance) = owner.instance_map.get(&token) {
+            let annotation = &owner.sample_annotation_map[&instance.annotation_tokens[0]];
+            owner.sample_map[&annotation.sample_token].scene_token
+        } else if owner.log_map.contains_key(&token) {
+            owner
+                .scene_map
+                .values()
+                .find(|scene| scene.log_token == token)?
+                .token
+        } else {
+            return None;
+        };
+
+        let index = *owner.scene_versions.get(&scene_token)?;
+        Some(&owner.versions[index])
+    }
+
+    /// Re-read the table through the dataset storage and rebuild only
+    /// the indexes depending on it.
+    ///
+    /// Existing references keep pointing to the records before the
+    /// reload. The reference and chain checks of [DatasetLoader] are
+    /// performed, and the dataset is left unchanged if they fail.
+    pub fn reload_table(&mut self, table: Table) -> Result<()> {
+        self.reload_tables(&[table])
+    }
+
+    /// Re-read multiple tables at once. It is required when the edits
+    /// to one table are inconsistent without the edits to another,
+    /// such as adding annotations to an instance.
+    pub fn reload_tables(&mut self, tables: &[Table]) -> Result<()> {
+        let inner = loader::reload_tables(&self.owner, tables)?;
+        *self = Self::from_inner(inner);
+        Ok(())
+    }
+
+    pub fn attribute(&self, token: Token) -> Option<AttributeRef> {
+        let ref_ = self
+            .owner
+            .clone()
+            .filter_map(|owner| owner.attribute_map.get(&token))?;
+        Some(AttributeRef::new(self.owner.clone(), ref_))
+    }
+
+    pub fn calibrated_sensor(&self, token: Token) -> Option<CalibratedSensorRef> {
+        let ref_ = self
+            .owner
+            .clone()
+            .filter_map(|owner| owner.calibrated_sensor_map.get(&token))?;
+        Some(CalibratedSensorRef::new(self.owner.clone(), ref_))
+    }
+
+    pub fn category(&self, token: Token) -> Option<CategoryRef> {
+        let ref_ = self
+            .owner
+            .clone()
+            .filter_map(|owner| owner.category_map.get(&token))?;
+        Some(CategoryRef::new(self.owner.clone(), ref_))
+    }
+
+    pub fn ego_pose(&self, token: Token) -> Option<EgoPoseRef> {
+        let ref_ = self
+            .owner
+            .clone()
+            .filter_map(|owner| owner.ego_pose_map.get(&token))?;
+        Some(EgoPoseRef::new(self.owner.clone(), ref_))
+    }
+
+    pub fn instance(&self, token: Token) -> Option<InstanceRef> {
+        let ref_ = self
+            .owner
+            .clone()
+            .filter_map(|owner| owner.instance_map.get(&token))?;
+        Some(InstanceRef::new(self.owner.clone(), ref_))
+    }
+
+    pub fn log(&self, token: Token) -> Option<LogRef> {
+        let ref_ = self
+            .owner
+            .clone()
+            .filter_map(|owner| owner.log_map.get(&token))?;
+        Some(LogRef::new(self.owner.clone(), ref_))
+    }
+
+    pub fn map(&self, token: Token) -> Option<MapRef> {
+        let ref_ = self
+            .owner
+            .clone()
+            .filter_map(|owner| owner.map_map.get(&token))?;
+        Some(MapRef::new(self.owner.clone(), ref_))
+    }
+
+    pub fn scene(&self, token: Token) -> Option<SceneRef> {
+        let ref_ = self
+            .owner
+            .clone()
+            .filter_map(|owner| owner.scene_map.get(&token))?;
+        Some(SceneRef::new(self.owner.clone(), ref_))
+    }
+
+    /// Iterate over the logs captured within the date range, sorted by
+    /// capture dates.
+    pub fn logs_by_date<R>(&self, range: R) -> impl Iterator<Item = LogRef> + '_
+    where
+        R: RangeBounds<NaiveDate>,
+    {
+        self.owner
+            .log_date_map
+            .range(range)
+            .flat_map(|(_, tokens)| tokens)
+            .map(|token| self.owner.clone().map(|owner| &owner.log_map[token]))
+            .map(|ref_| LogRef::new(self.owner.clone(), ref_))
+    }
+
+    /// Iterate over the logs captured by the vehicle, sorted by
+    /// capture dates.
+    pub fn logs_by_vehicle<'a>(&'a self, vehicle: &str) -> impl Iterator<Item = LogRef> + 'a {
+        self.owner
+            .log_vehicle_map
+            .get(vehicle)
+            .into_iter()
+            .flatten()
+            .map(|token| self.owner.clone().map(|owner| &owner.log_map[token]))
+            .map(|ref_| LogRef::new(self.owner.clone(), ref_))
+    }
+
+    /// Find the scene by its name, such as "scene-0061". If versions
+    /// merged in the dataset have scenes of the same name, the scene
+    /// of the first version is returned. Use
+    /// [Dataset::scene_by_version_and_name] or [Dataset::scenes_by_name]
+    /// to tell them apart.
+    pub fn scene_by_name(&self, name: &str) -> Option<SceneRef> {
+        self.scenes_by_name(name).next()
+    }
+
+    /// Find the scene by its name in the version, such as
+    /// `v1.0-trainval`.
+    pub fn scene_by_version_and_name(&self, version: &str, name: &str) -> Option<SceneRef> {
+        let index = self.owner.versions.iter().position(|v| v == version)?;
+        self.scenes_by_name(name)
+            .find(|scene| self.owner.scene_versions.get(&scene.token) == Some(&index))
+    }
+
+    /// Iterate over the scenes of the name in the order of versions.
+    /// There are more than one only if merged versions have scenes of
+    /// the same name.
+    pub fn scenes_by_name<'a>(&'a self, name: &str) -> impl Iterator<Item = SceneRef> + 'a {
+        self.owner
+            .scene_name_map
+            .get(name)
+            .into_iter()
+            .flatten()
+            .map(|token| self.owner.clone().map(|owner| &owner.scene_map[token]))
+            .map(|ref_| SceneRef::new(self.owner.clone(), ref_))
+    }
+
+    pub fn sample(&self, token: Token) -> Option<SampleRef> {
+        let ref_ = self
+            .owner
+            .clone()
+            .filter_map(|owner| owner.sample_map.get(&token))?;
+        Some(SampleRef::new(self.owner.clone(), ref_))
+    }
+
+    pub fn sample_annotation(&self, token: Token) -> Option<SampleAnnotationRef> {
+        let ref_ = self
+            .owner
+            .clone()
+            .filter_map(|owner| owner.sample_annotation_map.get(&token))?;
+        Some(SampleAnnotationRef::new(self.owner.clone(), ref_))
+    }
+
+    pub fn sample_data(&self, token: Token) -> Option<SampleDataRef> {
+        let ref_ = self
+            .owner
+            .clone()
+            .filter_map(|owner| owner.sample_data_map.get(&token))?;
+        Some(SampleDataRef::new(self.owner.clone(), ref_))
+    }
+
+    pub fn sensor(&self, token: Token) -> Option<SensorRef> {
+        let ref_ = self
+            .owner
+            .clone()
+            .filter_map(|owner| owner.sensor_map.get(&token))?;
+        Some(SensorRef::new(self.owner.clone(), ref_))
+    }
+
+    pub fn visibility(&self, token: VisibilityToken) -> Option<VisibilityRef> {
+        let ref_ = self
+            .owner
+            .clone()
+            .filter_map(|owner| owner.visibility_map.get(&token))?;
+        Some(VisibilityRef::new(self.owner.clone(), ref_))
+    }
+}
+
+macro_rules! impl_field_iter {
+    ($method_name:ident, $field_name:ident, $item_ty:ident) => {
+        impl Dataset {
+            pub fn $method_name(&self) -> impl Iterator<Item = $item_ty> + Send + Sync + '_ {
+                self.owner
+                    .clone()
+                    .flat_map(|owner| owner.$field_name.values())
+                    .map(|item| $item_ty::new(self.owner.clone(), item))
+            }
+        }
+    };
+}
+
+impl_field_iter!(attribute_iter, attribute_map, AttributeRef);
+impl_field_iter!(
+    calibrated_sensor_iter,
+    calibrated_sensor_map,
+    CalibratedSensorRef
+);
+impl_field_iter!(category_iter, category_map, CategoryRef);
+impl_field_iter!(ego_pose_iter, ego_pose_map, EgoPoseRef);
+impl_field_iter!(instance_iter, instance_map, InstanceRef);
+impl_field_iter!(log_iter, log_map, LogRef);
+impl_field_iter!(map_iter, map_map, MapRef);
+impl_field_iter!(scene_iter, scene_map, SceneRef);
+impl_field_iter!(sample_iter, sample_map, SampleRef);
+impl_field_iter!(
+    sample_annotation_iter,
+    sample_annotation_map,
+    SampleAnnotationRef
+);
+impl_field_iter!(sample_data_iter, sample_data_map, SampleDataRef);
+impl_field_iter!(sensor_iter, sensor_map, SensorRef);
+impl_field_iter!(visibility_iter, visibility_map, VisibilityRef);
+
+impl Dataset {
+    /// Iterate over the key frame sample data, skipping sweeps.
+    pub fn keyframe_sample_data_iter(
+        &self,
+    ) -> impl Iterator<Item = SampleDataRef> + Send + Sync + '_ {
+        self.sample_data_iter().filter(|data| data.is_key_frame)
+    }
+
+    /// Iterate over the sample data with timestamps in `[start, end)`
+    /// in time order. The range is found by binary search on the sample
+    /// data sorted by timestamp.
+    pub fn sample_data_between(
+        &self,
+        start: NaiveDateTime,
+        end: NaiveDateTime,
+    ) -> impl Iterator<Item = SampleDataRef> + Send + Sync + Clone + '_ {
+        let range = sample_data_range(&self.owner, start, end);
+        self.owner.sorted_sample_data_tokens[range]
+            .iter()
+            .map(|token| {
+                self.owner
+                    .clone()
+                    .map(|owner| &owner.sample_data_map[token])
+            })
+            .map(|ref_| SampleDataRef::new(self.owner.clone(), ref_))
+    }
+
+    /// Iterate over the sample data, including sweeps, in time order.
+    /// Only the sample data of the channel are visited if it is given.
+    pub fn sample_data_iter_sorted(
+        &self,
+        channel: Option<Channel>,
+    ) -> impl Iterator<Item = SampleDataRef> + Send + Sync + Clone + '_ {
+        self.owner
+            .sorted_sample_data_tokens
+            .iter()
+            .map(|token| {
+                self.owner
+                    .clone()
+                    .map(|owner| &owner.sample_data_map[token])
+            })
+            .map(|ref_| SampleDataRef::new(self.owner.clone(), ref_))
+            .filter(move |data| is_of_channel(data, channel))
+    }
+
+    /// Get the ego pose nearest in time to the timestamp. See
+    /// [Dataset::ego_to_global_at] for interpolated poses.
+    pub fn ego_pose_at(&self, timestamp: NaiveDateTime) -> Option<EgoPoseRef> {
+        let token = nearest_ego_pose(&self.owner, timestamp, None)?;
+        self.ego_pose(token)
+    }
+}
+
+fn is_of_channel(data: &SampleDataRef, channel: Option<Channel>) -> bool {
+    match channel {
+        Some(channel) => data.channel() == channel,
+        None => true,
+    }
+}
+
+/// Get the index range of the sample data with timestamps in
+/// `[start, end)` in the sorted sample data tokens.
+fn sample_data_range(
+    inner: &DatasetInner,
+    start: NaiveDateTime,
+    end: NaiveDateTime,
+) -> Range<usize> {
+    let tokens = &inner.sorted_sample_data_tokens;
+    let timestamp = |token: &Token| inner.sample_data_map[token].timestamp;
+    let start_index = tokens.partition_point(|token| timestamp(token) < start);
+    let end_index = tokens.partition_point(|token| timestamp(token) < end);
+    start_index..end_index.max(start_index)
+}
+
+/// Get the tokens of the last ego pose before the timestamp and the
+/// first ego pose at or after the timestamp. If the scene token is
+/// given, only the ego poses of the sample data of the scene are
+/// considered.
+pub(crate) fn ego_pose_neighbors(
+    inner: &DatasetInner,
+    timestamp: NaiveDateTime,
+    scene_token: Option<Token>,
+) -> [Option<Token>; 2] {
+    let Some(scene_token) = scene_token else {
+        let tokens = &inner.sorted_ego_pose_tokens;
+        let index = tokens.partition_point(|token| inner.ego_pose_map[token].timestamp < timestamp);
+        let prev = index.checked_sub(1).map(|prev| tokens[prev]);
+        return [prev, tokens.get(index).copied()];
+    };
+
+    let Some(tokens) = inner.scene_sample_data_map.get(&scene_token) else {
+        return [None, None];
+    };
+    let ego_pose_of = |index: usize| inner.sample_data_map[&tokens[index]].ego_pose_token;
+    let index = tokens.partition_point(|token| inner.sample_data_map[token].timestamp < timestamp);
+    let prev = index.checked_sub(1).map(ego_pose_of);
+    let next = (index < tokens.len()).then(|| ego_pose_of(index));
+    [prev, next]
+}
+
+fn nearest_ego_pose(
+    inner: &DatasetInner,
+    timestamp: NaiveDateTime,
+    scene_token: Option<Token>,
+) -> Option<Token> {
+    ego_pose_neighbors(inner, timestamp, scene_token)
+        .into_iter()
+        .flatten()
+        .min_by_key(|token| (inner.ego_pose_map[token].timestamp - timestamp).abs())
+}
+
+impl CalibratedSensorRef {
+    pub fn sensor(&self) -> SensorRef {
+        let ref_ = self
+            .owner
+            .clone()
+            .map(|owner| &owner.sensor_map[&self.ref_.sensor_token]);
+        SensorRef::new(self.owner.clone(), ref_)
+    }
+}
+
+impl InstanceRef {
+    pub fn category(&self) -> CategoryRef {
+        let ref_ = self
+            .owner
+            .clone()
+            .map(|owner| &owner.category_map[&self.ref_.category_token]);
+        CategoryRef::new(self.owner.clone(), ref_)
+    }
+
+    pub fn annotation_iter(
+        &self,
+    ) -> impl Iterator<Item = SampleAnnotationRef> + Send + Sync + Clone + '_ {
+        self.ref_
+            .annotation_tokens
+            .iter()
+            .map(|token| {
+                self.owner
+                    .clone()
+                    .map(|owner| &owner.sample_annotation_map[token])
+            })
+            .map(|ref_| SampleAnnotationRef::new(self.owner.clone(), ref_))
+    }
+}
+
+impl LogRef {
+    /// Iterate over the scenes of the log sorted by time.
+    pub fn scene_iter(&self) -> impl Iterator<Item = SceneRef> + Send + Sync + Clone + '_ {
+        self.owner
+            .log_scene_map
+            .get(&self.ref_.token)
+            .into_iter()
+            .flatten()
+            .map(|token| self.owner.clone().map(|owner| &owner.scene_map[token]))
+            .map(|ref_| SceneRef::new(self.owner.clone(), ref_))
+    }
+
+    // pub fn logfile(&self) -> Option<PathBuf> {
+    //     Some(self.owner.dataset_dir.join(self.ref_.logfile.as_ref()?))
+    // }
+
+    pub fn map(&self) -> Option<MapRef> {
+        let ref_ = self.owner.clone().filter_map(|owner| {
+            owner
+                .map_map
+                .values()
+                .find(|map| map.log_tokens.contains(&self.ref_.token))
+        })?;
+        Some(MapRef::new(self.owner.clone(), ref_))
+    }
+}
+
+impl MapRef {
+    pub fn log_iter(&self) -> impl Iterator<Item = LogRef> + Send + Sync + Clone + '_ {
+        self.ref_
+            .log_tokens
+            .iter()
+            .map(|token| self.owner.clone().map(|owner| &owner.log_map[token]))
+            .map(|ref_| LogRef::new(self.owner.clone(), ref_))
+    }
+
+    /// Get the path of the file, which is joined with the dataset
+    /// directory on demand.
+    pub fn path(&self) -> DataPath<'_> {
+        DataPath::new(&self.owner.dataset_dir, &self.ref_.filename)
+    }
+
+    /// Read the file content through the dataset storage with the
+    /// default read options.
+    pub fn read_bytes(&self) -> io::Result<Vec<u8>> {
+        self.read_bytes_with_options(&self.owner.read_options)
+    }
+
+    pub fn read_bytes_with_options(&self, options: &ReadOptions) -> io::Result<Vec<u8>> {
+        read_data_with_options(&self.owner.storage, self.path(), options)
+    }
+}
+
+impl SceneRef {
+    /// Get the version defining the scene.
+    pub fn version(&self) -> &str {
+        &self.owner.versions[self.owner.scene_versions[&self.ref_.token]]
+    }
+
+    pub fn log(&self) -> LogRef {
+        let ref_ = self
+            .owner
+            .clone()
+            .map(|owner| &owner.log_map[&self.ref_.log_token]);
+        LogRef::new(self.owner.clone(), ref_)
+    }
+
+    pub fn sample_iter(&self) -> impl Iterator<Item = SampleRef> + Send + Sync + Clone + '_ {
+        self.ref_
+            .sample_tokens
+            .iter()
+            .map(|token| self.owner.clone().map(|owner| &owner.sample_map[token]))
+            .map(|ref_| SampleRef::new(self.owner.clone(), ref_))
+    }
+
+    /// Iterate over the sample data of the scene, including sweeps,
+    /// with timestamps in `[start, end)` in time order.
+    pub fn sample_data_between(
+        &self,
+        start: NaiveDateTime,
+        end: NaiveDateTime,
+    ) -> impl Iterator<Item = SampleDataRef> + Send + Sync + Clone + '_ {
+        let range = sample_data_range(&self.owner, start, end);
+        self.owner.sorted_sample_data_tokens[range]
+            .iter()
+            .filter(|token| {
+                let sample_token = self.owner.sample_data_map[*token].sample_token;
+                self.owner.sample_map[&sample_token].scene_token == self.ref_.token
+            })
+            .map(|token| {
+                self.owner
+                    .clone()
+                    .map(|owner| &owner.sample_data_map[token])
+            })
+            .map(|ref_| SampleDataRef::new(self.owner.clone(), ref_))
+    }
+
+    /// Iterate over the sample data of the scene, including sweeps, in
+    /// time order. Only the sample data of the channel are visited if
+    /// it is given.
+    pub fn sample_data_iter_chronological(
+        &self,
+        channel: Option<Channel>,
+    ) -> impl Iterator<Item = SampleDataRef> + Send + Sync + Clone + '_ {
+        // Find the time span of the scene in the sorted sample data
+        let timestamps = self
+            .ref_
+            .sample_tokens
+            .iter()
+            .flat_map(|token| &self.owner.sample_map[token].sample_data_tokens)
+            .map(|token| self.owner.sample_data_map[token].timestamp);
+        let range = match (timestamps.clone().min(), timestamps.max()) {
+            (Some(start), Some(end)) => {
+                let tokens = &self.owner.sorted_sample_data_tokens;
+                let timestamp = |token: &Token| self.owner.sample_data_map[token].timestamp;
+                tokens.partition_point(|token| timestamp(token) < start)
+                    ..tokens.partition_point(|token| timestamp(token) <= end)
+            }
+            _ => 0..0,
+        };
+
+        self.owner.sorted_sample_data_tokens[range]
+            .iter()
+            .filter(|token| {
+                let sample_token = self.owner.sample_data_map[*token].sample_token;
+                self.owner.sample_map[&sample_token].scene_token == self.ref_.token
+            })
+            .map(|token| {
+                self.owner
+                    .clone()
+                    .map(|owner| &owner.sample_data_map[token])
+            })
+            .map(|ref_| SampleDataRef::new(self.owner.clone(), ref_))
+            .filter(move |data| is_of_channel(data, channel))
+    }
+
+    /// Get the ego pose of the sample data of the scene nearest in
+    /// time to the timestamp.
+    pub fn ego_pose_at(&self, timestamp: NaiveDateTime) -> Option<EgoPoseRef> {
+        let token = nearest_ego_pose(&self.owner, timestamp, Some(self.ref_.token))?;
+        let ref_ = self.owner.clone().map(|owner| &owner.ego_pose_map[&token]);
+        Some(EgoPoseRef::new(self.owner.clone(), ref_))
+    }
+}
+
+impl SampleRef {
+    pub fn next(&self) -> Option<SampleRef> {
+        let ref_ = self
+            .owner
+            .clone()
+            .filter_map(|owner| Some(&owner.sample_map[&self.ref_.next?]))?;
+        Some(SampleRef::new(self.owner.clone(), ref_))
+    }
+
+    pub fn prev(&self) -> Option<SampleRef> {
+        let ref_ = self
+            .owner
+            .clone()
+            .filter_map(|owner| Some(&owner.sample_map[&self.ref_.prev?]))?;
+        Some(SampleRef::new(self.owner.clone(), ref_))
+    }
+
+    pub fn scene(&self) -> SceneRef {
+        let ref_ = self
+            .owner
+            .clone()
+            .map(|owner| &owner.scene_map[&self.ref_.scene_token]);
+        SceneRef::new(self.owner.clone(), ref_)
+    }
+
+    pub fn annotation_iter(
+        &self,
+    ) -> impl Iterator<Item = SampleAnnotationRef> + Send + Sync + Clone + '_ {
+        self.ref_
+            .annotation_tokens
+            .iter()
+            .map(|token| {
+                self.owner
+                    .clone()
+                    .map(|owner| &owner.sample_annotation_map[token])
+            })
+            .map(|ref_| SampleAnnotationRef::new(self.owner.clone(), ref_))
+    }
+
+    pub fn sample_data_iter(
+        &self,
+    ) -> impl Iterator<Item = SampleDataRef> + Send + Sync + Clone + '_ {
+        self.ref_
+            .sample_data_tokens
+            .iter()
+            .map(|token| {
+                self.owner
+                    .clone()
+                    .map(|owner| &owner.sample_data_map[token])
+            })
+            .map(|ref_| SampleDataRef::new(self.owner.clone(), ref_))
+    }
+
+    /// Iterate over the key frame sample data of the sample, one per
+    /// sensor.
+    pub fn keyframe_data_iter(
+        &self,
+    ) -> impl Iterator<Item = SampleDataRef> + Send + Sync + Clone + '_ {
+        self.sample_data_iter().filter(|data| data.is_key_frame)
+    }
+
+    /// Get the key frame sample data captured by the sensor on the
+    /// channel.
+    pub fn sample_data_by_channel(&self, channel: Channel) -> Option<SampleDataRef> {
+        self.keyframe_data_iter()
+            .find(|data| data.channel() == channel)
+    }
+}
+
+impl SampleAnnotationRef {
+    pub fn sample(&self) -> SampleRef {
+        let ref_ = self
+            .owner
+            .clone()
+            .map(|owner| &owner.sample_map[&self.ref_.sample_token]);
+        SampleRef::new(self.owner.clone(), ref_)
+    }
+
+    pub fn instance(&self) -> InstanceRef {
+        let ref_ = self
+            .owner
+            .clone()
+            .map(|owner| &owner.instance_map[&self.ref_.instance_token]);
+        InstanceRef::new(self.owner.clone(), ref_)
+    }
+
+    pub fn attribute_iter(&self) -> impl Iterator<Item = AttributeRef> + Send + Sync + Clone + '_ {
+        self.ref_
+            .attribute_tokens
+            .iter()
+            .map(|token| self.owner.clone().map(|owner| &owner.attribute_map[token]))
+            .map(|ref_| AttributeRef::new(self.owner.clone(), ref_))
+    }
+
+    pub fn visibility(&self) -> Option<VisibilityRef> {
+        let ref_ = self
+            .owner
+            .clone()
+            .filter_map(|owner| Some(&owner.visibility_map[&self.ref_.visibility_token?]))?;
+        Some(VisibilityRef::new(self.owner.clone(), ref_))
+    }
+
+    pub fn next(&self) -> Option<SampleAnnotationRef> {
+        let ref_ = self
+            .owner
+            .clone()
+            .filter_map(|owner| Some(&owner.sample_annotation_map[&self.ref_.next?]))?;
+        Some(SampleAnnotationRef::new(self.owner.clone(), ref_))
+    }
+
+    pub fn prev(&self) -> Option<SampleAnnotationRef> {
+        let ref_ = self
+            .owner
+            .clone()
+            .filter_map(|owner| Some(&owner.sample_annotation_map[&self.ref_.prev?]))?;
+        Some(SampleAnnotationRef::new(self.owner.clone(), ref_))
+    }
+}
+
+impl SampleDataRef {
+    pub fn sample(&self) -> SampleRef {
+        let ref_ = self
+            .owner
+            .clone()
+            .map(|owner| &owner.sample_map[&self.ref_.sample_token]);
+        SampleRef::new(self.owner.clone(), ref_)
+    }
+
+    pub fn ego_pose(&self) -> EgoPoseRef {
+        let ref_ = self
+            .owner
+            .clone()
+            .map(|owner| &owner.ego_pose_map[&self.ref_.ego_pose_token]);
+        EgoPoseRef::new(self.owner.clone(), ref_)
+    }
+
+    pub fn calibrated_sensor(&self) -> CalibratedSensorRef {
+        let ref_ = self
+            .owner
+            .clone()
+            .map(|owner| &owner.calibrated_sensor_map[&self.ref_.calibrated_sensor_token]);
+        CalibratedSensorRef::new(self.owner.clone(), ref_)
+    }
+
+    /// Get the channel of the sensor capturing the data.
+    pub fn channel(&self) -> Channel {
+        self.owner.calibrated_sensor_channel_map[&self.ref_.calibrated_sensor_token].0
+    }
+
+    /// Get the modality of the sensor capturing the data.
+    pub fn modality(&self) -> Modality {
+        self.owner.calibrated_sensor_channel_map[&self.ref_.calibrated_sensor_token].1
+    }
+
+    /// Get the panoptic record of the sweep if any.
+    pub fn panoptic(&self) -> Option<&Panoptic> {
+        let token = self.owner.sample_data_panoptic_map.get(&self.ref_.token)?;
+        Some(&self.owner.panoptic_map[token])
+    }
+
+    pub fn is_camera(&self) -> bool {
+        self.modality() == Modality::Camera
+    }
+
+    pub fn is_lidar(&self) -> bool {
+        self.modality() == Modality::Lidar
+    }
+
+    pub fn is_radar(&self) -> bool {
+        self.modality() == Modality::Radar
+    }
+
+    pub fn next(&self) -> Option<SampleDataRef> {
+        let ref_ = self
+            .owner
+            .clone()
+            .filter_map(|owner| Some(&owner.sample_data_map[&self.ref_.next?]))?;
+        Some(SampleDataRef::new(self.owner.clone(), ref_))
+    }
+
+    pub fn prev(&self) -> Option<SampleDataRef> {
+        let ref_ = self
+            .owner
+            .clone()
+            .filter_map(|owner| Some(&owner.sample_data_map[&self.ref_.prev?]))?;
+        Some(SampleDataRef::new(self.owner.clone(), ref_))
+    }
+
+    /// Get the path of the file, which is joined with the dataset
+    /// directory on demand.
+    pub fn path(&self) -> DataPath<'_> {
+        DataPath::new(&self.owner.dataset_dir, &self.ref_.filename)
+    }
+
+    /// Read the file content through the dataset storage with the
+    /// default read options.
+    pub fn read_bytes(&self) -> io::Result<Vec<u8>> {
+        self.read_bytes_with_options(&self.owner.read_options)
+    }
+
+    pub fn read_bytes_with_options(&self, options: &ReadOptions) -> io::Result<Vec<u8>> {
+        read_data_with_options(&self.owner.storage, self.path(), options)
+    }
+
+    /// Get the size of the file in bytes through the dataset storage.
+    pub fn file_size(&self) -> io::Result<u64> {
+        self.owner.storage.data_size(self.path())
+    }
+}
@@ -0,0 +1,98 @@
+use super::{SampleAnnotationRef, SampleDataRef, SampleRef, SceneRef};
+use crate::serializable::{Channel, Token};
+
+/// Consecutive samples in a scene.
+#[derive(Clone)]
+pub struct SampleWindow {
+    pub samples: Vec<SampleRef>,
+}
+
+impl SampleWindow {
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    pub fn first(&self) -> &SampleRef {
+        &self.samples[0]
+    }
+
+    pub fn last(&self) -> &SampleRef {
+        self.samples.last().unwrap()
+    }
+
+    /// Get the key frame sample data of the channel for every sample.
+    /// It returns `None` if any of the samples lacks the channel.
+    pub fn sample_data_by_channel(&self, channel: Channel) -> Option<Vec<SampleDataRef>> {
+        self.samples
+            .iter()
+            .map(|sample| sample.sample_data_by_channel(channel))
+            .collect()
+    }
+
+    /// Get the annotations of the instance in every sample, which is
+    /// `None` if the instance is not annotated in the sample.
+    pub fn instance_annotations(&self, instance_token: Token) -> Vec<Option<SampleAnnotationRef>> {
+        self.samples
+            .iter()
+            .map(|sample| {
+                sample
+                    .annotation_iter()
+                    .find(|annotation| annotation.instance_token == instance_token)
+            })
+            .collect()
+    }
+}
+
+/// A sample with its neighbors in the scene.
+#[derive(Clone)]
+pub struct SampleTriple {
+    /// The previous sample, which is `None` for the first sample.
+    pub prev: Option<SampleRef>,
+    pub sample: SampleRef,
+    /// The next sample, which is `None` for the last sample.
+    pub next: Option<SampleRef>,
+}
+
+impl SceneRef {
+    /// Iterate over windows of `size` consecutive samples starting at
+    /// every `stride` samples. Trailing samples not filling a whole
+    /// window are skipped. It yields nothing if `size` or `stride` is
+    /// zero.
+    pub fn sample_windows(
+        &self,
+        size: usize,
+        stride: usize,
+    ) -> impl Iterator<Item = SampleWindow> + Send + Sync + '_ {
+        let samples: Vec<_> = self.sample_iter().collect();
+        let num_windows = if size == 0 || stride == 0 {
+            0
+        } else if samples.len() >= size {
+            (samples.len() - size) / stride + 1
+        } else {
+            0
+        };
+
+        (0..num_windows).map(move |index| {
+            let start = index * stride;
+            SampleWindow {
+                samples: samples[start..(start + size)].to_vec(),
+            }
+        })
+    }
+
+    /// Iterate over every sample of the scene with its previous and
+    /// next samples.
+    pub fn sample_triples(&self) -> impl Iterator<Item = SampleTriple> + Send + Sync + '_ {
+        let samples: Vec<_> = self.sample_iter().collect();
+
+        (0..samples.len()).map(move |index| SampleTriple {
+            prev: index.checked_sub(1).map(|prev| samples[prev].clone()),
+            sample: samples[index].clone(),
+            next: samples.get(index + 1).cloned(),
+        })
+    }
+}
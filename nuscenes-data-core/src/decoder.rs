@@ -0,0 +1,284 @@
+//! Background decoding of sample data.
+//!
+//! A [Decoder] runs a decoding function on a fixed number of worker
+//! threads. Submitted jobs are served in the order of priorities and
+//! then in the order of submission. Each submission returns a
+//! [DecodeFuture], which can be awaited in async code or waited on
+//! in blocking code.
+//!
+//! ```ignore
+//! use nuscenes_data::decoder::Decoder;
+//! use nuscenes_data_image::prelude::*;
+//!
+//! let decoder = Decoder::new(4, |data| data.load_dynamic_image());
+//! let future = decoder.submit_with_priority(sample_data, 10);
+//! let image = future.await?;
+//! ```
+
+use crate::dataset::SampleDataRef;
+use std::{
+    cmp::Ordering,
+    collections::BinaryHeap,
+    future::Future,
+    panic::{self, AssertUnwindSafe},
+    pin::Pin,
+    sync::{
+        atomic::{self, AtomicBool},
+        Arc, Condvar, Mutex,
+    },
+    task::{Context, Poll, Waker},
+    thread::{self, JoinHandle},
+};
+
+/// The default priority of submitted jobs.
+pub const DEFAULT_PRIORITY: i32 = 0;
+
+/// A thread pool decoding sample data in the background.
+///
+/// Dropping the decoder finishes pending jobs and joins the worker
+/// threads.
+pub struct Decoder<T> {
+    shared: Arc<Shared<T>>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl<T> Decoder<T>
+where
+    T: Send + 'static,
+{
+    /// Create a decoder running `decode` on `num_threads` threads.
+    pub fn new<F>(num_threads: usize, decode: F) -> Self
+    where
+        F: Fn(&SampleDataRef) -> T + Send + Sync + 'static,
+    {
+        assert!(num_threads > 0, "num_threads must be positive");
+
+        let shared = Arc::new(Shared {
+            queue: Mutex::new(Queue {
+                jobs: BinaryHeap::new(),
+                next_seq: 0,
+                closed: false,
+            }),
+            available: Condvar::new(),
+        });
+        let decode = Arc::new(decode);
+
+        let workers = (0..num_threads)
+            .map(|_| {
+                let shared = shared.clone();
+                let decode = decode.clone();
+                thread::spawn(move || shared.run_worker(&*decode))
+            })
+            .collect();
+
+        Self { shared, workers }
+    }
+
+    /// Submit a job with the default priority.
+    pub fn submit(&self, data: SampleDataRef) -> DecodeFuture<T> {
+        self.submit_with_priority(data, DEFAULT_PRIORITY)
+    }
+
+    /// Submit a job. Jobs with higher priorities are decoded first.
+    pub fn submit_with_priority(&self, data: SampleDataRef, priority: i32) -> DecodeFuture<T> {
+        let slot = Arc::new(Slot::default());
+
+        {
+            let mut queue = self.shared.queue.lock().unwrap();
+            let seq = queue.next_seq;
+            queue.next_seq += 1;
+            queue.jobs.push(Job {
+                priority,
+                seq,
+                data,
+                slot: slot.clone(),
+            });
+        }
+        self.shared.available.notify_one();
+
+        DecodeFuture { slot }
+    }
+
+    /// Get the number of jobs waiting to be decoded.
+    pub fn num_pending(&self) -> usize {
+        self.shared.queue.lock().unwrap().jobs.len()
+    }
+
+    pub fn num_threads(&self) -> usize {
+        self.workers.len()
+    }
+}
+
+impl<T> Drop for Decoder<T> {
+    fn drop(&mut self) {
+        self.shared.queue.lock().unwrap().closed = true;
+        self.shared.available.notify_all();
+
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+/// The result of a submitted job.
+///
+/// Dropping the future before the job starts cancels the job. It
+/// panics when polled if the decoding function panicked.
+pub struct DecodeFuture<T> {
+    slot: Arc<Slot<T>>,
+}
+
+impl<T> DecodeFuture<T> {
+    /// Block the current thread until the job is done.
+    pub fn wait(self) -> T {
+        let mut state = self.slot.state.lock().unwrap();
+        while matches!(*state, SlotState::Pending { .. }) {
+            state = self.slot.done.wait(state).unwrap();
+        }
+
+        match std::mem::replace(&mut *state, SlotState::Taken) {
+            SlotState::Ready(output) => output,
+            SlotState::Panicked => panic!("the decoding function panicked"),
+            SlotState::Pending { .. } | SlotState::Taken => unreachable!(),
+        }
+    }
+
+    /// Check if the job is done without blocking.
+    pub fn is_ready(&self) -> bool {
+        !matches!(*self.slot.state.lock().unwrap(), SlotState::Pending { .. })
+    }
+}
+
+impl<T> Future for DecodeFuture<T> {
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = self.slot.state.lock().unwrap();
+        match std::mem::replace(&mut *state, SlotState::Taken) {
+            SlotState::Pending { .. } => {
+                *state = SlotState::Pending {
+                    waker: Some(cx.waker().clone()),
+                };
+                Poll::Pending
+            }
+            SlotState::Ready(output) => Poll::Ready(output),
+            SlotState::Panicked => panic!("the decoding function panicked"),
+            SlotState::Taken => panic!("the future is polled after completion"),
+        }
+    }
+}
+
+impl<T> Drop for DecodeFuture<T> {
+    fn drop(&mut self) {
+        self.slot.cancelled.store(true, atomic::Ordering::Relaxed);
+    }
+}
+
+struct Shared<T> {
+    queue: Mutex<Queue<T>>,
+    available: Condvar,
+}
+
+impl<T> Shared<T> {
+    fn run_worker<F>(&self, decode: &F)
+    where
+        F: Fn(&SampleDataRef) -> T,
+    {
+        loop {
+            // Wait for the next job
+            let job = {
+                let mut queue = self.queue.lock().unwrap();
+                loop {
+                    if let Some(job) = queue.jobs.pop() {
+                        break job;
+                    }
+                    if queue.closed {
+                        return;
+                    }
+                    queue = self.available.wait(queue).unwrap();
+                }
+            };
+
+            if job.slot.cancelled.load(atomic::Ordering::Relaxed) {
+                continue;
+            }
+
+            let result = panic::catch_unwind(AssertUnwindSafe(|| decode(&job.data)));
+            let new_state = match result {
+                Ok(output) => SlotState::Ready(output),
+                Err(_) => SlotState::Panicked,
+            };
+
+            let waker = {
+                let mut state = job.slot.state.lock().unwrap();
+                match std::mem::replace(&mut *state, new_state) {
+                    SlotState::Pending { waker } => waker,
+                    _ => None,
+                }
+            };
+            job.slot.done.notify_all();
+            if let Some(waker) = waker {
+                waker.wake();
+            }
+        }
+    }
+}
+
+struct Queue<T> {
+    jobs: BinaryHeap<Job<T>>,
+    next_seq: u64,
+    closed: bool,
+}
+
+struct Job<T> {
+    priority: i32,
+    seq: u64,
+    data: SampleDataRef,
+    slot: Arc<Slot<T>>,
+}
+
+impl<T> PartialEq for Job<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<T> Eq for Job<T> {}
+
+impl<T> PartialOrd for Job<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T> Ord for Job<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Higher priorities first, then earlier submissions first
+        self.priority
+            .cmp(&other.priority)
+            .then_with(|| other.seq.cmp(&self.seq))
+    }
+}
+
+struct Slot<T> {
+    state: Mutex<SlotState<T>>,
+    done: Condvar,
+    cancelled: AtomicBool,
+}
+
+impl<T> Default for Slot<T> {
+    fn default() -> Self {
+        Self {
+            state: Mutex::new(SlotState::Pending { waker: None }),
+            done: Condvar::new(),
+            cancelled: AtomicBool::new(false),
+        }
+    }
+}
+
+enum SlotState<T> {
+    Pending { waker: Option<Waker> },
+    Ready(T),
+    Panicked,
+    Taken,
+}
@@ -0,0 +1,123 @@
+//! Difficulty scores of samples for curriculum learning.
+//!
+//! A score combines normalized factors, each within `[0, 1]`, by a
+//! weighted average, so scores of different samples are comparable
+//! under the same [DifficultyConfig].
+
+use crate::{dataset::SampleRef, serializable::VisibilityLevel};
+
+/// The weights and normalization constants of difficulty factors. A
+/// zero weight disables the factor.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DifficultyConfig {
+    pub object_count_weight: f64,
+    /// The number of annotations at which the object count factor
+    /// saturates.
+    pub max_object_count: usize,
+    pub visibility_weight: f64,
+    pub night_weight: f64,
+    pub rain_weight: f64,
+    pub ego_speed_weight: f64,
+    /// The ego speed in m/s at which the speed factor saturates.
+    pub max_ego_speed: f64,
+}
+
+impl Default for DifficultyConfig {
+    fn default() -> Self {
+        Self {
+            object_count_weight: 1.0,
+            max_object_count: 50,
+            visibility_weight: 1.0,
+            night_weight: 1.0,
+            rain_weight: 1.0,
+            ego_speed_weight: 1.0,
+            max_ego_speed: 15.0,
+        }
+    }
+}
+
+/// The difficulty of a sample with the factors it is computed from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Difficulty {
+    /// The weighted average of the factors within `[0, 1]`.
+    pub score: f64,
+    pub object_count: usize,
+    /// The lowest visibility level among the annotations. It is
+    /// `None` if no annotation has a visibility level.
+    pub min_visibility: Option<VisibilityLevel>,
+    /// Whether the scene description mentions night.
+    pub night: bool,
+    /// Whether the scene description mentions rain.
+    pub rain: bool,
+    /// The ego speed in m/s. See [SampleRef::ego_speed].
+    pub ego_speed: Option<f64>,
+}
+
+impl SampleRef {
+    /// Score the difficulty of the sample. Samples with more, less
+    /// visible objects, captured at night, in rain or at higher speeds
+    /// score higher.
+    pub fn difficulty(&self, config: &DifficultyConfig) -> Difficulty {
+        let object_count = self.annotation_iter().count();
+        let min_visibility = self
+            .annotation_iter()
+            .filter_map(|annotation| Some(annotation.visibility()?.level))
+            .min();
+        let description = self.scene().description.to_lowercase();
+        let night = mentions(&description, "night");
+        let rain = mentions(&description, "rain");
+        let ego_speed = self.ego_speed();
+
+        let object_count_factor = if config.max_object_count == 0 {
+            0.0
+        } else {
+            (object_count as f64 / config.max_object_count as f64).min(1.0)
+        };
+        let visibility_factor = match min_visibility {
+            Some(VisibilityLevel::V0_40) => 1.0,
+            Some(VisibilityLevel::V40_60) => 2.0 / 3.0,
+            Some(VisibilityLevel::V60_80) => 1.0 / 3.0,
+            Some(VisibilityLevel::V80_100) | None => 0.0,
+        };
+        let ego_speed_factor = match ego_speed {
+            Some(speed) if config.max_ego_speed > 0.0 => (speed / config.max_ego_speed).min(1.0),
+            _ => 0.0,
+        };
+        let flag = |value: bool| if value { 1.0 } else { 0.0 };
+
+        let terms = [
+            (config.object_count_weight, object_count_factor),
+            (config.visibility_weight, visibility_factor),
+            (config.night_weight, flag(night)),
+            (config.rain_weight, flag(rain)),
+            (config.ego_speed_weight, ego_speed_factor),
+        ];
+        let total_weight: f64 = terms.iter().map(|(weight, _)| weight).sum();
+        let score = if total_weight > 0.0 {
+            terms
+                .iter()
+                .map(|(weight, factor)| weight * factor)
+                .sum::<f64>()
+                / total_weight
+        } else {
+            0.0
+        };
+
+        Difficulty {
+            score,
+            object_count,
+            min_visibility,
+            night,
+            rain,
+            ego_speed,
+        }
+    }
+}
+
+/// Check if the lowercase description contains the word, so that
+/// "rain" does not match "train" or "terrain".
+fn mentions(description: &str, word: &str) -> bool {
+    description
+        .split(|c: char| !c.is_alphanumeric())
+        .any(|token| token == word)
+}
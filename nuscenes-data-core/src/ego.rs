@@ -0,0 +1,65 @@
+//! Ego vehicle dimensions and removal of lidar self-returns.
+//!
+//! The ego frame has its origin at the center of the rear axle on the
+//! ground, with the x-axis pointing forward.
+
+use crate::{
+    dataset::SampleDataRef,
+    error::{Error, Result},
+    geometry::{Box3D, Transform},
+    pointcloud::PointCloud,
+};
+
+/// The length of the ego vehicle in meters.
+pub const EGO_LENGTH: f64 = 4.084;
+/// The width of the ego vehicle in meters, excluding mirrors.
+pub const EGO_WIDTH: f64 = 1.730;
+/// The height of the ego vehicle in meters.
+pub const EGO_HEIGHT: f64 = 1.562;
+/// The distance in meters from the rear axle to the rear end.
+pub const EGO_REAR_OVERHANG: f64 = 0.657;
+
+/// Get the bounding box of the ego vehicle in the ego frame, inflated
+/// by `margin` meters on every side.
+pub fn ego_box(margin: f64) -> Box3D {
+    let center = [EGO_LENGTH / 2.0 - EGO_REAR_OVERHANG, 0.0, EGO_HEIGHT / 2.0];
+    let size = [
+        EGO_WIDTH + 2.0 * margin,
+        EGO_LENGTH + 2.0 * margin,
+        EGO_HEIGHT + 2.0 * margin,
+    ];
+    Box3D::new(center, size, [1.0, 0.0, 0.0, 0.0])
+}
+
+impl PointCloud {
+    /// Remove points within the ego box inflated by `margin` meters.
+    /// Points are expressed in the sensor frame.
+    pub fn remove_ego_returns(&mut self, sensor_to_ego: &Transform, margin: f64) {
+        let ego_box = ego_box(margin);
+        self.retain(|_, &position| {
+            let point = sensor_to_ego.apply(position.map(|v| v as f64));
+            !ego_box.contains(point)
+        });
+    }
+
+    /// Remove points within the square of `radius` meters around the
+    /// sensor on the xy-plane, like `remove_close` of the devkit.
+    pub fn remove_close(&mut self, radius: f32) {
+        self.retain(|_, &[x, y, _]| !(x.abs() < radius && y.abs() < radius));
+    }
+}
+
+impl SampleDataRef {
+    /// Load a lidar `.bin` sweep and remove returns from the ego
+    /// vehicle.
+    pub fn load_lidar_without_ego_returns(&self, margin: f64) -> Result<PointCloud> {
+        let mut points = PointCloud::from_lidar_bin_bytes(&self.read_bytes()?)
+            .map_err(|_| Error::CorruptedFile(self.path().into()))?;
+
+        let calibrated_sensor = self.calibrated_sensor();
+        let sensor_to_ego =
+            Transform::new(calibrated_sensor.rotation, calibrated_sensor.translation);
+        points.remove_ego_returns(&sensor_to_ego, margin);
+        Ok(points)
+    }
+}
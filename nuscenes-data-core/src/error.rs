@@ -0,0 +1,55 @@
+use crate::serializable::SampleData;
+use std::{io, path::PathBuf};
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("corrupted file: {0:?}")]
+    CorruptedFile(PathBuf),
+    #[error("corrupted file: {0}")]
+    CorruptedDataset(String),
+    #[error("I/O error: {0:?}")]
+    IoError(io::Error),
+    #[error("parseing error: {0}")]
+    ParseError(String),
+    #[error(transparent)]
+    UnsupportedFormat(#[from] UnsupportedFormat),
+    #[error("invalid argument: {0}")]
+    InvalidArgument(String),
+}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        Self::IoError(error)
+    }
+}
+
+/// The reason why a file cannot be loaded by a loader. It is shared
+/// by the extension crates.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("unsupported format of {filename:?}: expected {expected}, found {found}")]
+pub struct UnsupportedFormat {
+    /// The formats accepted by the loader, such as `jpg`.
+    pub expected: String,
+    /// The format of the file, such as `bin`.
+    pub found: String,
+    pub filename: PathBuf,
+}
+
+impl UnsupportedFormat {
+    /// Create the error for a sample data file. The found format is
+    /// the file extension, or the file format of the record if the
+    /// file has no extension.
+    pub fn of_sample_data(data: &SampleData, expected: impl Into<String>) -> Self {
+        let found = match data.filename.extension() {
+            Some(ext) => ext.to_string_lossy().into_owned(),
+            None => data.fileformat.to_string(),
+        };
+        Self {
+            expected: expected.into(),
+            found,
+            filename: data.filename.clone(),
+        }
+    }
+}
@@ -0,0 +1,97 @@
+//! Dry-run size estimation of exports.
+//!
+//! Exporters have `estimate_*` counterparts that compute the number of
+//! files and bytes an export would produce without writing anything,
+//! such as [DatasetWriter::estimate](crate::writer::DatasetWriter::estimate).
+//! [Dataset::estimate_by_split] runs an estimation on the scenes of
+//! each split to plan disk budgets.
+//!
+//! ```ignore
+//! use nuscenes_data::{split::Split, writer::DatasetWriter};
+//!
+//! let estimates = dataset.estimate_by_split(&Split::ALL, |scenes| {
+//!     let scenes = scenes.iter().map(|scene| scene.token);
+//!     DatasetWriter::default().scenes(scenes).estimate(&dataset)
+//! })?;
+//! for (split, estimate) in estimates {
+//!     println!("{}: {estimate}", split.as_str());
+//! }
+//! ```
+
+use crate::{
+    dataset::{Dataset, SceneRef},
+    error::Result,
+    split::Split,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Display},
+    io::{self, Write},
+};
+
+/// The files and bytes an export would produce.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExportEstimate {
+    /// The number of exported samples.
+    pub num_samples: usize,
+    /// The number of written files, including manifests.
+    pub num_files: usize,
+    /// The total size of the written files in bytes.
+    pub num_bytes: u64,
+}
+
+impl ExportEstimate {
+    /// Add the counts of another estimate.
+    pub fn merge(&mut self, other: &ExportEstimate) {
+        self.num_samples += other.num_samples;
+        self.num_files += other.num_files;
+        self.num_bytes += other.num_bytes;
+    }
+}
+
+impl Display for ExportEstimate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} samples in {} files of {} bytes",
+            self.num_samples, self.num_files, self.num_bytes
+        )
+    }
+}
+
+impl Dataset {
+    /// Run the estimation on the scenes of each split in time order.
+    /// Splits without scenes in the dataset are estimated on no
+    /// scenes.
+    pub fn estimate_by_split<F>(
+        &self,
+        splits: &[Split],
+        mut estimate: F,
+    ) -> Result<BTreeMap<Split, ExportEstimate>>
+    where
+        F: FnMut(&[SceneRef]) -> Result<ExportEstimate>,
+    {
+        splits
+            .iter()
+            .map(|&split| Ok((split, estimate(&self.split_scenes(split))?)))
+            .collect()
+    }
+}
+
+/// A writer discarding the bytes, which measures serialized sizes.
+#[derive(Debug, Default)]
+pub struct CountingWriter {
+    pub num_bytes: u64,
+}
+
+impl Write for CountingWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.num_bytes += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,228 @@
+//! Annotation filtering of the detection benchmark.
+//!
+//! It follows `filter_eval_boxes` of the devkit, so that annotations
+//! used for training match those the official evaluator counts.
+
+use crate::{
+    dataset::{Dataset, SampleAnnotationRef, SampleRef},
+    geometry::Box3D,
+    overlap::bev_nms,
+    serializable::{Channel, Token},
+};
+use itertools::Itertools;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Display};
+
+/// The category of bike racks, in which parked bicycles and
+/// motorcycles are not evaluated.
+pub const BICYCLE_RACK_CATEGORY: &str = "static_object.bicycle_rack";
+
+/// A class of the detection benchmark.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DetectionClass {
+    Car,
+    Truck,
+    Bus,
+    Trailer,
+    ConstructionVehicle,
+    Pedestrian,
+    Motorcycle,
+    Bicycle,
+    TrafficCone,
+    Barrier,
+}
+
+impl DetectionClass {
+    pub const ALL: [DetectionClass; 10] = [
+        DetectionClass::Car,
+        DetectionClass::Truck,
+        DetectionClass::Bus,
+        DetectionClass::Trailer,
+        DetectionClass::ConstructionVehicle,
+        DetectionClass::Pedestrian,
+        DetectionClass::Motorcycle,
+        DetectionClass::Bicycle,
+        DetectionClass::TrafficCone,
+        DetectionClass::Barrier,
+    ];
+
+    /// Map a general category name to the detection class. It returns
+    /// `None` for categories that are not evaluated.
+    pub fn from_category_name(name: &str) -> Option<Self> {
+        let class = match name {
+            "vehicle.car" => Self::Car,
+            "vehicle.truck" => Self::Truck,
+            "vehicle.bus.bendy" | "vehicle.bus.rigid" => Self::Bus,
+            "vehicle.trailer" => Self::Trailer,
+            "vehicle.construction" => Self::ConstructionVehicle,
+            "human.pedestrian.adult"
+            | "human.pedestrian.child"
+            | "human.pedestrian.construction_worker"
+            | "human.pedestrian.police_officer" => Self::Pedestrian,
+            "vehicle.motorcycle" => Self::Motorcycle,
+            "vehicle.bicycle" => Self::Bicycle,
+            "movable_object.trafficcone" => Self::TrafficCone,
+            "movable_object.barrier" => Self::Barrier,
+            _ => return None,
+        };
+        Some(class)
+    }
+
+    /// Get the distance in meters from the ego vehicle within which
+    /// annotations of the class are evaluated. Annotations exactly at
+    /// the distance are excluded as in the devkit.
+    pub fn eval_range(&self) -> f64 {
+        match self {
+            Self::Car | Self::Truck | Self::Bus | Self::Trailer | Self::ConstructionVehicle => 50.0,
+            Self::Pedestrian | Self::Motorcycle | Self::Bicycle => 40.0,
+            Self::TrafficCone | Self::Barrier => 30.0,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Car => "car",
+            Self::Truck => "truck",
+            Self::Bus => "bus",
+            Self::Trailer => "trailer",
+            Self::ConstructionVehicle => "construction_vehicle",
+            Self::Pedestrian => "pedestrian",
+            Self::Motorcycle => "motorcycle",
+            Self::Bicycle => "bicycle",
+            Self::TrafficCone => "traffic_cone",
+            Self::Barrier => "barrier",
+        }
+    }
+}
+
+impl Display for DetectionClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A detection box in the format of benchmark submissions, expressed
+/// in the global frame.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DetectionBox {
+    pub sample_token: Token,
+    pub translation: [f64; 3],
+    pub size: [f64; 3],
+    pub rotation: [f64; 4],
+    /// The velocity in m/s on the xy-plane.
+    #[serde(default)]
+    pub velocity: [f64; 2],
+    pub detection_name: DetectionClass,
+    pub detection_score: f64,
+    /// The attribute name, which is empty for classes without
+    /// attributes.
+    #[serde(default)]
+    pub attribute_name: String,
+}
+
+impl From<&DetectionBox> for Box3D {
+    fn from(detection: &DetectionBox) -> Self {
+        Box3D::new(detection.translation, detection.size, detection.rotation)
+    }
+}
+
+/// Keep the detections with scores at least `min_score`.
+pub fn filter_by_score(detections: Vec<DetectionBox>, min_score: f64) -> Vec<DetectionBox> {
+    detections
+        .into_iter()
+        .filter(|detection| detection.detection_score >= min_score)
+        .collect()
+}
+
+/// Apply [bev_nms] to the detections of each sample and class in
+/// parallel. Detections of different classes never suppress each
+/// other. The result is grouped by samples and classes, and sorted by
+/// descending scores within each group.
+pub fn nms_per_class(detections: Vec<DetectionBox>, iou_threshold: f64) -> Vec<DetectionBox> {
+    let groups: Vec<Vec<DetectionBox>> = detections
+        .into_iter()
+        .into_group_map_by(|detection| (detection.sample_token, detection.detection_name))
+        .into_iter()
+        .sorted_by_key(|(key, _)| *key)
+        .map(|(_, group)| group)
+        .collect();
+
+    groups
+        .into_par_iter()
+        .flat_map_iter(|group| {
+            let boxes: Vec<Box3D> = group.iter().map(Box3D::from).collect();
+            let scores: Vec<f64> = group
+                .iter()
+                .map(|detection| detection.detection_score)
+                .collect();
+            let mut group: Vec<Option<DetectionBox>> = group.into_iter().map(Some).collect();
+            bev_nms(&boxes, &scores, iou_threshold)
+                .into_iter()
+                .map(move |index| group[index].take().unwrap())
+        })
+        .collect()
+}
+
+impl SampleAnnotationRef {
+    /// Get the detection class of the annotation, if it is evaluated.
+    pub fn detection_class(&self) -> Option<DetectionClass> {
+        DetectionClass::from_category_name(&self.instance().category().name)
+    }
+}
+
+impl Dataset {
+    /// Get the annotations of the sample counted by the detection
+    /// benchmark.
+    ///
+    /// Annotations are kept if they belong to a detection class, lie
+    /// within the class range from the ego vehicle, contain at least
+    /// one lidar or radar point, and are not bicycles or motorcycles
+    /// whose centers are in bike racks.
+    ///
+    /// The devkit measures distances from the ego pose of the top
+    /// lidar, so no annotations are kept for samples without
+    /// LIDAR_TOP data.
+    pub fn annotations_in_eval_range(&self, sample: &SampleRef) -> Vec<SampleAnnotationRef> {
+        let Some(lidar) = sample.sample_data_by_channel(Channel::LidarTop) else {
+            return vec![];
+        };
+        let [ex, ey, _] = lidar.ego_pose().translation;
+
+        let bike_racks: Vec<Box3D> = sample
+            .annotation_iter()
+            .filter(|annotation| annotation.instance().category().name == BICYCLE_RACK_CATEGORY)
+            .map(|annotation| Box3D::from(&*annotation))
+            .collect();
+
+        sample
+            .annotation_iter()
+            .filter(|annotation| {
+                let Some(class) = annotation.detection_class() else {
+                    return false;
+                };
+
+                let [x, y, _] = annotation.translation;
+                if (x - ex).hypot(y - ey) >= class.eval_range() {
+                    return false;
+                }
+
+                if annotation.num_lidar_pts + annotation.num_radar_pts == 0 {
+                    return false;
+                }
+
+                if matches!(class, DetectionClass::Bicycle | DetectionClass::Motorcycle) {
+                    let in_rack = bike_racks
+                        .iter()
+                        .any(|rack| rack.contains(annotation.translation));
+                    if in_rack {
+                        return false;
+                    }
+                }
+
+                true
+            })
+            .collect()
+    }
+}
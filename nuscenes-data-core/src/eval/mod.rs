@@ -0,0 +1,4 @@
+//! Evaluation metrics for nuScenes benchmarks.
+
+pub mod detection;
+pub mod prediction;
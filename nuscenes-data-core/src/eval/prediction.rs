@@ -0,0 +1,363 @@
+//! Metrics for the trajectory prediction benchmark.
+
+use crate::{
+    error::{Error, Result},
+    prediction::helper::PredictHelper,
+    serializable::Token,
+};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fs::File, io::BufReader, path::Path};
+
+/// The prediction horizon in seconds of the benchmark.
+pub const PREDICTION_SECONDS: f64 = 6.0;
+
+/// The distance in meters beyond which a prediction is a miss.
+pub const MISS_THRESHOLD: f64 = 2.0;
+
+/// A multi-modal trajectory prediction for an agent at a sample.
+///
+/// The layout follows the devkit submission format. Trajectories are
+/// in the global frame and sampled at 2Hz starting from the next
+/// sample.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Prediction {
+    #[serde(rename = "instance")]
+    pub instance_token: Token,
+    #[serde(rename = "sample")]
+    pub sample_token: Token,
+    #[serde(rename = "prediction")]
+    pub trajectories: Vec<Vec<[f64; 2]>>,
+    pub probabilities: Vec<f64>,
+}
+
+impl Prediction {
+    pub fn num_modes(&self) -> usize {
+        self.trajectories.len()
+    }
+
+    /// Get the `k` most probable trajectories in descending order of
+    /// probabilities.
+    pub fn top_k(&self, k: usize) -> Vec<&[[f64; 2]]> {
+        let mut indices: Vec<_> = (0..self.trajectories.len()).collect();
+        indices.sort_by(|&lhs, &rhs| {
+            let lhs = self.probabilities.get(lhs).copied().unwrap_or(0.0);
+            let rhs = self.probabilities.get(rhs).copied().unwrap_or(0.0);
+            rhs.total_cmp(&lhs)
+        });
+        indices
+            .into_iter()
+            .take(k)
+            .map(|index| self.trajectories[index].as_slice())
+            .collect()
+    }
+}
+
+/// Load predictions from a submission JSON file.
+pub fn load_predictions<P>(path: P) -> Result<Vec<Prediction>>
+where
+    P: AsRef<Path>,
+{
+    let path = path.as_ref();
+    let reader = BufReader::new(File::open(path)?);
+    let predictions = serde_json::from_reader(reader).map_err(|err| {
+        let msg = format!("failed to load file {}: {:?}", path.display(), err);
+        Error::ParseError(msg)
+    })?;
+    Ok(predictions)
+}
+
+#[derive(Debug, Clone)]
+pub struct PredictionEvalConfig {
+    /// The numbers of modes to evaluate top-k metrics.
+    pub ks: Vec<usize>,
+    /// The prediction horizon in seconds.
+    pub seconds: f64,
+    /// The distance threshold of the miss rate in meters.
+    pub miss_threshold: f64,
+}
+
+impl Default for PredictionEvalConfig {
+    fn default() -> Self {
+        Self {
+            ks: vec![1, 5, 10],
+            seconds: PREDICTION_SECONDS,
+            miss_threshold: MISS_THRESHOLD,
+        }
+    }
+}
+
+/// Metrics averaged over predictions. Top-k metrics are keyed by `k`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PredictionMetrics {
+    pub min_ade: BTreeMap<usize, f64>,
+    pub min_fde: BTreeMap<usize, f64>,
+    pub miss_rate: BTreeMap<usize, f64>,
+    /// Only available if a drivable area is provided.
+    pub off_road_rate: Option<f64>,
+    /// The number of predictions with ground truth.
+    pub num_predictions: usize,
+}
+
+/// Get the average displacement error between a trajectory and the
+/// ground truth over their common length. It returns `None` if either
+/// of them is empty.
+pub fn average_displacement_error(
+    trajectory: &[[f64; 2]],
+    ground_truth: &[[f64; 2]],
+) -> Option<f64> {
+    let errors: Vec<_> = displacement_errors(trajectory, ground_truth).collect();
+    if errors.is_empty() {
+        return None;
+    }
+    Some(errors.iter().sum::<f64>() / errors.len() as f64)
+}
+
+/// Get the displacement error at the last common point of a
+/// trajectory and the ground truth. It returns `None` if either of
+/// them is empty.
+pub fn final_displacement_error(trajectory: &[[f64; 2]], ground_truth: &[[f64; 2]]) -> Option<f64> {
+    displacement_errors(trajectory, ground_truth).last()
+}
+
+/// Get the minimum ADE among the `k` most probable modes. Modes
+/// without displacement errors are ignored, and it returns `None` if
+/// no mode has one.
+pub fn min_ade_k(prediction: &Prediction, ground_truth: &[[f64; 2]], k: usize) -> Option<f64> {
+    prediction
+        .top_k(k)
+        .into_iter()
+        .filter_map(|trajectory| average_displacement_error(trajectory, ground_truth))
+        .min_by(f64::total_cmp)
+}
+
+/// Get the minimum FDE among the `k` most probable modes. Modes
+/// without displacement errors are ignored, and it returns `None` if
+/// no mode has one.
+pub fn min_fde_k(prediction: &Prediction, ground_truth: &[[f64; 2]], k: usize) -> Option<f64> {
+    prediction
+        .top_k(k)
+        .into_iter()
+        .filter_map(|trajectory| final_displacement_error(trajectory, ground_truth))
+        .min_by(f64::total_cmp)
+}
+
+/// Check if all of the `k` most probable modes deviate from the ground
+/// truth by more than `threshold` meters at some point.
+pub fn is_miss_k(
+    prediction: &Prediction,
+    ground_truth: &[[f64; 2]],
+    k: usize,
+    threshold: f64,
+) -> bool {
+    prediction.top_k(k).into_iter().all(|trajectory| {
+        displacement_errors(trajectory, ground_truth).any(|error| error > threshold)
+    })
+}
+
+/// Evaluate predictions against the annotated future trajectories.
+///
+/// Predictions of agents without future annotations and predictions
+/// without non-empty modes are skipped. The averages are undefined if
+/// every prediction is skipped, which is an error, as is a `k` of
+/// zero in the config.
+pub fn evaluate_predictions(
+    helper: &PredictHelper,
+    predictions: &[Prediction],
+    config: &PredictionEvalConfig,
+    drivable: Option<&dyn DrivableArea>,
+) -> Result<PredictionMetrics> {
+    if config.ks.contains(&0) {
+        return Err(Error::InvalidArgument(
+            "the numbers of modes must be positive".to_string(),
+        ));
+    }
+
+    let mut min_ade: BTreeMap<usize, f64> = config.ks.iter().map(|&k| (k, 0.0)).collect();
+    let mut min_fde = min_ade.clone();
+    let mut miss_rate = min_ade.clone();
+    let mut off_road_sum = 0.0;
+    let mut num_predictions = 0;
+
+    for prediction in predictions {
+        let Some(ground_truth) = helper.get_future_for_agent(
+            prediction.instance_token,
+            prediction.sample_token,
+            config.seconds,
+            false,
+        ) else {
+            continue;
+        };
+        if ground_truth.is_empty()
+            || prediction.trajectories.is_empty()
+            || prediction
+                .trajectories
+                .iter()
+                .any(|trajectory| trajectory.is_empty())
+        {
+            continue;
+        }
+
+        // The errors are defined since every mode and the ground truth
+        // are non-empty
+        for &k in &config.ks {
+            *min_ade.get_mut(&k).unwrap() += min_ade_k(prediction, &ground_truth, k).unwrap();
+            *min_fde.get_mut(&k).unwrap() += min_fde_k(prediction, &ground_truth, k).unwrap();
+            if is_miss_k(prediction, &ground_truth, k, config.miss_threshold) {
+                *miss_rate.get_mut(&k).unwrap() += 1.0;
+            }
+        }
+
+        if let Some(drivable) = drivable {
+            off_road_sum += off_road_rate(helper, drivable, prediction).unwrap_or(0.0);
+        }
+        num_predictions += 1;
+    }
+
+    if num_predictions == 0 {
+        return Err(Error::InvalidArgument(
+            "none of the predictions has ground truth to evaluate".to_string(),
+        ));
+    }
+
+    // Average over predictions
+    let count = num_predictions as f64;
+    for value in min_ade
+        .values_mut()
+        .chain(min_fde.values_mut())
+        .chain(miss_rate.values_mut())
+    {
+        *value /= count;
+    }
+
+    Ok(PredictionMetrics {
+        min_ade,
+        min_fde,
+        miss_rate,
+        off_road_rate: drivable.map(|_| off_road_sum / count),
+        num_predictions,
+    })
+}
+
+/// Tells whether a location on the map is drivable.
+pub trait DrivableArea: Sync {
+    /// Check if the global `position` on the `location` map is
+    /// drivable.
+    fn is_drivable(&self, location: &str, position: [f64; 2]) -> bool;
+}
+
+/// Check if every point of the trajectory is on the drivable area.
+pub fn is_on_road(drivable: &dyn DrivableArea, location: &str, trajectory: &[[f64; 2]]) -> bool {
+    trajectory
+        .iter()
+        .all(|&position| drivable.is_drivable(location, position))
+}
+
+/// Get the fraction of predicted modes leaving the drivable area.
+///
+/// It returns `None` if the sample is not found or the prediction has
+/// no modes.
+pub fn off_road_rate(
+    helper: &PredictHelper,
+    drivable: &dyn DrivableArea,
+    prediction: &Prediction,
+) -> Option<f64> {
+    if prediction.trajectories.is_empty() {
+        return None;
+    }
+
+    let sample = helper.dataset().sample(prediction.sample_token)?;
+    let location = &sample.scene().log().location;
+    let num_off_road = prediction
+        .trajectories
+        .iter()
+        .filter(|trajectory| !is_on_road(drivable, location, trajectory))
+        .count();
+    Some(num_off_road as f64 / prediction.num_modes() as f64)
+}
+
+/// Get the fraction of predicted modes colliding with other annotated
+/// agents.
+///
+/// The i-th point of a trajectory is checked against the annotation
+/// boxes at the i-th future sample, inflated by half the width of the
+/// predicted agent. Points beyond the last annotated sample are not
+/// checked. It returns `None` if the agent is not annotated at the
+/// sample or the prediction has no modes.
+pub fn collision_rate(helper: &PredictHelper, prediction: &Prediction) -> Option<f64> {
+    if prediction.trajectories.is_empty() {
+        return None;
+    }
+
+    let annotation =
+        helper.get_sample_annotation(prediction.instance_token, prediction.sample_token)?;
+    let margin = annotation.size[0] / 2.0;
+    let horizon = prediction
+        .trajectories
+        .iter()
+        .map(|trajectory| trajectory.len())
+        .max()
+        .unwrap_or(0);
+
+    // Collect boxes of other agents at future samples
+    let mut future_boxes = vec![];
+    let mut sample = annotation.sample().next();
+    while let Some(curr) = sample {
+        if future_boxes.len() >= horizon {
+            break;
+        }
+
+        let boxes: Vec<_> = curr
+            .annotation_iter()
+            .filter(|other| other.instance_token != prediction.instance_token)
+            .map(|other| BevBox {
+                center: [other.translation[0], other.translation[1]],
+                half_width: other.size[0] / 2.0 + margin,
+                half_length: other.size[1] / 2.0 + margin,
+                yaw: other.yaw(),
+            })
+            .collect();
+        future_boxes.push(boxes);
+        sample = curr.next();
+    }
+
+    let num_collided = prediction
+        .trajectories
+        .iter()
+        .filter(|trajectory| {
+            trajectory
+                .iter()
+                .zip(&future_boxes)
+                .any(|(&position, boxes)| boxes.iter().any(|bbox| bbox.contains(position)))
+        })
+        .count();
+    Some(num_collided as f64 / prediction.num_modes() as f64)
+}
+
+fn displacement_errors<'a>(
+    trajectory: &'a [[f64; 2]],
+    ground_truth: &'a [[f64; 2]],
+) -> impl Iterator<Item = f64> + 'a {
+    trajectory
+        .iter()
+        .zip(ground_truth)
+        .map(|(lhs, rhs)| (lhs[0] - rhs[0]).hypot(lhs[1] - rhs[1]))
+}
+
+/// An oriented box on the xy-plane.
+struct BevBox {
+    center: [f64; 2],
+    half_width: f64,
+    half_length: f64,
+    yaw: f64,
+}
+
+impl BevBox {
+    fn contains(&self, position: [f64; 2]) -> bool {
+        let dx = position[0] - self.center[0];
+        let dy = position[1] - self.center[1];
+        let (sin, cos) = self.yaw.sin_cos();
+        let along = cos * dx + sin * dy;
+        let across = -sin * dx + cos * dy;
+        along.abs() <= self.half_length && across.abs() <= self.half_width
+    }
+}
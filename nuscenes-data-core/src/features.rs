@@ -0,0 +1,166 @@
+//! Flat per-point feature buffers for model input pipelines.
+//!
+//! Lidar points are written into a `Vec<f32>` with a configurable
+//! [FeatureLayout], such as (x, y, z, intensity) or (x, y, z,
+//! intensity, time). The time feature is the lag in seconds from the
+//! sweep to the key frame, which is filled in when sweeps are
+//! aggregated with [SweepGroup::load_lidar_features].
+
+use crate::{
+    dataset::SampleDataRef, error::Result, geometry::Transform, pointcloud::LidarBinPoint,
+    sweep::SweepGroup,
+};
+use serde::{Deserialize, Serialize};
+
+/// A feature of a point.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum PointFeature {
+    X,
+    Y,
+    Z,
+    Intensity,
+    RingIndex,
+    /// The time lag in seconds to the key frame.
+    Time,
+}
+
+/// The order of features of each point.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct FeatureLayout {
+    features: Vec<PointFeature>,
+}
+
+impl FeatureLayout {
+    pub fn new(features: &[PointFeature]) -> Self {
+        assert!(!features.is_empty(), "the layout must not be empty");
+        Self {
+            features: features.to_vec(),
+        }
+    }
+
+    /// The (x, y, z, intensity) layout.
+    pub fn xyzi() -> Self {
+        use PointFeature::*;
+        Self::new(&[X, Y, Z, Intensity])
+    }
+
+    /// The (x, y, z, intensity, ring index) layout of lidar `.bin`
+    /// files.
+    pub fn xyzi_ring() -> Self {
+        use PointFeature::*;
+        Self::new(&[X, Y, Z, Intensity, RingIndex])
+    }
+
+    /// The (x, y, z, intensity, time) layout.
+    pub fn xyzi_time() -> Self {
+        use PointFeature::*;
+        Self::new(&[X, Y, Z, Intensity, Time])
+    }
+
+    pub fn features(&self) -> &[PointFeature] {
+        &self.features
+    }
+
+    /// The number of values per point.
+    pub fn stride(&self) -> usize {
+        self.features.len()
+    }
+
+    /// Get the offset of the feature within a point.
+    pub fn offset_of(&self, feature: PointFeature) -> Option<usize> {
+        self.features.iter().position(|&other| other == feature)
+    }
+
+    fn push_point(&self, values: &mut Vec<f32>, point: &LidarBinPoint, time: f32) {
+        let [x, y, z, intensity, ring_index] = *point;
+        values.extend(self.features.iter().map(|feature| match feature {
+            PointFeature::X => x,
+            PointFeature::Y => y,
+            PointFeature::Z => z,
+            PointFeature::Intensity => intensity,
+            PointFeature::RingIndex => ring_index,
+            PointFeature::Time => time,
+        }));
+    }
+}
+
+/// Point features stored contiguously, point by point.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PointFeatures {
+    pub layout: FeatureLayout,
+    pub values: Vec<f32>,
+}
+
+impl PointFeatures {
+    pub fn new(layout: FeatureLayout) -> Self {
+        Self {
+            layout,
+            values: vec![],
+        }
+    }
+
+    pub fn stride(&self) -> usize {
+        self.layout.stride()
+    }
+
+    pub fn num_points(&self) -> usize {
+        self.values.len() / self.stride()
+    }
+
+    /// Get the features of the index-th point.
+    pub fn point(&self, index: usize) -> Option<&[f32]> {
+        let stride = self.stride();
+        self.values.get(index * stride..(index + 1) * stride)
+    }
+
+    /// Append points transformed into another frame.
+    fn extend_points(
+        &mut self,
+        points: &[LidarBinPoint],
+        transform: Option<&Transform>,
+        time: f32,
+    ) {
+        self.values.reserve(points.len() * self.stride());
+        for point in points {
+            let mut point = *point;
+            if let Some(transform) = transform {
+                let [x, y, z] = transform.apply_f32([point[0], point[1], point[2]]);
+                point[..3].copy_from_slice(&[x, y, z]);
+            }
+            self.layout.push_point(&mut self.values, &point, time);
+        }
+    }
+}
+
+impl SampleDataRef {
+    /// Load a lidar `.bin` file into point features. The time feature
+    /// is zero.
+    pub fn load_lidar_features(&self, layout: &FeatureLayout) -> Result<PointFeatures> {
+        let points = self.load_lidar_bin_points()?;
+        let mut features = PointFeatures::new(layout.clone());
+        features.extend_points(&points, None, 0.0);
+        Ok(features)
+    }
+}
+
+impl SweepGroup {
+    /// Aggregate the points of the key frame and up to `max_sweeps`
+    /// sweeps in the sensor frame of the key frame. The time feature
+    /// of each point is the time lag of its sweep.
+    pub fn load_lidar_features(
+        &self,
+        layout: &FeatureLayout,
+        max_sweeps: usize,
+    ) -> Result<PointFeatures> {
+        let mut features = self.key_frame.load_lidar_features(layout)?;
+        let global_to_key_frame = self.key_frame.sensor_to_global().inverse();
+
+        for sweep in self.sweeps.iter().take(max_sweeps) {
+            let points = sweep.data.load_lidar_bin_points()?;
+            let transform = global_to_key_frame.compose(&sweep.data.sensor_to_global());
+            features.extend_points(&points, Some(&transform), sweep.time_lag as f32);
+        }
+
+        Ok(features)
+    }
+}
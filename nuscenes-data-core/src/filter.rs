@@ -0,0 +1,63 @@
+//! Subsets of datasets in memory.
+//!
+//! The filters build new datasets from the selected scenes or channels
+//! with the same rules as [DatasetWriter]: dependent records are kept,
+//! chains are cut where they leave the selection, and counts are
+//! recomputed. Use [DatasetWriter] to save a subset on disk instead.
+//!
+//! The filters return an error if the selected records cannot be
+//! indexed, which indicates an inconsistent selection.
+
+use crate::{
+    dataset::{Dataset, SceneRef},
+    error::Result,
+    loader::index_selected,
+    serializable::Channel,
+    writer::DatasetWriter,
+};
+
+impl Dataset {
+    /// Keep the scenes satisfying the predicate and the records
+    /// depending on them.
+    ///
+    /// ```ignore
+    /// let night = dataset.filter_scenes(|scene| scene.description.contains("night"))?;
+    /// ```
+    pub fn filter_scenes<F>(&self, mut predicate: F) -> Result<Dataset>
+    where
+        F: FnMut(&SceneRef) -> bool,
+    {
+        let scenes = self
+            .scene_iter()
+            .filter(|scene| predicate(scene))
+            .map(|scene| scene.token);
+        self.filter_with(DatasetWriter::default().scenes(scenes))
+    }
+
+    /// Keep the scenes captured at the location, such as
+    /// `singapore-onenorth`.
+    pub fn filter_scenes_by_location(&self, location: &str) -> Result<Dataset> {
+        self.filter_scenes(|scene| scene.log().location == location)
+    }
+
+    /// Keep the scenes in the logs captured by the vehicle.
+    pub fn filter_scenes_by_vehicle(&self, vehicle: &str) -> Result<Dataset> {
+        self.filter_scenes(|scene| scene.log().vehicle == vehicle)
+    }
+
+    /// Keep only the sample data of the channels, together with the
+    /// ego poses and sensors they refer to. Scenes, samples and
+    /// annotations are kept entirely.
+    pub fn filter_channels<I>(&self, channels: I) -> Result<Dataset>
+    where
+        I: IntoIterator<Item = Channel>,
+    {
+        self.filter_with(DatasetWriter::default().channels(channels))
+    }
+
+    fn filter_with(&self, writer: DatasetWriter) -> Result<Dataset> {
+        let tables = writer.select(self);
+        let inner = index_selected(self, tables)?;
+        Ok(Dataset::from_inner(inner))
+    }
+}
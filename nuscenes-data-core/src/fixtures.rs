@@ -0,0 +1,82 @@
+//! A tiny dataset embedded in the crate for examples and tests.
+//!
+//! It contains one scene with two samples, each captured by a
+//! CAM_FRONT camera and a LIDAR_TOP lidar, and one annotated car.
+//! Only the metadata tables are included, so loading sample files
+//! fails.
+
+use crate::{error::Result, table::Table, Dataset};
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process,
+    sync::OnceLock,
+};
+
+/// The version directory name of the fixture.
+pub const VERSION: &str = "v1.0-fixture";
+
+macro_rules! fixture_table {
+    ($name:literal) => {
+        include_str!(concat!("../fixtures/v1.0-fixture/", $name, ".json"))
+    };
+}
+
+/// Get the content of a table file in the fixture.
+pub fn table_json(table: Table) -> &'static str {
+    match table {
+        Table::Attribute => fixture_table!("attribute"),
+        Table::CalibratedSensor => fixture_table!("calibrated_sensor"),
+        Table::Category => fixture_table!("category"),
+        Table::EgoPose => fixture_table!("ego_pose"),
+        Table::Instance => fixture_table!("instance"),
+        Table::Log => fixture_table!("log"),
+        Table::Map => fixture_table!("map"),
+        Table::Sample => fixture_table!("sample"),
+        Table::SampleAnnotation => fixture_table!("sample_annotation"),
+        Table::SampleData => fixture_table!("sample_data"),
+        Table::Scene => fixture_table!("scene"),
+        Table::Sensor => fixture_table!("sensor"),
+        Table::Visibility => fixture_table!("visibility"),
+    }
+}
+
+/// Write the fixture tables to the version directory in
+/// `dataset_dir`.
+pub fn write_to<P>(dataset_dir: P) -> Result<()>
+where
+    P: AsRef<Path>,
+{
+    let meta_dir = dataset_dir.as_ref().join(VERSION);
+    fs::create_dir_all(&meta_dir)?;
+
+    for table in Table::ALL {
+        fs::write(meta_dir.join(table.file_name()), table_json(table))?;
+    }
+    Ok(())
+}
+
+/// Get a dataset directory containing the fixture. The fixture is
+/// written to a temporary directory once per process.
+pub fn dataset_dir() -> Result<PathBuf> {
+    static DIR: OnceLock<PathBuf> = OnceLock::new();
+
+    if let Some(dir) = DIR.get() {
+        return Ok(dir.clone());
+    }
+
+    let dir = env::temp_dir().join(format!("nuscenes-data-fixture-{}", process::id()));
+    write_to(&dir)?;
+    Ok(DIR.get_or_init(|| dir).clone())
+}
+
+/// Load the fixture dataset.
+///
+/// ```
+/// let dataset = nuscenes_data_core::fixtures::load()?;
+/// assert_eq!(dataset.scene_iter().count(), 1);
+/// # Ok::<(), nuscenes_data_core::error::Error>(())
+/// ```
+pub fn load() -> Result<Dataset> {
+    Dataset::load(VERSION, dataset_dir()?)
+}
@@ -0,0 +1,138 @@
+//! Scene flow ground truth generation.
+//!
+//! Points of a lidar key frame are assigned to annotation boxes. A
+//! point inside a box moves rigidly with the box to the annotation of
+//! the same instance in the next sample, and other points are
+//! considered static in the global frame.
+
+use crate::{
+    dataset::{SampleDataRef, SampleRef},
+    error::{Error, Result},
+    geometry::{Box3D, Transform},
+    pointcloud::PointCloud,
+    serializable::{Channel, Token},
+};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone)]
+pub struct FlowOptions {
+    /// The lidar channel to generate flow for.
+    pub channel: Channel,
+    /// Exclude the ego motion from flow vectors, so that static points
+    /// have zero flow. Otherwise, the flow of a point is its
+    /// displacement seen by the sensor, which moves with the vehicle.
+    pub compensate_ego_motion: bool,
+}
+
+impl Default for FlowOptions {
+    fn default() -> Self {
+        Self {
+            channel: Channel::LidarTop,
+            compensate_ego_motion: true,
+        }
+    }
+}
+
+/// Per-point scene flow of a lidar sweep.
+#[derive(Debug, Clone)]
+pub struct SceneFlow {
+    /// The sweep the flow is computed for.
+    pub sample_data_token: Token,
+    /// Flow vectors in meters, expressed in the sensor frame of the
+    /// sweep.
+    pub flow: Vec<[f32; 3]>,
+    /// The instance each point is assigned to, or `None` if the point
+    /// is static.
+    pub instance_tokens: Vec<Option<Token>>,
+    /// The time span of the flow in seconds.
+    pub dt: f64,
+}
+
+/// Generate scene flow from `sample` to `next_sample` with default
+/// options.
+pub fn generate(sample: &SampleRef, next_sample: &SampleRef) -> Result<SceneFlow> {
+    generate_with_options(sample, next_sample, &FlowOptions::default())
+}
+
+/// Generate scene flow from `sample` to `next_sample`.
+///
+/// Points in boxes of instances not annotated in `next_sample` are
+/// treated as static.
+pub fn generate_with_options(
+    sample: &SampleRef,
+    next_sample: &SampleRef,
+    options: &FlowOptions,
+) -> Result<SceneFlow> {
+    let data = lidar_data(sample, options.channel)?;
+    let next_data = lidar_data(next_sample, options.channel)?;
+    let points = PointCloud::from_lidar_bin_bytes(&data.read_bytes()?)
+        .map_err(|_| Error::CorruptedFile(data.path().into()))?;
+
+    let to_global = data.sensor_to_global();
+    let global_to_sensor = to_global.inverse();
+    let global_to_next_sensor = next_data.sensor_to_global().inverse();
+
+    // Pair boxes of the same instance in both samples
+    let next_boxes: HashMap<Token, Box3D> = next_sample
+        .annotation_iter()
+        .map(|annotation| (annotation.instance_token, Box3D::from(&*annotation)))
+        .collect();
+    let motions: Vec<(Token, Box3D, Transform)> = sample
+        .annotation_iter()
+        .filter_map(|annotation| {
+            let curr_box = Box3D::from(&*annotation);
+            let next_box = next_boxes.get(&annotation.instance_token)?;
+            let motion = next_box
+                .to_transform()
+                .compose(&curr_box.to_transform().inverse());
+            Some((annotation.instance_token, curr_box, motion))
+        })
+        .collect();
+
+    let (flow, instance_tokens) = points
+        .positions
+        .iter()
+        .map(|&position| {
+            let point = to_global.apply(position.map(|v| v as f64));
+            let assigned = motions
+                .iter()
+                .find(|(_, curr_box, _)| curr_box.contains(point));
+            let next_point = match assigned {
+                Some((_, _, motion)) => motion.apply(point),
+                None => point,
+            };
+
+            let [x, y, z] = if options.compensate_ego_motion {
+                let start = global_to_sensor.apply(point);
+                let end = global_to_sensor.apply(next_point);
+                [end[0] - start[0], end[1] - start[1], end[2] - start[2]]
+            } else {
+                let end = global_to_next_sensor.apply(next_point);
+                let [px, py, pz] = position;
+                [end[0] - px as f64, end[1] - py as f64, end[2] - pz as f64]
+            };
+
+            let flow = [x as f32, y as f32, z as f32];
+            (flow, assigned.map(|(token, _, _)| *token))
+        })
+        .unzip();
+
+    let dt = (next_data.timestamp - data.timestamp)
+        .num_microseconds()
+        .unwrap() as f64
+        / 1_000_000.0;
+
+    Ok(SceneFlow {
+        sample_data_token: data.token,
+        flow,
+        instance_tokens,
+        dt,
+    })
+}
+
+fn lidar_data(sample: &SampleRef, channel: Channel) -> Result<SampleDataRef> {
+    sample.sample_data_by_channel(channel).ok_or_else(|| {
+        let msg = format!("the sample {} has no {} data", sample.token, channel);
+        Error::CorruptedDataset(msg)
+    })
+}
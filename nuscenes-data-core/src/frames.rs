@@ -0,0 +1,170 @@
+//! Camera and lidar frame tuples for self-supervised learning.
+//!
+//! Each tuple consists of a target camera frame, the lidar sweep
+//! closest in time, and neighboring camera frames at configured gaps
+//! along with their poses relative to the target frame. Relative
+//! poses between arbitrary sample pairs are available through
+//! [SampleRef::relative_pose_to].
+
+use crate::{
+    dataset::{Dataset, SampleDataRef, SampleRef, SceneRef},
+    geometry::Transform,
+    serializable::Channel,
+};
+
+#[derive(Debug, Clone)]
+pub struct FrameTupleConfig {
+    pub camera: Channel,
+    pub lidar: Channel,
+    /// The offsets of context frames in number of camera frames. For
+    /// example, `-1` is the previous frame.
+    pub frame_gaps: Vec<isize>,
+    /// Use only key frames as target frames. Context frames may still
+    /// be non-key frames.
+    pub key_frames_only: bool,
+}
+
+impl Default for FrameTupleConfig {
+    fn default() -> Self {
+        Self {
+            camera: Channel::CamFront,
+            lidar: Channel::LidarTop,
+            frame_gaps: vec![-1, 1],
+            key_frames_only: false,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct FrameTuple {
+    /// The target camera frame.
+    pub image: SampleDataRef,
+    /// The lidar sweep closest in time to the target frame.
+    pub lidar: SampleDataRef,
+    /// Transforms points from the lidar frame to the target camera
+    /// frame.
+    pub lidar_to_camera: Transform,
+    /// Context frames in the order of `frame_gaps`.
+    pub context: Vec<ContextFrame>,
+}
+
+#[derive(Clone)]
+pub struct ContextFrame {
+    pub gap: isize,
+    pub image: SampleDataRef,
+    /// Transforms points from the context camera frame to the target
+    /// camera frame.
+    pub relative_pose: Transform,
+}
+
+impl SceneRef {
+    /// Iterate over frame tuples in the scene in chronological order.
+    ///
+    /// Target frames without every context frame are skipped.
+    pub fn frame_tuple_iter(
+        &self,
+        config: &FrameTupleConfig,
+    ) -> impl Iterator<Item = FrameTuple> + Send + Sync {
+        let images = channel_frames(self, config.camera);
+        let lidars = channel_frames(self, config.lidar);
+        let config = config.clone();
+
+        (0..images.len()).filter_map(move |index| {
+            let image = &images[index];
+            if config.key_frames_only && !image.is_key_frame {
+                return None;
+            }
+            let camera_to_global = image.sensor_to_global();
+            let global_to_camera = camera_to_global.inverse();
+
+            // Find the closest lidar sweep
+            let pos = lidars.partition_point(|lidar| lidar.timestamp < image.timestamp);
+            let lidar = [pos.checked_sub(1), Some(pos)]
+                .into_iter()
+                .flatten()
+                .filter_map(|pos| lidars.get(pos))
+                .min_by_key(|lidar| {
+                    (lidar.timestamp - image.timestamp)
+                        .num_microseconds()
+                        .map(i64::abs)
+                })?
+                .clone();
+            let lidar_to_camera = global_to_camera.compose(&lidar.sensor_to_global());
+
+            let context = config
+                .frame_gaps
+                .iter()
+                .map(|&gap| {
+                    let context_index = index.checked_add_signed(gap)?;
+                    let context_image = images.get(context_index)?.clone();
+                    let relative_pose = global_to_camera.compose(&context_image.sensor_to_global());
+                    Some(ContextFrame {
+                        gap,
+                        image: context_image,
+                        relative_pose,
+                    })
+                })
+                .collect::<Option<Vec<_>>>()?;
+
+            Some(FrameTuple {
+                image: image.clone(),
+                lidar,
+                lidar_to_camera,
+                context,
+            })
+        })
+    }
+}
+
+impl SampleRef {
+    /// Get the transform from the ego frame of this sample to the ego
+    /// frame of the `other` sample.
+    ///
+    /// The ego pose of a sample is the one of its LIDAR_TOP key frame.
+    /// It returns `None` if either sample lacks the lidar data.
+    pub fn relative_pose_to(&self, other: &SampleRef) -> Option<Transform> {
+        let from = self
+            .sample_data_by_channel(Channel::LidarTop)?
+            .ego_to_global();
+        let to = other
+            .sample_data_by_channel(Channel::LidarTop)?
+            .ego_to_global();
+        Some(to.inverse().compose(&from))
+    }
+
+    /// Get the transform from the camera frame in this sample to the
+    /// camera frame in the `other` sample. The ego poses at the
+    /// camera timestamps are used.
+    pub fn relative_camera_pose_to(
+        &self,
+        other: &SampleRef,
+        channel: Channel,
+    ) -> Option<Transform> {
+        let from = self.sample_data_by_channel(channel)?.sensor_to_global();
+        let to = other.sample_data_by_channel(channel)?.sensor_to_global();
+        Some(to.inverse().compose(&from))
+    }
+}
+
+impl Dataset {
+    /// Iterate over frame tuples in all scenes.
+    pub fn frame_tuple_iter<'a>(
+        &'a self,
+        config: &'a FrameTupleConfig,
+    ) -> impl Iterator<Item = FrameTuple> + Send + Sync + 'a {
+        self.scene_iter()
+            .flat_map(move |scene| scene.frame_tuple_iter(config))
+    }
+}
+
+/// Collect sample data of the channel in the scene sorted by
+/// timestamps, including non-key frames.
+fn channel_frames(scene: &SceneRef, channel: Channel) -> Vec<SampleDataRef> {
+    let mut frames: Vec<_> = scene
+        .sample_iter()
+        .flat_map(|sample| sample.sample_data_iter().collect::<Vec<_>>())
+        .filter(|data| data.channel() == channel)
+        .collect();
+    frames.sort_by_key(|data| data.timestamp);
+    frames
+}
@@ -0,0 +1,372 @@
+//! Rigid transformation helpers.
+//!
+//! Quaternions follow the nuScenes convention, that is, `[w, x, y,
+//! z]` arrays.
+
+use crate::{
+    dataset::{self, Dataset, DatasetInner, SampleAnnotationRef, SampleDataRef, SceneRef},
+    serializable::{EgoPose, SampleAnnotation, Token},
+};
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+
+/// Multiply two quaternions.
+pub fn quat_mul(lhs: [f64; 4], rhs: [f64; 4]) -> [f64; 4] {
+    let [w1, x1, y1, z1] = lhs;
+    let [w2, x2, y2, z2] = rhs;
+    [
+        w1 * w2 - x1 * x2 - y1 * y2 - z1 * z2,
+        w1 * x2 + x1 * w2 + y1 * z2 - z1 * y2,
+        w1 * y2 - x1 * z2 + y1 * w2 + z1 * x2,
+        w1 * z2 + x1 * y2 - y1 * x2 + z1 * w2,
+    ]
+}
+
+/// Get the conjugate of a quaternion, which is the inverse rotation
+/// of a unit quaternion.
+pub fn quat_conj(quat: [f64; 4]) -> [f64; 4] {
+    let [w, x, y, z] = quat;
+    [w, -x, -y, -z]
+}
+
+pub fn quat_normalize(quat: [f64; 4]) -> [f64; 4] {
+    let norm = quat.iter().map(|v| v * v).sum::<f64>().sqrt();
+    quat.map(|v| v / norm)
+}
+
+/// Rotate a point by a unit quaternion.
+pub fn quat_rotate(quat: [f64; 4], point: [f64; 3]) -> [f64; 3] {
+    let [_, x, y, z] = quat_mul(
+        quat_mul(quat, [0.0, point[0], point[1], point[2]]),
+        quat_conj(quat),
+    );
+    [x, y, z]
+}
+
+/// Spherically interpolate two unit quaternions, where the `ratio` is
+/// zero at `lhs` and one at `rhs`. It takes the shorter arc.
+pub fn quat_slerp(lhs: [f64; 4], rhs: [f64; 4], ratio: f64) -> [f64; 4] {
+    let mut dot: f64 = lhs.iter().zip(&rhs).map(|(a, b)| a * b).sum();
+    let rhs = if dot < 0.0 {
+        dot = -dot;
+        rhs.map(|v| -v)
+    } else {
+        rhs
+    };
+
+    // Fall back to linear interpolation for nearly equal rotations
+    let (lhs_weight, rhs_weight) = if dot > 0.9995 {
+        (1.0 - ratio, ratio)
+    } else {
+        let theta = dot.acos();
+        let sin = theta.sin();
+        (
+            ((1.0 - ratio) * theta).sin() / sin,
+            (ratio * theta).sin() / sin,
+        )
+    };
+    let mut quat = [0.0; 4];
+    for (i, value) in quat.iter_mut().enumerate() {
+        *value = lhs[i] * lhs_weight + rhs[i] * rhs_weight;
+    }
+    quat_normalize(quat)
+}
+
+/// Convert a unit quaternion to (roll, pitch, yaw) angles in radians,
+/// where the rotation is applied in X, Y and then Z fixed axes.
+pub fn quat_to_rpy(quat: [f64; 4]) -> [f64; 3] {
+    let [w, x, y, z] = quat;
+    let roll = (2.0 * (w * x + y * z)).atan2(1.0 - 2.0 * (x * x + y * y));
+    let pitch = (2.0 * (w * y - z * x)).clamp(-1.0, 1.0).asin();
+    let yaw = (2.0 * (w * z + x * y)).atan2(1.0 - 2.0 * (y * y + z * z));
+    [roll, pitch, yaw]
+}
+
+/// Get the heading angle of a unit quaternion, that is, the angle of
+/// the rotated x-axis projected on the xy-plane.
+pub fn quat_yaw(quat: [f64; 4]) -> f64 {
+    let [x, y, _] = quat_rotate(quat, [1.0, 0.0, 0.0]);
+    y.atan2(x)
+}
+
+/// Create the unit quaternion rotating about the z-axis by the yaw
+/// angle in radians.
+pub fn yaw_quat(yaw: f64) -> [f64; 4] {
+    let (sin, cos) = (yaw / 2.0).sin_cos();
+    [cos, 0.0, 0.0, sin]
+}
+
+/// Get the area of a simple polygon.
+pub fn polygon_area(polygon: &[[f64; 2]]) -> f64 {
+    let sum: f64 = polygon
+        .iter()
+        .zip(polygon.iter().cycle().skip(1))
+        .map(|(a, b)| a[0] * b[1] - b[0] * a[1])
+        .sum();
+    sum.abs() / 2.0
+}
+
+/// A rigid transformation, which rotates a point and then translates
+/// it.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Transform {
+    pub rotation: [f64; 4],
+    pub translation: [f64; 3],
+}
+
+impl Transform {
+    pub fn identity() -> Self {
+        Self {
+            rotation: [1.0, 0.0, 0.0, 0.0],
+            translation: [0.0; 3],
+        }
+    }
+
+    pub fn new(rotation: [f64; 4], translation: [f64; 3]) -> Self {
+        Self {
+            rotation: quat_normalize(rotation),
+            translation,
+        }
+    }
+
+    pub fn inverse(&self) -> Self {
+        let rotation = quat_conj(self.rotation);
+        let [x, y, z] = quat_rotate(rotation, self.translation);
+        Self {
+            rotation,
+            translation: [-x, -y, -z],
+        }
+    }
+
+    /// Compose two transforms such that the `other` transform is
+    /// applied first.
+    pub fn compose(&self, other: &Transform) -> Self {
+        let [x, y, z] = self.apply(other.translation);
+        Self {
+            rotation: quat_normalize(quat_mul(self.rotation, other.rotation)),
+            translation: [x, y, z],
+        }
+    }
+
+    pub fn apply(&self, point: [f64; 3]) -> [f64; 3] {
+        let [x, y, z] = quat_rotate(self.rotation, point);
+        let [tx, ty, tz] = self.translation;
+        [x + tx, y + ty, z + tz]
+    }
+
+    pub fn apply_f32(&self, point: [f32; 3]) -> [f32; 3] {
+        self.apply(point.map(|v| v as f64)).map(|v| v as f32)
+    }
+
+    pub fn rpy(&self) -> [f64; 3] {
+        quat_to_rpy(self.rotation)
+    }
+
+    /// Interpolate two transforms, where the `ratio` is zero at `self`
+    /// and one at `other`. Translations are interpolated linearly and
+    /// rotations spherically.
+    pub fn interpolate(&self, other: &Transform, ratio: f64) -> Self {
+        let mut translation = [0.0; 3];
+        for (i, value) in translation.iter_mut().enumerate() {
+            *value = self.translation[i] + (other.translation[i] - self.translation[i]) * ratio;
+        }
+        Self {
+            rotation: quat_slerp(self.rotation, other.rotation, ratio),
+            translation,
+        }
+    }
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+/// An oriented 3D box.
+///
+/// The size follows the nuScenes convention `[width, length,
+/// height]`, where the length is along the x-axis of the box.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Box3D {
+    pub center: [f64; 3],
+    pub size: [f64; 3],
+    pub rotation: [f64; 4],
+}
+
+impl Box3D {
+    pub fn new(center: [f64; 3], size: [f64; 3], rotation: [f64; 4]) -> Self {
+        Self {
+            center,
+            size,
+            rotation: quat_normalize(rotation),
+        }
+    }
+
+    /// Create a box rotated about the z-axis by the yaw angle in
+    /// radians.
+    pub fn from_yaw(center: [f64; 3], size: [f64; 3], yaw: f64) -> Self {
+        Self {
+            center,
+            size,
+            rotation: yaw_quat(yaw),
+        }
+    }
+
+    /// Drop the roll and pitch of the box, keeping the heading as
+    /// defined by [quat_yaw].
+    pub fn to_yaw_only(&self) -> Self {
+        Self::from_yaw(self.center, self.size, self.yaw())
+    }
+
+    /// Get the transform from the box frame to the frame the box is
+    /// expressed in.
+    pub fn to_transform(&self) -> Transform {
+        Transform {
+            rotation: self.rotation,
+            translation: self.center,
+        }
+    }
+
+    /// Express the box in another frame.
+    pub fn transform(&self, transform: &Transform) -> Self {
+        let pose = transform.compose(&self.to_transform());
+        Self {
+            center: pose.translation,
+            size: self.size,
+            rotation: pose.rotation,
+        }
+    }
+
+    pub fn yaw(&self) -> f64 {
+        quat_yaw(self.rotation)
+    }
+
+    /// Check if the point is inside the box, including the surface.
+    pub fn contains(&self, point: [f64; 3]) -> bool {
+        let [x, y, z] = self.to_transform().inverse().apply(point);
+        let [width, length, height] = self.size;
+        x.abs() <= length / 2.0 && y.abs() <= width / 2.0 && z.abs() <= height / 2.0
+    }
+
+    /// Get the four corners of the footprint on the xy-plane in
+    /// counterclockwise order, ignoring the roll and pitch.
+    pub fn bev_corners(&self) -> [[f64; 2]; 4] {
+        let [width, length, _] = self.size;
+        let (hw, hl) = (width / 2.0, length / 2.0);
+        let [x, y, _] = self.center;
+        let (sin, cos) = self.yaw().sin_cos();
+        [[hl, -hw], [hl, hw], [-hl, hw], [-hl, -hw]]
+            .map(|[dx, dy]| [x + cos * dx - sin * dy, y + sin * dx + cos * dy])
+    }
+
+    /// Get the eight corners in the devkit order. The first four
+    /// corners face the forward direction and the first two corners
+    /// of each face are on the top.
+    pub fn corners(&self) -> [[f64; 3]; 8] {
+        let [width, length, height] = self.size;
+        let (hw, hl, hh) = (width / 2.0, length / 2.0, height / 2.0);
+        let transform = self.to_transform();
+        [
+            [hl, hw, hh],
+            [hl, -hw, hh],
+            [hl, -hw, -hh],
+            [hl, hw, -hh],
+            [-hl, hw, hh],
+            [-hl, -hw, hh],
+            [-hl, -hw, -hh],
+            [-hl, hw, -hh],
+        ]
+        .map(|corner| transform.apply(corner))
+    }
+}
+
+impl From<&SampleAnnotation> for Box3D {
+    fn from(annotation: &SampleAnnotation) -> Self {
+        Self::new(annotation.translation, annotation.size, annotation.rotation)
+    }
+}
+
+impl SampleAnnotationRef {
+    /// Get the heading of the box about the global z-axis in radians.
+    pub fn yaw(&self) -> f64 {
+        quat_yaw(self.rotation)
+    }
+}
+
+impl SampleDataRef {
+    /// Get the transform from the sensor frame to the ego frame.
+    pub fn sensor_to_ego(&self) -> Transform {
+        let calibrated_sensor = self.calibrated_sensor();
+        Transform::new(calibrated_sensor.rotation, calibrated_sensor.translation)
+    }
+
+    /// Get the transform from the ego frame to the global frame at the
+    /// time the data is captured.
+    pub fn ego_to_global(&self) -> Transform {
+        let ego_pose = self.ego_pose();
+        Transform::new(ego_pose.rotation, ego_pose.translation)
+    }
+
+    /// Get the transform from the sensor frame to the global frame at
+    /// the time the data is captured.
+    pub fn sensor_to_global(&self) -> Transform {
+        self.ego_to_global().compose(&self.sensor_to_ego())
+    }
+
+    /// Transform points from the sensor frame to the ego frame in
+    /// place.
+    pub fn transform_points_to_ego(&self, points: &mut [[f32; 3]]) {
+        transform_points(&self.sensor_to_ego(), points);
+    }
+
+    /// Transform points from the sensor frame to the global frame in
+    /// place.
+    pub fn transform_points_to_global(&self, points: &mut [[f32; 3]]) {
+        transform_points(&self.sensor_to_global(), points);
+    }
+}
+
+fn transform_points(transform: &Transform, points: &mut [[f32; 3]]) {
+    for point in points {
+        *point = transform.apply_f32(*point);
+    }
+}
+
+impl Dataset {
+    /// Get the transform from the ego frame to the global frame at the
+    /// timestamp, interpolated between the ego poses before and after
+    /// it. It returns `None` if the timestamp is not within the time
+    /// span of the ego poses.
+    pub fn ego_to_global_at(&self, timestamp: NaiveDateTime) -> Option<Transform> {
+        interpolate_ego_pose(self, timestamp, None)
+    }
+}
+
+impl SceneRef {
+    /// Get the transform from the ego frame to the global frame at the
+    /// timestamp, interpolated between the ego poses of the sample data
+    /// of the scene. It returns `None` if the timestamp is not within
+    /// the time span of the scene.
+    pub fn ego_to_global_at(&self, timestamp: NaiveDateTime) -> Option<Transform> {
+        interpolate_ego_pose(&self.dataset(), timestamp, Some(self.token))
+    }
+}
+
+fn interpolate_ego_pose(
+    inner: &DatasetInner,
+    timestamp: NaiveDateTime,
+    scene_token: Option<Token>,
+) -> Option<Transform> {
+    let [prev, next] = dataset::ego_pose_neighbors(inner, timestamp, scene_token);
+    let next = &inner.ego_pose_map[&next?];
+    let to_transform = |pose: &EgoPose| Transform::new(pose.rotation, pose.translation);
+    if next.timestamp == timestamp {
+        return Some(to_transform(next));
+    }
+
+    let prev = &inner.ego_pose_map[&prev?];
+    let span = (next.timestamp - prev.timestamp).num_microseconds()? as f64;
+    let ratio = (timestamp - prev.timestamp).num_microseconds()? as f64 / span;
+    Some(to_transform(prev).interpolate(&to_transform(next), ratio))
+}
@@ -0,0 +1,146 @@
+//! Annotation density heatmaps over maps.
+//!
+//! Annotation centers are counted on a grid in the map frame, whose
+//! origin is the bottom-left corner of the map raster.
+//! [MapRef::annotation_heatmaps_cached] stores the heatmaps in a
+//! [DerivedCache].
+
+use crate::{
+    cache::{ArtifactKey, DerivedCache},
+    dataset::MapRef,
+    error::Result,
+    serializable::Token,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+
+/// The cache entry of heatmaps counted by
+/// [MapRef::annotation_heatmaps_cached].
+pub const HEATMAP_ARTIFACT: ArtifactKey = ArtifactKey::new("annotation_heatmaps", 1);
+
+/// A grid of annotation counts. Cells are stored row by row, where
+/// row 0 is at the smallest y coordinate.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Heatmap {
+    /// The cell size in meters.
+    pub cell_size: f64,
+    pub width: usize,
+    pub height: usize,
+    pub counts: Vec<u32>,
+}
+
+impl Heatmap {
+    pub fn new(cell_size: f64, width: usize, height: usize) -> Self {
+        assert_valid_cell_size(cell_size);
+        Self {
+            cell_size,
+            width,
+            height,
+            counts: vec![0; width * height],
+        }
+    }
+
+    pub fn get(&self, row: usize, col: usize) -> Option<u32> {
+        (row < self.height && col < self.width).then(|| self.counts[row * self.width + col])
+    }
+
+    /// Get the cell index of a position in the map frame.
+    pub fn cell_of(&self, x: f64, y: f64) -> Option<(usize, usize)> {
+        if x < 0.0 || y < 0.0 {
+            return None;
+        }
+        let col = (x / self.cell_size) as usize;
+        let row = (y / self.cell_size) as usize;
+        (row < self.height && col < self.width).then_some((row, col))
+    }
+
+    /// Count a position in the map frame. Positions outside the grid
+    /// are ignored.
+    pub fn add(&mut self, x: f64, y: f64) {
+        if let Some((row, col)) = self.cell_of(x, y) {
+            self.counts[row * self.width + col] += 1;
+        }
+    }
+
+    pub fn max_count(&self) -> u32 {
+        self.counts.iter().copied().max().unwrap_or(0)
+    }
+
+    pub fn total(&self) -> u64 {
+        self.counts.iter().map(|&count| count as u64).sum()
+    }
+
+    /// Get the counts scaled to [0, 1] by the maximum count.
+    pub fn normalized(&self) -> Vec<f32> {
+        let max = self.max_count().max(1) as f32;
+        self.counts
+            .iter()
+            .map(|&count| count as f32 / max)
+            .collect()
+    }
+
+    /// Get the counts as rows.
+    pub fn rows(&self) -> Vec<Vec<u32>> {
+        self.counts
+            .chunks(self.width.max(1))
+            .map(|row| row.to_vec())
+            .collect()
+    }
+}
+
+impl MapRef {
+    /// Count annotation centers of the scenes on this map, grouped by
+    /// category names. All heatmaps share the same grid, which covers
+    /// all annotations on the map.
+    pub fn annotation_heatmaps(&self, cell_size: f64) -> BTreeMap<String, Heatmap> {
+        assert_valid_cell_size(cell_size);
+        let log_tokens: HashSet<Token> = self.log_tokens.iter().copied().collect();
+        let dataset = self.dataset();
+
+        let positions: Vec<(String, f64, f64)> = dataset
+            .scene_iter()
+            .filter(|scene| log_tokens.contains(&scene.log_token))
+            .flat_map(|scene| scene.sample_iter().collect::<Vec<_>>())
+            .flat_map(|sample| sample.annotation_iter().collect::<Vec<_>>())
+            .map(|annotation| {
+                let [x, y, _] = annotation.translation;
+                let category = annotation.instance().category().name.clone();
+                (category, x, y)
+            })
+            .collect();
+
+        let max_x = positions.iter().map(|p| p.1).fold(0.0, f64::max);
+        let max_y = positions.iter().map(|p| p.2).fold(0.0, f64::max);
+        let width = (max_x / cell_size) as usize + 1;
+        let height = (max_y / cell_size) as usize + 1;
+
+        let mut heatmaps = BTreeMap::new();
+        for (category, x, y) in positions {
+            heatmaps
+                .entry(category)
+                .or_insert_with(|| Heatmap::new(cell_size, width, height))
+                .add(x, y);
+        }
+        heatmaps
+    }
+
+    /// Load the heatmaps of [MapRef::annotation_heatmaps] from the
+    /// cache, or count and store them if they are not cached for the
+    /// map and the cell size.
+    pub fn annotation_heatmaps_cached(
+        &self,
+        cache: &DerivedCache,
+        cell_size: f64,
+    ) -> Result<BTreeMap<String, Heatmap>> {
+        assert_valid_cell_size(cell_size);
+        let key = HEATMAP_ARTIFACT.with_params(&(self.token, cell_size));
+        cache.load_or_compute_json(key, || Ok(self.annotation_heatmaps(cell_size)))
+    }
+}
+
+fn assert_valid_cell_size(cell_size: f64) {
+    assert!(
+        cell_size > 0.0 && cell_size.is_finite(),
+        "cell_size must be positive and finite"
+    );
+}
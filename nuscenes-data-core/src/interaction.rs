@@ -0,0 +1,173 @@
+//! Interactions between nearby agents.
+//!
+//! Relative positions and velocities are expressed in the box frame of
+//! the annotation, where x points forward and y points left.
+
+use crate::{
+    dataset::SampleAnnotationRef,
+    geometry::{quat_conj, quat_rotate, Box3D},
+    serializable::Token,
+    spatial::DEFAULT_CELL_SIZE,
+};
+use std::fmt::{self, Display};
+
+/// The default search radius in meters of interactions.
+pub const DEFAULT_INTERACTION_RADIUS: f64 = 20.0;
+
+/// Half of the lane width in meters, used to decide whether an agent
+/// is on the path of the other.
+pub const LANE_HALF_WIDTH: f64 = 1.75;
+
+/// The minimum speed in m/s of a pedestrian to be crossing.
+pub const MIN_CROSSING_SPEED: f64 = 0.2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum InteractionKind {
+    /// The other vehicle is ahead on the path of this vehicle.
+    LeadingVehicle,
+    /// The other vehicle is behind on the path of this vehicle.
+    FollowingVehicle,
+    /// The other pedestrian is ahead of this vehicle and walking
+    /// across its path.
+    CrossingPedestrian,
+    /// The other agent is nearby without a specific interaction.
+    Nearby,
+}
+
+impl InteractionKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::LeadingVehicle => "leading_vehicle",
+            Self::FollowingVehicle => "following_vehicle",
+            Self::CrossingPedestrian => "crossing_pedestrian",
+            Self::Nearby => "nearby",
+        }
+    }
+}
+
+impl Display for InteractionKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// An agent near an annotation.
+#[derive(Debug, Clone)]
+pub struct Interaction {
+    pub other_annotation_token: Token,
+    pub other_category: String,
+    /// The center distance in meters on the xy plane.
+    pub distance: f64,
+    /// The center of the other agent in the box frame.
+    pub relative_position: [f64; 2],
+    pub kind: InteractionKind,
+}
+
+impl SampleAnnotationRef {
+    /// Find agents within [DEFAULT_INTERACTION_RADIUS] and classify
+    /// the interactions, sorted by distances.
+    pub fn interactions(&self) -> Vec<Interaction> {
+        self.interactions_within(DEFAULT_INTERACTION_RADIUS)
+    }
+
+    /// Find agents within the radius in meters and classify the
+    /// interactions, sorted by distances.
+    pub fn interactions_within(&self, radius: f64) -> Vec<Interaction> {
+        let index = self.sample().annotation_index(DEFAULT_CELL_SIZE);
+        let [x, y, _] = self.translation;
+        let this_box = Box3D::from(&**self);
+        let is_vehicle = self.instance().category().name.starts_with("vehicle.");
+
+        index
+            .within([x, y], radius)
+            .into_iter()
+            .filter(|(_, other)| other.token != self.token)
+            .map(|(distance, other)| {
+                let other_category = other.instance().category().name.clone();
+                let relative_position = to_box_frame(&this_box, other.translation);
+
+                let kind = if !is_vehicle {
+                    InteractionKind::Nearby
+                } else if other_category.starts_with("vehicle.") {
+                    classify_vehicle(relative_position)
+                } else if other_category.starts_with("human.pedestrian.") {
+                    classify_pedestrian(&this_box, relative_position, other)
+                } else {
+                    InteractionKind::Nearby
+                };
+
+                Interaction {
+                    other_annotation_token: other.token,
+                    other_category,
+                    distance,
+                    relative_position,
+                    kind,
+                }
+            })
+            .collect()
+    }
+
+    /// Estimate the velocity in m/s in the global frame from the
+    /// neighboring annotations of the same instance.
+    pub fn velocity(&self) -> Option<[f64; 2]> {
+        let prev = self.prev();
+        let next = self.next();
+        let (first, last) = match (&prev, &next) {
+            (Some(prev), Some(next)) => (prev, next),
+            (Some(prev), None) => (prev, self),
+            (None, Some(next)) => (self, next),
+            (None, None) => return None,
+        };
+
+        let time_diff = (last.sample().timestamp - first.sample().timestamp).num_microseconds()?
+            as f64
+            / 1_000_000.0;
+        if time_diff <= 0.0 {
+            return None;
+        }
+        Some([
+            (last.translation[0] - first.translation[0]) / time_diff,
+            (last.translation[1] - first.translation[1]) / time_diff,
+        ])
+    }
+}
+
+fn to_box_frame(this_box: &Box3D, point: [f64; 3]) -> [f64; 2] {
+    let [x, y, _] = this_box.to_transform().inverse().apply(point);
+    [x, y]
+}
+
+fn classify_vehicle([x, y]: [f64; 2]) -> InteractionKind {
+    if y.abs() > LANE_HALF_WIDTH {
+        InteractionKind::Nearby
+    } else if x > 0.0 {
+        InteractionKind::LeadingVehicle
+    } else {
+        InteractionKind::FollowingVehicle
+    }
+}
+
+fn classify_pedestrian(
+    this_box: &Box3D,
+    [x, y]: [f64; 2],
+    pedestrian: &SampleAnnotationRef,
+) -> InteractionKind {
+    if x <= 0.0 {
+        return InteractionKind::Nearby;
+    }
+    let Some([vx, vy]) = pedestrian.velocity() else {
+        return InteractionKind::Nearby;
+    };
+
+    // Express the velocity in the box frame
+    let [vx, vy, _] = quat_rotate(quat_conj(this_box.rotation), [vx, vy, 0.0]);
+    let on_path = y.abs() <= LANE_HALF_WIDTH;
+    let approaching = y * vy < 0.0;
+    let crossing = vx.hypot(vy) >= MIN_CROSSING_SPEED && vy.abs() > vx.abs();
+
+    if crossing && (on_path || approaching) {
+        InteractionKind::CrossingPedestrian
+    } else {
+        InteractionKind::Nearby
+    }
+}
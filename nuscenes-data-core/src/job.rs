@@ -0,0 +1,214 @@
+//! Resumable long-running jobs over scenes.
+//!
+//! Exports of a full dataset run for hours. A [ResumableJob] processes
+//! scenes one by one and records the completed scenes together with
+//! the job state in a checkpoint file after each scene. If the job is
+//! interrupted, it is reopened with the same checkpoint path and skips
+//! the completed scenes. The checkpoint is replaced atomically, so a
+//! crash leaves either the previous or the next checkpoint.
+//!
+//! The work on a scene must be safe to redo, since a scene interrupted
+//! in the middle is processed again. Exporters achieve this by storing
+//! the output file sizes in the state and truncating the outputs to
+//! them on resume.
+
+use crate::{
+    dataset::SceneRef,
+    error::{Error, Result},
+    serializable::Token,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{
+    collections::HashSet,
+    ffi::OsString,
+    fs::{self, File},
+    io::{self, BufReader, BufWriter},
+    path::{Path, PathBuf},
+};
+
+/// The content of a checkpoint file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Checkpoint<S> {
+    /// The name of the job, which must match when the job is resumed.
+    pub job: String,
+    /// The completed scenes in the order of completion.
+    pub completed_scenes: Vec<Token>,
+    /// The job state after the last completed scene.
+    pub state: S,
+}
+
+/// A job processing scenes with a checkpoint after each scene.
+///
+/// ```ignore
+/// use nuscenes_data::job::ResumableJob;
+///
+/// let mut job = ResumableJob::open("/path/to/out/checkpoint.json", "count")?;
+/// job.run(dataset.scene_iter(), |scene, num_samples: &mut usize| {
+///     *num_samples += scene.sample_iter().count();
+///     Ok(())
+/// })?;
+/// let num_samples = job.finish()?;
+/// ```
+#[derive(Debug)]
+pub struct ResumableJob<S> {
+    path: PathBuf,
+    checkpoint: Checkpoint<S>,
+    completed: HashSet<Token>,
+}
+
+impl<S> ResumableJob<S>
+where
+    S: Clone + Default + Serialize + DeserializeOwned,
+{
+    /// Open the job with the checkpoint file. The job starts over if
+    /// the checkpoint does not exist, and it is an error if the
+    /// checkpoint belongs to another job.
+    pub fn open<P>(path: P, job: &str) -> Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref().to_owned();
+        let checkpoint = if path.is_file() {
+            let reader = BufReader::new(File::open(&path)?);
+            let checkpoint: Checkpoint<S> =
+                serde_json::from_reader(reader).map_err(|_| Error::CorruptedFile(path.clone()))?;
+            if checkpoint.job != job {
+                return Err(Error::CorruptedDataset(format!(
+                    "the checkpoint {} belongs to the job {}, but not {}",
+                    path.display(),
+                    checkpoint.job,
+                    job
+                )));
+            }
+            checkpoint
+        } else {
+            Checkpoint {
+                job: job.to_string(),
+                completed_scenes: vec![],
+                state: S::default(),
+            }
+        };
+        let completed = checkpoint.completed_scenes.iter().copied().collect();
+
+        Ok(Self {
+            path,
+            checkpoint,
+            completed,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Check if the job continues from a checkpoint with completed
+    /// scenes.
+    pub fn is_resumed(&self) -> bool {
+        !self.checkpoint.completed_scenes.is_empty()
+    }
+
+    pub fn is_completed(&self, scene_token: Token) -> bool {
+        self.completed.contains(&scene_token)
+    }
+
+    pub fn completed_scenes(&self) -> &[Token] {
+        &self.checkpoint.completed_scenes
+    }
+
+    /// Get the state after the last completed scene.
+    pub fn state(&self) -> &S {
+        &self.checkpoint.state
+    }
+
+    /// Mark the scene as completed with the new state and write the
+    /// checkpoint.
+    pub fn commit(&mut self, scene_token: Token, state: S) -> Result<()> {
+        if self.completed.insert(scene_token) {
+            self.checkpoint.completed_scenes.push(scene_token);
+        }
+        self.checkpoint.state = state;
+        write_checkpoint(&self.path, &self.checkpoint)
+    }
+
+    /// Run the closure on each scene not completed yet, and commit the
+    /// state it updates after each scene. The closure receives a copy
+    /// of the state, which is discarded if it fails.
+    pub fn run<I, F>(&mut self, scenes: I, mut op: F) -> Result<()>
+    where
+        I: IntoIterator<Item = SceneRef>,
+        F: FnMut(&SceneRef, &mut S) -> Result<()>,
+    {
+        for scene in scenes {
+            if self.is_completed(scene.token) {
+                continue;
+            }
+            let mut state = self.checkpoint.state.clone();
+            op(&scene, &mut state)?;
+            self.commit(scene.token, state)?;
+        }
+        Ok(())
+    }
+
+    /// Remove the checkpoint file and return the final state.
+    pub fn finish(self) -> Result<S> {
+        if let Err(err) = fs::remove_file(&self.path) {
+            if err.kind() != io::ErrorKind::NotFound {
+                return Err(err.into());
+            }
+        }
+        Ok(self.checkpoint.state)
+    }
+}
+
+/// Get the default checkpoint path of an output file, which appends
+/// `.checkpoint.json` to the file name.
+pub fn checkpoint_path_of(output: &Path) -> PathBuf {
+    let mut path = OsString::from(output);
+    path.push(".checkpoint.json");
+    PathBuf::from(path)
+}
+
+/// Write to a temporary file and rename it, so that an interrupted
+/// write does not leave a truncated checkpoint.
+fn write_checkpoint<S>(path: &Path, checkpoint: &Checkpoint<S>) -> Result<()>
+where
+    S: Serialize,
+{
+    let mut tmp = OsString::from(path);
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+
+    let mut writer = BufWriter::new(File::create(&tmp)?);
+    serde_json::to_writer(&mut writer, checkpoint).map_err(io::Error::from)?;
+    writer
+        .into_inner()
+        .map_err(|err| err.into_error())?
+        .sync_all()?;
+    fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+/// Open an output file to append after the first `len` bytes, which is
+/// the size recorded in a checkpoint. Bytes written after the
+/// checkpoint are discarded.
+#[cfg(any(feature = "tfrecord", feature = "webdataset"))]
+pub(crate) fn reopen_truncated(path: &Path, len: u64) -> Result<File> {
+    use std::io::{Seek, SeekFrom};
+
+    let mut file = fs::OpenOptions::new().write(true).open(path)?;
+    file.set_len(len)?;
+    file.seek(SeekFrom::End(0))?;
+    Ok(file)
+}
+
+/// Flush the writer to the disk and get the size of the file written
+/// so far.
+#[cfg(any(feature = "tfrecord", feature = "webdataset"))]
+pub(crate) fn flushed_len(writer: &mut BufWriter<File>) -> Result<u64> {
+    use std::io::Write;
+
+    writer.flush()?;
+    let file = writer.get_ref();
+    file.sync_data()?;
+    Ok(file.metadata()?.len())
+}
@@ -0,0 +1,355 @@
+//! Lazy loading of metadata tables.
+//!
+//! [DatasetLoader::load_lazy] indexes the byte range of each record by
+//! its token instead of deserializing the tables, which takes a small
+//! fraction of the memory of [Dataset](crate::Dataset) on large
+//! versions such as `v1.0-trainval`. Records are deserialized on each
+//! access. Table files are read through the
+//! [storage](DatasetLoader#structfield.storage) of the loader, or
+//! mapped into memory if the `mmap` feature is enabled and the storage
+//! is [local](Storage::is_local).
+
+use crate::{
+    error::{Error, Result},
+    loader::DatasetLoader,
+    serializable::{
+        Attribute, CalibratedSensor, Category, EgoPose, Instance, Log, Map, Sample,
+        SampleAnnotation, SampleData, Scene, Sensor, Token, Visibility,
+    },
+    storage::{read_with_options, LocalStorage, ReadOptions, Storage},
+    table::{Table, TableRecord},
+};
+use rayon::prelude::*;
+use serde::{de::DeserializeOwned, Deserialize};
+use std::{
+    collections::HashMap,
+    marker::PhantomData,
+    ops::{Deref, Range},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+/// The content of a table file.
+enum TableBytes {
+    #[cfg(feature = "mmap")]
+    Mapped(memmap2::Mmap),
+    Owned(Vec<u8>),
+}
+
+impl TableBytes {
+    fn open(storage: &Arc<dyn Storage>, path: &Path, options: &ReadOptions) -> Result<Self> {
+        #[cfg(feature = "mmap")]
+        if storage.is_local() {
+            let file = std::fs::File::open(path)?;
+            // SAFETY: The mapping is read-only. The file is assumed not
+            // to be modified while it is mapped.
+            let mmap = unsafe { memmap2::Mmap::map(&file)? };
+            return Ok(Self::Mapped(mmap));
+        }
+
+        Ok(Self::Owned(read_with_options(storage, path, options)?))
+    }
+}
+
+impl Deref for TableBytes {
+    type Target = [u8];
+
+    fn deref(&self) -> &Self::Target {
+        match self {
+            #[cfg(feature = "mmap")]
+            Self::Mapped(mmap) => mmap,
+            Self::Owned(bytes) => bytes,
+        }
+    }
+}
+
+/// A table whose records are deserialized on access.
+pub struct LazyTable<T> {
+    path: PathBuf,
+    bytes: TableBytes,
+    /// The token and byte range of each record in file order.
+    records: Vec<(Token, Range<usize>)>,
+    /// The index into `records` of each token.
+    index: HashMap<Token, usize>,
+    _phantom: PhantomData<fn() -> T>,
+}
+
+impl<T> LazyTable<T>
+where
+    T: DeserializeOwned + TableRecord,
+{
+    /// Open the table file on the local file system and index its
+    /// records.
+    pub fn open<P>(path: P) -> Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let storage: Arc<dyn Storage> = Arc::new(LocalStorage);
+        Self::open_with_storage(&storage, path, &ReadOptions::default())
+    }
+
+    /// Read the table file from the storage and index its records.
+    pub fn open_with_storage<P>(
+        storage: &Arc<dyn Storage>,
+        path: P,
+        options: &ReadOptions,
+    ) -> Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let bytes = TableBytes::open(storage, path, options)?;
+        let ranges = split_records(&bytes).ok_or_else(|| {
+            Error::CorruptedDataset(format!(
+                "failed to index file {}: not an array of objects",
+                path.display()
+            ))
+        })?;
+
+        #[derive(Deserialize)]
+        struct TokenOnly {
+            token: Token,
+        }
+
+        let records: Vec<(Token, Range<usize>)> = ranges
+            .into_par_iter()
+            .map(|range| {
+                let record: TokenOnly =
+                    serde_json::from_slice(&bytes[range.clone()]).map_err(|err| {
+                        Error::CorruptedDataset(format!(
+                            "failed to index file {} at byte {}: {err}",
+                            path.display(),
+                            range.start
+                        ))
+                    })?;
+                Ok((record.token, range))
+            })
+            .collect::<Result<_>>()?;
+        let index = records
+            .iter()
+            .enumerate()
+            .map(|(index, (token, _))| (*token, index))
+            .collect();
+
+        Ok(Self {
+            path: path.to_owned(),
+            bytes,
+            records,
+            index,
+            _phantom: PhantomData,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    pub fn contains(&self, token: Token) -> bool {
+        self.index.contains_key(&token)
+    }
+
+    /// Iterate over the tokens in file order.
+    pub fn tokens(&self) -> impl Iterator<Item = Token> + '_ {
+        self.records.iter().map(|(token, _)| *token)
+    }
+
+    /// Get the JSON text of the record.
+    pub fn raw(&self, token: Token) -> Option<&[u8]> {
+        let (_, range) = &self.records[*self.index.get(&token)?];
+        Some(&self.bytes[range.clone()])
+    }
+
+    /// Deserialize the record with the token.
+    pub fn get(&self, token: Token) -> Result<Option<T>> {
+        let Some(&index) = self.index.get(&token) else {
+            return Ok(None);
+        };
+        self.parse(index).map(Some)
+    }
+
+    /// Deserialize the records one by one in file order.
+    pub fn iter(&self) -> impl Iterator<Item = Result<T>> + '_ {
+        (0..self.records.len()).map(|index| self.parse(index))
+    }
+
+    /// Deserialize the records satisfying the predicate into a map,
+    /// such as the records of a few scenes.
+    pub fn load_where<F>(&self, predicate: F) -> Result<HashMap<Token, T>>
+    where
+        T: Send,
+        F: Fn(&T) -> bool + Sync,
+    {
+        (0..self.records.len())
+            .into_par_iter()
+            .filter_map(|index| match self.parse(index) {
+                Ok(record) => predicate(&record).then(|| Ok((self.records[index].0, record))),
+                Err(err) => Some(Err(err)),
+            })
+            .collect()
+    }
+
+    fn parse(&self, index: usize) -> Result<T> {
+        let (token, range) = &self.records[index];
+        serde_json::from_slice(&self.bytes[range.clone()]).map_err(|err| {
+            Error::CorruptedDataset(format!(
+                "failed to load file {} near token {token}: {err}",
+                self.path.display()
+            ))
+        })
+    }
+}
+
+/// The metadata tables of a version loaded by
+/// [DatasetLoader::load_lazy].
+///
+/// Records are not linked or checked. Follow the tokens in records to
+/// navigate between tables.
+pub struct LazyDataset {
+    pub version: String,
+    pub dataset_dir: PathBuf,
+    pub attribute: LazyTable<Attribute>,
+    pub calibrated_sensor: LazyTable<CalibratedSensor>,
+    pub category: LazyTable<Category>,
+    pub ego_pose: LazyTable<EgoPose>,
+    pub instance: LazyTable<Instance>,
+    pub log: LazyTable<Log>,
+    pub map: LazyTable<Map>,
+    pub sample: LazyTable<Sample>,
+    pub sample_annotation: LazyTable<SampleAnnotation>,
+    pub sample_data: LazyTable<SampleData>,
+    pub scene: LazyTable<Scene>,
+    pub sensor: LazyTable<Sensor>,
+    /// The visibility levels, which are few and keyed by their own
+    /// token type.
+    pub visibility: Vec<Visibility>,
+}
+
+impl DatasetLoader {
+    /// Index the tables of the version without deserializing them.
+    /// The table files are read through the storage of the loader with
+    /// its read options.
+    ///
+    /// Integrity checks, time offsets, sweep dropping and record hooks
+    /// are not applied. Use [DatasetLoader::load] if the dataset fits
+    /// in memory.
+    pub fn load_lazy<P>(&self, version: &str, dir: P) -> Result<LazyDataset>
+    where
+        P: AsRef<Path>,
+    {
+        let dataset_dir = dir.as_ref();
+        let meta_dir = dataset_dir.join(version);
+
+        let visibility = {
+            let path = meta_dir.join(Table::Visibility.file_name());
+            let bytes = read_with_options(&self.storage, &path, &self.read_options)?;
+            serde_json::from_slice(&bytes).map_err(|err| {
+                Error::ParseError(format!("failed to load {}: {err}", path.display()))
+            })?
+        };
+
+        Ok(LazyDataset {
+            version: version.to_string(),
+            dataset_dir: dataset_dir.to_owned(),
+            attribute: self.open_lazy_table(&meta_dir)?,
+            calibrated_sensor: self.open_lazy_table(&meta_dir)?,
+            category: self.open_lazy_table(&meta_dir)?,
+            ego_pose: self.open_lazy_table(&meta_dir)?,
+            instance: self.open_lazy_table(&meta_dir)?,
+            log: self.open_lazy_table(&meta_dir)?,
+            map: self.open_lazy_table(&meta_dir)?,
+            sample: self.open_lazy_table(&meta_dir)?,
+            sample_annotation: self.open_lazy_table(&meta_dir)?,
+            sample_data: self.open_lazy_table(&meta_dir)?,
+            scene: self.open_lazy_table(&meta_dir)?,
+            sensor: self.open_lazy_table(&meta_dir)?,
+            visibility,
+        })
+    }
+
+    fn open_lazy_table<T>(&self, meta_dir: &Path) -> Result<LazyTable<T>>
+    where
+        T: DeserializeOwned + TableRecord,
+    {
+        let path = meta_dir.join(T::TABLE.file_name());
+        LazyTable::open_with_storage(&self.storage, path, &self.read_options)
+    }
+}
+
+/// Find the byte ranges of the objects in a JSON array. It returns
+/// `None` if the text is not an array of objects, such as an object
+/// closed by `]` or text after the array. The content of each object
+/// is validated when it is deserialized.
+fn split_records(bytes: &[u8]) -> Option<Vec<Range<usize>>> {
+    /// The expected token outside the objects.
+    #[derive(PartialEq, Eq)]
+    enum State {
+        /// The opening bracket of the array.
+        ArrayStart,
+        /// The first object or the closing bracket.
+        FirstRecord,
+        /// A comma or the closing bracket.
+        Separator,
+        /// The next object after a comma.
+        NextRecord,
+        /// Nothing but whitespaces after the closing bracket.
+        ArrayEnd,
+    }
+
+    let mut ranges = vec![];
+    let mut state = State::ArrayStart;
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut escaped = false;
+    let mut start = 0;
+
+    for (offset, &byte) in bytes.iter().enumerate() {
+        if in_string {
+            match byte {
+                _ if escaped => escaped = false,
+                b'\\' => escaped = true,
+                b'"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+
+        // Inside an object
+        if depth > 0 {
+            match byte {
+                b'"' => in_string = true,
+                b'{' | b'[' => depth += 1,
+                b']' if depth == 1 => return None,
+                b'}' if depth == 1 => {
+                    ranges.push(start..offset + 1);
+                    depth = 0;
+                    state = State::Separator;
+                }
+                b'}' | b']' => depth -= 1,
+                _ => {}
+            }
+            continue;
+        }
+
+        match (&state, byte) {
+            (_, b' ' | b'\t' | b'\n' | b'\r') => {}
+            (State::ArrayStart, b'[') => state = State::FirstRecord,
+            (State::FirstRecord | State::NextRecord, b'{') => {
+                start = offset;
+                depth = 1;
+            }
+            (State::Separator, b',') => state = State::NextRecord,
+            (State::FirstRecord | State::Separator, b']') => state = State::ArrayEnd,
+            _ => return None,
+        }
+    }
+
+    (state == State::ArrayEnd).then_some(ranges)
+}
@@ -0,0 +1,71 @@
+//! Core of the [nuscenes-data](https://docs.rs/nuscenes-data/) crate.
+//!
+//! This crate implements the dataset loader. Extension crates depend
+//! on this crate, while users should depend on `nuscenes-data`, which
+//! re-exports this crate and the extension crates enabled by cargo
+//! features.
+
+pub mod browser;
+pub mod bundle;
+pub mod cache;
+pub mod calibration;
+pub mod camera;
+pub mod canbus;
+pub mod coverage;
+pub mod data_loader;
+pub mod dataset;
+pub mod decoder;
+pub mod difficulty;
+pub mod ego;
+pub mod error;
+pub mod estimate;
+pub mod eval;
+pub mod features;
+pub mod filter;
+#[cfg(feature = "test-fixtures")]
+pub mod fixtures;
+pub mod flow;
+pub mod frames;
+pub mod geometry;
+pub mod heatmap;
+pub mod interaction;
+pub mod job;
+pub mod lazy;
+pub mod lidarseg;
+pub mod loader;
+pub mod npz;
+pub mod odometry;
+pub mod overlap;
+pub mod panoptic;
+pub mod parallel;
+pub mod pointcloud;
+pub mod prediction;
+pub mod prefetch;
+pub mod projection;
+pub mod radar;
+pub mod range_image;
+pub mod reid;
+pub mod report;
+pub mod resplit;
+pub mod rig;
+pub mod sampler;
+pub mod schema;
+pub mod selection;
+pub mod serializable;
+pub mod shuffle;
+pub mod spatial;
+pub mod split;
+pub mod statistics;
+pub mod storage;
+pub mod sweep;
+pub mod table;
+#[cfg(feature = "tfrecord")]
+pub mod tfrecord;
+pub mod token_set;
+pub mod tracks;
+pub mod utils;
+#[cfg(feature = "webdataset")]
+pub mod webdataset;
+pub mod writer;
+
+pub use crate::{dataset::Dataset, loader::DatasetLoader, serializable::Token};
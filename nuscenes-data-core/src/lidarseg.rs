@@ -0,0 +1,220 @@
+//! Consistency audit between lidarseg labels and annotation boxes.
+//!
+//! The class indices of lidarseg labels are mapped to categories by
+//! the `index` field of the category table. A category is treated as
+//! an object class if any annotation refers to it. In each key frame
+//! lidar sweep, points labeled with an object class are expected to
+//! fall inside some annotation box of the same category. Classes
+//! whose points mostly fall outside such boxes are reported.
+
+use crate::{
+    dataset::{Dataset, SampleDataRef, SampleRef, SceneRef},
+    error::{Error, Result},
+    geometry::Box3D,
+    serializable::Token,
+    storage::{read_data_with_options, DataPath},
+};
+use rayon::prelude::*;
+use std::{
+    collections::{HashMap, HashSet},
+    io,
+    path::{Path, PathBuf},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LidarsegAuditOptions {
+    /// Report a class in a frame if the fraction of its points inside
+    /// boxes of the same category is below this ratio.
+    pub min_inside_ratio: f64,
+    /// Ignore classes with fewer labeled points in a frame.
+    pub min_points: usize,
+}
+
+impl Default for LidarsegAuditOptions {
+    fn default() -> Self {
+        Self {
+            min_inside_ratio: 0.8,
+            min_points: 10,
+        }
+    }
+}
+
+/// An object class whose labeled points mostly fall outside the boxes
+/// of the category in a lidar sweep.
+#[derive(Debug, Clone)]
+pub struct LidarsegMismatch {
+    pub sample_data_token: Token,
+    pub category: String,
+    /// The number of points labeled with the class.
+    pub num_points: usize,
+    /// The number of labeled points inside boxes of the category.
+    pub num_inside: usize,
+}
+
+impl LidarsegMismatch {
+    pub fn inside_ratio(&self) -> f64 {
+        self.num_inside as f64 / self.num_points as f64
+    }
+}
+
+/// The audit result of a scene.
+#[derive(Debug, Clone)]
+pub struct SceneLidarsegAudit {
+    pub scene_token: Token,
+    pub scene_name: String,
+    /// The number of sweeps having lidarseg labels.
+    pub num_labeled_frames: usize,
+    pub mismatches: Vec<LidarsegMismatch>,
+}
+
+impl SampleDataRef {
+    /// Get the conventional path of the lidarseg label file, which is
+    /// `lidarseg/<version>/<token>_lidarseg.bin` in the dataset
+    /// directory.
+    pub fn lidarseg_path(&self) -> PathBuf {
+        self.dataset().dataset_dir.join(self.lidarseg_file_name())
+    }
+
+    /// Load the lidarseg labels of the sweep through the dataset
+    /// storage. It returns `None` if the label file does not exist.
+    pub fn load_lidarseg_labels(&self) -> Result<Option<Vec<u8>>> {
+        let dataset = self.dataset();
+        let file_name = self.lidarseg_file_name();
+        let path = DataPath::new(&dataset.dataset_dir, &file_name);
+        match read_data_with_options(&dataset.storage, path, &dataset.read_options) {
+            Ok(labels) => Ok(Some(labels)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Get the path of the lidarseg label file relative to the dataset
+    /// directory.
+    fn lidarseg_file_name(&self) -> PathBuf {
+        let version = self.sample().scene().version().to_string();
+        Path::new("lidarseg")
+            .join(version)
+            .join(format!("{}_lidarseg.bin", self.token))
+    }
+}
+
+impl Dataset {
+    /// Audit the lidarseg labels of all scenes in parallel. Scenes
+    /// without lidarseg labels are reported with no labeled frames.
+    pub fn audit_lidarseg(
+        &self,
+        options: &LidarsegAuditOptions,
+    ) -> Result<Vec<SceneLidarsegAudit>> {
+        let classes = object_classes(self);
+        let scenes: Vec<_> = self.scene_iter().collect();
+        scenes
+            .par_iter()
+            .map(|scene| audit_scene(scene, &classes, options))
+            .collect()
+    }
+}
+
+/// Map the class indices of object classes to category names.
+fn object_classes(dataset: &Dataset) -> HashMap<u8, String> {
+    let annotated: HashSet<Token> = dataset
+        .instance_map
+        .values()
+        .map(|instance| instance.category_token)
+        .collect();
+    dataset
+        .category_map
+        .values()
+        .filter(|category| annotated.contains(&category.token))
+        .filter_map(|category| Some((category.index?, category.name.clone())))
+        .collect()
+}
+
+fn audit_scene(
+    scene: &SceneRef,
+    classes: &HashMap<u8, String>,
+    options: &LidarsegAuditOptions,
+) -> Result<SceneLidarsegAudit> {
+    let mut audit = SceneLidarsegAudit {
+        scene_token: scene.token,
+        scene_name: scene.name.clone(),
+        num_labeled_frames: 0,
+        mismatches: vec![],
+    };
+
+    for sample in scene.sample_iter() {
+        let sweeps = sample.keyframe_data_iter().filter(|data| data.is_lidar());
+        for data in sweeps {
+            let Some(labels) = data.load_lidarseg_labels()? else {
+                continue;
+            };
+            audit.num_labeled_frames += 1;
+            audit
+                .mismatches
+                .extend(audit_sweep(&sample, &data, &labels, classes, options)?);
+        }
+    }
+
+    Ok(audit)
+}
+
+fn audit_sweep(
+    sample: &SampleRef,
+    data: &SampleDataRef,
+    labels: &[u8],
+    classes: &HashMap<u8, String>,
+    options: &LidarsegAuditOptions,
+) -> Result<Vec<LidarsegMismatch>> {
+    let points = data.load_lidar_bin_points()?;
+    if points.len() != labels.len() {
+        return Err(Error::CorruptedDataset(format!(
+            "the lidarseg labels of sample data {} has {} labels, but the sweep has {} points",
+            data.token,
+            labels.len(),
+            points.len()
+        )));
+    }
+
+    // Express the boxes in the sensor frame
+    let global_to_sensor = data.sensor_to_global().inverse();
+    let boxes: Vec<(String, Box3D)> = sample
+        .annotation_iter()
+        .map(|annotation| {
+            let category = annotation.instance().category().name.clone();
+            let bbox = Box3D::from(&*annotation).transform(&global_to_sensor);
+            (category, bbox)
+        })
+        .collect();
+
+    // Count labeled points and points inside boxes per class
+    let mut counts: HashMap<u8, (usize, usize)> = HashMap::new();
+    for (point, &label) in points.iter().zip(labels) {
+        let Some(category) = classes.get(&label) else {
+            continue;
+        };
+        let [x, y, z, ..] = *point;
+        let position = [x as f64, y as f64, z as f64];
+        let is_inside = boxes
+            .iter()
+            .any(|(name, bbox)| name == category && bbox.contains(position));
+
+        let (num_points, num_inside) = counts.entry(label).or_default();
+        *num_points += 1;
+        if is_inside {
+            *num_inside += 1;
+        }
+    }
+
+    let mut mismatches: Vec<_> = counts
+        .into_iter()
+        .filter(|&(_, (num_points, _))| num_points >= options.min_points)
+        .map(|(label, (num_points, num_inside))| LidarsegMismatch {
+            sample_data_token: data.token,
+            category: classes[&label].clone(),
+            num_points,
+            num_inside,
+        })
+        .filter(|mismatch| mismatch.inside_ratio() < options.min_inside_ratio)
+        .collect();
+    mismatches.sort_by(|lhs, rhs| lhs.category.cmp(&rhs.category));
+    Ok(mismatches)
+}
@@ -0,0 +1,3071 @@
+use crate::{
+    dataset::{Dataset, DatasetInner, InstanceInner, SampleInner, SceneInner},
+    error::{Error, Result},
+    serializable::{
+        Attribute, CalibratedSensor, Category, Channel, EgoPose, Instance, Log, Map, Modality,
+        Panoptic, Sample, SampleAnnotation, SampleData, Scene, Sensor, Token, Visibility,
+        VisibilityToken,
+    },
+    storage::{read_with_options, DataPath, LocalStorage, ReadOptions, Storage},
+    table::{Table, TableRecord},
+    utils::{ParallelIteratorExt, WithToken},
+    writer::SelectedTables,
+};
+use chrono::{Duration, NaiveDate, NaiveDateTime};
+use itertools::Itertools;
+use rayon::prelude::*;
+use serde::{
+    de::{DeserializeSeed, Deserializer, SeqAccess, Visitor},
+    Deserialize, Serialize,
+};
+use std::{
+    any::Any,
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    fmt::{self, Debug, Write},
+    fs::{self, File},
+    io::{self, BufReader, Read},
+    marker::PhantomData,
+    mem,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+/// Report an issue if the condition does not hold.
+macro_rules! ensure_issue {
+    ($issues:expr, $cond:expr, $kind:expr, $table:expr, $token:expr, $($arg:expr),*) => {
+        {
+            if !$cond {
+                $issues.push(Some($table), Some($token), $kind, format!($($arg),*));
+            }
+        }
+    };
+}
+
+/// A group of built-in integrity checks run by [DatasetLoader].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Rule {
+    /// Tokens refer to existing records.
+    References,
+    /// Linked records, such as samples in scenes, are consistent with
+    /// their heads and tails.
+    Chains,
+    /// Field values are plausible, such as camera intrinsics.
+    Values,
+    /// Sample data and map files exist in the storage.
+    Files,
+    /// Sample data file formats match their sensor modalities and
+    /// file name extensions.
+    FileFormats,
+    /// Sample data and map files exist in the storage and are not
+    /// empty, which catches partial downloads. It covers
+    /// [Rule::Files] at the cost of reading the file sizes.
+    FileSizes,
+}
+
+impl Rule {
+    pub const ALL: [Rule; 6] = [
+        Rule::References,
+        Rule::Chains,
+        Rule::Values,
+        Rule::Files,
+        Rule::FileFormats,
+        Rule::FileSizes,
+    ];
+
+    /// The rules enabled by default. File checks are excluded since
+    /// they touch every file in the storage.
+    pub const DEFAULT: [Rule; 4] = [
+        Rule::References,
+        Rule::Chains,
+        Rule::Values,
+        Rule::FileFormats,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Rule::References => "references",
+            Rule::Chains => "chains",
+            Rule::Values => "values",
+            Rule::Files => "files",
+            Rule::FileFormats => "file_formats",
+            Rule::FileSizes => "file_sizes",
+        }
+    }
+}
+
+type CheckFn = dyn Fn(&Dataset) -> std::result::Result<(), String> + Send + Sync;
+
+/// A user-defined check run on the loaded dataset.
+#[derive(Clone)]
+pub struct CustomCheck {
+    pub name: String,
+    check: Arc<CheckFn>,
+}
+
+impl CustomCheck {
+    /// Create a check that returns an error message if the dataset
+    /// is invalid.
+    pub fn new<F>(name: impl Into<String>, check: F) -> Self
+    where
+        F: Fn(&Dataset) -> std::result::Result<(), String> + Send + Sync + 'static,
+    {
+        Self {
+            name: name.into(),
+            check: Arc::new(check),
+        }
+    }
+
+    pub fn run(&self, dataset: &Dataset) -> Result<()> {
+        (self.check)(dataset)
+            .map_err(|msg| Error::CorruptedDataset(format!("check {} failed: {}", self.name, msg)))
+    }
+}
+
+impl Debug for CustomCheck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CustomCheck")
+            .field("name", &self.name)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The kind of a [ValidationIssue].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IssueKind {
+    /// A token does not refer to any record.
+    DanglingReference,
+    /// Linked records are inconsistent with each other or with their
+    /// heads and tails.
+    BrokenChain,
+    /// A field value is implausible.
+    InvalidValue,
+    /// A sample data or map file does not exist.
+    MissingFile,
+    /// A sample data file format does not match the sensor modality or
+    /// the file name extension.
+    FileFormatMismatch,
+    /// A sample data or map file is empty.
+    EmptyFile,
+    /// Records of different versions have the same token.
+    DuplicateToken,
+    /// A user-defined check failed.
+    CustomCheck,
+}
+
+impl IssueKind {
+    /// Get the built-in rule reporting the issues of this kind.
+    pub fn rule(&self) -> Option<Rule> {
+        Some(match self {
+            IssueKind::DanglingReference => Rule::References,
+            IssueKind::BrokenChain => Rule::Chains,
+            IssueKind::InvalidValue => Rule::Values,
+            IssueKind::MissingFile => Rule::Files,
+            IssueKind::FileFormatMismatch => Rule::FileFormats,
+            IssueKind::EmptyFile => Rule::FileSizes,
+            IssueKind::DuplicateToken | IssueKind::CustomCheck => return None,
+        })
+    }
+}
+
+/// A problem found by [DatasetLoader::validate].
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+pub struct ValidationIssue {
+    /// The version whose records have the issue. It is empty for
+    /// custom checks, which run on all versions.
+    pub version: String,
+    /// The table of the offending record, which is `None` for panoptic
+    /// records and for issues not tied to a table.
+    pub table: Option<Table>,
+    /// The token of the offending record.
+    pub token: Option<Token>,
+    /// The channel of the offending sample data, which is set by the
+    /// file checks.
+    pub channel: Option<Channel>,
+    pub kind: IssueKind,
+    pub message: String,
+}
+
+/// All issues found by [DatasetLoader::validate].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ValidationReport {
+    /// The issues sorted by version, table, token and kind.
+    pub issues: Vec<ValidationIssue>,
+}
+
+impl ValidationReport {
+    pub fn is_valid(&self) -> bool {
+        self.issues.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.issues.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.issues.is_empty()
+    }
+
+    pub fn issues_of_table(&self, table: Table) -> impl Iterator<Item = &ValidationIssue> + '_ {
+        self.issues
+            .iter()
+            .filter(move |issue| issue.table == Some(table))
+    }
+
+    pub fn issues_of_kind(&self, kind: IssueKind) -> impl Iterator<Item = &ValidationIssue> + '_ {
+        self.issues.iter().filter(move |issue| issue.kind == kind)
+    }
+
+    /// Count the issues of each kind.
+    pub fn count_by_kind(&self) -> BTreeMap<IssueKind, usize> {
+        self.issues
+            .iter()
+            .map(|issue| issue.kind)
+            .counts()
+            .into_iter()
+            .collect()
+    }
+
+    /// Count the issues of each table.
+    pub fn count_by_table(&self) -> BTreeMap<Option<Table>, usize> {
+        self.issues
+            .iter()
+            .map(|issue| issue.table)
+            .counts()
+            .into_iter()
+            .collect()
+    }
+
+    /// Count the missing and empty sample data files of each channel.
+    pub fn count_file_issues_by_channel(&self) -> BTreeMap<Channel, usize> {
+        self.issues
+            .iter()
+            .filter(|issue| matches!(issue.kind, IssueKind::MissingFile | IssueKind::EmptyFile))
+            .filter_map(|issue| issue.channel)
+            .counts()
+            .into_iter()
+            .collect()
+    }
+
+    /// Convert the report into the error [DatasetLoader::load] would
+    /// return, which describes the first issue.
+    pub fn into_result(self) -> Result<()> {
+        into_first_error(self.issues)
+    }
+}
+
+impl fmt::Display for ValidationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{} issues", self.issues.len())?;
+        for issue in &self.issues {
+            if !issue.version.is_empty() {
+                write!(f, "{}: ", issue.version)?;
+            }
+            writeln!(f, "{:?}: {}", issue.kind, issue.message)?;
+        }
+        Ok(())
+    }
+}
+
+/// The records dropped by [DatasetLoader::lenient] loading.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct LoadWarnings {
+    /// The issue of each dropped record in the order of removal.
+    /// Records dropped because they refer to dropped records are
+    /// reported with [IssueKind::DanglingReference].
+    pub dropped: Vec<ValidationIssue>,
+}
+
+impl LoadWarnings {
+    pub fn len(&self) -> usize {
+        self.dropped.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.dropped.is_empty()
+    }
+
+    /// Count the dropped records of each table. Panoptic records are
+    /// counted under `None`.
+    pub fn count_by_table(&self) -> BTreeMap<Option<Table>, usize> {
+        self.dropped
+            .iter()
+            .map(|issue| issue.table)
+            .counts()
+            .into_iter()
+            .collect()
+    }
+
+    /// Count the dropped records of each reason.
+    pub fn count_by_kind(&self) -> BTreeMap<IssueKind, usize> {
+        self.dropped
+            .iter()
+            .map(|issue| issue.kind)
+            .counts()
+            .into_iter()
+            .collect()
+    }
+}
+
+/// Collects the issues found by the built-in checks on a version.
+struct Issues {
+    version: String,
+    issues: Mutex<Vec<ValidationIssue>>,
+}
+
+impl Issues {
+    fn new(version: &str) -> Self {
+        Self {
+            version: version.to_string(),
+            issues: Mutex::new(vec![]),
+        }
+    }
+
+    fn push(&self, table: Option<Table>, token: Option<Token>, kind: IssueKind, message: String) {
+        self.issues.lock().unwrap().push(ValidationIssue {
+            version: self.version.clone(),
+            table,
+            token,
+            channel: None,
+            kind,
+            message,
+        });
+    }
+
+    fn push_sample_data(
+        &self,
+        token: Token,
+        channel: Option<Channel>,
+        kind: IssueKind,
+        message: String,
+    ) {
+        self.issues.lock().unwrap().push(ValidationIssue {
+            version: self.version.clone(),
+            table: Some(Table::SampleData),
+            token: Some(token),
+            channel,
+            kind,
+            message,
+        });
+    }
+
+    fn into_sorted(self) -> Vec<ValidationIssue> {
+        let mut issues = self.issues.into_inner().unwrap();
+        issues.sort();
+        issues
+    }
+}
+
+fn into_first_error(issues: Vec<ValidationIssue>) -> Result<()> {
+    let num_issues = issues.len();
+    let Some(first) = issues.into_iter().next() else {
+        return Ok(());
+    };
+    let message = match num_issues {
+        1 => first.message,
+        _ => format!("{}, and {} more issues", first.message, num_issues - 1),
+    };
+    Err(Error::CorruptedDataset(message))
+}
+
+/// How [DatasetLoader] runs the integrity checks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CheckMode {
+    Skip,
+    /// Fail on the issues of the first version having any.
+    FailFast,
+    /// Collect the issues of all versions.
+    Report,
+}
+
+/// The computed fields attached to a record by [RecordHook]s.
+pub type RecordFields = BTreeMap<String, serde_json::Value>;
+
+type HookFn = dyn Fn(&mut dyn Any, &mut RecordFields) + Send + Sync;
+
+/// A user-defined closure run on each record of a table at load time.
+///
+/// Hooks run in parallel on the records of each version before the
+/// integrity checks, so they can fix records the checks would reject.
+/// Fields inserted by hooks are kept in the dataset and retrieved by
+/// [Dataset::record_fields].
+#[derive(Clone)]
+pub struct RecordHook {
+    table: Table,
+    hook: Arc<HookFn>,
+}
+
+impl RecordHook {
+    pub fn new<T, F>(hook: F) -> Self
+    where
+        T: TableRecord,
+        F: Fn(&mut T, &mut RecordFields) + Send + Sync + 'static,
+    {
+        Self {
+            table: T::TABLE,
+            hook: Arc::new(move |record, fields| {
+                let record = record
+                    .downcast_mut::<T>()
+                    .expect("the record type does not match the table");
+                hook(record, fields);
+            }),
+        }
+    }
+
+    /// Get the table whose records the hook runs on.
+    pub fn table(&self) -> Table {
+        self.table
+    }
+}
+
+impl Debug for RecordHook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RecordHook")
+            .field("table", &self.table)
+            .finish_non_exhaustive()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DatasetLoader {
+    /// Run the integrity checks. All checks are skipped if it is
+    /// false.
+    pub check: bool,
+    /// The built-in checks to run.
+    pub rules: Vec<Rule>,
+    /// The user-defined checks to run after the built-in checks.
+    pub custom_checks: Vec<CustomCheck>,
+    /// The storage to read sample files.
+    pub storage: Arc<dyn Storage>,
+    /// The default options to read sample files.
+    pub read_options: ReadOptions,
+    /// The time offsets added to sample data timestamps of each
+    /// channel, to correct known clock skews.
+    pub time_offsets: HashMap<Channel, Duration>,
+    /// Drop the sample data of sweeps and their ego poses after the
+    /// integrity checks to cut memory. The `prev` and `next` fields of
+    /// key frames are relinked to the adjacent key frames of the same
+    /// sensor.
+    pub drop_sweeps: bool,
+    /// The user-defined closures run on records at load time.
+    pub record_hooks: Vec<RecordHook>,
+    /// Drop the records failing the built-in checks instead of
+    /// returning an error. The dropped records are reported by
+    /// [Dataset::load_warnings].
+    pub lenient: bool,
+}
+
+impl DatasetLoader {
+    /// Set the built-in checks to run.
+    ///
+    /// ```ignore
+    /// use nuscenes_data::loader::{DatasetLoader, Rule};
+    ///
+    /// let loader = DatasetLoader::default()
+    ///     .checks(&[Rule::Chains, Rule::Files])
+    ///     .custom_check("non-empty", |dataset| {
+    ///         if dataset.scene_iter().next().is_none() {
+    ///             return Err("no scenes".to_string());
+    ///         }
+    ///         Ok(())
+    ///     });
+    /// ```
+    pub fn checks(mut self, rules: &[Rule]) -> Self {
+        self.rules = rules.to_vec();
+        self
+    }
+
+    /// Add the offset to sample data timestamps of the channel when
+    /// loading. Nearest-timestamp queries and sweeps use the corrected
+    /// timestamps.
+    pub fn time_offset(mut self, channel: Channel, offset: Duration) -> Self {
+        self.time_offsets.insert(channel, offset);
+        self
+    }
+
+    /// Keep only key frame sample data. See
+    /// [DatasetLoader::drop_sweeps](DatasetLoader#structfield.drop_sweeps).
+    pub fn drop_sweeps(mut self) -> Self {
+        self.drop_sweeps = true;
+        self
+    }
+
+    /// Drop the records failing the built-in checks instead of failing
+    /// to load.
+    ///
+    /// Records with issues are removed, and the checks run again
+    /// until no issues are left, so that removals cascade to the
+    /// records referring to removed ones. Samples, sample annotations
+    /// and sample data are unlinked from their chains, whose neighbors
+    /// and heads are relinked around them. Loading still fails on
+    /// issues not tied to records, such as failed custom checks.
+    ///
+    /// The reference checks always run in this mode to find the records
+    /// referring to removed ones, and only the records referring to the
+    /// tables losing records are checked again. Loading fails if
+    /// [DatasetLoader::check](DatasetLoader#structfield.check) is
+    /// disabled, since no records could be dropped.
+    ///
+    /// ```ignore
+    /// let dataset = DatasetLoader::default().lenient().load("v1.0-trainval", dir)?;
+    /// for (table, count) in dataset.load_warnings().count_by_table() {
+    ///     println!("dropped {count} records of {table:?}");
+    /// }
+    /// ```
+    pub fn lenient(mut self) -> Self {
+        self.lenient = true;
+        self
+    }
+
+    /// Add a closure run on each record of the table of `T` at load
+    /// time. It is also run on reloaded tables.
+    ///
+    /// ```ignore
+    /// use nuscenes_data::{loader::DatasetLoader, serializable::Category};
+    ///
+    /// let loader = DatasetLoader::default().postprocess(|category: &mut Category, fields| {
+    ///     category.name = category.name.to_lowercase();
+    ///     fields.insert("depth".to_string(), category.name.split('.').count().into());
+    /// });
+    /// ```
+    pub fn postprocess<T, F>(mut self, hook: F) -> Self
+    where
+        T: TableRecord,
+        F: Fn(&mut T, &mut RecordFields) + Send + Sync + 'static,
+    {
+        self.record_hooks.push(RecordHook::new(hook));
+        self
+    }
+
+    /// Add a user-defined check.
+    pub fn custom_check<F>(mut self, name: impl Into<String>, check: F) -> Self
+    where
+        F: Fn(&Dataset) -> std::result::Result<(), String> + Send + Sync + 'static,
+    {
+        self.custom_checks.push(CustomCheck::new(name, check));
+        self
+    }
+
+    /// Load the dataset directory.
+    ///
+    /// ```ignore
+    /// use nuscenes_data::{DatasetLoader, error::Result};
+    ///
+    /// # fn main() -> Result<()> {
+    /// let loader = DatasetLoader {
+    ///     check: true,
+    ///     ..Default::default()
+    /// };
+    /// let dataset = loader.load("1.02", "/path/to/your/dataset")?;
+    /// #     OK(())
+    /// # }
+    /// ```
+    pub fn load<P>(&self, version: &str, dir: P) -> Result<Dataset>
+    where
+        P: AsRef<Path>,
+    {
+        self.load_many(&[version], dir)
+    }
+
+    /// Load multiple versions under the same dataset directory into
+    /// one dataset, such as `v1.0-trainval` and `v1.0-test`.
+    ///
+    /// Integrity checks run on each version separately. Records
+    /// shared by versions, such as categories and sensors, are taken
+    /// from the first version defining them. Other records, such as
+    /// scenes and samples, must have distinct tokens across versions,
+    /// or an error is returned.
+    ///
+    /// ```ignore
+    /// use nuscenes_data::DatasetLoader;
+    ///
+    /// let dataset = DatasetLoader::default()
+    ///     .load_many(&["v1.0-trainval", "v1.0-test"], "/path/to/your/dataset")?;
+    /// let version = dataset.version_of(sample_token);
+    /// ```
+    pub fn load_many<P>(&self, versions: &[&str], dir: P) -> Result<Dataset>
+    where
+        P: AsRef<Path>,
+    {
+        let dir = dir.as_ref();
+        let tables = versions
+            .iter()
+            .map(|version| load_json_files(&dir.join(version)));
+        let (dataset, _) = self.load_checked(versions, dir, self.load_mode()?, tables)?;
+        Ok(dataset.expect("datasets are built unless issues are collected"))
+    }
+
+    /// Get the check mode of [DatasetLoader::load_many].
+    fn load_mode(&self) -> Result<CheckMode> {
+        if self.check {
+            Ok(CheckMode::FailFast)
+        } else if self.lenient {
+            Err(Error::InvalidArgument(
+                "lenient loading requires the integrity checks".to_string(),
+            ))
+        } else {
+            Ok(CheckMode::Skip)
+        }
+    }
+
+    /// Run all integrity checks on a version and report every issue
+    /// instead of failing on the first one. See
+    /// [DatasetLoader::validate_many].
+    ///
+    /// ```ignore
+    /// use nuscenes_data::{loader::Rule, table::Table, DatasetLoader};
+    ///
+    /// let report = DatasetLoader::default()
+    ///     .checks(&Rule::ALL)
+    ///     .validate("v1.0-trainval", "/path/to/your/dataset")?;
+    /// for issue in report.issues_of_table(Table::SampleData) {
+    ///     println!("{}: {}", issue.token.unwrap(), issue.message);
+    /// }
+    /// ```
+    pub fn validate<P>(&self, version: &str, dir: P) -> Result<ValidationReport>
+    where
+        P: AsRef<Path>,
+    {
+        self.validate_many(&[version], dir)
+    }
+
+    /// Run the integrity checks on multiple versions, regardless of
+    /// [DatasetLoader::check](DatasetLoader#structfield.check), and
+    /// collect the issues of all versions.
+    ///
+    /// The configured rules run on every version. Custom checks need
+    /// an indexed dataset, so they only run if the built-in checks
+    /// find no issues. Errors are still returned if table files cannot
+    /// be read or parsed.
+    pub fn validate_many<P>(&self, versions: &[&str], dir: P) -> Result<ValidationReport>
+    where
+        P: AsRef<Path>,
+    {
+        let dir = dir.as_ref();
+        let tables = versions
+            .iter()
+            .map(|version| load_json_files(&dir.join(version)));
+        let (_, issues) = self.load_checked(versions, dir, CheckMode::Report, tables)?;
+        Ok(ValidationReport { issues })
+    }
+
+    /// Run the checks in the mode on the tables of the versions, which
+    /// are loaded one by one from `tables`, and build the dataset. The
+    /// dataset is not built in the report mode if issues are found or
+    /// there are no custom checks to run.
+    fn load_checked<I>(
+        &self,
+        versions: &[&str],
+        dataset_dir: &Path,
+        mode: CheckMode,
+        tables: I,
+    ) -> Result<(Option<Dataset>, Vec<ValidationIssue>)>
+    where
+        I: IntoIterator<Item = Result<LoadJson>>,
+    {
+        let Self {
+            ref custom_checks,
+            ref time_offsets,
+            drop_sweeps,
+            ref record_hooks,
+            ..
+        } = *self;
+
+        if versions.is_empty() {
+            return Err(Error::CorruptedDataset(
+                "no versions are specified".to_string(),
+            ));
+        }
+
+        if let Some(version) = versions.iter().duplicates().next() {
+            return Err(Error::CorruptedDataset(format!(
+                "the version {version} is specified more than once"
+            )));
+        }
+
+        let mut issues = vec![];
+        let mut dropped = vec![];
+        let mut merged: Option<LoadJson> = None;
+        let mut mergeable = true;
+        let mut scene_versions = HashMap::new();
+        let mut record_fields = HashMap::new();
+
+        for ((index, version), load_json) in versions.iter().enumerate().zip(tables) {
+            // Load .json files
+            let mut load_json = load_json?;
+            load_json.run_record_hooks(record_hooks, &mut record_fields);
+
+            // Check the data integrity if requested
+            if mode != CheckMode::Skip {
+                let lenient = mode == CheckMode::FailFast && self.lenient;
+                let mut rules: BTreeSet<Rule> = self.rules.iter().copied().collect();
+                if lenient {
+                    rules.insert(Rule::References);
+                }
+
+                let mut version_issues =
+                    self.check_version(&load_json, dataset_dir, version, &rules, None);
+                if lenient {
+                    loop {
+                        let removed = load_json.remove_records(&version_issues);
+                        if removed.is_empty() {
+                            break;
+                        }
+                        let removed_tables: HashSet<Table> =
+                            removed.iter().filter_map(|issue| issue.table).collect();
+                        dropped.extend(removed);
+                        version_issues = self.check_version(
+                            &load_json,
+                            dataset_dir,
+                            version,
+                            &rules,
+                            Some(&removed_tables),
+                        );
+                    }
+                }
+
+                if mode == CheckMode::FailFast {
+                    into_first_error(version_issues)?;
+                } else {
+                    issues.extend(version_issues);
+                }
+            }
+
+            if !mergeable {
+                continue;
+            }
+            for &token in load_json.scene_map.keys() {
+                scene_versions.entry(token).or_insert(index);
+            }
+            merged = match merged {
+                None => Some(load_json),
+                Some(merged) => match merged.merge(load_json, version) {
+                    Ok(merged) => Some(merged),
+                    Err(Error::CorruptedDataset(message)) if mode == CheckMode::Report => {
+                        // Keep checking the other versions
+                        issues.push(ValidationIssue {
+                            version: version.to_string(),
+                            table: None,
+                            token: None,
+                            channel: None,
+                            kind: IssueKind::DuplicateToken,
+                            message,
+                        });
+                        mergeable = false;
+                        None
+                    }
+                    Err(err) => return Err(err),
+                },
+            };
+        }
+
+        if mode == CheckMode::Report && (!issues.is_empty() || custom_checks.is_empty()) {
+            return Ok((None, issues));
+        }
+
+        // Correct clock skews
+        let mut load_json = merged.unwrap();
+        apply_time_offsets(
+            &mut load_json.sample_data_map,
+            &load_json.calibrated_sensor_map,
+            &load_json.sensor_map,
+            time_offsets,
+        );
+
+        if drop_sweeps {
+            remove_sweeps(&mut load_json.sample_data_map);
+            retain_used_ego_poses(&mut load_json.ego_pose_map, &load_json.sample_data_map);
+        }
+
+        // Index internal associated records
+        let versions: Vec<String> = versions.iter().map(|version| version.to_string()).collect();
+        let mut inner = index_records(
+            versions,
+            scene_versions,
+            dataset_dir.to_owned(),
+            self,
+            load_json,
+            record_fields,
+        )?;
+        inner.load_warnings = Arc::new(LoadWarnings { dropped });
+        let dataset = Dataset::from_inner(inner);
+
+        match mode {
+            CheckMode::Skip => {}
+            CheckMode::FailFast => {
+                for custom_check in custom_checks {
+                    custom_check.run(&dataset)?;
+                }
+            }
+            CheckMode::Report => {
+                // Failed checks are reported, while other errors
+                // such as failed reads abort the validation.
+                for custom_check in custom_checks {
+                    match custom_check.run(&dataset) {
+                        Ok(()) => {}
+                        Err(Error::CorruptedDataset(message)) => issues.push(ValidationIssue {
+                            version: String::new(),
+                            table: None,
+                            token: None,
+                            channel: None,
+                            kind: IssueKind::CustomCheck,
+                            message,
+                        }),
+                        Err(err) => return Err(err),
+                    }
+                }
+            }
+        }
+
+        Ok((Some(dataset), issues))
+    }
+
+    /// Run the built-in checks on the records of a version.
+    ///
+    /// If `removed` is given, only the checks depending on the records
+    /// of those tables run, which are the reference checks of records
+    /// referring to them and the chain checks.
+    fn check_version(
+        &self,
+        load_json: &LoadJson,
+        dataset_dir: &Path,
+        version: &str,
+        rules: &BTreeSet<Rule>,
+        removed: Option<&HashSet<Table>>,
+    ) -> Vec<ValidationIssue> {
+        let issues = Issues::new(version);
+        let affected = |tables: &[Table]| match removed {
+            Some(removed) => tables.iter().any(|table| removed.contains(table)),
+            None => true,
+        };
+        for &rule in rules {
+            match rule {
+                Rule::References => check_references(load_json, &issues, affected),
+                Rule::Chains if affected(&CHAINED_TABLES) => check_chains(load_json, &issues),
+                Rule::Chains => {}
+                // The other checks only depend on the checked records
+                _ if removed.is_some() => {}
+                Rule::Values => check_values(load_json, &issues),
+                // File size checks cover existence checks
+                Rule::Files if rules.contains(&Rule::FileSizes) => {}
+                Rule::Files => check_files(load_json, dataset_dir, &*self.storage, false, &issues),
+                Rule::FileFormats => check_file_formats(load_json, &issues),
+                Rule::FileSizes => {
+                    check_files(load_json, dataset_dir, &*self.storage, true, &issues)
+                }
+            }
+        }
+        issues.into_sorted()
+    }
+}
+
+#[cfg(feature = "async")]
+impl DatasetLoader {
+    /// Load a version without blocking the async executor. See
+    /// [DatasetLoader::load].
+    ///
+    /// ```ignore
+    /// use nuscenes_data::DatasetLoader;
+    ///
+    /// let dataset = DatasetLoader::default()
+    ///     .load_async("v1.0-mini", "/path/to/your/dataset")
+    ///     .await?;
+    /// ```
+    pub async fn load_async<P>(&self, version: &str, dir: P) -> Result<Dataset>
+    where
+        P: AsRef<Path>,
+    {
+        self.load_many_async(&[version], dir).await
+    }
+
+    /// Load multiple versions without blocking the async executor.
+    /// See [DatasetLoader::load_many].
+    ///
+    /// The table files of all versions are read concurrently with
+    /// `tokio::fs` and parsed on the blocking thread pool, where the
+    /// checks and the indexing run as well. It must be called within
+    /// a tokio runtime.
+    pub async fn load_many_async<P>(&self, versions: &[&str], dir: P) -> Result<Dataset>
+    where
+        P: AsRef<Path>,
+    {
+        let mode = self.load_mode()?;
+        let dir = dir.as_ref().to_owned();
+
+        let tasks: Vec<_> = versions
+            .iter()
+            .map(|version| tokio::spawn(load_json_files_async(dir.join(version))))
+            .collect();
+        let mut tables = Vec::with_capacity(tasks.len());
+        for task in tasks {
+            tables.push(join_task(task).await?);
+        }
+
+        let loader = self.clone();
+        let versions: Vec<String> = versions.iter().map(|version| version.to_string()).collect();
+        let task = tokio::task::spawn_blocking(move || {
+            let versions: Vec<&str> = versions.iter().map(String::as_str).collect();
+            let tables = tables.into_iter().map(Ok);
+            let (dataset, _) = loader.load_checked(&versions, &dir, mode, tables)?;
+            Ok(dataset.expect("datasets are built unless issues are collected"))
+        });
+        join_task(task).await
+    }
+}
+
+impl Default for DatasetLoader {
+    fn default() -> Self {
+        Self {
+            check: true,
+            rules: Rule::DEFAULT.to_vec(),
+            custom_checks: vec![],
+            storage: Arc::new(LocalStorage),
+            read_options: ReadOptions::default(),
+            time_offsets: HashMap::new(),
+            drop_sweeps: false,
+            record_hooks: vec![],
+            lenient: false,
+        }
+    }
+}
+
+struct LoadJson {
+    pub attribute_map: HashMap<Token, Attribute>,
+    pub calibrated_sensor_map: HashMap<Token, CalibratedSensor>,
+    pub category_map: HashMap<Token, Category>,
+    pub ego_pose_map: HashMap<Token, EgoPose>,
+    pub instance_map: HashMap<Token, Instance>,
+    pub log_map: HashMap<Token, Log>,
+    pub map_map: HashMap<Token, Map>,
+    pub scene_map: HashMap<Token, Scene>,
+    pub sample_map: HashMap<Token, Sample>,
+    pub sample_annotation_map: HashMap<Token, SampleAnnotation>,
+    pub sample_data_map: HashMap<Token, SampleData>,
+    pub sensor_map: HashMap<Token, Sensor>,
+    pub visibility_map: HashMap<VisibilityToken, Visibility>,
+    pub panoptic_map: HashMap<Token, Panoptic>,
+}
+
+impl LoadJson {
+    /// Copy the records of a loaded dataset.
+    fn from_inner(inner: &DatasetInner) -> Self {
+        Self {
+            attribute_map: (*inner.attribute_map).clone(),
+            calibrated_sensor_map: (*inner.calibrated_sensor_map).clone(),
+            category_map: (*inner.category_map).clone(),
+            ego_pose_map: (*inner.ego_pose_map).clone(),
+            instance_map: inner
+                .instance_map
+                .iter()
+                .map(|(token, instance)| (*token, instance.to_instance()))
+                .collect(),
+            log_map: (*inner.log_map).clone(),
+            map_map: (*inner.map_map).clone(),
+            scene_map: inner
+                .scene_map
+                .iter()
+                .map(|(token, scene)| (*token, scene.to_scene()))
+                .collect(),
+            sample_map: inner
+                .sample_map
+                .iter()
+                .map(|(token, sample)| (*token, sample.to_sample()))
+                .collect(),
+            sample_annotation_map: (*inner.sample_annotation_map).clone(),
+            sample_data_map: (*inner.sample_data_map).clone(),
+            sensor_map: (*inner.sensor_map).clone(),
+            visibility_map: (*inner.visibility_map).clone(),
+            panoptic_map: (*inner.panoptic_map).clone(),
+        }
+    }
+
+    /// Run the hooks on the records and collect the attached fields.
+    fn run_record_hooks(
+        &mut self,
+        hooks: &[RecordHook],
+        record_fields: &mut HashMap<Token, RecordFields>,
+    ) {
+        if hooks.is_empty() {
+            return;
+        }
+        run_record_hooks(&mut self.attribute_map, hooks, record_fields);
+        run_record_hooks(&mut self.calibrated_sensor_map, hooks, record_fields);
+        run_record_hooks(&mut self.category_map, hooks, record_fields);
+        run_record_hooks(&mut self.ego_pose_map, hooks, record_fields);
+        run_record_hooks(&mut self.instance_map, hooks, record_fields);
+        run_record_hooks(&mut self.log_map, hooks, record_fields);
+        run_record_hooks(&mut self.map_map, hooks, record_fields);
+        run_record_hooks(&mut self.scene_map, hooks, record_fields);
+        run_record_hooks(&mut self.sample_map, hooks, record_fields);
+        run_record_hooks(&mut self.sample_annotation_map, hooks, record_fields);
+        run_record_hooks(&mut self.sample_data_map, hooks, record_fields);
+        run_record_hooks(&mut self.sensor_map, hooks, record_fields);
+    }
+
+    /// Remove the records with the issues. It returns the issue of
+    /// each removed record, which is the first one if a record has
+    /// several.
+    fn remove_records(&mut self, issues: &[ValidationIssue]) -> Vec<ValidationIssue> {
+        let mut removed = vec![];
+        let mut seen = HashSet::new();
+
+        for issue in issues {
+            let Some(token) = issue.token else {
+                continue;
+            };
+            if !seen.insert((issue.table, token)) {
+                continue;
+            }
+
+            let is_removed = match issue.table {
+                Some(Table::Attribute) => self.attribute_map.remove(&token).is_some(),
+                Some(Table::CalibratedSensor) => {
+                    self.calibrated_sensor_map.remove(&token).is_some()
+                }
+                Some(Table::Category) => self.category_map.remove(&token).is_some(),
+                Some(Table::EgoPose) => self.ego_pose_map.remove(&token).is_some(),
+                Some(Table::Instance) => self.instance_map.remove(&token).is_some(),
+                Some(Table::Log) => self.log_map.remove(&token).is_some(),
+                Some(Table::Map) => self.map_map.remove(&token).is_some(),
+                Some(Table::Scene) => self.scene_map.remove(&token).is_some(),
+                Some(Table::Sensor) => self.sensor_map.remove(&token).is_some(),
+                Some(Table::Sample) => self.remove_sample(token),
+                Some(Table::SampleAnnotation) => self.remove_sample_annotation(token),
+                Some(Table::SampleData) => unlink(&mut self.sample_data_map, token).is_some(),
+                // Visibility records are never at fault
+                Some(Table::Visibility) => false,
+                None => self.panoptic_map.remove(&token).is_some(),
+            };
+            if is_removed {
+                removed.push(issue.clone());
+            }
+        }
+
+        removed
+    }
+
+    /// Remove a sample and move the ends of its scene to the
+    /// neighbors.
+    fn remove_sample(&mut self, token: Token) -> bool {
+        let Some(sample) = unlink(&mut self.sample_map, token) else {
+            return false;
+        };
+        if let Some(scene) = self.scene_map.get_mut(&sample.scene_token) {
+            if let Some(next) = sample.next.filter(|_| scene.first_sample_token == token) {
+                scene.first_sample_token = next;
+            }
+            if let Some(prev) = sample.prev.filter(|_| scene.last_sample_token == token) {
+                scene.last_sample_token = prev;
+            }
+            scene.nbr_samples = scene.nbr_samples.saturating_sub(1);
+        }
+        true
+    }
+
+    /// Remove a sample annotation and move the ends of its instance to
+    /// the neighbors.
+    fn remove_sample_annotation(&mut self, token: Token) -> bool {
+        let Some(annotation) = unlink(&mut self.sample_annotation_map, token) else {
+            return false;
+        };
+        if let Some(instance) = self.instance_map.get_mut(&annotation.instance_token) {
+            if let Some(next) = annotation
+                .next
+                .filter(|_| instance.first_annotation_token == token)
+            {
+                instance.first_annotation_token = next;
+            }
+            if let Some(prev) = annotation
+                .prev
+                .filter(|_| instance.last_annotation_token == token)
+            {
+                instance.last_annotation_token = prev;
+            }
+            instance.nbr_annotations = instance.nbr_annotations.saturating_sub(1);
+        }
+        true
+    }
+
+    /// Add records of another version. Records of shared tables with
+    /// existing tokens are skipped, while token collisions in other
+    /// tables are errors.
+    fn merge(mut self, other: LoadJson, version: &str) -> Result<Self> {
+        fn extend<K, V>(map: &mut HashMap<K, V>, other: HashMap<K, V>)
+        where
+            K: Eq + std::hash::Hash,
+        {
+            for (key, value) in other {
+                map.entry(key).or_insert(value);
+            }
+        }
+
+        fn extend_unique<V>(
+            map: &mut HashMap<Token, V>,
+            other: HashMap<Token, V>,
+            table: &str,
+            version: &str,
+        ) -> Result<()> {
+            if let Some(token) = other.keys().find(|token| map.contains_key(token)) {
+                return Err(Error::CorruptedDataset(format!(
+                    "the {table} token {token} of version {version} collides with a previous version"
+                )));
+            }
+            map.extend(other);
+            Ok(())
+        }
+
+        let LoadJson {
+            attribute_map,
+            calibrated_sensor_map,
+            category_map,
+            ego_pose_map,
+            instance_map,
+            log_map,
+            map_map,
+            scene_map,
+            sample_map,
+            sample_annotation_map,
+            sample_data_map,
+            sensor_map,
+            visibility_map,
+            panoptic_map,
+        } = other;
+
+        // Tables shared by versions
+        extend(&mut self.attribute_map, attribute_map);
+        extend(&mut self.category_map, category_map);
+        extend(&mut self.sensor_map, sensor_map);
+        extend(&mut self.visibility_map, visibility_map);
+
+        // Tables specific to each version
+        extend_unique(
+            &mut self.calibrated_sensor_map,
+            calibrated_sensor_map,
+            Table::CalibratedSensor.as_str(),
+            version,
+        )?;
+        extend_unique(
+            &mut self.ego_pose_map,
+            ego_pose_map,
+            Table::EgoPose.as_str(),
+            version,
+        )?;
+        extend_unique(
+            &mut self.instance_map,
+            instance_map,
+            Table::Instance.as_str(),
+            version,
+        )?;
+        extend_unique(&mut self.log_map, log_map, Table::Log.as_str(), version)?;
+        extend_unique(
+            &mut self.scene_map,
+            scene_map,
+            Table::Scene.as_str(),
+            version,
+        )?;
+        extend_unique(
+            &mut self.sample_map,
+            sample_map,
+            Table::Sample.as_str(),
+            version,
+        )?;
+        extend_unique(
+            &mut self.sample_annotation_map,
+            sample_annotation_map,
+            Table::SampleAnnotation.as_str(),
+            version,
+        )?;
+        extend_unique(
+            &mut self.sample_data_map,
+            sample_data_map,
+            Table::SampleData.as_str(),
+            version,
+        )?;
+        extend_unique(&mut self.panoptic_map, panoptic_map, "panoptic", version)?;
+
+        // Maps list logs of all versions
+        for (token, map) in map_map {
+            match self.map_map.get_mut(&token) {
+                Some(existing) => {
+                    for log_token in map.log_tokens {
+                        if !existing.log_tokens.contains(&log_token) {
+                            existing.log_tokens.push(log_token);
+                        }
+                    }
+                }
+                None => {
+                    self.map_map.insert(token, map);
+                }
+            }
+        }
+
+        Ok(self)
+    }
+}
+
+/// A record in a chain linked by `prev` and `next` tokens.
+trait Linked {
+    fn links_mut(&mut self) -> (&mut Option<Token>, &mut Option<Token>);
+}
+
+impl Linked for Sample {
+    fn links_mut(&mut self) -> (&mut Option<Token>, &mut Option<Token>) {
+        (&mut self.prev, &mut self.next)
+    }
+}
+
+impl Linked for SampleAnnotation {
+    fn links_mut(&mut self) -> (&mut Option<Token>, &mut Option<Token>) {
+        (&mut self.prev, &mut self.next)
+    }
+}
+
+impl Linked for SampleData {
+    fn links_mut(&mut self) -> (&mut Option<Token>, &mut Option<Token>) {
+        (&mut self.prev, &mut self.next)
+    }
+}
+
+/// Remove a record from its chain and link its neighbors to each
+/// other.
+fn unlink<T>(map: &mut HashMap<Token, T>, token: Token) -> Option<T>
+where
+    T: Linked,
+{
+    let mut record = map.remove(&token)?;
+    let (&mut prev, &mut next) = record.links_mut();
+
+    if let Some(prev_record) = prev.and_then(|prev| map.get_mut(&prev)) {
+        let (_, prev_next) = prev_record.links_mut();
+        if *prev_next == Some(token) {
+            *prev_next = next;
+        }
+    }
+    if let Some(next_record) = next.and_then(|next| map.get_mut(&next)) {
+        let (next_prev, _) = next_record.links_mut();
+        if *next_prev == Some(token) {
+            *next_prev = prev;
+        }
+    }
+
+    Some(record)
+}
+
+fn load_json_files(dir: &Path) -> Result<LoadJson> {
+    let mut attribute_map: Result<HashMap<Token, Attribute>> = Ok(Default::default());
+    let mut calibrated_sensor_map: Result<HashMap<Token, CalibratedSensor>> =
+        Ok(Default::default());
+    let mut category_map: Result<HashMap<Token, Category>> = Ok(Default::default());
+    let mut ego_pose_map: Result<HashMap<Token, EgoPose>> = Ok(Default::default());
+    let mut instance_map: Result<HashMap<Token, Instance>> = Ok(Default::default());
+    let mut log_map: Result<HashMap<Token, Log>> = Ok(Default::default());
+    let mut map_map: Result<HashMap<Token, Map>> = Ok(Default::default());
+    let mut sample_annotation_map: Result<HashMap<Token, SampleAnnotation>> =
+        Ok(Default::default());
+    let mut sample_data_map: Result<HashMap<Token, SampleData>> = Ok(Default::default());
+    let mut sample_map: Result<HashMap<Token, Sample>> = Ok(Default::default());
+    let mut scene_map: Result<HashMap<Token, Scene>> = Ok(Default::default());
+    let mut sensor_map: Result<HashMap<Token, Sensor>> = Ok(Default::default());
+    let mut visibility_map: Result<HashMap<VisibilityToken, Visibility>> = Ok(Default::default());
+    let mut panoptic_map: Result<HashMap<Token, Panoptic>> = Ok(Default::default());
+
+    rayon::scope(|scope| {
+        scope.spawn(|_| {
+            attribute_map = load_map(dir.join("attribute.json"));
+        });
+        scope.spawn(|_| {
+            calibrated_sensor_map = load_map(dir.join("calibrated_sensor.json"));
+        });
+        scope.spawn(|_| {
+            category_map = load_map(dir.join("category.json"));
+        });
+        scope.spawn(|_| {
+            ego_pose_map = load_map(dir.join("ego_pose.json"));
+        });
+        scope.spawn(|_| {
+            instance_map = load_map(dir.join("instance.json"));
+        });
+        scope.spawn(|_| {
+            log_map = load_map(dir.join("log.json"));
+        });
+        scope.spawn(|_| {
+            map_map = load_map(dir.join("map.json"));
+        });
+        scope.spawn(|_| {
+            sample_annotation_map = load_map(dir.join("sample_annotation.json"));
+        });
+        scope.spawn(|_| {
+            sample_data_map = load_map(dir.join("sample_data.json"));
+        });
+        scope.spawn(|_| {
+            sample_map = load_map(dir.join("sample.json"));
+        });
+        scope.spawn(|_| {
+            scene_map = load_map(dir.join("scene.json"));
+        });
+        scope.spawn(|_| {
+            sensor_map = load_map(dir.join("sensor.json"));
+        });
+        scope.spawn(|_| {
+            visibility_map = (|| {
+                let vec: Vec<Visibility> = load_json(dir.join("visibility.json"))?;
+                let map: HashMap<VisibilityToken, Visibility> =
+                    vec.into_iter().map(|item| (item.token, item)).collect();
+                Ok(map)
+            })();
+        });
+        scope.spawn(|_| {
+            // Panoptic labels are an optional extension
+            let path = dir.join("panoptic.json");
+            if path.exists() {
+                panoptic_map = load_map(path);
+            }
+        });
+    });
+
+    let attribute_map = attribute_map?;
+    let calibrated_sensor_map = calibrated_sensor_map?;
+    let category_map = category_map?;
+    let ego_pose_map = ego_pose_map?;
+    let instance_map = instance_map?;
+    let log_map = log_map?;
+    let map_map = map_map?;
+    let sample_annotation_map = sample_annotation_map?;
+    let sample_data_map = sample_data_map?;
+    let sample_map = sample_map?;
+    let scene_map = scene_map?;
+    let sensor_map = sensor_map?;
+    let visibility_map = visibility_map?;
+    let panoptic_map = panoptic_map?;
+
+    Ok(LoadJson {
+        attribute_map,
+        calibrated_sensor_map,
+        category_map,
+        ego_pose_map,
+        instance_map,
+        log_map,
+        map_map,
+        scene_map,
+        sample_map,
+        sample_annotation_map,
+        sample_data_map,
+        sensor_map,
+        visibility_map,
+        panoptic_map,
+    })
+}
+
+/// Read the table files with `tokio::fs` concurrently, and parse each
+/// of them on the blocking thread pool.
+#[cfg(feature = "async")]
+async fn load_json_files_async(dir: PathBuf) -> Result<LoadJson> {
+    let attribute_map = tokio::spawn(load_map_async(dir.join("attribute.json")));
+    let calibrated_sensor_map = tokio::spawn(load_map_async(dir.join("calibrated_sensor.json")));
+    let category_map = tokio::spawn(load_map_async(dir.join("category.json")));
+    let ego_pose_map = tokio::spawn(load_map_async(dir.join("ego_pose.json")));
+    let instance_map = tokio::spawn(load_map_async(dir.join("instance.json")));
+    let log_map = tokio::spawn(load_map_async(dir.join("log.json")));
+    let map_map = tokio::spawn(load_map_async(dir.join("map.json")));
+    let sample_annotation_map = tokio::spawn(load_map_async(dir.join("sample_annotation.json")));
+    let sample_data_map = tokio::spawn(load_map_async(dir.join("sample_data.json")));
+    let sample_map = tokio::spawn(load_map_async(dir.join("sample.json")));
+    let scene_map = tokio::spawn(load_map_async(dir.join("scene.json")));
+    let sensor_map = tokio::spawn(load_map_async(dir.join("sensor.json")));
+    let visibility_map = tokio::spawn({
+        let path = dir.join("visibility.json");
+        async move {
+            let vec: Vec<Visibility> = load_json_async(path).await?;
+            let map: HashMap<VisibilityToken, Visibility> =
+                vec.into_iter().map(|item| (item.token, item)).collect();
+            Ok(map)
+        }
+    });
+    let panoptic_map = tokio::spawn({
+        // Panoptic labels are an optional extension
+        let path = dir.join("panoptic.json");
+        async move {
+            if tokio::fs::metadata(&path).await.is_ok() {
+                load_map_async(path).await
+            } else {
+                Ok(HashMap::new())
+            }
+        }
+    });
+
+    Ok(LoadJson {
+        attribute_map: join_task(attribute_map).await?,
+        calibrated_sensor_map: join_task(calibrated_sensor_map).await?,
+        category_map: join_task(category_map).await?,
+        ego_pose_map: join_task(ego_pose_map).await?,
+        instance_map: join_task(instance_map).await?,
+        log_map: join_task(log_map).await?,
+        map_map: join_task(map_map).await?,
+        scene_map: join_task(scene_map).await?,
+        sample_map: join_task(sample_map).await?,
+        sample_annotation_map: join_task(sample_annotation_map).await?,
+        sample_data_map: join_task(sample_data_map).await?,
+        sensor_map: join_task(sensor_map).await?,
+        visibility_map: join_task(visibility_map).await?,
+        panoptic_map: join_task(panoptic_map).await?,
+    })
+}
+
+/// Wait for a task, resuming its panic if it panicked.
+#[cfg(feature = "async")]
+async fn join_task<T>(task: tokio::task::JoinHandle<Result<T>>) -> Result<T> {
+    match task.await {
+        Ok(result) => result,
+        Err(err) if err.is_panic() => std::panic::resume_unwind(err.into_panic()),
+        Err(err) => Err(io::Error::new(io::ErrorKind::Interrupted, err).into()),
+    }
+}
+
+/// Check that tokens refer to existing records. Only the records
+/// referring to tables for which `affected` returns true are checked.
+fn check_references<F>(load_json: &LoadJson, issues: &Issues, affected: F)
+where
+    F: Fn(&[Table]) -> bool,
+{
+    let LoadJson {
+        attribute_map,
+        calibrated_sensor_map,
+        category_map,
+        ego_pose_map,
+        instance_map,
+        log_map,
+        map_map,
+        scene_map,
+        sample_map,
+        sample_annotation_map,
+        sample_data_map,
+        sensor_map,
+        visibility_map,
+        panoptic_map,
+    } = load_json;
+    use IssueKind::DanglingReference as Kind;
+
+    // check calibrated sensor integrity
+    if affected(&[Table::Sensor]) {
+        calibrated_sensor_map
+            .par_iter()
+            .for_each(|(&token, calibrated_sensor)| {
+                ensure_issue!(
+                    issues,
+                    sensor_map.contains_key(&calibrated_sensor.sensor_token),
+                    Kind,
+                    Table::CalibratedSensor,
+                    token,
+                    "the token {} does not refer to any sensor",
+                    calibrated_sensor.sensor_token
+                );
+            });
+    }
+
+    // check sample annotation integrity
+    if affected(&[
+        Table::Sample,
+        Table::Instance,
+        Table::Attribute,
+        Table::Visibility,
+        Table::SampleAnnotation,
+    ]) {
+        sample_annotation_map
+            .par_iter()
+            .for_each(|(&token, sample_annotation)| {
+                let table = Table::SampleAnnotation;
+                ensure_issue!(
+                    issues,
+                    sample_map.contains_key(&sample_annotation.sample_token),
+                    Kind,
+                    table,
+                    token,
+                    "the token {} does not refer to any sample",
+                    sample_annotation.sample_token
+                );
+
+                ensure_issue!(
+                    issues,
+                    instance_map.contains_key(&sample_annotation.instance_token),
+                    Kind,
+                    table,
+                    token,
+                    "the token {} does not refer to any instance",
+                    sample_annotation.instance_token
+                );
+
+                for attribute_token in &sample_annotation.attribute_tokens {
+                    ensure_issue!(
+                        issues,
+                        attribute_map.contains_key(attribute_token),
+                        Kind,
+                        table,
+                        token,
+                        "the token {} does not refer to any attribute",
+                        attribute_token
+                    );
+                }
+
+                if let Some(visibility_token) = &sample_annotation.visibility_token {
+                    ensure_issue!(
+                        issues,
+                        visibility_map.contains_key(visibility_token),
+                        Kind,
+                        table,
+                        token,
+                        "the token {} does not refer to any visibility",
+                        visibility_token
+                    );
+                }
+
+                for link in [&sample_annotation.prev, &sample_annotation.next]
+                    .into_iter()
+                    .flatten()
+                {
+                    ensure_issue!(
+                        issues,
+                        sample_annotation_map.contains_key(link),
+                        Kind,
+                        table,
+                        token,
+                        "the token {} does not refer to any sample annotation",
+                        link
+                    );
+                }
+            });
+    }
+
+    // check instance integrity
+    if affected(&[Table::SampleAnnotation, Table::Category]) {
+        instance_map.par_iter().for_each(|(&token, instance)| {
+            for annotation_token in [
+                instance.first_annotation_token,
+                instance.last_annotation_token,
+            ] {
+                ensure_issue!(
+                    issues,
+                    sample_annotation_map.contains_key(&annotation_token),
+                    Kind,
+                    Table::Instance,
+                    token,
+                    "the token {} does not refer to any sample annotation",
+                    annotation_token
+                );
+            }
+
+            ensure_issue!(
+                issues,
+                category_map.contains_key(&instance.category_token),
+                Kind,
+                Table::Instance,
+                token,
+                "the token {} does not refer to any sample category",
+                instance.category_token
+            );
+        });
+    }
+
+    // check map integrity
+    if affected(&[Table::Log]) {
+        map_map.par_iter().for_each(|(&map_token, map)| {
+            for log_token in &map.log_tokens {
+                ensure_issue!(
+                issues,
+                log_map.contains_key(log_token),
+                Kind,
+                Table::Map,
+                map_token,
+                "in the map {map_token}, the log_token {log_token} does not refer to any valid log"
+            );
+            }
+        });
+    }
+
+    // check sample integrity
+    if affected(&[Table::Scene, Table::Sample]) {
+        sample_map.par_iter().for_each(|(&token, sample)| {
+            ensure_issue!(
+                issues,
+                scene_map.contains_key(&sample.scene_token),
+                Kind,
+                Table::Sample,
+                token,
+                "the token {} does not refer to any scene",
+                sample.scene_token
+            );
+
+            for link in [&sample.prev, &sample.next].into_iter().flatten() {
+                ensure_issue!(
+                    issues,
+                    sample_map.contains_key(link),
+                    Kind,
+                    Table::Sample,
+                    token,
+                    "the token {} does not refer to any sample",
+                    link
+                );
+            }
+        });
+    }
+
+    // check scene integrity
+    if affected(&[Table::Log, Table::Sample]) {
+        scene_map.par_iter().for_each(|(&token, scene)| {
+            ensure_issue!(
+                issues,
+                log_map.contains_key(&scene.log_token),
+                Kind,
+                Table::Scene,
+                token,
+                "the token {} does not refer to any log",
+                scene.log_token
+            );
+
+            for sample_token in [scene.first_sample_token, scene.last_sample_token] {
+                ensure_issue!(
+                    issues,
+                    sample_map.contains_key(&sample_token),
+                    Kind,
+                    Table::Scene,
+                    token,
+                    "the token {} does not refer to any sample",
+                    sample_token
+                );
+            }
+        });
+    }
+
+    // check sample data integrity
+    if affected(&[
+        Table::Sample,
+        Table::EgoPose,
+        Table::CalibratedSensor,
+        Table::SampleData,
+    ]) {
+        sample_data_map
+            .par_iter()
+            .for_each(|(&token, sample_data)| {
+                let table = Table::SampleData;
+                ensure_issue!(
+                    issues,
+                    sample_map.contains_key(&sample_data.sample_token),
+                    Kind,
+                    table,
+                    token,
+                    "the token {} does not refer to any sample",
+                    sample_data.sample_token
+                );
+
+                ensure_issue!(
+                    issues,
+                    ego_pose_map.contains_key(&sample_data.ego_pose_token),
+                    Kind,
+                    table,
+                    token,
+                    "the token {} does not refer to any ego pose",
+                    sample_data.ego_pose_token
+                );
+
+                ensure_issue!(
+                    issues,
+                    calibrated_sensor_map.contains_key(&sample_data.calibrated_sensor_token),
+                    Kind,
+                    table,
+                    token,
+                    "the token {} does not refer to any calibrated sensor",
+                    sample_data.calibrated_sensor_token
+                );
+
+                for link in [&sample_data.prev, &sample_data.next].into_iter().flatten() {
+                    ensure_issue!(
+                        issues,
+                        sample_data_map.contains_key(link),
+                        Kind,
+                        table,
+                        token,
+                        "the token {} does not refer to any sample data",
+                        link
+                    );
+                }
+            });
+    }
+
+    // check panoptic integrity
+    if affected(&[Table::SampleData]) {
+        panoptic_map.par_iter().for_each(|(&token, panoptic)| {
+            if !sample_data_map.contains_key(&panoptic.sample_data_token) {
+                issues.push(
+                    None,
+                    Some(token),
+                    Kind,
+                    format!(
+                        "the token {} of the panoptic record does not refer to any sample data",
+                        panoptic.sample_data_token
+                    ),
+                );
+            }
+        });
+    }
+}
+
+/// The tables whose records are linked into chains or are their heads.
+const CHAINED_TABLES: [Table; 5] = [
+    Table::Instance,
+    Table::Sample,
+    Table::SampleAnnotation,
+    Table::SampleData,
+    Table::Scene,
+];
+
+/// Check that linked records are consistent with their heads and
+/// tails.
+fn check_chains(load_json: &LoadJson, issues: &Issues) {
+    let LoadJson {
+        instance_map,
+        scene_map,
+        sample_map,
+        sample_annotation_map,
+        sample_data_map,
+        ..
+    } = load_json;
+
+    // Check sample_annotation.{next,prev} fields integrity
+    check_links(
+        issues,
+        Table::SampleAnnotation,
+        sample_annotation_map,
+        |annotation| (annotation.prev, annotation.next),
+    );
+
+    // Check instance.first_annotation_token
+    check_chain_ends(
+        issues,
+        ChainEnd {
+            table: Table::SampleAnnotation,
+            records: sample_annotation_map,
+            link: "prev",
+            is_end: |annotation: &SampleAnnotation| annotation.prev.is_none(),
+        },
+        ChainOwner {
+            table: Table::Instance,
+            records: instance_map,
+            field: "first_annotation_token",
+            end_of: |instance: &Instance| instance.first_annotation_token,
+        },
+    );
+
+    // Check instance.last_annotation_token
+    check_chain_ends(
+        issues,
+        ChainEnd {
+            table: Table::SampleAnnotation,
+            records: sample_annotation_map,
+            link: "next",
+            is_end: |annotation: &SampleAnnotation| annotation.next.is_none(),
+        },
+        ChainOwner {
+            table: Table::Instance,
+            records: instance_map,
+            field: "last_annotation_token",
+            end_of: |instance: &Instance| instance.last_annotation_token,
+        },
+    );
+
+    // Check instance.nbr_annotations
+    // TODO: implement the parallel algorithm to count the length of chained annotations
+    // {
+    //     for (instance_token, instance) in instance_map {
+    //         let mut annotation_token = &instance.first_annotation_token;
+    //         let mut prev_annotation_token = None;
+    //         let mut count = 0;
+
+    //         loop {
+    //             let annotation = match sample_annotation_map.get(annotation_token) {
+    //                 Some(annotation) => annotation,
+    //                 None => {
+    //                     match prev_annotation_token {
+    //                         Some(prev) => bail_corrupted!("the sample_annotation with token {prev} points to next token {annotation_token} that does not exist"),
+    //                         None => bail_corrupted!("the instance with token {instance_token} points to first_annotation_token {annotation_token} that does not exist"),
+    //                     }
+    //                 }
+    //             };
+
+    //             ensure_corrupted!(
+    //                 prev_annotation_token == annotation.prev.as_ref(),
+    //                 "the prev field is not correct in sample annotation with token {}",
+    //                 annotation_token
+    //             );
+
+    //             count += 1;
+
+    //             prev_annotation_token = Some(annotation_token);
+    //             annotation_token = match &annotation.next {
+    //                 Some(next) => next,
+    //                 None => {
+    //                     ensure_corrupted!(
+    //                         &instance.last_annotation_token == annotation_token,
+    //                         "the last_annotation_token is not correct in instance with token {}",
+    //                         instance_token
+    //                     );
+    //                     ensure_corrupted!(
+    //                         count == instance.nbr_annotations,
+    //                         "the nbr_annotations is not correct in instance with token {}",
+    //                         instance_token
+    //                     );
+    //                     break;
+    //                 }
+    //             };
+    //         }
+    //     }
+    // }
+
+    // Check sample.{next,prev} fields integrity
+    check_links(issues, Table::Sample, sample_map, |sample| {
+        (sample.prev, sample.next)
+    });
+
+    // Check scene.first_sample_token
+    check_chain_ends(
+        issues,
+        ChainEnd {
+            table: Table::Sample,
+            records: sample_map,
+            link: "prev",
+            is_end: |sample: &Sample| sample.prev.is_none(),
+        },
+        ChainOwner {
+            table: Table::Scene,
+            records: scene_map,
+            field: "first_sample_token",
+            end_of: |scene: &Scene| scene.first_sample_token,
+        },
+    );
+
+    // Check scene.last_sample_token
+    check_chain_ends(
+        issues,
+        ChainEnd {
+            table: Table::Sample,
+            records: sample_map,
+            link: "next",
+            is_end: |sample: &Sample| sample.next.is_none(),
+        },
+        ChainOwner {
+            table: Table::Scene,
+            records: scene_map,
+            field: "last_sample_token",
+            end_of: |scene: &Scene| scene.last_sample_token,
+        },
+    );
+
+    // Check scene.nbr_samples
+    // TODO: implement a parallel algorithm to check scene.nbr_samples
+    // for (scene_token, scene) in scene_map {
+    //     let mut prev_sample_token = None;
+    //     let mut sample_token = &scene.first_sample_token;
+    //     let mut count = 0;
+
+    //     loop {
+    //         let sample = match sample_map.get(sample_token) {
+    //                 Some(sample) => sample,
+    //                 None => {
+    //                     match prev_sample_token {
+    //                         Some(prev) => bail_corrupted!("the sample with token {} points to a next token {} that does not exist", prev, sample_token),
+    //                         None => bail_corrupted!("the scene with token {} points to first_sample_token {} that does not exist", scene_token, sample_token),
+    //                     }
+    //                 }
+    //         };
+
+    //         ensure_corrupted!(
+    //             prev_sample_token == sample.prev.as_ref(),
+    //             "the prev field in sample with token {} is not correct",
+    //             sample_token
+    //         );
+
+    //         prev_sample_token = Some(sample_token);
+    //         count += 1;
+
+    //         sample_token = match &sample.next {
+    //             Some(next) => next,
+    //             None => {
+    //                 ensure_corrupted!(
+    //                     sample_token == &scene.last_sample_token,
+    //                     "the last_sample_token is not correct in scene with token {}",
+    //                     scene_token
+    //                 );
+    //                 ensure_corrupted!(
+    //                     count == scene.nbr_samples,
+    //                     "the nbr_samples in scene with token {} is not correct",
+    //                     scene_token
+    //                 );
+    //                 break;
+    //             }
+    //         };
+    //     }
+    // }
+
+    // Check sample_data.{next,prev} fields integrity
+    check_links(issues, Table::SampleData, sample_data_map, |data| {
+        (data.prev, data.next)
+    });
+}
+
+/// Report records whose prev and next links do not point back to each
+/// other. Links to missing records are reported by
+/// [Rule::References].
+fn check_links<T, F>(issues: &Issues, table: Table, records: &HashMap<Token, T>, links: F)
+where
+    T: Sync,
+    F: Fn(&T) -> (Option<Token>, Option<Token>) + Sync,
+{
+    let prev_edges: HashSet<(Token, Token)> = records
+        .par_iter()
+        .filter_map(|(&token, record)| Some((links(record).0?, token)))
+        .filter(|(prev, _)| records.contains_key(prev))
+        .collect();
+    let next_edges: HashSet<(Token, Token)> = records
+        .par_iter()
+        .filter_map(|(&token, record)| Some((token, links(record).1?)))
+        .filter(|(_, next)| records.contains_key(next))
+        .collect();
+
+    prev_edges
+        .par_iter()
+        .filter(|edge| !next_edges.contains(edge))
+        .for_each(|&(prev, token)| {
+            issues.push(
+                Some(table),
+                Some(token),
+                IssueKind::BrokenChain,
+                format!(
+                    "the {table} {token} has prev {prev}, but the next of {prev} is not {token}"
+                ),
+            );
+        });
+    next_edges
+        .par_iter()
+        .filter(|edge| !prev_edges.contains(edge))
+        .for_each(|&(token, next)| {
+            issues.push(
+                Some(table),
+                Some(token),
+                IssueKind::BrokenChain,
+                format!(
+                    "the {table} {token} has next {next}, but the prev of {next} is not {token}"
+                ),
+            );
+        });
+}
+
+/// The records ending chains, such as samples without prev.
+struct ChainEnd<'a, T, F> {
+    table: Table,
+    records: &'a HashMap<Token, T>,
+    /// The name of the null link at the end.
+    link: &'static str,
+    is_end: F,
+}
+
+/// The records referring to the ends of chains, such as scenes.
+struct ChainOwner<'a, O, G> {
+    table: Table,
+    records: &'a HashMap<Token, O>,
+    /// The name of the field referring to the end.
+    field: &'static str,
+    end_of: G,
+}
+
+/// Report chain ends not referred to by any owner, and owners referring
+/// to records other than chain ends.
+fn check_chain_ends<T, F, O, G>(
+    issues: &Issues,
+    end: ChainEnd<'_, T, F>,
+    owner: ChainOwner<'_, O, G>,
+) where
+    T: Sync,
+    F: Fn(&T) -> bool + Sync,
+    O: Sync,
+    G: Fn(&O) -> Token + Sync,
+{
+    let ends: HashSet<Token> = end
+        .records
+        .par_iter()
+        .filter(|(_, record)| (end.is_end)(record))
+        .map(|(&token, _)| token)
+        .collect();
+    let referred: HashSet<Token> = owner
+        .records
+        .par_iter()
+        .map(|(_, record)| (owner.end_of)(record))
+        .collect();
+
+    owner.records.par_iter().for_each(|(&token, record)| {
+        let end_token = (owner.end_of)(record);
+        if end.records.contains_key(&end_token) && !ends.contains(&end_token) {
+            issues.push(
+                Some(owner.table),
+                Some(token),
+                IssueKind::BrokenChain,
+                format!(
+                    "the {} {token} has {} {end_token}, whose {} is not null",
+                    owner.table, owner.field, end.link
+                ),
+            );
+        }
+    });
+    ends.par_iter()
+        .filter(|token| !referred.contains(token))
+        .for_each(|&token| {
+            issues.push(
+                Some(end.table),
+                Some(token),
+                IssueKind::BrokenChain,
+                format!(
+                    "the {} {token} has a null {}, but it is not the {} of any {}",
+                    end.table, end.link, owner.field, owner.table
+                ),
+            );
+        });
+}
+
+/// Check that sample data and map files exist, and that they are not
+/// empty if `check_sizes` is set. Sample data issues carry the
+/// channels to report the missing files of each sensor.
+fn check_files(
+    load_json: &LoadJson,
+    dataset_dir: &Path,
+    storage: &dyn Storage,
+    check_sizes: bool,
+    issues: &Issues,
+) {
+    let LoadJson {
+        calibrated_sensor_map,
+        map_map,
+        sample_data_map,
+        sensor_map,
+        ..
+    } = load_json;
+
+    let file_issue = |path: DataPath<'_>| -> Option<(IssueKind, String)> {
+        if !check_sizes {
+            return (!storage.data_exists(path))
+                .then(|| (IssueKind::MissingFile, "does not exist".to_string()));
+        }
+        match storage.data_size(path) {
+            Ok(0) => Some((IssueKind::EmptyFile, "is empty".to_string())),
+            Ok(_) => None,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                Some((IssueKind::MissingFile, "does not exist".to_string()))
+            }
+            Err(err) => Some((IssueKind::MissingFile, format!("cannot be read: {err}"))),
+        }
+    };
+
+    sample_data_map
+        .par_iter()
+        .for_each(|(&token, sample_data)| {
+            let path = DataPath::new(dataset_dir, &sample_data.filename);
+            let Some((kind, reason)) = file_issue(path) else {
+                return;
+            };
+            let channel = calibrated_sensor_map
+                .get(&sample_data.calibrated_sensor_token)
+                .and_then(|calibrated_sensor| sensor_map.get(&calibrated_sensor.sensor_token))
+                .map(|sensor| sensor.channel);
+            let message = match channel {
+                Some(channel) => format!(
+                    "the file {} of the {} sample data {} {}",
+                    path,
+                    channel.as_str(),
+                    token,
+                    reason
+                ),
+                None => format!("the file {} of the sample data {} {}", path, token, reason),
+            };
+            issues.push_sample_data(token, channel, kind, message);
+        });
+
+    map_map.par_iter().for_each(|(&token, map)| {
+        let path = DataPath::new(dataset_dir, &map.filename);
+        if let Some((kind, reason)) = file_issue(path) {
+            let message = format!("the file {} of the map {} {}", path, token, reason);
+            issues.push(Some(Table::Map), Some(token), kind, message);
+        }
+    });
+}
+
+/// Check that sample data file formats match the sensor modalities
+/// and the file name extensions.
+fn check_file_formats(load_json: &LoadJson, issues: &Issues) {
+    let LoadJson {
+        calibrated_sensor_map,
+        sample_data_map,
+        sensor_map,
+        ..
+    } = load_json;
+
+    sample_data_map
+        .par_iter()
+        .for_each(|(&token, sample_data)| {
+            let fileformat = sample_data.fileformat;
+            let extension = sample_data
+                .filename
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .map(|ext| ext.to_ascii_lowercase());
+            let matches_extension = extension
+                .as_deref()
+                .is_some_and(|ext| fileformat.extensions().contains(&ext));
+            ensure_issue!(
+                issues,
+                matches_extension,
+                IssueKind::FileFormatMismatch,
+                Table::SampleData,
+                token,
+                "the file {} of the sample data {} does not match the file format {}",
+                sample_data.filename.display(),
+                token,
+                fileformat
+            );
+
+            // Dangling references are reported by Rule::References
+            let Some(sensor) = calibrated_sensor_map
+                .get(&sample_data.calibrated_sensor_token)
+                .and_then(|calibrated_sensor| sensor_map.get(&calibrated_sensor.sensor_token))
+            else {
+                return;
+            };
+            ensure_issue!(
+                issues,
+                sensor.modality.file_format() == fileformat,
+                IssueKind::FileFormatMismatch,
+                Table::SampleData,
+                token,
+                "the sample data {} of the {} sensor {} has the file format {}",
+                token,
+                sensor.modality,
+                sensor.channel,
+                fileformat
+            );
+        });
+}
+
+/// Check that field values are plausible.
+fn check_values(load_json: &LoadJson, issues: &Issues) {
+    let LoadJson {
+        calibrated_sensor_map,
+        sample_data_map,
+        sensor_map,
+        ..
+    } = load_json;
+    use IssueKind::InvalidValue as Kind;
+
+    // check camera intrinsics
+    calibrated_sensor_map
+        .par_iter()
+        .for_each(|(&token, calibrated_sensor)| {
+            // Dangling references are reported by Rule::References
+            let Some(sensor) = sensor_map.get(&calibrated_sensor.sensor_token) else {
+                return;
+            };
+            let is_camera = sensor.modality == Modality::Camera;
+            let table = Table::CalibratedSensor;
+
+            match calibrated_sensor.intrinsic() {
+                Some(intrinsic) => {
+                    ensure_issue!(
+                        issues,
+                        is_camera,
+                        Kind,
+                        table,
+                        token,
+                        "the calibrated sensor {} has intrinsics, but the sensor is a {}",
+                        token,
+                        sensor.modality
+                    );
+                    ensure_issue!(
+                        issues,
+                        intrinsic.fx > 0.0 && intrinsic.fy > 0.0,
+                        Kind,
+                        table,
+                        token,
+                        "the calibrated sensor {} has non-positive focal lengths",
+                        token
+                    );
+                }
+                None => {
+                    ensure_issue!(
+                        issues,
+                        !is_camera,
+                        Kind,
+                        table,
+                        token,
+                        "the calibrated sensor {} of a camera has no intrinsics",
+                        token
+                    );
+                }
+            }
+        });
+
+    sample_data_map.par_iter().for_each(|(&token, sample_data)| {
+        let Some(calibrated_sensor) = calibrated_sensor_map.get(&sample_data.calibrated_sensor_token)
+        else {
+            return;
+        };
+        let Some(intrinsic) = calibrated_sensor.intrinsic() else {
+            return;
+        };
+        if sample_data.width == 0 || sample_data.height == 0 {
+            return;
+        }
+
+        ensure_issue!(
+            issues,
+            (0.0..=sample_data.width as f64).contains(&intrinsic.cx)
+                && (0.0..=sample_data.height as f64).contains(&intrinsic.cy),
+            Kind,
+            Table::SampleData,
+            token,
+            "the principal point of the calibrated sensor {} is outside the image of the sample data {}",
+            calibrated_sensor.token,
+            token
+        );
+    });
+}
+
+fn index_records(
+    versions: Vec<String>,
+    scene_versions: HashMap<Token, usize>,
+    dataset_dir: PathBuf,
+    loader: &DatasetLoader,
+    load_json: LoadJson,
+    record_fields: HashMap<Token, RecordFields>,
+) -> Result<DatasetInner> {
+    let LoadJson {
+        attribute_map,
+        calibrated_sensor_map,
+        category_map,
+        ego_pose_map,
+        instance_map,
+        log_map,
+        map_map,
+        scene_map,
+        sample_map,
+        sample_annotation_map,
+        sample_data_map,
+        sensor_map,
+        visibility_map,
+        panoptic_map,
+    } = load_json;
+
+    // convert some types for ease of usage
+    let instance_internal_map = index_instances(instance_map, &sample_annotation_map)?;
+    let scene_internal_map = index_scenes(scene_map, &sample_map)?;
+    let sample_internal_map = index_samples(sample_map, &sample_annotation_map, &sample_data_map);
+
+    // sort records by timestamp
+    let sorted_ego_pose_tokens = sort_by_timestamp(&ego_pose_map, |pose| pose.timestamp);
+    let sorted_sample_tokens = sort_by_timestamp(&sample_internal_map, |sample| sample.timestamp);
+    let sorted_sample_data_tokens = sort_by_timestamp(&sample_data_map, |data| data.timestamp);
+    let sorted_scene_tokens = sort_scenes(&scene_internal_map, &sample_internal_map);
+    let calibrated_sensor_channel_map = index_channels(&calibrated_sensor_map, &sensor_map);
+    let scene_name_map = index_scene_names(&scene_internal_map, &scene_versions);
+    let log_scene_map = index_log_scenes(&scene_internal_map, &sorted_scene_tokens);
+    let scene_sample_data_map = index_scene_sample_data(
+        &sample_data_map,
+        &sample_internal_map,
+        &sorted_sample_data_tokens,
+    );
+    let (log_date_map, log_vehicle_map) = index_logs(&log_map);
+    let sample_data_panoptic_map = index_panoptic(&panoptic_map);
+
+    // construct result
+    let inner = DatasetInner {
+        version: versions.join("+"),
+        versions,
+        scene_versions: Arc::new(scene_versions),
+        dataset_dir,
+        storage: loader.storage.clone(),
+        read_options: loader.read_options.clone(),
+        time_offsets: loader.time_offsets.clone(),
+        drop_sweeps: loader.drop_sweeps,
+        record_hooks: loader.record_hooks.clone(),
+        record_fields: Arc::new(record_fields),
+        attribute_map: Arc::new(attribute_map),
+        calibrated_sensor_map: Arc::new(calibrated_sensor_map),
+        category_map: Arc::new(category_map),
+        ego_pose_map: Arc::new(ego_pose_map),
+        instance_map: Arc::new(instance_internal_map),
+        log_map: Arc::new(log_map),
+        map_map: Arc::new(map_map),
+        sample_map: Arc::new(sample_internal_map),
+        sample_annotation_map: Arc::new(sample_annotation_map),
+        sample_data_map: Arc::new(sample_data_map),
+        scene_map: Arc::new(scene_internal_map),
+        sensor_map: Arc::new(sensor_map),
+        visibility_map: Arc::new(visibility_map),
+        panoptic_map: Arc::new(panoptic_map),
+        calibrated_sensor_channel_map: Arc::new(calibrated_sensor_channel_map),
+        scene_name_map: Arc::new(scene_name_map),
+        log_scene_map: Arc::new(log_scene_map),
+        scene_sample_data_map: Arc::new(scene_sample_data_map),
+        log_date_map: Arc::new(log_date_map),
+        log_vehicle_map: Arc::new(log_vehicle_map),
+        sample_data_panoptic_map: Arc::new(sample_data_panoptic_map),
+        sorted_ego_pose_tokens,
+        sorted_scene_tokens,
+        sorted_sample_tokens,
+        sorted_sample_data_tokens,
+        load_warnings: Default::default(),
+    };
+
+    Ok(inner)
+}
+
+/// Re-read the tables through the dataset storage and rebuild the
+/// indexes depending on them. Other tables are shared with the
+/// original dataset.
+///
+/// The reloaded tables are checked against the others with
+/// [Rule::References], and with [Rule::Chains] if any chained table
+/// is reloaded, before they replace the original tables.
+pub(crate) fn reload_tables(inner: &DatasetInner, tables: &[Table]) -> Result<DatasetInner> {
+    if inner.versions.len() > 1 {
+        return Err(Error::CorruptedDataset(
+            "tables cannot be reloaded on datasets of multiple versions".to_string(),
+        ));
+    }
+
+    let meta_dir = inner.dataset_dir.join(&inner.version);
+    let reload = |table: Table| tables.contains(&table);
+    let path = |table: Table| meta_dir.join(table.file_name());
+    let mut record_fields = (*inner.record_fields).clone();
+
+    // Read the tables into the records of the whole dataset
+    let mut load_json = LoadJson::from_inner(inner);
+    if reload(Table::Attribute) {
+        load_json.attribute_map = load_table(inner, &mut record_fields, &path(Table::Attribute))?;
+    }
+    if reload(Table::CalibratedSensor) {
+        load_json.calibrated_sensor_map =
+            load_table(inner, &mut record_fields, &path(Table::CalibratedSensor))?;
+    }
+    if reload(Table::Category) {
+        load_json.category_map = load_table(inner, &mut record_fields, &path(Table::Category))?;
+    }
+    if reload(Table::Instance) {
+        load_json.instance_map = load_table(inner, &mut record_fields, &path(Table::Instance))?;
+    }
+    if reload(Table::Log) {
+        load_json.log_map = load_table(inner, &mut record_fields, &path(Table::Log))?;
+    }
+    if reload(Table::Map) {
+        load_json.map_map = load_table(inner, &mut record_fields, &path(Table::Map))?;
+    }
+    if reload(Table::Sample) {
+        load_json.sample_map = load_table(inner, &mut record_fields, &path(Table::Sample))?;
+    }
+    if reload(Table::SampleAnnotation) {
+        load_json.sample_annotation_map =
+            load_table(inner, &mut record_fields, &path(Table::SampleAnnotation))?;
+    }
+    if reload(Table::Scene) {
+        load_json.scene_map = load_table(inner, &mut record_fields, &path(Table::Scene))?;
+    }
+    if reload(Table::Sensor) {
+        load_json.sensor_map = load_table(inner, &mut record_fields, &path(Table::Sensor))?;
+    }
+    if reload(Table::Visibility) {
+        let vec: Vec<Visibility> = read_table(inner, &path(Table::Visibility))?;
+        load_json.visibility_map = vec.into_iter().map(|item| (item.token, item)).collect();
+    }
+    if reload(Table::SampleData) {
+        let mut sample_data_map: HashMap<Token, SampleData> =
+            load_table(inner, &mut record_fields, &path(Table::SampleData))?;
+        apply_time_offsets(
+            &mut sample_data_map,
+            &load_json.calibrated_sensor_map,
+            &load_json.sensor_map,
+            &inner.time_offsets,
+        );
+        if inner.drop_sweeps {
+            remove_sweeps(&mut sample_data_map);
+        }
+        load_json.sample_data_map = sample_data_map;
+    }
+    if reload(Table::EgoPose) {
+        let mut ego_pose_map: HashMap<Token, EgoPose> =
+            load_table(inner, &mut record_fields, &path(Table::EgoPose))?;
+        if inner.drop_sweeps {
+            retain_used_ego_poses(&mut ego_pose_map, &load_json.sample_data_map);
+        }
+        load_json.ego_pose_map = ego_pose_map;
+    }
+
+    // Check the reloaded records before indexing them, since the
+    // accessors assume the references are valid
+    let issues = Issues::new(&inner.version);
+    check_references(&load_json, &issues, |_| true);
+    if tables.iter().any(|table| CHAINED_TABLES.contains(table)) {
+        check_chains(&load_json, &issues);
+    }
+    into_first_error(issues.into_sorted())?;
+
+    // Replace tables without derived indexes
+    let mut new = inner.clone();
+    if reload(Table::Attribute) {
+        new.attribute_map = Arc::new(mem::take(&mut load_json.attribute_map));
+    }
+    if reload(Table::CalibratedSensor) {
+        new.calibrated_sensor_map = Arc::new(mem::take(&mut load_json.calibrated_sensor_map));
+    }
+    if reload(Table::Category) {
+        new.category_map = Arc::new(mem::take(&mut load_json.category_map));
+    }
+    if reload(Table::Log) {
+        let log_map = mem::take(&mut load_json.log_map);
+        let (log_date_map, log_vehicle_map) = index_logs(&log_map);
+        new.log_date_map = Arc::new(log_date_map);
+        new.log_vehicle_map = Arc::new(log_vehicle_map);
+        new.log_map = Arc::new(log_map);
+    }
+    if reload(Table::Map) {
+        new.map_map = Arc::new(mem::take(&mut load_json.map_map));
+    }
+    if reload(Table::Sensor) {
+        new.sensor_map = Arc::new(mem::take(&mut load_json.sensor_map));
+    }
+    if reload(Table::Visibility) {
+        new.visibility_map = Arc::new(mem::take(&mut load_json.visibility_map));
+    }
+    if reload(Table::CalibratedSensor) || reload(Table::Sensor) {
+        new.calibrated_sensor_channel_map =
+            Arc::new(index_channels(&new.calibrated_sensor_map, &new.sensor_map));
+    }
+    if reload(Table::EgoPose) {
+        let ego_pose_map = mem::take(&mut load_json.ego_pose_map);
+        new.sorted_ego_pose_tokens = sort_by_timestamp(&ego_pose_map, |pose| pose.timestamp);
+        new.ego_pose_map = Arc::new(ego_pose_map);
+    }
+    if reload(Table::SampleAnnotation) {
+        new.sample_annotation_map = Arc::new(mem::take(&mut load_json.sample_annotation_map));
+    }
+    if reload(Table::SampleData) {
+        let sample_data_map = mem::take(&mut load_json.sample_data_map);
+        new.sorted_sample_data_tokens = sort_by_timestamp(&sample_data_map, |data| data.timestamp);
+        new.sample_data_map = Arc::new(sample_data_map);
+    }
+
+    // Rebuild instance indexes
+    if reload(Table::Instance) || reload(Table::SampleAnnotation) {
+        let instance_map = mem::take(&mut load_json.instance_map);
+        new.instance_map = Arc::new(index_instances(instance_map, &new.sample_annotation_map)?);
+    }
+
+    // Rebuild sample and scene indexes
+    let samples_changed =
+        reload(Table::Sample) || reload(Table::SampleAnnotation) || reload(Table::SampleData);
+    let scenes_changed = reload(Table::Scene) || reload(Table::Sample);
+
+    if samples_changed || scenes_changed {
+        let sample_map = mem::take(&mut load_json.sample_map);
+
+        if scenes_changed {
+            let scene_map = mem::take(&mut load_json.scene_map);
+            new.scene_map = Arc::new(index_scenes(scene_map, &sample_map)?);
+            new.scene_versions = Arc::new(new.scene_map.keys().map(|&token| (token, 0)).collect());
+            new.scene_name_map = Arc::new(index_scene_names(&new.scene_map, &new.scene_versions));
+        }
+
+        if samples_changed {
+            let sample_internal_map =
+                index_samples(sample_map, &new.sample_annotation_map, &new.sample_data_map);
+            if reload(Table::Sample) {
+                new.sorted_sample_tokens =
+                    sort_by_timestamp(&sample_internal_map, |sample| sample.timestamp);
+            }
+            new.sample_map = Arc::new(sample_internal_map);
+        }
+
+        if scenes_changed {
+            new.sorted_scene_tokens = sort_scenes(&new.scene_map, &new.sample_map);
+            new.log_scene_map =
+                Arc::new(index_log_scenes(&new.scene_map, &new.sorted_scene_tokens));
+        }
+
+        new.scene_sample_data_map = Arc::new(index_scene_sample_data(
+            &new.sample_data_map,
+            &new.sample_map,
+            &new.sorted_sample_data_tokens,
+        ));
+    }
+
+    new.record_fields = Arc::new(record_fields);
+    Ok(new)
+}
+
+/// Index the records selected from a dataset into a new dataset with
+/// the same versions and settings. The selected records are already
+/// linked, so integrity checks are not run.
+pub(crate) fn index_selected(inner: &DatasetInner, tables: SelectedTables) -> Result<DatasetInner> {
+    fn to_map<T>(records: Vec<T>) -> HashMap<Token, T>
+    where
+        T: WithToken,
+    {
+        records
+            .into_iter()
+            .map(|record| (record.token(), record))
+            .collect()
+    }
+
+    let SelectedTables {
+        attributes,
+        calibrated_sensors,
+        categories,
+        ego_poses,
+        instances,
+        logs,
+        maps,
+        samples,
+        sample_annotations,
+        sample_data,
+        scenes,
+        sensors,
+        visibility,
+        panoptic,
+    } = tables;
+
+    let scene_versions = scenes
+        .iter()
+        .map(|scene| (scene.token, inner.scene_versions[&scene.token]))
+        .collect();
+    let load_json = LoadJson {
+        attribute_map: to_map(attributes),
+        calibrated_sensor_map: to_map(calibrated_sensors),
+        category_map: to_map(categories),
+        ego_pose_map: to_map(ego_poses),
+        instance_map: to_map(instances),
+        log_map: to_map(logs),
+        map_map: to_map(maps),
+        scene_map: to_map(scenes),
+        sample_map: to_map(samples),
+        sample_annotation_map: to_map(sample_annotations),
+        sample_data_map: to_map(sample_data),
+        sensor_map: to_map(sensors),
+        visibility_map: visibility
+            .into_iter()
+            .map(|visibility| (visibility.token, visibility))
+            .collect(),
+        panoptic_map: to_map(panoptic),
+    };
+
+    // Keep the settings for reloading tables
+    let loader = DatasetLoader {
+        storage: inner.storage.clone(),
+        read_options: inner.read_options.clone(),
+        time_offsets: inner.time_offsets.clone(),
+        drop_sweeps: inner.drop_sweeps,
+        record_hooks: inner.record_hooks.clone(),
+        ..Default::default()
+    };
+    let mut new = index_records(
+        inner.versions.clone(),
+        scene_versions,
+        inner.dataset_dir.clone(),
+        &loader,
+        load_json,
+        HashMap::new(),
+    )?;
+    new.record_fields = inner.record_fields.clone();
+    new.load_warnings = inner.load_warnings.clone();
+    Ok(new)
+}
+
+fn index_instances(
+    instance_map: HashMap<Token, Instance>,
+    sample_annotation_map: &HashMap<Token, SampleAnnotation>,
+) -> Result<HashMap<Token, InstanceInner>> {
+    instance_map
+        .into_par_iter()
+        .map(|(instance_token, instance)| -> Result<_> {
+            let ret = InstanceInner::from(instance, sample_annotation_map)?;
+            Ok((instance_token, ret))
+        })
+        .par_try_collect()
+}
+
+fn index_scenes(
+    scene_map: HashMap<Token, Scene>,
+    sample_map: &HashMap<Token, Sample>,
+) -> Result<HashMap<Token, SceneInner>> {
+    scene_map
+        .into_par_iter()
+        .map(|(scene_token, scene)| -> Result<_> {
+            let internal = SceneInner::from(scene, sample_map)?;
+            Ok((scene_token, internal))
+        })
+        .par_try_collect()
+}
+
+fn index_samples(
+    sample_map: HashMap<Token, Sample>,
+    sample_annotation_map: &HashMap<Token, SampleAnnotation>,
+    sample_data_map: &HashMap<Token, SampleData>,
+) -> HashMap<Token, SampleInner> {
+    // keep track of relations from samples to sample annotations
+    let mut sample_to_annotation_groups = sample_annotation_map
+        .iter()
+        .map(|(sample_annotation_token, sample_annotation)| {
+            (sample_annotation.sample_token, *sample_annotation_token)
+        })
+        .into_group_map();
+
+    // keep track of relations from samples to sample data
+    let mut sample_to_sample_data_groups = sample_data_map
+        .iter()
+        .map(|(sample_data_token, sample_data)| (sample_data.sample_token, *sample_data_token))
+        .into_group_map();
+
+    sample_map
+        .into_iter()
+        .map(|(sample_token, sample)| {
+            let sample_data_tokens = sample_to_sample_data_groups
+                .remove(&sample_token)
+                .unwrap_or_default();
+            let annotation_tokens = sample_to_annotation_groups
+                .remove(&sample_token)
+                .unwrap_or_default();
+            let internal = SampleInner::from(sample, annotation_tokens, sample_data_tokens);
+            (sample_token, internal)
+        })
+        .collect()
+}
+
+/// Group the scenes by names, keeping the scenes sorted by the indexes
+/// of their versions.
+fn index_scene_names(
+    scene_map: &HashMap<Token, SceneInner>,
+    scene_versions: &HashMap<Token, usize>,
+) -> HashMap<String, Vec<Token>> {
+    let mut scenes: Vec<&SceneInner> = scene_map.values().collect();
+    scenes.sort_by_key(|scene| (scene_versions.get(&scene.token), scene.token));
+    scenes
+        .into_iter()
+        .map(|scene| (scene.name.clone(), scene.token))
+        .into_group_map()
+}
+
+/// Group the scenes by logs, keeping the scenes sorted by time.
+fn index_log_scenes(
+    scene_map: &HashMap<Token, SceneInner>,
+    sorted_scene_tokens: &[Token],
+) -> HashMap<Token, Vec<Token>> {
+    sorted_scene_tokens
+        .iter()
+        .map(|token| (scene_map[token].log_token, *token))
+        .into_group_map()
+}
+
+/// Group the sample data by scenes, keeping the order of the sorted
+/// tokens.
+fn index_scene_sample_data(
+    sample_data_map: &HashMap<Token, SampleData>,
+    sample_map: &HashMap<Token, SampleInner>,
+    sorted_sample_data_tokens: &[Token],
+) -> HashMap<Token, Vec<Token>> {
+    sorted_sample_data_tokens
+        .iter()
+        .filter_map(|token| {
+            let sample = sample_map.get(&sample_data_map[token].sample_token)?;
+            Some((sample.scene_token, *token))
+        })
+        .into_group_map()
+}
+
+/// Index the logs by capture dates and vehicles.
+fn index_logs(
+    log_map: &HashMap<Token, Log>,
+) -> (BTreeMap<NaiveDate, Vec<Token>>, HashMap<String, Vec<Token>>) {
+    let mut logs: Vec<&Log> = log_map.values().collect();
+    logs.sort_by_key(|log| (log.date_captured, log.token));
+
+    let mut date_map: BTreeMap<NaiveDate, Vec<Token>> = BTreeMap::new();
+    let mut vehicle_map: HashMap<String, Vec<Token>> = HashMap::new();
+    for log in logs {
+        date_map
+            .entry(log.date_captured)
+            .or_default()
+            .push(log.token);
+        vehicle_map
+            .entry(log.vehicle.clone())
+            .or_default()
+            .push(log.token);
+    }
+    (date_map, vehicle_map)
+}
+
+/// Resolve the channel and modality of each calibrated sensor.
+fn index_channels(
+    calibrated_sensor_map: &HashMap<Token, CalibratedSensor>,
+    sensor_map: &HashMap<Token, Sensor>,
+) -> HashMap<Token, (Channel, Modality)> {
+    calibrated_sensor_map
+        .iter()
+        .filter_map(|(token, calibrated_sensor)| {
+            let sensor = sensor_map.get(&calibrated_sensor.sensor_token)?;
+            Some((*token, (sensor.channel, sensor.modality)))
+        })
+        .collect()
+}
+
+/// Index the panoptic records by sample data.
+fn index_panoptic(panoptic_map: &HashMap<Token, Panoptic>) -> HashMap<Token, Token> {
+    panoptic_map
+        .values()
+        .map(|panoptic| (panoptic.sample_data_token, panoptic.token))
+        .collect()
+}
+
+/// Add the per-channel offsets to sample data timestamps.
+fn apply_time_offsets(
+    sample_data_map: &mut HashMap<Token, SampleData>,
+    calibrated_sensor_map: &HashMap<Token, CalibratedSensor>,
+    sensor_map: &HashMap<Token, Sensor>,
+    time_offsets: &HashMap<Channel, Duration>,
+) {
+    if time_offsets.is_empty() {
+        return;
+    }
+
+    for data in sample_data_map.values_mut() {
+        let Some(calibrated_sensor) = calibrated_sensor_map.get(&data.calibrated_sensor_token)
+        else {
+            continue;
+        };
+        let Some(sensor) = sensor_map.get(&calibrated_sensor.sensor_token) else {
+            continue;
+        };
+        if let Some(offset) = time_offsets.get(&sensor.channel) {
+            data.timestamp += *offset;
+        }
+    }
+}
+
+/// Remove the sample data of sweeps and link each key frame to the
+/// adjacent key frames of the same sensor.
+fn remove_sweeps(sample_data_map: &mut HashMap<Token, SampleData>) {
+    let find_key_frame = |mut token: Option<Token>, forward: bool| {
+        while let Some(curr) = token {
+            let data = sample_data_map.get(&curr)?;
+            if data.is_key_frame {
+                return Some(curr);
+            }
+            token = if forward { data.next } else { data.prev };
+        }
+        None
+    };
+    let links: Vec<(Token, Option<Token>, Option<Token>)> = sample_data_map
+        .par_iter()
+        .filter(|(_, data)| data.is_key_frame)
+        .map(|(&token, data)| {
+            let prev = find_key_frame(data.prev, false);
+            let next = find_key_frame(data.next, true);
+            (token, prev, next)
+        })
+        .collect();
+
+    sample_data_map.retain(|_, data| data.is_key_frame);
+    for (token, prev, next) in links {
+        let data = sample_data_map.get_mut(&token).unwrap();
+        data.prev = prev;
+        data.next = next;
+    }
+}
+
+/// Remove the ego poses not referred by any sample data.
+fn retain_used_ego_poses(
+    ego_pose_map: &mut HashMap<Token, EgoPose>,
+    sample_data_map: &HashMap<Token, SampleData>,
+) {
+    let used: HashSet<Token> = sample_data_map
+        .values()
+        .map(|data| data.ego_pose_token)
+        .collect();
+    ego_pose_map.retain(|token, _| used.contains(token));
+}
+
+fn sort_by_timestamp<T, F>(map: &HashMap<Token, T>, timestamp: F) -> Vec<Token>
+where
+    T: Sync,
+    F: Fn(&T) -> NaiveDateTime + Sync,
+{
+    let mut sorted_pairs: Vec<(&Token, NaiveDateTime)> = map
+        .par_iter()
+        .map(|(token, record)| (token, timestamp(record)))
+        .collect();
+    sorted_pairs.par_sort_unstable_by_key(|(_, timestamp)| *timestamp);
+    sorted_pairs
+        .into_par_iter()
+        .map(|(token, _)| *token)
+        .collect()
+}
+
+/// Sort scenes by the timestamps of their first samples.
+fn sort_scenes(
+    scene_map: &HashMap<Token, SceneInner>,
+    sample_map: &HashMap<Token, SampleInner>,
+) -> Vec<Token> {
+    let mut sorted_pairs: Vec<_> = scene_map
+        .par_iter()
+        .map(|(scene_token, scene)| {
+            let timestamps: Vec<NaiveDateTime> = scene
+                .sample_tokens
+                .par_iter()
+                .map(|sample_token| {
+                    let sample = sample_map
+                        .get(sample_token)
+                        .expect("internal error: invalid sample_token");
+                    sample.timestamp
+                })
+                .collect();
+
+            let timestamp = timestamps
+                .into_par_iter()
+                .min()
+                .expect("scene.sample_tokens must not be empty");
+
+            (scene_token, timestamp)
+        })
+        .collect();
+    sorted_pairs.par_sort_unstable_by_key(|(_, timestamp)| *timestamp);
+
+    sorted_pairs
+        .into_par_iter()
+        .map(|(token, _)| *token)
+        .collect()
+}
+
+/// Read the table through the dataset storage and run the hooks on
+/// its records.
+fn load_table<T>(
+    inner: &DatasetInner,
+    record_fields: &mut HashMap<Token, RecordFields>,
+    path: &Path,
+) -> Result<HashMap<Token, T>>
+where
+    T: for<'a> Deserialize<'a> + TableRecord + WithToken + Send,
+    Vec<T>: rayon::iter::IntoParallelIterator<Item = T>,
+{
+    let mut map = to_token_map(read_table(inner, path)?);
+    run_record_hooks(&mut map, &inner.record_hooks, record_fields);
+    Ok(map)
+}
+
+/// Read the records of a table file through the dataset storage.
+fn read_table<T>(inner: &DatasetInner, path: &Path) -> Result<Vec<T>>
+where
+    T: for<'a> Deserialize<'a>,
+{
+    let bytes = read_with_options(&inner.storage, path, &inner.read_options)?;
+    parse_json(bytes.as_slice(), path)
+}
+
+/// Run the hooks of the table on the records in parallel. Records are
+/// re-keyed afterwards since hooks may patch their tokens.
+fn run_record_hooks<T>(
+    map: &mut HashMap<Token, T>,
+    hooks: &[RecordHook],
+    record_fields: &mut HashMap<Token, RecordFields>,
+) where
+    T: TableRecord + WithToken,
+{
+    let hooks: Vec<&RecordHook> = hooks
+        .iter()
+        .filter(|hook| hook.table() == T::TABLE)
+        .collect();
+    if hooks.is_empty() {
+        return;
+    }
+
+    let fields: Vec<(Token, RecordFields)> = map
+        .par_iter_mut()
+        .filter_map(|(_, record)| {
+            let mut fields = RecordFields::new();
+            for hook in &hooks {
+                (hook.hook)(record, &mut fields);
+            }
+            (!fields.is_empty()).then(|| (record.token(), fields))
+        })
+        .collect();
+
+    *map = mem::take(map)
+        .into_values()
+        .map(|record| (record.token(), record))
+        .collect();
+    record_fields.extend(fields);
+}
+
+fn load_map<T, P>(path: P) -> Result<HashMap<Token, T>>
+where
+    P: AsRef<Path>,
+    T: for<'a> Deserialize<'a> + WithToken + Send,
+    Vec<T>: rayon::iter::IntoParallelIterator<Item = T>,
+{
+    let vec: Vec<T> = load_json(path)?;
+    Ok(to_token_map(vec))
+}
+
+#[cfg(feature = "async")]
+async fn load_map_async<T>(path: PathBuf) -> Result<HashMap<Token, T>>
+where
+    T: for<'a> Deserialize<'a> + WithToken + Send + 'static,
+    Vec<T>: rayon::iter::IntoParallelIterator<Item = T>,
+{
+    let vec: Vec<T> = load_json_async(path).await?;
+    Ok(to_token_map(vec))
+}
+
+fn to_token_map<T>(vec: Vec<T>) -> HashMap<Token, T>
+where
+    T: WithToken + Send,
+    Vec<T>: rayon::iter::IntoParallelIterator<Item = T>,
+{
+    vec.into_par_iter()
+        .map(|item| (item.token(), item))
+        .collect()
+}
+
+fn load_json<T, P>(path: P) -> Result<Vec<T>>
+where
+    P: AsRef<Path>,
+    T: for<'a> Deserialize<'a>,
+{
+    let path = path.as_ref();
+    parse_json(BufReader::new(File::open(path)?), path)
+}
+
+/// Read the file with `tokio::fs` and parse it on the blocking thread
+/// pool.
+#[cfg(feature = "async")]
+async fn load_json_async<T>(path: PathBuf) -> Result<Vec<T>>
+where
+    T: for<'a> Deserialize<'a> + Send + 'static,
+{
+    let bytes = tokio::fs::read(&path).await?;
+    join_task(tokio::task::spawn_blocking(move || {
+        parse_json(bytes.as_slice(), &path)
+    }))
+    .await
+}
+
+/// Parse the records of the file read by the reader.
+fn parse_json<T, R>(reader: R, path: &Path) -> Result<Vec<T>>
+where
+    T: for<'a> Deserialize<'a>,
+    R: Read,
+{
+    let mut deserializer = serde_json::Deserializer::from_reader(reader);
+    let mut num_records = 0;
+    let seed = RecordsSeed {
+        num_records: &mut num_records,
+        _phantom: PhantomData,
+    };
+
+    let result = seed
+        .deserialize(&mut deserializer)
+        .and_then(|records| deserializer.end().map(|()| records));
+    result.map_err(|err| {
+        let mut msg = format!(
+            "failed to load file {}: record {}",
+            path.display(),
+            num_records
+        );
+        if let Some((offset, token)) = locate_json_error(path, err.line(), err.column()) {
+            if let Some(token) = token {
+                write!(msg, " near token {token}").unwrap();
+            }
+            write!(msg, " at byte {offset}").unwrap();
+        }
+        write!(msg, ": {err}").unwrap();
+        Error::CorruptedDataset(msg)
+    })
+}
+
+/// Deserializes an array of records while counting the records
+/// completed, so errors can name the failing record.
+struct RecordsSeed<'a, T> {
+    num_records: &'a mut usize,
+    _phantom: PhantomData<T>,
+}
+
+impl<'de, T> DeserializeSeed<'de> for RecordsSeed<'_, T>
+where
+    T: Deserialize<'de>,
+{
+    type Value = Vec<T>;
+
+    fn deserialize<D>(self, deserializer: D) -> std::result::Result<Vec<T>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de, T> Visitor<'de> for RecordsSeed<'_, T>
+where
+    T: Deserialize<'de>,
+{
+    type Value = Vec<T>;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("an array of records")
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> std::result::Result<Vec<T>, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let mut records = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+        while let Some(record) = seq.next_element()? {
+            records.push(record);
+            *self.num_records += 1;
+        }
+        Ok(records)
+    }
+}
+
+const TOKEN_KEY: &[u8] = b"\"token\"";
+
+/// Find the byte offset of an error position and the last token
+/// before it. The file is read again, so it is only called on
+/// failures.
+fn locate_json_error(path: &Path, line: usize, column: usize) -> Option<(usize, Option<String>)> {
+    if line == 0 {
+        return None;
+    }
+    let bytes = fs::read(path).ok()?;
+
+    let line_start = if line == 1 {
+        0
+    } else {
+        let (index, _) = bytes
+            .iter()
+            .enumerate()
+            .filter(|(_, &byte)| byte == b'\n')
+            .nth(line - 2)?;
+        index + 1
+    };
+    let offset = (line_start + column.saturating_sub(1)).min(bytes.len());
+
+    let token = bytes[..offset]
+        .windows(TOKEN_KEY.len())
+        .rposition(|window| window == TOKEN_KEY)
+        .and_then(|index| {
+            let rest = &bytes[index + TOKEN_KEY.len()..offset];
+            let start = rest.iter().position(|&byte| byte == b'"')? + 1;
+            let len = rest[start..].iter().position(|&byte| byte == b'"')?;
+            Some(String::from_utf8_lossy(&rest[start..start + len]).into_owned())
+        });
+    Some((offset, token))
+}
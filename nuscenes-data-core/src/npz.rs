@@ -0,0 +1,214 @@
+//! A minimal reader of NumPy `.npz` archives.
+//!
+//! An `.npz` file is a zip archive of `.npy` files, one per array.
+//! Only stored and deflated entries without zip64 extensions are
+//! supported, which covers archives written by `numpy.savez` and
+//! `numpy.savez_compressed` for arrays smaller than 4 GiB.
+
+use crate::error::{Error, Result};
+use flate2::read::DeflateDecoder;
+use std::{collections::HashMap, io::Read};
+
+const EOCD_SIGNATURE: u32 = 0x06054b50;
+const CENTRAL_SIGNATURE: u32 = 0x02014b50;
+const LOCAL_SIGNATURE: u32 = 0x04034b50;
+const EOCD_SIZE: usize = 22;
+const NPY_MAGIC: &[u8] = b"\x93NUMPY";
+
+/// An array decoded from a `.npy` file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NpyArray {
+    /// The NumPy type string, such as `<u2`.
+    pub descr: String,
+    pub fortran_order: bool,
+    pub shape: Vec<usize>,
+    /// The raw bytes of the elements.
+    pub data: Vec<u8>,
+}
+
+impl NpyArray {
+    /// Parse the contents of a `.npy` file.
+    pub fn parse(bytes: &[u8]) -> Result<Self> {
+        if !bytes.starts_with(NPY_MAGIC) || bytes.len() < 10 {
+            return Err(parse_error("not a .npy file"));
+        }
+
+        let major = bytes[6];
+        let (header_len, header_start) = match major {
+            1 => (u16::from_le_bytes([bytes[8], bytes[9]]) as usize, 10),
+            2 | 3 => (read_u32(bytes, 8)? as usize, 12),
+            _ => return Err(parse_error(format!("unsupported .npy version {major}"))),
+        };
+        let header = bytes
+            .get(header_start..header_start + header_len)
+            .ok_or_else(|| parse_error("truncated .npy header"))?;
+        let header =
+            std::str::from_utf8(header).map_err(|_| parse_error("non-UTF-8 .npy header"))?;
+
+        let descr = header_value(header, "descr")?
+            .trim_matches(|c| c == '\'' || c == '"')
+            .to_string();
+        let fortran_order = header_value(header, "fortran_order")? == "True";
+        let shape = header_value(header, "shape")?
+            .trim_matches(|c| c == '(' || c == ')')
+            .split(',')
+            .map(str::trim)
+            .filter(|dim| !dim.is_empty())
+            .map(|dim| {
+                dim.parse()
+                    .map_err(|_| parse_error(format!("invalid .npy shape dimension {dim}")))
+            })
+            .collect::<Result<Vec<usize>>>()?;
+
+        Ok(Self {
+            descr,
+            fortran_order,
+            shape,
+            data: bytes[header_start + header_len..].to_vec(),
+        })
+    }
+
+    /// Get the number of elements.
+    pub fn len(&self) -> usize {
+        self.shape.iter().product()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Convert the elements into `u16` values. Unsigned integer arrays
+    /// of at most 16 bits are accepted.
+    pub fn to_u16_vec(&self) -> Result<Vec<u16>> {
+        let len = self.len();
+        let values: Vec<u16> = match self.descr.as_str() {
+            "|u1" | "<u1" | ">u1" => self.data.iter().map(|&value| value as u16).collect(),
+            "<u2" => self
+                .data
+                .chunks_exact(2)
+                .map(|chunk| u16::from_le_bytes([chunk[0], chunk[1]]))
+                .collect(),
+            ">u2" => self
+                .data
+                .chunks_exact(2)
+                .map(|chunk| u16::from_be_bytes([chunk[0], chunk[1]]))
+                .collect(),
+            descr => {
+                return Err(parse_error(format!(
+                    "cannot convert .npy type {descr} to u16"
+                )))
+            }
+        };
+
+        if values.len() < len {
+            return Err(parse_error(format!(
+                "the .npy array has {} elements, but its shape requires {len}",
+                values.len()
+            )));
+        }
+        Ok(values[..len].to_vec())
+    }
+}
+
+/// Read all arrays of an `.npz` archive. The arrays are keyed by the
+/// entry names without the `.npy` suffix, such as `data`.
+pub fn read_npz(bytes: &[u8]) -> Result<HashMap<String, NpyArray>> {
+    // Locate the end of central directory record
+    let eocd = (0..=bytes.len().saturating_sub(EOCD_SIZE))
+        .rev()
+        .find(|&offset| read_u32(bytes, offset).ok() == Some(EOCD_SIGNATURE))
+        .ok_or_else(|| parse_error("not a zip archive"))?;
+    let num_entries = read_u16(bytes, eocd + 10)? as usize;
+    let mut offset = read_u32(bytes, eocd + 16)? as usize;
+
+    let mut arrays = HashMap::with_capacity(num_entries);
+    for _ in 0..num_entries {
+        if read_u32(bytes, offset)? != CENTRAL_SIGNATURE {
+            return Err(parse_error("corrupted zip central directory"));
+        }
+        let method = read_u16(bytes, offset + 10)?;
+        let compressed_size = read_u32(bytes, offset + 20)?;
+        let uncompressed_size = read_u32(bytes, offset + 24)?;
+        let name_len = read_u16(bytes, offset + 28)? as usize;
+        let extra_len = read_u16(bytes, offset + 30)? as usize;
+        let comment_len = read_u16(bytes, offset + 32)? as usize;
+        let local_offset = read_u32(bytes, offset + 42)? as usize;
+        let name = bytes
+            .get(offset + 46..offset + 46 + name_len)
+            .ok_or_else(|| parse_error("truncated zip central directory"))?;
+        let name = String::from_utf8_lossy(name).into_owned();
+        offset += 46 + name_len + extra_len + comment_len;
+
+        if compressed_size == u32::MAX || uncompressed_size == u32::MAX {
+            return Err(parse_error(format!("zip64 entry {name} is not supported")));
+        }
+
+        // Locate the entry data after the local header
+        if read_u32(bytes, local_offset)? != LOCAL_SIGNATURE {
+            return Err(parse_error(format!("corrupted zip entry {name}")));
+        }
+        let data_offset = local_offset
+            + 30
+            + read_u16(bytes, local_offset + 26)? as usize
+            + read_u16(bytes, local_offset + 28)? as usize;
+        let data = bytes
+            .get(data_offset..data_offset + compressed_size as usize)
+            .ok_or_else(|| parse_error(format!("truncated zip entry {name}")))?;
+
+        let npy = match method {
+            0 => data.to_vec(),
+            8 => {
+                let mut npy = Vec::with_capacity(uncompressed_size as usize);
+                DeflateDecoder::new(data).read_to_end(&mut npy)?;
+                npy
+            }
+            _ => {
+                return Err(parse_error(format!(
+                    "unsupported compression method {method} of zip entry {name}"
+                )))
+            }
+        };
+
+        let key = name.strip_suffix(".npy").unwrap_or(&name).to_string();
+        arrays.insert(key, NpyArray::parse(&npy)?);
+    }
+
+    Ok(arrays)
+}
+
+/// Find the value of the key in the header dict, such as
+/// `{'descr': '<u2', 'fortran_order': False, 'shape': (34688,), }`.
+fn header_value<'a>(header: &'a str, key: &str) -> Result<&'a str> {
+    let pattern = format!("'{key}':");
+    let start = header
+        .find(&pattern)
+        .ok_or_else(|| parse_error(format!("missing {key} in .npy header")))?
+        + pattern.len();
+    let rest = header[start..].trim_start();
+
+    let end = if rest.starts_with('(') {
+        rest.find(')').map(|end| end + 1)
+    } else {
+        rest.find([',', '}'])
+    }
+    .ok_or_else(|| parse_error(format!("invalid {key} in .npy header")))?;
+    Ok(rest[..end].trim())
+}
+
+fn read_u16(bytes: &[u8], offset: usize) -> Result<u16> {
+    let bytes = bytes
+        .get(offset..offset + 2)
+        .ok_or_else(|| parse_error("unexpected end of zip archive"))?;
+    Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Result<u32> {
+    let bytes = bytes
+        .get(offset..offset + 4)
+        .ok_or_else(|| parse_error("unexpected end of zip archive"))?;
+    Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+fn parse_error(msg: impl Into<String>) -> Error {
+    Error::ParseError(msg.into())
+}
@@ -0,0 +1,151 @@
+//! Consistency checks of ego poses against wheel odometry.
+//!
+//! The odometry is supplied by the caller, such as the vehicle speeds
+//! recorded on the CAN bus by
+//! [SceneCanBus::odometry](crate::canbus::SceneCanBus::odometry). Ego
+//! pose displacements between key frames are compared with the
+//! distances integrated from the speeds, and intervals that disagree
+//! are reported as glitches.
+
+use crate::{
+    dataset::{SampleRef, SceneRef},
+    serializable::{Channel, Token},
+};
+use chrono::NaiveDateTime;
+
+/// A speed measurement of the ego vehicle.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OdometrySample {
+    pub timestamp: NaiveDateTime,
+    /// The speed in m/s.
+    pub speed: f64,
+}
+
+/// Tolerances of the odometry check.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OdometryCheckOptions {
+    /// The allowed absolute difference in meters.
+    pub absolute_tolerance: f64,
+    /// The allowed difference relative to the odometry distance.
+    pub relative_tolerance: f64,
+}
+
+impl Default for OdometryCheckOptions {
+    fn default() -> Self {
+        Self {
+            absolute_tolerance: 1.0,
+            relative_tolerance: 0.2,
+        }
+    }
+}
+
+/// An interval between consecutive samples where the ego pose
+/// displacement disagrees with the odometry.
+#[derive(Debug, Clone)]
+pub struct PoseGlitch {
+    pub start_sample_token: Token,
+    pub end_sample_token: Token,
+    /// The planar displacement of the ego poses in meters.
+    pub pose_distance: f64,
+    /// The distance integrated from the odometry in meters.
+    pub odometry_distance: f64,
+}
+
+impl PoseGlitch {
+    pub fn error(&self) -> f64 {
+        (self.pose_distance - self.odometry_distance).abs()
+    }
+}
+
+impl SceneRef {
+    /// Compare the ego pose displacement between consecutive samples
+    /// with the odometry. Intervals not covered by the odometry are
+    /// skipped.
+    pub fn check_ego_odometry(
+        &self,
+        odometry: &[OdometrySample],
+        options: &OdometryCheckOptions,
+    ) -> Vec<PoseGlitch> {
+        let mut odometry = odometry.to_vec();
+        odometry.sort_by_key(|sample| sample.timestamp);
+
+        let poses: Vec<_> = self
+            .sample_iter()
+            .filter_map(|sample| {
+                let position = ego_position(&sample)?;
+                Some((sample, position))
+            })
+            .collect();
+
+        poses
+            .windows(2)
+            .filter_map(|pair| {
+                let (start, [x1, y1]) = &pair[0];
+                let (end, [x2, y2]) = &pair[1];
+                let odometry_distance = integrate_speed(&odometry, start.timestamp, end.timestamp)?;
+                let pose_distance = (x2 - x1).hypot(y2 - y1);
+
+                let tolerance =
+                    options.absolute_tolerance + options.relative_tolerance * odometry_distance;
+                ((pose_distance - odometry_distance).abs() > tolerance).then(|| PoseGlitch {
+                    start_sample_token: start.token,
+                    end_sample_token: end.token,
+                    pose_distance,
+                    odometry_distance,
+                })
+            })
+            .collect()
+    }
+}
+
+fn ego_position(sample: &SampleRef) -> Option<[f64; 2]> {
+    let data = sample.sample_data_by_channel(Channel::LidarTop)?;
+    let [x, y, _] = data.ego_pose().translation;
+    Some([x, y])
+}
+
+/// Integrate linearly interpolated speeds over the time range. It
+/// returns `None` if the odometry does not cover the range.
+fn integrate_speed(
+    odometry: &[OdometrySample],
+    start: NaiveDateTime,
+    end: NaiveDateTime,
+) -> Option<f64> {
+    let first = odometry.first()?;
+    let last = odometry.last()?;
+    if start < first.timestamp || end > last.timestamp {
+        return None;
+    }
+
+    let speed_at = |time: NaiveDateTime| -> f64 {
+        let index = odometry.partition_point(|sample| sample.timestamp < time);
+        let next = &odometry[index];
+        if index == 0 || next.timestamp == time {
+            return next.speed;
+        }
+        let prev = &odometry[index - 1];
+        let ratio = seconds(time - prev.timestamp) / seconds(next.timestamp - prev.timestamp);
+        prev.speed + (next.speed - prev.speed) * ratio
+    };
+
+    // Integrate with the trapezoidal rule over the interval split at
+    // the odometry timestamps.
+    let times: Vec<NaiveDateTime> = std::iter::once(start)
+        .chain(
+            odometry
+                .iter()
+                .map(|sample| sample.timestamp)
+                .filter(|&time| start < time && time < end),
+        )
+        .chain(std::iter::once(end))
+        .collect();
+    let distance = times
+        .windows(2)
+        .map(|pair| seconds(pair[1] - pair[0]) * (speed_at(pair[0]) + speed_at(pair[1])) / 2.0)
+        .sum();
+    Some(distance)
+}
+
+fn seconds(duration: chrono::Duration) -> f64 {
+    duration.num_microseconds().unwrap() as f64 / 1_000_000.0
+}
@@ -0,0 +1,218 @@
+//! Bird's-eye-view overlaps between boxes.
+//!
+//! Boxes are reduced to their yaw-only footprints on the xy-plane. The
+//! footprints and circumscribed radii are computed once per box, and
+//! pairs whose circumscribed circles are disjoint are skipped before
+//! the exact polygon intersection, which makes matrices over crowded
+//! samples cheap.
+
+use crate::{
+    dataset::SampleRef,
+    geometry::{polygon_area, Box3D},
+    serializable::Token,
+};
+use rayon::prelude::*;
+
+/// A dense matrix of IoU values in row-major order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IouMatrix {
+    rows: usize,
+    cols: usize,
+    values: Vec<f64>,
+}
+
+impl IouMatrix {
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
+    pub fn get(&self, row: usize, col: usize) -> f64 {
+        assert!(row < self.rows && col < self.cols);
+        self.values[row * self.cols + col]
+    }
+
+    pub fn row(&self, row: usize) -> &[f64] {
+        &self.values[row * self.cols..(row + 1) * self.cols]
+    }
+
+    pub fn values(&self) -> &[f64] {
+        &self.values
+    }
+}
+
+/// Compute the BEV IoU of two boxes.
+pub fn bev_iou(lhs: &Box3D, rhs: &Box3D) -> f64 {
+    Footprint::new(lhs).iou(&Footprint::new(rhs))
+}
+
+/// Compute the pairwise BEV IoU of the boxes in parallel. The matrix
+/// is symmetric with ones on the diagonal for non-degenerate boxes.
+pub fn bev_iou_matrix(boxes: &[Box3D]) -> IouMatrix {
+    let footprints: Vec<Footprint> = boxes.iter().map(Footprint::new).collect();
+    let len = footprints.len();
+
+    // Compute the upper triangle and mirror it
+    let mut values = vec![0.0; len * len];
+    values
+        .par_chunks_mut(len.max(1))
+        .enumerate()
+        .for_each(|(row, values)| {
+            for col in row..len {
+                values[col] = footprints[row].iou(&footprints[col]);
+            }
+        });
+    for row in 0..len {
+        for col in 0..row {
+            values[row * len + col] = values[col * len + row];
+        }
+    }
+
+    IouMatrix {
+        rows: len,
+        cols: len,
+        values,
+    }
+}
+
+/// Compute the BEV IoU between each box of `lhs` and each box of
+/// `rhs` in parallel, such as between detections and ground truths.
+pub fn bev_iou_cross_matrix(lhs: &[Box3D], rhs: &[Box3D]) -> IouMatrix {
+    let lhs: Vec<Footprint> = lhs.iter().map(Footprint::new).collect();
+    let rhs: Vec<Footprint> = rhs.iter().map(Footprint::new).collect();
+    let cols = rhs.len();
+
+    let mut values = vec![0.0; lhs.len() * cols];
+    values
+        .par_chunks_mut(cols.max(1))
+        .zip(&lhs)
+        .for_each(|(values, lhs)| {
+            for (value, rhs) in values.iter_mut().zip(&rhs) {
+                *value = lhs.iou(rhs);
+            }
+        });
+
+    IouMatrix {
+        rows: lhs.len(),
+        cols,
+        values,
+    }
+}
+
+/// Select boxes by greedy non-maximum suppression on BEV IoU.
+///
+/// Boxes are visited in descending order of scores, and a box is
+/// suppressed if its IoU with a selected box exceeds `iou_threshold`.
+/// It returns the indices of selected boxes in descending order of
+/// scores.
+pub fn bev_nms(boxes: &[Box3D], scores: &[f64], iou_threshold: f64) -> Vec<usize> {
+    assert_eq!(boxes.len(), scores.len());
+
+    let mut order: Vec<usize> = (0..boxes.len()).collect();
+    order.sort_by(|&lhs, &rhs| scores[rhs].total_cmp(&scores[lhs]));
+
+    let mut selected: Vec<(usize, Footprint)> = vec![];
+    for index in order {
+        let footprint = Footprint::new(&boxes[index]);
+        let suppressed = selected
+            .iter()
+            .any(|(_, other)| footprint.iou(other) > iou_threshold);
+        if !suppressed {
+            selected.push((index, footprint));
+        }
+    }
+
+    selected.into_iter().map(|(index, _)| index).collect()
+}
+
+impl SampleRef {
+    /// Compute the pairwise BEV IoU of the annotation boxes. The rows
+    /// and columns follow the order of the returned annotation tokens.
+    pub fn annotation_bev_iou_matrix(&self) -> (Vec<Token>, IouMatrix) {
+        let (tokens, boxes): (Vec<Token>, Vec<Box3D>) = self
+            .annotation_iter()
+            .map(|annotation| (annotation.token, Box3D::from(&*annotation)))
+            .unzip();
+        (tokens, bev_iou_matrix(&boxes))
+    }
+}
+
+/// The precomputed footprint of a box.
+struct Footprint {
+    corners: [[f64; 2]; 4],
+    center: [f64; 2],
+    radius: f64,
+    area: f64,
+}
+
+impl Footprint {
+    fn new(bbox: &Box3D) -> Self {
+        let [width, length, _] = bbox.size;
+        let [x, y, _] = bbox.center;
+        Self {
+            corners: bbox.bev_corners(),
+            center: [x, y],
+            radius: width.hypot(length) / 2.0,
+            area: width * length,
+        }
+    }
+
+    fn iou(&self, other: &Footprint) -> f64 {
+        let distance = (self.center[0] - other.center[0]).hypot(self.center[1] - other.center[1]);
+        if distance >= self.radius + other.radius {
+            return 0.0;
+        }
+
+        let intersection = polygon_area(&clip_convex(&self.corners, &other.corners));
+        let union = self.area + other.area - intersection;
+        if union <= 0.0 {
+            return 0.0;
+        }
+        intersection / union
+    }
+}
+
+/// Clip a convex polygon by another counterclockwise convex polygon
+/// using the Sutherland-Hodgman algorithm.
+fn clip_convex(subject: &[[f64; 2]], clip: &[[f64; 2]]) -> Vec<[f64; 2]> {
+    let mut output = subject.to_vec();
+
+    for (index, &start) in clip.iter().enumerate() {
+        if output.is_empty() {
+            break;
+        }
+        let end = clip[(index + 1) % clip.len()];
+        // Positive on the left side of the edge, which is inside
+        let side = |p: [f64; 2]| {
+            (end[0] - start[0]) * (p[1] - start[1]) - (end[1] - start[1]) * (p[0] - start[0])
+        };
+
+        let input = std::mem::take(&mut output);
+        for (index, &curr) in input.iter().enumerate() {
+            let prev = input[(index + input.len() - 1) % input.len()];
+            let (prev_side, curr_side) = (side(prev), side(curr));
+            let intersect = || {
+                let ratio = prev_side / (prev_side - curr_side);
+                [
+                    prev[0] + (curr[0] - prev[0]) * ratio,
+                    prev[1] + (curr[1] - prev[1]) * ratio,
+                ]
+            };
+
+            match (prev_side >= 0.0, curr_side >= 0.0) {
+                (true, true) => output.push(curr),
+                (true, false) => output.push(intersect()),
+                (false, true) => {
+                    output.push(intersect());
+                    output.push(curr);
+                }
+                (false, false) => {}
+            }
+        }
+    }
+
+    output
+}
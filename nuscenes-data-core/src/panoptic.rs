@@ -0,0 +1,64 @@
+//! Panoptic nuScenes labels.
+//!
+//! The `panoptic.json` table links lidar sweeps to `.npz` label files.
+//! Each file stores a `data` array of one `u16` label per point, which
+//! encodes the class index times [PANOPTIC_INSTANCE_DIVISOR] plus the
+//! instance id.
+
+use crate::{
+    dataset::SampleDataRef,
+    error::{Error, Result},
+    npz,
+    storage::{read_data_with_options, DataPath},
+};
+
+/// The divisor separating class indices and instance ids in raw
+/// panoptic labels.
+pub const PANOPTIC_INSTANCE_DIVISOR: u16 = 1000;
+
+/// The panoptic label of a lidar point.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PanopticLabel {
+    /// The class index, which maps to the `index` field of categories.
+    pub semantic_id: u8,
+    /// The instance id unique within the sweep, or zero if the point
+    /// does not belong to any instance.
+    pub instance_id: u16,
+}
+
+impl PanopticLabel {
+    pub fn from_raw(raw: u16) -> Self {
+        Self {
+            semantic_id: (raw / PANOPTIC_INSTANCE_DIVISOR) as u8,
+            instance_id: raw % PANOPTIC_INSTANCE_DIVISOR,
+        }
+    }
+
+    pub fn to_raw(&self) -> u16 {
+        self.semantic_id as u16 * PANOPTIC_INSTANCE_DIVISOR + self.instance_id
+    }
+}
+
+impl SampleDataRef {
+    /// Load the panoptic labels of the points of the sweep. It returns
+    /// `None` if the sweep has no panoptic record.
+    pub fn load_panoptic_labels(&self) -> Result<Option<Vec<PanopticLabel>>> {
+        let Some(panoptic) = self.panoptic() else {
+            return Ok(None);
+        };
+
+        let dataset = self.dataset();
+        let path = DataPath::new(&dataset.dataset_dir, &panoptic.filename);
+        let bytes = read_data_with_options(&dataset.storage, path, &dataset.read_options)?;
+        let arrays = npz::read_npz(&bytes)?;
+        let array = arrays
+            .get("data")
+            .ok_or_else(|| Error::ParseError(format!("missing data array in {path}")))?;
+        let labels = array
+            .to_u16_vec()?
+            .into_iter()
+            .map(PanopticLabel::from_raw)
+            .collect();
+        Ok(Some(labels))
+    }
+}
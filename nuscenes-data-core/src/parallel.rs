@@ -0,0 +1,79 @@
+//! Per-scene parallel processing.
+//!
+//! Bulk jobs such as exports and statistics are split by scene. Each
+//! scene is processed by one task on the rayon thread pool, so the
+//! records of a scene stay on one thread and at most one scene per
+//! worker is in flight. Run the methods inside
+//! [ThreadPool::install](rayon::ThreadPool::install) to bound the
+//! number of workers.
+
+use crate::dataset::{Dataset, SceneRef};
+use rayon::prelude::*;
+
+impl Dataset {
+    /// Run the closure on every scene in parallel.
+    ///
+    /// ```ignore
+    /// dataset.par_for_each_scene(|scene| {
+    ///     export_scene(&scene, &out_dir).unwrap();
+    /// });
+    /// ```
+    pub fn par_for_each_scene<F>(&self, op: F)
+    where
+        F: Fn(SceneRef) + Send + Sync,
+    {
+        self.par_scene_iter().for_each(op);
+    }
+
+    /// Run the closure on every scene in parallel and collect the
+    /// results in the order of the scene start times.
+    pub fn par_map_scenes<F, T>(&self, op: F) -> Vec<T>
+    where
+        F: Fn(SceneRef) -> T + Send + Sync,
+        T: Send,
+    {
+        self.par_scene_iter().map(op).collect()
+    }
+
+    /// Run the fallible closure on every scene in parallel and collect
+    /// the results in the order of the scene start times. It stops at
+    /// the first error.
+    pub fn par_try_map_scenes<F, T, E>(&self, op: F) -> Result<Vec<T>, E>
+    where
+        F: Fn(SceneRef) -> Result<T, E> + Send + Sync,
+        T: Send,
+        E: Send,
+    {
+        self.par_scene_iter().map(op).collect()
+    }
+
+    /// Map every scene in parallel and reduce the results, without
+    /// keeping the results of all scenes in memory. The `reduce`
+    /// closure must be associative, and `identity` must return its
+    /// identity element.
+    ///
+    /// ```ignore
+    /// let num_annotations = dataset.par_map_reduce_scenes(
+    ///     |scene| scene.sample_iter().map(|sample| sample.annotation_tokens.len()).sum(),
+    ///     || 0,
+    ///     |lhs, rhs| lhs + rhs,
+    /// );
+    /// ```
+    pub fn par_map_reduce_scenes<M, I, R, T>(&self, map: M, identity: I, reduce: R) -> T
+    where
+        M: Fn(SceneRef) -> T + Send + Sync,
+        I: Fn() -> T + Send + Sync,
+        R: Fn(T, T) -> T + Send + Sync,
+        T: Send,
+    {
+        self.par_scene_iter().map(map).reduce(identity, reduce)
+    }
+
+    /// Iterate over the scenes in parallel, one scene per task.
+    fn par_scene_iter(&self) -> impl IndexedParallelIterator<Item = SceneRef> + '_ {
+        self.sorted_scene_tokens
+            .par_iter()
+            .with_max_len(1)
+            .map(|&token| self.scene(token).unwrap())
+    }
+}
@@ -0,0 +1,367 @@
+//! Modality-independent point cloud representation.
+//!
+//! Lidar sweeps, radar scans and lidarseg labels are converted into
+//! [PointCloud], which stores point positions along with named
+//! per-point attributes.
+
+use crate::{
+    dataset::SampleDataRef,
+    error::{Error, Result},
+    range_image::{self, RangeImage, RangeViewConfig},
+};
+use std::{collections::BTreeMap, fs, mem, ops::Deref, path::Path};
+
+/// Attribute name of lidar intensity values.
+pub const INTENSITY: &str = "intensity";
+/// Attribute name of lidar ring (beam) indices.
+pub const RING_INDEX: &str = "ring_index";
+/// Attribute name of lidarseg class indices.
+pub const LIDARSEG_LABEL: &str = "lidarseg_label";
+
+/// The number of `f32` values per point in nuScenes lidar `.bin` files.
+pub const LIDAR_BIN_POINT_LEN: usize = 5;
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct PointCloud {
+    pub positions: Vec<[f32; 3]>,
+    attributes: BTreeMap<String, PointAttribute>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PointAttribute {
+    F32(Vec<f32>),
+    I32(Vec<i32>),
+    I16(Vec<i16>),
+    I8(Vec<i8>),
+    U8(Vec<u8>),
+}
+
+impl PointAttribute {
+    pub fn len(&self) -> usize {
+        match self {
+            Self::F32(values) => values.len(),
+            Self::I32(values) => values.len(),
+            Self::I16(values) => values.len(),
+            Self::I8(values) => values.len(),
+            Self::U8(values) => values.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Get the value at `index` converted to `f64`.
+    pub fn get_f64(&self, index: usize) -> Option<f64> {
+        let value = match self {
+            Self::F32(values) => *values.get(index)? as f64,
+            Self::I32(values) => *values.get(index)? as f64,
+            Self::I16(values) => *values.get(index)? as f64,
+            Self::I8(values) => *values.get(index)? as f64,
+            Self::U8(values) => *values.get(index)? as f64,
+        };
+        Some(value)
+    }
+
+    fn retain_indices(&mut self, keep: &[bool]) {
+        fn retain<T>(values: &mut Vec<T>, keep: &[bool]) {
+            let mut iter = keep.iter();
+            values.retain(|_| *iter.next().unwrap());
+        }
+
+        match self {
+            Self::F32(values) => retain(values, keep),
+            Self::I32(values) => retain(values, keep),
+            Self::I16(values) => retain(values, keep),
+            Self::I8(values) => retain(values, keep),
+            Self::U8(values) => retain(values, keep),
+        }
+    }
+}
+
+impl PointCloud {
+    pub fn new(positions: Vec<[f32; 3]>) -> Self {
+        Self {
+            positions,
+            attributes: BTreeMap::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    pub fn attribute(&self, name: &str) -> Option<&PointAttribute> {
+        self.attributes.get(name)
+    }
+
+    pub fn attributes(&self) -> impl Iterator<Item = (&str, &PointAttribute)> + '_ {
+        self.attributes
+            .iter()
+            .map(|(name, values)| (name.as_str(), values))
+    }
+
+    /// Add or replace a per-point attribute.
+    ///
+    /// It fails if the number of values does not match the number of
+    /// points.
+    pub fn insert_attribute<S>(&mut self, name: S, values: PointAttribute) -> Result<()>
+    where
+        S: Into<String>,
+    {
+        let name = name.into();
+        if values.len() != self.len() {
+            let msg = format!(
+                "the attribute {name} has {} values, but the point cloud has {} points",
+                values.len(),
+                self.len()
+            );
+            return Err(Error::ParseError(msg));
+        }
+        self.attributes.insert(name, values);
+        Ok(())
+    }
+
+    pub fn remove_attribute(&mut self, name: &str) -> Option<PointAttribute> {
+        self.attributes.remove(name)
+    }
+
+    /// Keep only the points for which the predicate returns true.
+    /// Attributes are filtered accordingly.
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(usize, &[f32; 3]) -> bool,
+    {
+        let keep: Vec<bool> = self
+            .positions
+            .iter()
+            .enumerate()
+            .map(|(index, position)| f(index, position))
+            .collect();
+
+        let mut iter = keep.iter();
+        self.positions.retain(|_| *iter.next().unwrap());
+        self.attributes
+            .values_mut()
+            .for_each(|values| values.retain_indices(&keep));
+    }
+
+    /// Parse the content of a nuScenes lidar `.bin` file, which
+    /// consists of (x, y, z, intensity, ring_index) `f32` tuples.
+    pub fn from_lidar_bin_bytes(bytes: &[u8]) -> Result<Self> {
+        check_lidar_bin_size(bytes.len())?;
+        let point_size = mem::size_of::<LidarBinPoint>();
+
+        let num_points = bytes.len() / point_size;
+        let mut positions = Vec::with_capacity(num_points);
+        let mut intensities = Vec::with_capacity(num_points);
+        let mut ring_indices = Vec::with_capacity(num_points);
+
+        bytes.chunks_exact(point_size).for_each(|chunk| {
+            let mut values = chunk
+                .chunks_exact(mem::size_of::<f32>())
+                .map(|bytes| f32::from_le_bytes(bytes.try_into().unwrap()));
+            let mut next = || values.next().unwrap();
+            positions.push([next(), next(), next()]);
+            intensities.push(next());
+            ring_indices.push(next() as i32);
+        });
+
+        let mut pcd = Self::new(positions);
+        pcd.insert_attribute(INTENSITY, PointAttribute::F32(intensities))?;
+        pcd.insert_attribute(RING_INDEX, PointAttribute::I32(ring_indices))?;
+        Ok(pcd)
+    }
+
+    /// Load the nuScenes lidar `.bin` file of the sample data through
+    /// the dataset storage.
+    pub fn load_lidar_bin(data: &SampleDataRef) -> Result<Self> {
+        let bytes = data.read_bytes()?;
+        Self::from_lidar_bin_bytes(&bytes).map_err(|_| Error::CorruptedFile(data.path().into()))
+    }
+
+    /// Attach lidarseg class indices to the points.
+    pub fn set_lidarseg_labels(&mut self, labels: Vec<u8>) -> Result<()> {
+        self.insert_attribute(LIDARSEG_LABEL, PointAttribute::U8(labels))
+    }
+}
+
+/// Project the points in the lidar frame onto a range image with
+/// uniform elevation and azimuth bins. Points outside the field of
+/// view are dropped, and the nearest point is kept if several fall
+/// into a pixel.
+///
+/// Rows go from `fov_up` at the top to `fov_down` at the bottom, and
+/// columns from `azimuth_max` to `azimuth_min`, where the azimuth is
+/// measured counterclockwise from the x axis.
+pub fn to_range_image(points: &PointCloud, config: &RangeViewConfig) -> RangeImage {
+    let RangeViewConfig {
+        height,
+        width,
+        fov_up,
+        fov_down,
+        azimuth_min,
+        azimuth_max,
+    } = *config;
+    assert!(height > 0, "height must be positive");
+    assert!(fov_down < fov_up, "fov_down must be below fov_up");
+
+    let span = fov_up - fov_down;
+    range_image::project(points, height, width, [azimuth_min, azimuth_max], |index| {
+        let [x, y, z] = points.positions[index].map(|value| value as f64);
+        let elevation = z.atan2(x.hypot(y)).to_degrees();
+        if !(fov_down..=fov_up).contains(&elevation) {
+            return None;
+        }
+        let row = ((fov_up - elevation) / span * height as f64) as usize;
+        Some(row.min(height - 1))
+    })
+}
+
+/// A raw point of nuScenes lidar `.bin` files, consisting of x, y, z,
+/// intensity and ring index.
+pub type LidarBinPoint = [f32; LIDAR_BIN_POINT_LEN];
+
+/// The points of a lidar `.bin` file, either mapped into memory or
+/// owned.
+///
+/// Mapping requires the `mmap` feature and a little-endian target.
+/// The file must not be modified while it is mapped. Mapping opens
+/// the file on the local file system and bypasses the dataset
+/// [Storage](crate::storage::Storage).
+pub enum LidarBinPoints {
+    #[cfg(feature = "mmap")]
+    Mapped(memmap2::Mmap),
+    Owned(Vec<LidarBinPoint>),
+}
+
+impl LidarBinPoints {
+    /// Copy the points from the content of a lidar `.bin` file.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        check_lidar_bin_size(bytes.len())?;
+        let points = bytes
+            .chunks_exact(mem::size_of::<LidarBinPoint>())
+            .map(|chunk| {
+                let mut values = chunk
+                    .chunks_exact(mem::size_of::<f32>())
+                    .map(|bytes| f32::from_le_bytes(bytes.try_into().unwrap()));
+                [(); LIDAR_BIN_POINT_LEN].map(|()| values.next().unwrap())
+            })
+            .collect();
+        Ok(Self::Owned(points))
+    }
+
+    /// Map the file into memory. It falls back to reading the file on
+    /// big-endian targets.
+    #[cfg(feature = "mmap")]
+    pub fn map<P>(path: P) -> Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        if cfg!(target_endian = "big") {
+            return Self::from_bytes(&fs::read(path)?);
+        }
+
+        let file = fs::File::open(path)?;
+        // SAFETY: The mapping is read-only. The file is assumed not
+        // to be modified while it is mapped.
+        let mmap = unsafe { memmap2::Mmap::map(&file)? };
+        check_lidar_bin_size(mmap.len()).map_err(|_| Error::CorruptedFile(path.to_owned()))?;
+        if safe_transmute::transmute_many_pedantic::<LidarBinPoint>(&mmap).is_err() {
+            // Mappings are page-aligned, so it only happens on
+            // unusual platforms.
+            return Self::from_bytes(&mmap);
+        }
+        Ok(Self::Mapped(mmap))
+    }
+
+    /// Map the file if the `mmap` feature is enabled, or read it
+    /// otherwise.
+    pub fn open<P>(path: P) -> Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        #[cfg(feature = "mmap")]
+        return Self::map(path);
+
+        #[cfg(not(feature = "mmap"))]
+        {
+            let path = path.as_ref();
+            Self::from_bytes(&fs::read(path)?).map_err(|_| Error::CorruptedFile(path.to_owned()))
+        }
+    }
+
+    pub fn is_mapped(&self) -> bool {
+        match self {
+            #[cfg(feature = "mmap")]
+            Self::Mapped(_) => true,
+            Self::Owned(_) => false,
+        }
+    }
+
+    pub fn as_slice(&self) -> &[LidarBinPoint] {
+        match self {
+            #[cfg(feature = "mmap")]
+            Self::Mapped(mmap) => {
+                // The size and alignment are checked on mapping
+                safe_transmute::transmute_many_pedantic::<LidarBinPoint>(mmap).unwrap()
+            }
+            Self::Owned(points) => points,
+        }
+    }
+
+    /// Convert the points into [PointCloud].
+    pub fn to_point_cloud(&self) -> PointCloud {
+        let points = self.as_slice();
+        let positions = points.iter().map(|&[x, y, z, ..]| [x, y, z]).collect();
+        let intensities = points.iter().map(|point| point[3]).collect();
+        let ring_indices = points.iter().map(|point| point[4] as i32).collect();
+
+        let mut pcd = PointCloud::new(positions);
+        pcd.insert_attribute(INTENSITY, PointAttribute::F32(intensities))
+            .unwrap();
+        pcd.insert_attribute(RING_INDEX, PointAttribute::I32(ring_indices))
+            .unwrap();
+        pcd
+    }
+}
+
+impl Deref for LidarBinPoints {
+    type Target = [LidarBinPoint];
+
+    fn deref(&self) -> &Self::Target {
+        self.as_slice()
+    }
+}
+
+impl SampleDataRef {
+    /// Load the raw points of a lidar `.bin` file. The file is mapped
+    /// into memory if the `mmap` feature is enabled and the dataset
+    /// storage is [local](crate::storage::Storage::is_local).
+    /// Otherwise, it is read from the storage.
+    pub fn load_lidar_bin_points(&self) -> Result<LidarBinPoints> {
+        #[cfg(feature = "mmap")]
+        if self.dataset().storage.is_local() {
+            if let Ok(points) = LidarBinPoints::map(self.path().to_path_buf()) {
+                return Ok(points);
+            }
+        }
+
+        let bytes = self.read_bytes()?;
+        LidarBinPoints::from_bytes(&bytes).map_err(|_| Error::CorruptedFile(self.path().into()))
+    }
+}
+
+fn check_lidar_bin_size(len: usize) -> Result<()> {
+    let point_size = mem::size_of::<LidarBinPoint>();
+    if !len.is_multiple_of(point_size) {
+        let msg = format!("the buffer size {len} is not multiple of {point_size}");
+        return Err(Error::ParseError(msg));
+    }
+    Ok(())
+}
@@ -0,0 +1,184 @@
+//! Input features for trajectory prediction models.
+//!
+//! For each (instance, sample) pair, it extracts the past states of
+//! the agent, the states of neighboring agents and optionally map
+//! features around the agent.
+
+use super::{
+    convert_global_coords_to_local,
+    helper::{iterate_annotations, Direction},
+    wrap_angle,
+};
+use crate::{
+    dataset::{Dataset, SampleAnnotationRef},
+    geometry::quat_yaw,
+    serializable::Token,
+};
+use chrono::NaiveDateTime;
+use rayon::prelude::*;
+
+#[derive(Debug, Clone)]
+pub struct FeatureConfig {
+    /// The length of the past trajectory in seconds.
+    pub past_seconds: f64,
+    /// Agents within this distance in meters are considered neighbors.
+    pub neighbor_radius: f64,
+    /// Express positions and headings in the agent frame instead of
+    /// the global frame.
+    pub in_agent_frame: bool,
+}
+
+impl Default for FeatureConfig {
+    fn default() -> Self {
+        Self {
+            past_seconds: 2.0,
+            neighbor_radius: 50.0,
+            in_agent_frame: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AgentState {
+    pub timestamp: NaiveDateTime,
+    pub position: [f64; 2],
+    /// The heading angle in radians.
+    pub heading: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct NeighborFeatures {
+    pub instance_token: Token,
+    pub category: String,
+    pub current: AgentState,
+    /// Past states ordered from the most recent one.
+    pub past: Vec<AgentState>,
+}
+
+#[derive(Debug, Clone)]
+pub struct AgentFeatures {
+    pub instance_token: Token,
+    pub sample_token: Token,
+    pub category: String,
+    pub current: AgentState,
+    /// Past states ordered from the most recent one.
+    pub past: Vec<AgentState>,
+    pub neighbors: Vec<NeighborFeatures>,
+    pub map: Option<MapFeatures>,
+}
+
+#[derive(Debug, Clone)]
+pub enum MapFeatures {
+    /// A raster image stored in row-major order with interleaved
+    /// channels.
+    Raster {
+        width: usize,
+        height: usize,
+        channels: usize,
+        data: Vec<u8>,
+    },
+    /// Polylines in the requested frame.
+    Vectors(Vec<Vec<[f64; 2]>>),
+}
+
+/// Provides map features around an agent.
+pub trait MapFeatureExtractor: Sync {
+    /// Extract map features at the `location` map for an agent at the
+    /// global `position` with the global `heading`.
+    fn extract(&self, location: &str, position: [f64; 2], heading: f64) -> MapFeatures;
+}
+
+/// Extract features for the instance at the sample.
+pub fn extract_features(
+    dataset: &Dataset,
+    instance_token: Token,
+    sample_token: Token,
+    config: &FeatureConfig,
+    map: Option<&dyn MapFeatureExtractor>,
+) -> Option<AgentFeatures> {
+    let sample = dataset.sample(sample_token)?;
+    let annotation = sample
+        .annotation_iter()
+        .find(|annotation| annotation.instance_token == instance_token)?;
+    Some(annotation_features(&annotation, config, map))
+}
+
+/// Extract features for every (instance, sample) pair in the dataset.
+pub fn extract_all_features(
+    dataset: &Dataset,
+    config: &FeatureConfig,
+    map: Option<&dyn MapFeatureExtractor>,
+) -> Vec<AgentFeatures> {
+    let annotations: Vec<_> = dataset.sample_annotation_iter().collect();
+    annotations
+        .par_iter()
+        .map(|annotation| annotation_features(annotation, config, map))
+        .collect()
+}
+
+fn annotation_features(
+    annotation: &SampleAnnotationRef,
+    config: &FeatureConfig,
+    map: Option<&dyn MapFeatureExtractor>,
+) -> AgentFeatures {
+    let sample = annotation.sample();
+    let translation = annotation.translation;
+    let rotation = annotation.rotation;
+    let agent_heading = quat_yaw(rotation);
+
+    let to_state = |annotation: &SampleAnnotationRef| {
+        let [x, y, _] = annotation.translation;
+        let state = AgentState {
+            timestamp: annotation.sample().timestamp,
+            position: [x, y],
+            heading: annotation.yaw(),
+        };
+
+        if config.in_agent_frame {
+            AgentState {
+                position: convert_global_coords_to_local(state.position, translation, rotation),
+                heading: wrap_angle(state.heading - agent_heading),
+                ..state
+            }
+        } else {
+            state
+        }
+    };
+    let past_states = |annotation: &SampleAnnotationRef| -> Vec<AgentState> {
+        iterate_annotations(annotation, config.past_seconds, Direction::Past)
+            .iter()
+            .map(to_state)
+            .collect()
+    };
+
+    let neighbors: Vec<_> = sample
+        .annotation_iter()
+        .filter(|other| other.instance_token != annotation.instance_token)
+        .filter(|other| {
+            let dx = other.translation[0] - translation[0];
+            let dy = other.translation[1] - translation[1];
+            dx.hypot(dy) <= config.neighbor_radius
+        })
+        .map(|other| NeighborFeatures {
+            instance_token: other.instance_token,
+            category: other.instance().category().name.clone(),
+            current: to_state(&other),
+            past: past_states(&other),
+        })
+        .collect();
+
+    let map = map.map(|map| {
+        let location = &sample.scene().log().location;
+        map.extract(location, [translation[0], translation[1]], agent_heading)
+    });
+
+    AgentFeatures {
+        instance_token: annotation.instance_token,
+        sample_token: annotation.sample_token,
+        category: annotation.instance().category().name.clone(),
+        current: to_state(annotation),
+        past: past_states(annotation),
+        neighbors,
+        map,
+    }
+}
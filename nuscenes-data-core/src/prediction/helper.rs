@@ -0,0 +1,268 @@
+//! Agent state queries mirroring the devkit's `PredictHelper`.
+
+use super::{convert_global_coords_to_local, wrap_angle};
+use crate::{
+    dataset::{Dataset, SampleAnnotationRef},
+    serializable::Token,
+};
+use std::collections::HashMap;
+
+/// The extra tolerance in seconds when collecting past or future
+/// annotations.
+const TIME_BUFFER: f64 = 0.15;
+
+/// The expected number of annotated samples per second.
+const SAMPLES_PER_SECOND: f64 = 2.0;
+
+/// The default maximum time difference in seconds between
+/// consecutive annotations used to compute derivatives.
+pub const DEFAULT_MAX_TIME_DIFF: f64 = 1.5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Direction {
+    Past,
+    Future,
+}
+
+/// Agent state queries on a dataset.
+///
+/// Quantities that the devkit reports as `nan` are `None` here.
+pub struct PredictHelper {
+    dataset: Dataset,
+    annotation_index: HashMap<(Token, Token), Token>,
+}
+
+impl PredictHelper {
+    pub fn new(dataset: Dataset) -> Self {
+        let annotation_index = dataset
+            .sample_annotation_iter()
+            .map(|annotation| {
+                (
+                    (annotation.sample_token, annotation.instance_token),
+                    annotation.token,
+                )
+            })
+            .collect();
+
+        Self {
+            dataset,
+            annotation_index,
+        }
+    }
+
+    pub fn dataset(&self) -> &Dataset {
+        &self.dataset
+    }
+
+    pub fn get_sample_annotation(
+        &self,
+        instance_token: Token,
+        sample_token: Token,
+    ) -> Option<SampleAnnotationRef> {
+        let token = self.annotation_index.get(&(sample_token, instance_token))?;
+        self.dataset.sample_annotation(*token)
+    }
+
+    /// Get the past xy positions of the agent, ordered from the most
+    /// recent one. The current position is excluded.
+    pub fn get_past_for_agent(
+        &self,
+        instance_token: Token,
+        sample_token: Token,
+        seconds: f64,
+        in_agent_frame: bool,
+    ) -> Option<Vec<[f64; 2]>> {
+        self.get_past_or_future_for_agent(
+            instance_token,
+            sample_token,
+            seconds,
+            in_agent_frame,
+            Direction::Past,
+        )
+    }
+
+    /// Get the future xy positions of the agent in chronological
+    /// order. The current position is excluded.
+    pub fn get_future_for_agent(
+        &self,
+        instance_token: Token,
+        sample_token: Token,
+        seconds: f64,
+        in_agent_frame: bool,
+    ) -> Option<Vec<[f64; 2]>> {
+        self.get_past_or_future_for_agent(
+            instance_token,
+            sample_token,
+            seconds,
+            in_agent_frame,
+            Direction::Future,
+        )
+    }
+
+    /// Get the past positions of all agents at the sample, keyed by
+    /// instance tokens.
+    pub fn get_past_for_sample(
+        &self,
+        sample_token: Token,
+        seconds: f64,
+        in_agent_frame: bool,
+    ) -> Option<HashMap<Token, Vec<[f64; 2]>>> {
+        self.get_past_or_future_for_sample(sample_token, seconds, in_agent_frame, Direction::Past)
+    }
+
+    /// Get the future positions of all agents at the sample, keyed by
+    /// instance tokens.
+    pub fn get_future_for_sample(
+        &self,
+        sample_token: Token,
+        seconds: f64,
+        in_agent_frame: bool,
+    ) -> Option<HashMap<Token, Vec<[f64; 2]>>> {
+        self.get_past_or_future_for_sample(sample_token, seconds, in_agent_frame, Direction::Future)
+    }
+
+    /// Get the speed in m/s computed from the previous annotation.
+    pub fn get_velocity_for_agent(
+        &self,
+        instance_token: Token,
+        sample_token: Token,
+        max_time_diff: f64,
+    ) -> Option<f64> {
+        let (current, prev, time_diff) =
+            self.prev_annotation_pair(instance_token, sample_token, max_time_diff)?;
+        let dx = current.translation[0] - prev.translation[0];
+        let dy = current.translation[1] - prev.translation[1];
+        Some(dx.hypot(dy) / time_diff)
+    }
+
+    /// Get the heading change rate in rad/s computed from the previous
+    /// annotation.
+    pub fn get_heading_change_rate_for_agent(
+        &self,
+        instance_token: Token,
+        sample_token: Token,
+        max_time_diff: f64,
+    ) -> Option<f64> {
+        let (current, prev, time_diff) =
+            self.prev_annotation_pair(instance_token, sample_token, max_time_diff)?;
+        let diff = wrap_angle(current.yaw() - prev.yaw());
+        Some(diff / time_diff)
+    }
+
+    /// Get the acceleration in m/s^2 computed from the velocities at
+    /// this and the previous annotation.
+    pub fn get_acceleration_for_agent(
+        &self,
+        instance_token: Token,
+        sample_token: Token,
+        max_time_diff: f64,
+    ) -> Option<f64> {
+        let (_, prev, time_diff) =
+            self.prev_annotation_pair(instance_token, sample_token, max_time_diff)?;
+        let current_velocity =
+            self.get_velocity_for_agent(instance_token, sample_token, max_time_diff)?;
+        let prev_velocity =
+            self.get_velocity_for_agent(instance_token, prev.sample_token, max_time_diff)?;
+        Some((current_velocity - prev_velocity) / time_diff)
+    }
+
+    fn get_past_or_future_for_agent(
+        &self,
+        instance_token: Token,
+        sample_token: Token,
+        seconds: f64,
+        in_agent_frame: bool,
+        direction: Direction,
+    ) -> Option<Vec<[f64; 2]>> {
+        let start = self.get_sample_annotation(instance_token, sample_token)?;
+        let coords = iterate_annotations(&start, seconds, direction)
+            .iter()
+            .map(|annotation| {
+                let [x, y, _] = annotation.translation;
+                if in_agent_frame {
+                    convert_global_coords_to_local([x, y], start.translation, start.rotation)
+                } else {
+                    [x, y]
+                }
+            })
+            .collect();
+        Some(coords)
+    }
+
+    fn get_past_or_future_for_sample(
+        &self,
+        sample_token: Token,
+        seconds: f64,
+        in_agent_frame: bool,
+        direction: Direction,
+    ) -> Option<HashMap<Token, Vec<[f64; 2]>>> {
+        let sample = self.dataset.sample(sample_token)?;
+        let map = sample
+            .annotation_iter()
+            .filter_map(|annotation| {
+                let coords = self.get_past_or_future_for_agent(
+                    annotation.instance_token,
+                    sample_token,
+                    seconds,
+                    in_agent_frame,
+                    direction,
+                )?;
+                Some((annotation.instance_token, coords))
+            })
+            .collect();
+        Some(map)
+    }
+
+    fn prev_annotation_pair(
+        &self,
+        instance_token: Token,
+        sample_token: Token,
+        max_time_diff: f64,
+    ) -> Option<(SampleAnnotationRef, SampleAnnotationRef, f64)> {
+        let current = self.get_sample_annotation(instance_token, sample_token)?;
+        let prev = current.prev()?;
+        let time_diff = seconds_between(&prev, &current);
+        (time_diff <= max_time_diff).then_some((current, prev, time_diff))
+    }
+}
+
+/// Collect annotations of the same instance within the time span in
+/// the direction, ordered from the nearest one in time.
+pub(crate) fn iterate_annotations(
+    start: &SampleAnnotationRef,
+    seconds: f64,
+    direction: Direction,
+) -> Vec<SampleAnnotationRef> {
+    let seconds_with_buffer = seconds + TIME_BUFFER;
+    let max_annotations = (SAMPLES_PER_SECOND * seconds) as usize;
+    let step = |annotation: &SampleAnnotationRef| match direction {
+        Direction::Past => annotation.prev(),
+        Direction::Future => annotation.next(),
+    };
+
+    let mut annotations = vec![];
+    let mut current = step(start);
+
+    while let Some(annotation) = current {
+        if annotations.len() >= max_annotations {
+            break;
+        }
+
+        let elapsed = seconds_between(start, &annotation).abs();
+        if elapsed >= seconds_with_buffer {
+            break;
+        }
+
+        current = step(&annotation);
+        annotations.push(annotation);
+    }
+
+    annotations
+}
+
+/// Get the time in seconds elapsed from the `from` annotation to the
+/// `to` annotation.
+fn seconds_between(from: &SampleAnnotationRef, to: &SampleAnnotationRef) -> f64 {
+    let duration = to.sample().timestamp - from.sample().timestamp;
+    duration.num_microseconds().unwrap() as f64 / 1_000_000.0
+}
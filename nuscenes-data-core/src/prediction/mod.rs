@@ -0,0 +1,44 @@
+//! Trajectory prediction utilities.
+//!
+//! The agent frame follows the nuScenes devkit convention, in which
+//! the agent is located at the origin and heads to the +y direction.
+
+pub mod features;
+pub mod helper;
+
+use crate::geometry::quat_yaw;
+use std::f64::consts::PI;
+
+/// Convert a global xy position to the frame of an agent located at
+/// `translation` with `rotation`.
+pub fn convert_global_coords_to_local(
+    position: [f64; 2],
+    translation: [f64; 3],
+    rotation: [f64; 4],
+) -> [f64; 2] {
+    let angle = PI / 2.0 - quat_yaw(rotation);
+    let (sin, cos) = angle.sin_cos();
+    let x = position[0] - translation[0];
+    let y = position[1] - translation[1];
+    [cos * x - sin * y, sin * x + cos * y]
+}
+
+/// Convert a position in the agent frame back to the global frame.
+pub fn convert_local_coords_to_global(
+    position: [f64; 2],
+    translation: [f64; 3],
+    rotation: [f64; 4],
+) -> [f64; 2] {
+    let angle = quat_yaw(rotation) - PI / 2.0;
+    let (sin, cos) = angle.sin_cos();
+    let [x, y] = position;
+    [
+        cos * x - sin * y + translation[0],
+        sin * x + cos * y + translation[1],
+    ]
+}
+
+/// Wrap an angle into the `[-pi, pi)` range.
+pub fn wrap_angle(angle: f64) -> f64 {
+    (angle + PI).rem_euclid(2.0 * PI) - PI
+}
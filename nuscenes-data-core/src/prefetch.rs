@@ -0,0 +1,99 @@
+//! Prefetching iterator combinators.
+//!
+//! [PrefetchExt::prefetch] decodes sample data ahead of consumption on
+//! a [Decoder] while preserving the order of the source iterator.
+//!
+//! ```ignore
+//! use nuscenes_data::prefetch::PrefetchExt;
+//!
+//! for (data, payload) in sample.sample_data_iter().prefetch(8) {
+//!     let payload = payload?;
+//! }
+//! ```
+
+use crate::{
+    data_loader::Payload,
+    dataset::SampleDataRef,
+    decoder::{DecodeFuture, Decoder},
+    error::Result,
+};
+use std::{collections::VecDeque, thread};
+
+/// The payload decoded by the registered loaders.
+pub type DecodedPayload = Result<Payload>;
+
+pub trait PrefetchExt: Iterator<Item = SampleDataRef> + Sized {
+    /// Decode up to `depth` items ahead with the registered loaders.
+    fn prefetch(self, depth: usize) -> Prefetch<Self, DecodedPayload> {
+        self.prefetch_with(depth, |data| data.load_any())
+    }
+
+    /// Decode up to `depth` items ahead with a custom decoding
+    /// function.
+    fn prefetch_with<T, F>(self, depth: usize, decode: F) -> Prefetch<Self, T>
+    where
+        T: Send + 'static,
+        F: Fn(&SampleDataRef) -> T + Send + Sync + 'static,
+    {
+        assert!(depth > 0, "depth must be positive");
+        let num_threads = thread::available_parallelism()
+            .map(|num| num.get())
+            .unwrap_or(1)
+            .min(depth);
+
+        Prefetch {
+            iter: self,
+            decoder: Decoder::new(num_threads, decode),
+            pending: VecDeque::with_capacity(depth),
+            depth,
+        }
+    }
+}
+
+impl<I> PrefetchExt for I where I: Iterator<Item = SampleDataRef> {}
+
+/// An iterator yielding sample data with the decoded payloads in the
+/// order of the source iterator.
+pub struct Prefetch<I, T> {
+    iter: I,
+    decoder: Decoder<T>,
+    pending: VecDeque<(SampleDataRef, DecodeFuture<T>)>,
+    depth: usize,
+}
+
+impl<I, T> Prefetch<I, T> {
+    /// Get the number of items submitted but not yet yielded.
+    pub fn num_in_flight(&self) -> usize {
+        self.pending.len()
+    }
+}
+
+impl<I, T> Iterator for Prefetch<I, T>
+where
+    I: Iterator<Item = SampleDataRef>,
+    T: Send + 'static,
+{
+    type Item = (SampleDataRef, T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.pending.len() < self.depth {
+            let Some(data) = self.iter.next() else {
+                break;
+            };
+            let future = self.decoder.submit(data.clone());
+            self.pending.push_back((data, future));
+        }
+
+        let (data, future) = self.pending.pop_front()?;
+        Some((data, future.wait()))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let (lower, upper) = self.iter.size_hint();
+        let num = self.pending.len();
+        (
+            lower.saturating_add(num),
+            upper.and_then(|upper| upper.checked_add(num)),
+        )
+    }
+}
@@ -0,0 +1,315 @@
+//! Projection of annotation boxes onto camera images.
+//!
+//! The same [ProjectionOptions] drive both rendering boxes on images
+//! and exporting 2D annotations, so that both agree on which boxes
+//! are shown and how they are clipped. [project_all_boxes_cached]
+//! stores the boxes of a whole dataset in a [DerivedCache].
+
+use crate::{
+    cache::{ArtifactKey, DerivedCache},
+    camera::CameraIntrinsic,
+    dataset::{Dataset, SampleDataRef},
+    error::Result,
+    geometry::{polygon_area, Box3D},
+    serializable::{Modality, Token},
+};
+use serde::{Deserialize, Serialize};
+
+/// Points closer to the image plane than this depth in meters are
+/// considered behind the camera.
+const MIN_DEPTH: f64 = 0.1;
+
+/// The cache entry of boxes projected by [project_all_boxes_cached].
+pub const PROJECTED_BOXES_ARTIFACT: ArtifactKey = ArtifactKey::new("projected_boxes", 1);
+
+/// The criterion to keep a box by the visibility of its corners.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum BoxVisibility {
+    /// Keep boxes with all corners in the image.
+    All,
+    /// Keep boxes with at least one corner in the image.
+    #[default]
+    Any,
+    /// Keep all boxes.
+    None,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProjectionOptions {
+    pub visibility: BoxVisibility,
+    /// Clip 2D boxes to the image bounds. Otherwise, 2D boxes enclose
+    /// the whole projected 3D boxes and may extend beyond the image.
+    pub clip_to_image: bool,
+    /// Drop boxes that would lose more than this fraction of the
+    /// projected area by clipping.
+    pub max_truncation: Option<f64>,
+    /// Drop boxes without any lidar or radar points, which are fully
+    /// occluded from the sensors.
+    pub omit_occluded: bool,
+}
+
+impl Default for ProjectionOptions {
+    fn default() -> Self {
+        Self {
+            visibility: BoxVisibility::Any,
+            clip_to_image: true,
+            max_truncation: None,
+            omit_occluded: false,
+        }
+    }
+}
+
+/// An annotation box projected onto a camera image.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProjectedBox {
+    pub sample_annotation_token: Token,
+    pub sample_data_token: Token,
+    /// The 3D box in the camera frame.
+    pub box_camera: Box3D,
+    /// The projected corners in the order of [Box3D::corners]. Each
+    /// corner is `[u, v, depth]`, where `u` and `v` are NaN if the
+    /// depth is not positive.
+    pub corners: [[f64; 3]; 8],
+    /// The 2D box `[min_x, min_y, max_x, max_y]` in pixels, which is
+    /// clipped to the image if requested by the options.
+    pub bbox: [f64; 4],
+    /// The fraction of the projected area outside the image.
+    pub truncation: f64,
+}
+
+impl SampleDataRef {
+    /// Project annotation boxes of the sample onto this camera image.
+    ///
+    /// It returns `None` if the sample data is not a camera image.
+    /// Boxes are annotated at the sample timestamp, so they can be
+    /// misaligned on non-key frames.
+    pub fn project_boxes(&self, options: &ProjectionOptions) -> Option<Vec<ProjectedBox>> {
+        let calibrated_sensor = self.calibrated_sensor();
+        if calibrated_sensor.sensor().modality != Modality::Camera {
+            return None;
+        }
+        let intrinsic = calibrated_sensor.intrinsic()?;
+        let image_size = [self.width as f64, self.height as f64];
+        let global_to_camera = self.sensor_to_global().inverse();
+
+        let boxes = self
+            .sample()
+            .annotation_iter()
+            .filter(|annotation| {
+                !options.omit_occluded || annotation.num_lidar_pts + annotation.num_radar_pts > 0
+            })
+            .filter_map(|annotation| {
+                let box_camera = Box3D::from(&*annotation).transform(&global_to_camera);
+                let corners = box_camera.corners().map(|point| {
+                    let [u, v] = intrinsic.project(point).unwrap_or([f64::NAN; 2]);
+                    [u, v, point[2]]
+                });
+
+                let in_image = |[u, v, depth]: &[f64; 3]| {
+                    *depth > MIN_DEPTH
+                        && (0.0..=image_size[0]).contains(u)
+                        && (0.0..=image_size[1]).contains(v)
+                };
+                let keep = match options.visibility {
+                    BoxVisibility::All => corners.iter().all(in_image),
+                    BoxVisibility::Any => corners.iter().any(in_image),
+                    BoxVisibility::None => true,
+                };
+                if !keep {
+                    return None;
+                }
+
+                let hull = convex_hull(&visible_outline(&box_camera, &intrinsic));
+                let clipped = clip_polygon(&hull, image_size);
+                let hull_area = polygon_area(&hull);
+                let truncation = if hull_area > 0.0 {
+                    (1.0 - polygon_area(&clipped) / hull_area).clamp(0.0, 1.0)
+                } else {
+                    1.0
+                };
+                if matches!(options.max_truncation, Some(max) if truncation > max) {
+                    return None;
+                }
+
+                let bbox = if options.clip_to_image {
+                    bounding_box(&clipped)?
+                } else {
+                    bounding_box(&hull)?
+                };
+
+                Some(ProjectedBox {
+                    sample_annotation_token: annotation.token,
+                    sample_data_token: self.token,
+                    box_camera,
+                    corners,
+                    bbox,
+                    truncation,
+                })
+            })
+            .collect();
+
+        Some(boxes)
+    }
+}
+
+/// Project boxes onto all key frame camera images in the dataset,
+/// for example, to export 2D annotations.
+pub fn project_all_boxes(dataset: &Dataset, options: &ProjectionOptions) -> Vec<ProjectedBox> {
+    let mut boxes: Vec<_> = dataset
+        .keyframe_sample_data_iter()
+        .filter_map(|data| data.project_boxes(options))
+        .flatten()
+        .collect();
+    boxes.sort_by_key(|projected| {
+        (
+            projected.sample_data_token,
+            projected.sample_annotation_token,
+        )
+    });
+    boxes
+}
+
+/// Load the boxes of [project_all_boxes] from the cache, or project
+/// and store them if they are not cached for the options.
+pub fn project_all_boxes_cached(
+    dataset: &Dataset,
+    cache: &DerivedCache,
+    options: &ProjectionOptions,
+) -> Result<Vec<ProjectedBox>> {
+    let key = PROJECTED_BOXES_ARTIFACT.with_params(options);
+    cache.load_or_compute_json(key, || Ok(project_all_boxes(dataset, options)))
+}
+
+/// Get the projected points of the box part in front of the camera.
+///
+/// Box edges crossing the near plane are cut at the plane, so that
+/// boxes partially behind the camera project to finite outlines.
+fn visible_outline(box_camera: &Box3D, intrinsic: &CameraIntrinsic) -> Vec<[f64; 2]> {
+    const EDGES: [(usize, usize); 12] = [
+        (0, 1),
+        (1, 2),
+        (2, 3),
+        (3, 0),
+        (4, 5),
+        (5, 6),
+        (6, 7),
+        (7, 4),
+        (0, 4),
+        (1, 5),
+        (2, 6),
+        (3, 7),
+    ];
+
+    let corners = box_camera.corners();
+    let mut points: Vec<[f64; 3]> = corners
+        .iter()
+        .filter(|corner| corner[2] > MIN_DEPTH)
+        .copied()
+        .collect();
+    points.extend(EDGES.iter().filter_map(|&(i, j)| {
+        let (a, b) = (corners[i], corners[j]);
+        if (a[2] > MIN_DEPTH) == (b[2] > MIN_DEPTH) {
+            return None;
+        }
+        let ratio = (MIN_DEPTH - a[2]) / (b[2] - a[2]);
+        Some([0, 1, 2].map(|k| a[k] + (b[k] - a[k]) * ratio))
+    }));
+
+    points
+        .into_iter()
+        .filter_map(|point| intrinsic.project(point))
+        .collect()
+}
+
+/// Compute the convex hull in counter-clockwise order using the
+/// monotone chain algorithm.
+fn convex_hull(points: &[[f64; 2]]) -> Vec<[f64; 2]> {
+    let mut points = points.to_vec();
+    points.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    points.dedup();
+    if points.len() < 3 {
+        return points;
+    }
+
+    let mut lower = half_hull(points.iter());
+    let mut upper = half_hull(points.iter().rev());
+    lower.pop();
+    upper.pop();
+    lower.extend(upper);
+    lower
+}
+
+fn half_hull<'a>(points: impl Iterator<Item = &'a [f64; 2]>) -> Vec<[f64; 2]> {
+    let cross = |o: [f64; 2], a: [f64; 2], b: [f64; 2]| {
+        (a[0] - o[0]) * (b[1] - o[1]) - (a[1] - o[1]) * (b[0] - o[0])
+    };
+
+    let mut hull: Vec<[f64; 2]> = vec![];
+    for &point in points {
+        while hull.len() >= 2 && cross(hull[hull.len() - 2], hull[hull.len() - 1], point) <= 0.0 {
+            hull.pop();
+        }
+        hull.push(point);
+    }
+    hull
+}
+
+/// Clip a convex polygon to the image rectangle using the
+/// Sutherland-Hodgman algorithm.
+fn clip_polygon(polygon: &[[f64; 2]], [width, height]: [f64; 2]) -> Vec<[f64; 2]> {
+    // Each boundary is (axis, bound, keep points below the bound)
+    let boundaries = [
+        (0, 0.0, false),
+        (0, width, true),
+        (1, 0.0, false),
+        (1, height, true),
+    ];
+
+    boundaries
+        .iter()
+        .fold(polygon.to_vec(), |input, &(axis, bound, below)| {
+            let inside = |point: &[f64; 2]| {
+                if below {
+                    point[axis] <= bound
+                } else {
+                    point[axis] >= bound
+                }
+            };
+            let intersect = |a: [f64; 2], b: [f64; 2]| {
+                let ratio = (bound - a[axis]) / (b[axis] - a[axis]);
+                [a[0] + (b[0] - a[0]) * ratio, a[1] + (b[1] - a[1]) * ratio]
+            };
+
+            let mut output = vec![];
+            for (index, &curr) in input.iter().enumerate() {
+                let prev = input[(index + input.len() - 1) % input.len()];
+                match (inside(&prev), inside(&curr)) {
+                    (true, true) => output.push(curr),
+                    (true, false) => output.push(intersect(prev, curr)),
+                    (false, true) => {
+                        output.push(intersect(prev, curr));
+                        output.push(curr);
+                    }
+                    (false, false) => {}
+                }
+            }
+            output
+        })
+}
+
+fn bounding_box(points: &[[f64; 2]]) -> Option<[f64; 4]> {
+    if points.is_empty() {
+        return None;
+    }
+    let min_x = points.iter().map(|p| p[0]).fold(f64::INFINITY, f64::min);
+    let min_y = points.iter().map(|p| p[1]).fold(f64::INFINITY, f64::min);
+    let max_x = points
+        .iter()
+        .map(|p| p[0])
+        .fold(f64::NEG_INFINITY, f64::max);
+    let max_y = points
+        .iter()
+        .map(|p| p[1])
+        .fold(f64::NEG_INFINITY, f64::max);
+    Some([min_x, min_y, max_x, max_y])
+}
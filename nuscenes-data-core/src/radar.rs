@@ -0,0 +1,241 @@
+//! Clustering of radar points into object-level detections.
+//!
+//! Radar returns of a scan are grouped by DBSCAN on the xy plane.
+//! Points are neighbors only if their velocities are also close, so
+//! that objects passing each other are not merged. The clusters can be
+//! used as weak detections or as priors of detectors.
+
+use crate::{
+    error::{Error, Result},
+    pointcloud::{PointAttribute, PointCloud},
+    spatial::SpatialIndex,
+};
+
+/// Attribute name of radar velocities along x in m/s.
+pub const VX: &str = "vx";
+/// Attribute name of radar velocities along y in m/s.
+pub const VY: &str = "vy";
+/// Attribute name of radar velocities along x in m/s compensated by
+/// the ego motion.
+pub const VX_COMP: &str = "vx_comp";
+/// Attribute name of radar velocities along y in m/s compensated by
+/// the ego motion.
+pub const VY_COMP: &str = "vy_comp";
+/// Attribute name of radar cross sections in dBsm.
+pub const RCS: &str = "rcs";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RadarClusterConfig {
+    /// The maximum xy distance in meters between neighboring points.
+    pub eps: f64,
+    /// The minimum number of neighbors, including the point itself,
+    /// of core points.
+    pub min_points: usize,
+    /// The maximum difference in m/s between velocities of
+    /// neighboring points. Velocities are ignored if it is `None`.
+    pub max_velocity_diff: Option<f64>,
+    /// Use the velocities compensated by the ego motion.
+    pub compensated: bool,
+}
+
+impl Default for RadarClusterConfig {
+    fn default() -> Self {
+        Self {
+            eps: 2.0,
+            min_points: 2,
+            max_velocity_diff: Some(1.0),
+            compensated: true,
+        }
+    }
+}
+
+/// A cluster of radar points in the frame of the point cloud.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RadarCluster {
+    /// The indices of the points in the point cloud.
+    pub point_indices: Vec<usize>,
+    pub centroid: [f64; 3],
+    /// The minimum corner of the axis-aligned extent.
+    pub min: [f64; 3],
+    /// The maximum corner of the axis-aligned extent.
+    pub max: [f64; 3],
+    /// The mean velocity in m/s if the point cloud has velocities.
+    pub velocity: Option<[f64; 2]>,
+    /// The mean radar cross section if the point cloud has it.
+    pub rcs: Option<f64>,
+}
+
+impl RadarCluster {
+    pub fn len(&self) -> usize {
+        self.point_indices.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.point_indices.is_empty()
+    }
+
+    /// Get the size `[x, y, z]` of the extent.
+    pub fn size(&self) -> [f64; 3] {
+        [
+            self.max[0] - self.min[0],
+            self.max[1] - self.min[1],
+            self.max[2] - self.min[2],
+        ]
+    }
+}
+
+/// Cluster the points of a radar scan, such as a point cloud loaded
+/// from a radar `.pcd` file. Noise points are not assigned to any
+/// cluster.
+///
+/// It fails if velocity gating is enabled and the point cloud has no
+/// velocity attributes.
+pub fn cluster_radar_points(
+    cloud: &PointCloud,
+    config: &RadarClusterConfig,
+) -> Result<Vec<RadarCluster>> {
+    let RadarClusterConfig {
+        eps,
+        min_points,
+        max_velocity_diff,
+        compensated,
+    } = *config;
+    assert!(eps > 0.0, "eps must be positive");
+
+    let (vx_name, vy_name) = if compensated {
+        (VX_COMP, VY_COMP)
+    } else {
+        (VX, VY)
+    };
+    let velocities: Option<Vec<[f64; 2]>> =
+        match (cloud.attribute(vx_name), cloud.attribute(vy_name)) {
+            (Some(vx), Some(vy)) => Some(
+                (0..cloud.len())
+                    .map(|index| [attribute_value(vx, index), attribute_value(vy, index)])
+                    .collect(),
+            ),
+            _ if max_velocity_diff.is_some() => {
+                return Err(Error::ParseError(format!(
+                    "the point cloud has no attributes {vx_name} and {vy_name} for velocity gating"
+                )));
+            }
+            _ => None,
+        };
+
+    let mut index = SpatialIndex::new(eps);
+    for (point_index, &[x, y, _]) in cloud.positions.iter().enumerate() {
+        index.insert([x as f64, y as f64], point_index);
+    }
+
+    // Find the neighbors within the distance and velocity gates
+    let neighbors = |point_index: usize| -> Vec<usize> {
+        let [x, y, _] = cloud.positions[point_index];
+        index
+            .within([x as f64, y as f64], eps)
+            .into_iter()
+            .map(|(_, &other)| other)
+            .filter(|&other| match (&velocities, max_velocity_diff) {
+                (Some(velocities), Some(max_diff)) => {
+                    let [vx, vy] = velocities[point_index];
+                    let [other_vx, other_vy] = velocities[other];
+                    (vx - other_vx).hypot(vy - other_vy) <= max_diff
+                }
+                _ => true,
+            })
+            .collect()
+    };
+
+    // DBSCAN
+    let mut labels: Vec<Option<usize>> = vec![None; cloud.len()];
+    let mut visited = vec![false; cloud.len()];
+    let mut num_clusters = 0;
+
+    for start in 0..cloud.len() {
+        if visited[start] {
+            continue;
+        }
+        visited[start] = true;
+
+        let mut queue = neighbors(start);
+        if queue.len() < min_points {
+            continue;
+        }
+
+        let label = num_clusters;
+        num_clusters += 1;
+        labels[start] = Some(label);
+
+        while let Some(point_index) = queue.pop() {
+            if labels[point_index].is_none() {
+                labels[point_index] = Some(label);
+            }
+            if visited[point_index] {
+                continue;
+            }
+            visited[point_index] = true;
+
+            let found = neighbors(point_index);
+            if found.len() >= min_points {
+                queue.extend(found);
+            }
+        }
+    }
+
+    let mut point_indices: Vec<Vec<usize>> = vec![vec![]; num_clusters];
+    for (point_index, label) in labels.into_iter().enumerate() {
+        if let Some(label) = label {
+            point_indices[label].push(point_index);
+        }
+    }
+
+    let rcs = cloud.attribute(RCS);
+    let clusters = point_indices
+        .into_iter()
+        .map(|point_indices| {
+            let count = point_indices.len() as f64;
+            let mut sum = [0.0; 3];
+            let mut min = [f64::INFINITY; 3];
+            let mut max = [f64::NEG_INFINITY; 3];
+            for &point_index in &point_indices {
+                let position = cloud.positions[point_index];
+                for axis in 0..3 {
+                    let value = position[axis] as f64;
+                    sum[axis] += value;
+                    min[axis] = min[axis].min(value);
+                    max[axis] = max[axis].max(value);
+                }
+            }
+
+            let velocity = velocities.as_ref().map(|velocities| {
+                let [vx, vy] = point_indices.iter().fold([0.0; 2], |[vx, vy], &index| {
+                    [vx + velocities[index][0], vy + velocities[index][1]]
+                });
+                [vx / count, vy / count]
+            });
+            let rcs = rcs.map(|rcs| {
+                point_indices
+                    .iter()
+                    .map(|&index| attribute_value(rcs, index))
+                    .sum::<f64>()
+                    / count
+            });
+
+            RadarCluster {
+                centroid: sum.map(|sum| sum / count),
+                min,
+                max,
+                velocity,
+                rcs,
+                point_indices,
+            }
+        })
+        .collect();
+
+    Ok(clusters)
+}
+
+fn attribute_value(attribute: &PointAttribute, index: usize) -> f64 {
+    attribute
+        .get_f64(index)
+        .expect("attributes have one value per point")
+}
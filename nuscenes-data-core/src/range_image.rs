@@ -0,0 +1,432 @@
+//! Beam models of the top lidar and range image projections.
+//!
+//! The top lidar of nuScenes is a Velodyne HDL-32E, whose 32 beams are
+//! spread from -30.67 to +10.67 degrees of elevation. A [BeamModel]
+//! assigns points to beams by their elevation angles, and projects
+//! sweeps onto cylindrical range images with one row per beam.
+//!
+//! [BeamModel::hdl32e] indexes the beams by elevation from the bottom,
+//! which is not necessarily the order of the ring indices stored in
+//! lidar `.bin` files. Use [BeamModel::fit] to obtain a model indexed
+//! by the ring indices of actual sweeps.
+//!
+//! [to_range_image](crate::pointcloud::to_range_image) bins the
+//! elevation uniformly instead, as most range-view pipelines do.
+//! [generate_range_images] stores its images of all key frame sweeps
+//! in a [DerivedCache].
+
+use crate::{
+    cache::{self, ArtifactKey, DerivedCache},
+    dataset::{Dataset, SampleDataRef},
+    error::{Error, Result},
+    pointcloud::{self, PointCloud, INTENSITY, RING_INDEX},
+    serializable::Modality,
+};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// The number of beams of the HDL-32E.
+pub const HDL32E_NUM_BEAMS: usize = 32;
+
+/// The cache entry of range images projected by
+/// [generate_range_images].
+pub const RANGE_IMAGE_ARTIFACT: ArtifactKey = ArtifactKey::new("range_images", 1);
+
+/// The nominal elevation angles in degrees of the HDL-32E beams from
+/// the bottom to the top.
+pub const HDL32E_ELEVATIONS_DEG: [f64; HDL32E_NUM_BEAMS] = [
+    -30.67, -29.33, -28.00, -26.67, -25.33, -24.00, -22.67, -21.33, -20.00, -18.67, -17.33, -16.00,
+    -14.67, -13.33, -12.00, -10.67, -9.33, -8.00, -6.67, -5.33, -4.00, -2.67, -1.33, 0.00, 1.33,
+    2.67, 4.00, 5.33, 6.67, 8.00, 9.33, 10.67,
+];
+
+/// The elevation angles of the beams of a spinning lidar.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BeamModel {
+    /// The elevation angle in radians of each beam.
+    elevations: Vec<f64>,
+    /// The range image row of each beam. Row 0 is the top beam.
+    rows: Vec<usize>,
+}
+
+impl BeamModel {
+    /// Create a model from the elevation angles in radians indexed by
+    /// beam.
+    pub fn new(elevations: Vec<f64>) -> Self {
+        assert!(!elevations.is_empty(), "the model must have beams");
+
+        let mut order: Vec<usize> = (0..elevations.len()).collect();
+        order.sort_by(|&lhs, &rhs| elevations[rhs].total_cmp(&elevations[lhs]));
+        let mut rows = vec![0; elevations.len()];
+        for (row, beam) in order.into_iter().enumerate() {
+            rows[beam] = row;
+        }
+
+        Self { elevations, rows }
+    }
+
+    /// The nominal model of the HDL-32E, whose beams are indexed from
+    /// the bottom.
+    pub fn hdl32e() -> Self {
+        Self::new(
+            HDL32E_ELEVATIONS_DEG
+                .iter()
+                .map(|deg| deg.to_radians())
+                .collect(),
+        )
+    }
+
+    /// Estimate the elevation angle of each ring index from a lidar
+    /// sweep with the `ring_index` attribute, such as one loaded from a
+    /// `.bin` file. The elevation of each ring is the median over its
+    /// points.
+    ///
+    /// It fails if the sweep has no ring indices or some ring below the
+    /// largest index has no points.
+    pub fn fit(cloud: &PointCloud) -> Result<Self> {
+        let rings = cloud.attribute(RING_INDEX).ok_or_else(|| {
+            Error::ParseError(format!("the point cloud has no attribute {RING_INDEX}"))
+        })?;
+
+        let mut per_ring: Vec<Vec<f64>> = vec![];
+        for (index, &position) in cloud.positions.iter().enumerate() {
+            let ring = rings
+                .get_f64(index)
+                .expect("attributes have one value per point");
+            if ring < 0.0 {
+                return Err(Error::ParseError(format!("negative ring index {ring}")));
+            }
+            let ring = ring as usize;
+            if ring >= per_ring.len() {
+                per_ring.resize_with(ring + 1, Vec::new);
+            }
+            let [x, y, z] = position;
+            let elevation = elevation_of([x as f64, y as f64, z as f64]);
+            if elevation.is_finite() {
+                per_ring[ring].push(elevation);
+            }
+        }
+
+        let elevations = per_ring
+            .into_iter()
+            .enumerate()
+            .map(|(ring, mut elevations)| {
+                if elevations.is_empty() {
+                    return Err(Error::ParseError(format!("ring {ring} has no points")));
+                }
+                elevations.sort_by(f64::total_cmp);
+                Ok(elevations[elevations.len() / 2])
+            })
+            .collect::<Result<Vec<_>>>()?;
+        if elevations.is_empty() {
+            return Err(Error::ParseError("the point cloud is empty".to_string()));
+        }
+
+        Ok(Self::new(elevations))
+    }
+
+    pub fn num_beams(&self) -> usize {
+        self.elevations.len()
+    }
+
+    /// The elevation angles in radians indexed by beam.
+    pub fn elevations(&self) -> &[f64] {
+        &self.elevations
+    }
+
+    /// Get the elevation angle in radians of the beam.
+    pub fn elevation(&self, beam: usize) -> Option<f64> {
+        self.elevations.get(beam).copied()
+    }
+
+    /// Get the range image row of the beam, where row 0 is the top
+    /// beam.
+    pub fn row(&self, beam: usize) -> Option<usize> {
+        self.rows.get(beam).copied()
+    }
+
+    /// Find the beam whose elevation is the closest to that of the
+    /// point in the lidar frame.
+    pub fn beam_of(&self, point: [f64; 3]) -> usize {
+        let elevation = elevation_of(point);
+        self.elevations
+            .iter()
+            .enumerate()
+            .min_by(|(_, lhs), (_, rhs)| {
+                (*lhs - elevation)
+                    .abs()
+                    .total_cmp(&(*rhs - elevation).abs())
+            })
+            .map(|(beam, _)| beam)
+            .unwrap()
+    }
+
+    /// Find the beam of each point of the point cloud.
+    pub fn beam_indices(&self, cloud: &PointCloud) -> Vec<usize> {
+        cloud
+            .positions
+            .iter()
+            .map(|&[x, y, z]| self.beam_of([x as f64, y as f64, z as f64]))
+            .collect()
+    }
+
+    /// Project the points in the lidar frame onto a cylindrical range
+    /// image with one row per beam.
+    ///
+    /// The azimuth is measured counterclockwise from the x axis.
+    /// Column 0 starts at azimuth π and the azimuth decreases along the
+    /// columns. If several points fall into a pixel, the nearest one is
+    /// kept.
+    ///
+    /// It fails if [RangeImageConfig::use_ring_index] is set and the
+    /// ring indices of the point cloud are missing or exceed the beams
+    /// of the model.
+    pub fn range_image(&self, cloud: &PointCloud, config: &RangeImageConfig) -> Result<RangeImage> {
+        let RangeImageConfig {
+            width,
+            use_ring_index,
+        } = *config;
+        let beams: Vec<usize> = if use_ring_index {
+            let rings = cloud.attribute(RING_INDEX).ok_or_else(|| {
+                Error::ParseError(format!("the point cloud has no attribute {RING_INDEX}"))
+            })?;
+            (0..cloud.len())
+                .map(|index| {
+                    let ring = rings
+                        .get_f64(index)
+                        .expect("attributes have one value per point");
+                    if ring < 0.0 || ring as usize >= self.num_beams() {
+                        return Err(Error::ParseError(format!(
+                            "ring index {ring} is out of the {} beams of the model",
+                            self.num_beams()
+                        )));
+                    }
+                    Ok(ring as usize)
+                })
+                .collect::<Result<_>>()?
+        } else {
+            self.beam_indices(cloud)
+        };
+
+        let rows: Vec<Option<usize>> = beams
+            .into_iter()
+            .map(|beam| Some(self.rows[beam]))
+            .collect();
+        Ok(project(
+            cloud,
+            self.num_beams(),
+            width,
+            [-180.0, 180.0],
+            |index| rows[index],
+        ))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RangeImageConfig {
+    /// The number of columns over a full revolution. The HDL-32E at
+    /// 20 Hz fires about 1084 times per revolution.
+    pub width: usize,
+    /// Place points on the rows of their `ring_index` attribute instead
+    /// of the closest beams by elevation. The model must be indexed by
+    /// ring, such as one from [BeamModel::fit].
+    pub use_ring_index: bool,
+}
+
+impl Default for RangeImageConfig {
+    fn default() -> Self {
+        Self {
+            width: 1084,
+            use_ring_index: false,
+        }
+    }
+}
+
+/// The uniform binning of [to_range_image](crate::pointcloud::to_range_image).
+/// Angles are in degrees.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RangeViewConfig {
+    /// The number of elevation bins.
+    pub height: usize,
+    /// The number of azimuth bins.
+    pub width: usize,
+    /// The upper elevation bound of the top row.
+    pub fov_up: f64,
+    /// The lower elevation bound of the bottom row.
+    pub fov_down: f64,
+    /// The lower azimuth bound of the last column.
+    pub azimuth_min: f64,
+    /// The upper azimuth bound of the first column.
+    pub azimuth_max: f64,
+}
+
+impl Default for RangeViewConfig {
+    /// One row per HDL-32E beam, with the rows centered on the nominal
+    /// beam elevations, over a full revolution.
+    fn default() -> Self {
+        Self {
+            height: HDL32E_NUM_BEAMS,
+            width: 1084,
+            fov_up: 11.33,
+            fov_down: -31.33,
+            azimuth_min: -180.0,
+            azimuth_max: 180.0,
+        }
+    }
+}
+
+/// A cylindrical projection of a lidar sweep in row-major order.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RangeImage {
+    pub width: usize,
+    pub height: usize,
+    /// The range in meters of each pixel, or zero if no point falls
+    /// into it.
+    pub ranges: Vec<f32>,
+    /// The index of the point in the point cloud of each pixel.
+    /// Other attributes can be gathered by it.
+    pub point_indices: Vec<Option<usize>>,
+    /// The intensity of each pixel, or zero if no point falls into it.
+    /// It is present if the point cloud has the `intensity` attribute.
+    pub intensities: Option<Vec<f32>>,
+}
+
+impl RangeImage {
+    /// Get the range at the pixel if a point falls into it.
+    pub fn range(&self, row: usize, col: usize) -> Option<f32> {
+        self.point_index(row, col)
+            .map(|_| self.ranges[row * self.width + col])
+    }
+
+    /// Get the intensity at the pixel if a point falls into it.
+    pub fn intensity(&self, row: usize, col: usize) -> Option<f32> {
+        let intensities = self.intensities.as_ref()?;
+        self.point_index(row, col)
+            .map(|_| intensities[row * self.width + col])
+    }
+
+    /// Get the index of the point at the pixel.
+    pub fn point_index(&self, row: usize, col: usize) -> Option<usize> {
+        if row >= self.height || col >= self.width {
+            return None;
+        }
+        self.point_indices[row * self.width + col]
+    }
+}
+
+impl SampleDataRef {
+    /// Get the range image of this lidar sweep by
+    /// [to_range_image](crate::pointcloud::to_range_image). It is read
+    /// from the cache if [generate_range_images] ran with the config,
+    /// or projected otherwise. It fails if the sample data is not a
+    /// lidar sweep.
+    pub fn range_image(
+        &self,
+        cache: &DerivedCache,
+        config: &RangeViewConfig,
+    ) -> Result<RangeImage> {
+        let key = RANGE_IMAGE_ARTIFACT.with_params(config);
+        match cache.get(key) {
+            Some(dir) if dir.join(range_image_file_name(self)).is_file() => {
+                cache::read_json(&dir.join(range_image_file_name(self)))
+            }
+            _ => project_sweep(self, config),
+        }
+    }
+}
+
+/// Project the key frame lidar sweeps of the dataset in parallel into
+/// the cache, unless they are cached for the config already. Each
+/// image is stored as `<sample_data_token>.json`. It returns the
+/// directory of the images.
+pub fn generate_range_images(
+    dataset: &Dataset,
+    cache: &DerivedCache,
+    config: &RangeViewConfig,
+) -> Result<PathBuf> {
+    let key = RANGE_IMAGE_ARTIFACT.with_params(config);
+    cache.get_or_build(key, |dir| {
+        let sweeps: Vec<_> = dataset
+            .keyframe_sample_data_iter()
+            .filter(|data| data.modality() == Modality::Lidar)
+            .collect();
+        sweeps.par_iter().try_for_each(|data| {
+            let image = project_sweep(data, config)?;
+            cache::write_json(&dir.join(range_image_file_name(data)), &image)
+        })
+    })
+}
+
+fn project_sweep(data: &SampleDataRef, config: &RangeViewConfig) -> Result<RangeImage> {
+    let cloud = data.load_lidar_bin_points()?.to_point_cloud();
+    Ok(pointcloud::to_range_image(&cloud, config))
+}
+
+fn range_image_file_name(data: &SampleDataRef) -> String {
+    format!("{}.json", data.token)
+}
+
+/// Project the points onto the pixels of a range image. `row_of`
+/// gives the row of each point, or `None` to drop it. Azimuth bounds
+/// are in degrees.
+pub(crate) fn project<F>(
+    cloud: &PointCloud,
+    height: usize,
+    width: usize,
+    [azimuth_min, azimuth_max]: [f64; 2],
+    mut row_of: F,
+) -> RangeImage
+where
+    F: FnMut(usize) -> Option<usize>,
+{
+    assert!(width > 0, "width must be positive");
+    assert!(
+        azimuth_min < azimuth_max,
+        "the azimuth bounds must be increasing"
+    );
+
+    let source_intensities = cloud.attribute(INTENSITY);
+    let mut image = RangeImage {
+        width,
+        height,
+        ranges: vec![0.0; width * height],
+        point_indices: vec![None; width * height],
+        intensities: source_intensities.map(|_| vec![0.0; width * height]),
+    };
+    let span = azimuth_max - azimuth_min;
+
+    for (index, &[x, y, z]) in cloud.positions.iter().enumerate() {
+        let range = (x * x + y * y + z * z).sqrt();
+        if range.is_nan() || range <= 0.0 {
+            continue;
+        }
+        let Some(row) = row_of(index) else {
+            continue;
+        };
+        let azimuth = (y as f64).atan2(x as f64).to_degrees();
+        if !(azimuth_min..=azimuth_max).contains(&azimuth) {
+            continue;
+        }
+        let col = (((azimuth_max - azimuth) / span * width as f64) as usize).min(width - 1);
+        let pixel = row * width + col;
+
+        if image.point_indices[pixel].is_none() || range < image.ranges[pixel] {
+            image.ranges[pixel] = range;
+            image.point_indices[pixel] = Some(index);
+            if let (Some(intensities), Some(source)) = (&mut image.intensities, source_intensities)
+            {
+                intensities[pixel] = source
+                    .get_f64(index)
+                    .expect("attributes have one value per point")
+                    as f32;
+            }
+        }
+    }
+
+    image
+}
+
+fn elevation_of([x, y, z]: [f64; 3]) -> f64 {
+    z.atan2(x.hypot(y))
+}
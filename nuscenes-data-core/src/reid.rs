@@ -0,0 +1,241 @@
+//! Links between instances representing the same physical object
+//! across scenes.
+//!
+//! Instances are scoped to scenes, so an object seen in several
+//! drives, such as a parked car, has one instance per scene. An
+//! [InstanceMatcher] decides whether two instances of different scenes
+//! are the same object, and [Dataset::link_instances] groups matched
+//! instances into identities. The identities can be saved as an
+//! auxiliary table next to the metadata tables.
+
+use crate::{
+    dataset::{Dataset, InstanceRef},
+    error::{Error, Result},
+    serializable::Token,
+};
+use itertools::Itertools;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, BufReader, BufWriter},
+    path::Path,
+};
+
+/// The file name of the auxiliary table.
+pub const INSTANCE_IDENTITY_FILE_NAME: &str = "instance_identity.json";
+
+/// Decide whether two instances of different scenes represent the
+/// same physical object. Features are extracted once per instance and
+/// compared pairwise.
+pub trait InstanceMatcher: Sync {
+    type Feature: Send + Sync;
+
+    /// Extract the feature of the instance. Instances without
+    /// features are never linked.
+    fn feature(&self, instance: &InstanceRef) -> Option<Self::Feature>;
+
+    fn matches(&self, lhs: &Self::Feature, rhs: &Self::Feature) -> bool;
+}
+
+/// Match static objects of the same category by their mean positions
+/// in the global frame.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StaticObjectMatcher {
+    /// The maximum distance in meters between the mean positions.
+    pub max_distance: f64,
+    /// Instances moving farther than this distance in meters from
+    /// their mean positions are not static.
+    pub max_motion: f64,
+}
+
+impl Default for StaticObjectMatcher {
+    fn default() -> Self {
+        Self {
+            max_distance: 1.0,
+            max_motion: 0.5,
+        }
+    }
+}
+
+impl InstanceMatcher for StaticObjectMatcher {
+    /// The category token and the mean position.
+    type Feature = (Token, [f64; 3]);
+
+    fn feature(&self, instance: &InstanceRef) -> Option<Self::Feature> {
+        let positions: Vec<[f64; 3]> = instance
+            .annotation_iter()
+            .map(|annotation| annotation.translation)
+            .collect();
+        if positions.is_empty() {
+            return None;
+        }
+
+        let num = positions.len() as f64;
+        let mean = [0, 1, 2].map(|axis| positions.iter().map(|p| p[axis]).sum::<f64>() / num);
+        let is_static = positions
+            .iter()
+            .all(|position| distance(position, &mean) <= self.max_motion);
+        is_static.then_some((instance.category_token, mean))
+    }
+
+    fn matches(&self, lhs: &Self::Feature, rhs: &Self::Feature) -> bool {
+        lhs.0 == rhs.0 && distance(&lhs.1, &rhs.1) <= self.max_distance
+    }
+}
+
+/// A group of instances representing the same physical object.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InstanceIdentity {
+    pub instance_tokens: Vec<Token>,
+}
+
+/// The identities of linked instances.
+#[derive(Debug, Clone, Default)]
+pub struct InstanceIdentities {
+    identities: Vec<InstanceIdentity>,
+    index: HashMap<Token, usize>,
+}
+
+impl InstanceIdentities {
+    pub fn new(identities: Vec<InstanceIdentity>) -> Self {
+        let index = identities
+            .iter()
+            .enumerate()
+            .flat_map(|(index, identity)| {
+                identity
+                    .instance_tokens
+                    .iter()
+                    .map(move |&token| (token, index))
+            })
+            .collect();
+        Self { identities, index }
+    }
+
+    pub fn identities(&self) -> &[InstanceIdentity] {
+        &self.identities
+    }
+
+    /// Get the identity the instance belongs to.
+    pub fn identity_of(&self, instance_token: Token) -> Option<&InstanceIdentity> {
+        Some(&self.identities[*self.index.get(&instance_token)?])
+    }
+
+    /// Iterate over the other instances of the same identity.
+    pub fn linked_instances(&self, instance_token: Token) -> impl Iterator<Item = Token> + '_ {
+        self.identity_of(instance_token)
+            .into_iter()
+            .flat_map(|identity| identity.instance_tokens.iter().copied())
+            .filter(move |&token| token != instance_token)
+    }
+
+    /// Save the identities as a JSON table.
+    pub fn save<P>(&self, path: P) -> Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        let writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(writer, &self.identities).map_err(io::Error::from)?;
+        Ok(())
+    }
+
+    /// Load a table saved by [InstanceIdentities::save].
+    pub fn load<P>(path: P) -> Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let reader = BufReader::new(File::open(path)?);
+        let identities = serde_json::from_reader(reader).map_err(|err| {
+            Error::ParseError(format!("failed to load {}: {err}", path.display()))
+        })?;
+        Ok(Self::new(identities))
+    }
+}
+
+impl Dataset {
+    /// Link the instances of different scenes matched by the matcher.
+    /// Only instances at the same location are compared. Instances
+    /// without any match are not included in the identities.
+    pub fn link_instances<M>(&self, matcher: &M) -> InstanceIdentities
+    where
+        M: InstanceMatcher,
+    {
+        // Extract features and group instances by locations
+        let instances: Vec<_> = self.instance_iter().collect();
+        let instances: Vec<(Token, Token, String, M::Feature)> = instances
+            .par_iter()
+            .filter_map(|instance| {
+                let scene = instance.annotation_iter().next()?.sample().scene();
+                let location = scene.log().location.clone();
+                let feature = matcher.feature(instance)?;
+                Some((instance.token, scene.token, location, feature))
+            })
+            .collect();
+        let instances = &instances;
+        let groups = (0..instances.len()).into_group_map_by(|&index| &instances[index].2);
+
+        // Find matched pairs of different scenes
+        let matched: Vec<(usize, usize)> = groups
+            .values()
+            .flat_map(|indices| {
+                indices
+                    .par_iter()
+                    .enumerate()
+                    .flat_map_iter(|(nth, &lhs)| {
+                        indices[nth + 1..]
+                            .iter()
+                            .filter(move |&&rhs| {
+                                let (_, lhs_scene, _, lhs_feature) = &instances[lhs];
+                                let (_, rhs_scene, _, rhs_feature) = &instances[rhs];
+                                lhs_scene != rhs_scene && matcher.matches(lhs_feature, rhs_feature)
+                            })
+                            .map(move |&rhs| (lhs, rhs))
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+
+        // Merge matched pairs with union-find
+        let mut parents: Vec<usize> = (0..instances.len()).collect();
+        for (lhs, rhs) in matched {
+            let lhs = find_root(&mut parents, lhs);
+            let rhs = find_root(&mut parents, rhs);
+            parents[lhs.max(rhs)] = lhs.min(rhs);
+        }
+        let roots: Vec<usize> = (0..instances.len())
+            .map(|index| find_root(&mut parents, index))
+            .collect();
+
+        let mut identities: Vec<InstanceIdentity> = (0..instances.len())
+            .into_group_map_by(|&index| roots[index])
+            .into_values()
+            .filter(|members| members.len() > 1)
+            .map(|members| {
+                let mut instance_tokens: Vec<Token> = members
+                    .into_iter()
+                    .map(|index| instances[index].0)
+                    .collect();
+                instance_tokens.sort();
+                InstanceIdentity { instance_tokens }
+            })
+            .collect();
+        identities.sort_by_key(|identity| identity.instance_tokens[0]);
+        InstanceIdentities::new(identities)
+    }
+}
+
+fn find_root(parents: &mut [usize], index: usize) -> usize {
+    let mut root = index;
+    while parents[root] != root {
+        root = parents[root];
+    }
+    parents[index] = root;
+    root
+}
+
+fn distance(lhs: &[f64; 3], rhs: &[f64; 3]) -> f64 {
+    let [dx, dy, dz] = [0, 1, 2].map(|axis| lhs[axis] - rhs[axis]);
+    (dx * dx + dy * dy + dz * dz).sqrt()
+}
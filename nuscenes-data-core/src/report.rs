@@ -0,0 +1,347 @@
+//! Human-readable dataset reports.
+
+use crate::{
+    dataset::{Dataset, SampleRef, SceneRef},
+    serializable::{Channel, Token},
+};
+use chrono::{Duration, NaiveDate};
+use itertools::Itertools;
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt::Write,
+};
+
+/// The maximum number of example samples kept per long-tail entry.
+pub const LONG_TAIL_MAX_EXAMPLES: usize = 5;
+
+/// Key statistics of a scene.
+#[derive(Debug, Clone)]
+pub struct SceneSummary {
+    pub scene_token: Token,
+    pub name: String,
+    pub description: String,
+    pub location: String,
+    pub vehicle: String,
+    pub date_captured: NaiveDate,
+    pub num_samples: usize,
+    pub num_annotations: usize,
+    pub num_instances: usize,
+    pub duration: Duration,
+    /// The ego travel distance in meters.
+    pub distance_traveled: f64,
+    /// The number of annotations per category, sorted in descending
+    /// order.
+    pub category_counts: Vec<(String, usize)>,
+    /// The ego positions in global frame at each sample.
+    pub ego_trajectory: Vec<[f64; 3]>,
+}
+
+impl SceneRef {
+    pub fn summary(&self) -> SceneSummary {
+        let log = self.log();
+        let samples: Vec<_> = self.sample_iter().collect();
+
+        let ego_trajectory: Vec<_> = samples.iter().filter_map(sample_ego_position).collect();
+        let distance_traveled = ego_trajectory
+            .iter()
+            .tuple_windows()
+            .map(|(from, to)| {
+                let dx = to[0] - from[0];
+                let dy = to[1] - from[1];
+                dx.hypot(dy)
+            })
+            .sum();
+
+        let duration = match (samples.first(), samples.last()) {
+            (Some(first), Some(last)) => last.timestamp - first.timestamp,
+            _ => Duration::zero(),
+        };
+
+        let annotations: Vec<_> = samples
+            .iter()
+            .flat_map(|sample| sample.annotation_iter().collect::<Vec<_>>())
+            .collect();
+        let num_instances = annotations
+            .iter()
+            .map(|annotation| annotation.instance_token)
+            .collect::<HashSet<_>>()
+            .len();
+        let category_counts: Vec<_> = annotations
+            .iter()
+            .map(|annotation| annotation.instance().category().name.clone())
+            .counts()
+            .into_iter()
+            .sorted_by(|(lname, lcount), (rname, rcount)| {
+                rcount.cmp(lcount).then_with(|| lname.cmp(rname))
+            })
+            .collect();
+
+        SceneSummary {
+            scene_token: self.token,
+            name: self.name.clone(),
+            description: self.description.clone(),
+            location: log.location.clone(),
+            vehicle: log.vehicle.clone(),
+            date_captured: log.date_captured,
+            num_samples: samples.len(),
+            num_annotations: annotations.len(),
+            num_instances,
+            duration,
+            distance_traveled,
+            category_counts,
+            ego_trajectory,
+        }
+    }
+}
+
+impl SceneSummary {
+    /// Render the summary as a standalone HTML page.
+    ///
+    /// The `map_image` and `thumbnails` are image paths relative to
+    /// the location of the HTML page.
+    pub fn to_html(&self, map_image: Option<&str>, thumbnails: &[String]) -> String {
+        let name = html_escape(&self.name);
+        let mut html = String::new();
+
+        writeln!(html, "<!DOCTYPE html>").unwrap();
+        writeln!(html, "<html>").unwrap();
+        writeln!(
+            html,
+            "<head><meta charset=\"utf-8\"><title>{name}</title></head>"
+        )
+        .unwrap();
+        writeln!(html, "<body>").unwrap();
+        writeln!(html, "<h1>{name}</h1>").unwrap();
+        writeln!(html, "<p>{}</p>", html_escape(&self.description)).unwrap();
+
+        writeln!(html, "<table>").unwrap();
+        let rows = [
+            ("Token", self.scene_token.to_string()),
+            ("Location", self.location.clone()),
+            ("Vehicle", self.vehicle.clone()),
+            ("Date captured", self.date_captured.to_string()),
+            ("Samples", self.num_samples.to_string()),
+            ("Annotations", self.num_annotations.to_string()),
+            ("Instances", self.num_instances.to_string()),
+            (
+                "Duration",
+                format!("{:.1} s", self.duration.num_milliseconds() as f64 / 1000.0),
+            ),
+            (
+                "Distance traveled",
+                format!("{:.1} m", self.distance_traveled),
+            ),
+        ];
+        for (key, value) in rows {
+            writeln!(
+                html,
+                "<tr><th>{key}</th><td>{}</td></tr>",
+                html_escape(&value)
+            )
+            .unwrap();
+        }
+        writeln!(html, "</table>").unwrap();
+
+        if let Some(map_image) = map_image {
+            writeln!(html, "<h2>Trajectory</h2>").unwrap();
+            writeln!(html, "<img src=\"{}\">", html_escape(map_image)).unwrap();
+        }
+
+        if !thumbnails.is_empty() {
+            writeln!(html, "<h2>Samples</h2>").unwrap();
+            for thumbnail in thumbnails {
+                writeln!(html, "<img src=\"{}\">", html_escape(thumbnail)).unwrap();
+            }
+        }
+
+        writeln!(html, "<h2>Categories</h2>").unwrap();
+        writeln!(html, "<table>").unwrap();
+        for (category, count) in &self.category_counts {
+            writeln!(
+                html,
+                "<tr><td>{}</td><td>{count}</td></tr>",
+                html_escape(category)
+            )
+            .unwrap();
+        }
+        writeln!(html, "</table>").unwrap();
+
+        writeln!(html, "</body>").unwrap();
+        writeln!(html, "</html>").unwrap();
+        html
+    }
+}
+
+/// The occurrence of a category or a category-attribute combination.
+#[derive(Debug, Clone)]
+pub struct LongTailEntry {
+    pub category: String,
+    /// Sorted attribute names. It is empty for category entries.
+    pub attributes: Vec<String>,
+    pub num_annotations: usize,
+    /// Up to [LONG_TAIL_MAX_EXAMPLES] samples containing the entry.
+    pub example_sample_tokens: Vec<Token>,
+    /// The number of annotations per scene name, sorted in
+    /// descending order.
+    pub scene_counts: Vec<(String, usize)>,
+}
+
+/// Rare categories and attribute combinations of a dataset.
+#[derive(Debug, Clone)]
+pub struct LongTailReport {
+    /// All categories sorted by annotation counts in ascending order,
+    /// including unused categories.
+    pub categories: Vec<LongTailEntry>,
+    /// Observed category-attribute combinations sorted by annotation
+    /// counts in ascending order.
+    pub attribute_combinations: Vec<LongTailEntry>,
+}
+
+impl LongTailReport {
+    pub fn rarest_categories(&self, n: usize) -> &[LongTailEntry] {
+        &self.categories[..n.min(self.categories.len())]
+    }
+
+    pub fn rarest_attribute_combinations(&self, n: usize) -> &[LongTailEntry] {
+        &self.attribute_combinations[..n.min(self.attribute_combinations.len())]
+    }
+
+    /// Render the report as CSV with one row per entry. Attributes
+    /// and example tokens are separated by semicolons.
+    pub fn to_csv(&self) -> String {
+        let mut csv =
+            String::from("kind,category,attributes,num_annotations,num_scenes,examples\n");
+        let entries = self
+            .categories
+            .iter()
+            .map(|entry| ("category", entry))
+            .chain(
+                self.attribute_combinations
+                    .iter()
+                    .map(|entry| ("attributes", entry)),
+            );
+        for (kind, entry) in entries {
+            writeln!(
+                csv,
+                "{kind},{},{},{},{},{}",
+                entry.category,
+                entry.attributes.join(";"),
+                entry.num_annotations,
+                entry.scene_counts.len(),
+                entry.example_sample_tokens.iter().join(";"),
+            )
+            .unwrap();
+        }
+        csv
+    }
+}
+
+#[derive(Default)]
+struct LongTailCounter {
+    num_annotations: usize,
+    example_sample_tokens: Vec<Token>,
+    scene_counts: HashMap<String, usize>,
+}
+
+impl LongTailCounter {
+    fn add(&mut self, sample_token: Token, scene_name: &str) {
+        self.num_annotations += 1;
+        if self.example_sample_tokens.len() < LONG_TAIL_MAX_EXAMPLES
+            && !self.example_sample_tokens.contains(&sample_token)
+        {
+            self.example_sample_tokens.push(sample_token);
+        }
+        *self.scene_counts.entry(scene_name.to_string()).or_default() += 1;
+    }
+
+    fn into_entry(self, category: String, attributes: Vec<String>) -> LongTailEntry {
+        let scene_counts = self
+            .scene_counts
+            .into_iter()
+            .sorted_by(|(lname, lcount), (rname, rcount)| {
+                rcount.cmp(lcount).then_with(|| lname.cmp(rname))
+            })
+            .collect();
+        LongTailEntry {
+            category,
+            attributes,
+            num_annotations: self.num_annotations,
+            example_sample_tokens: self.example_sample_tokens,
+            scene_counts,
+        }
+    }
+}
+
+impl Dataset {
+    /// List categories and category-attribute combinations from the
+    /// rarest to the most common.
+    pub fn long_tail_report(&self) -> LongTailReport {
+        let mut categories: BTreeMap<String, LongTailCounter> = self
+            .category_iter()
+            .map(|category| (category.name.clone(), LongTailCounter::default()))
+            .collect();
+        let mut combinations: BTreeMap<(String, Vec<String>), LongTailCounter> = BTreeMap::new();
+
+        for scene in self.scene_iter() {
+            for sample in scene.sample_iter() {
+                for annotation in sample.annotation_iter() {
+                    let category = annotation.instance().category().name.clone();
+                    let attributes: Vec<String> = annotation
+                        .attribute_iter()
+                        .map(|attribute| attribute.name.clone())
+                        .sorted()
+                        .collect();
+
+                    categories
+                        .entry(category.clone())
+                        .or_default()
+                        .add(sample.token, &scene.name);
+                    combinations
+                        .entry((category, attributes))
+                        .or_default()
+                        .add(sample.token, &scene.name);
+                }
+            }
+        }
+
+        // Stable sorts keep the name order among equal counts
+        let categories = categories
+            .into_iter()
+            .map(|(category, counter)| counter.into_entry(category, vec![]))
+            .sorted_by_key(|entry| entry.num_annotations)
+            .collect();
+        let attribute_combinations = combinations
+            .into_iter()
+            .map(|((category, attributes), counter)| counter.into_entry(category, attributes))
+            .sorted_by_key(|entry| entry.num_annotations)
+            .collect();
+
+        LongTailReport {
+            categories,
+            attribute_combinations,
+        }
+    }
+}
+
+/// Escape special characters in text to be embedded in HTML.
+pub fn html_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            ch => escaped.push(ch),
+        }
+    }
+    escaped
+}
+
+fn sample_ego_position(sample: &SampleRef) -> Option<[f64; 3]> {
+    let data = sample
+        .sample_data_by_channel(Channel::LidarTop)
+        .or_else(|| sample.keyframe_data_iter().next())?;
+    Some(data.ego_pose().translation)
+}
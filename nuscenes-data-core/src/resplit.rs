@@ -0,0 +1,396 @@
+//! Re-splitting, concatenation and thinning of scenes.
+//!
+//! These operations produce new [SceneTables] to be written into a
+//! version directory.
+//!
+//! When re-splitting, the first chunk of a scene keeps the scene token
+//! and name, while later chunks get derived tokens and names suffixed
+//! by the chunk index. Chains of samples, sample data and annotations
+//! are cut at chunk boundaries, and instances are split so that each
+//! instance stays in one scene.
+
+use crate::{
+    dataset::{Dataset, SceneRef},
+    error::Result,
+    serializable::{Instance, Sample, SampleAnnotation, SampleData, Scene, Token, TOKEN_LENGTH},
+    table::Table,
+    utils::{splitmix64, splitmix64_bytes},
+    writer::write_table,
+};
+use chrono::Duration;
+use itertools::Itertools;
+use std::{collections::HashMap, path::Path};
+
+/// The tables changed by scene operations. Other tables are unchanged.
+#[derive(Debug, Clone)]
+pub struct SceneTables {
+    pub scenes: Vec<Scene>,
+    pub samples: Vec<Sample>,
+    pub sample_data: Vec<SampleData>,
+    pub sample_annotations: Vec<SampleAnnotation>,
+    pub instances: Vec<Instance>,
+}
+
+impl SceneTables {
+    /// Write the changed tables into the version directory,
+    /// overwriting existing files.
+    pub fn write_to<P>(&self, meta_dir: P) -> Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        let meta_dir = meta_dir.as_ref();
+        write_table(meta_dir, Table::Scene, &self.scenes)?;
+        write_table(meta_dir, Table::Sample, &self.samples)?;
+        write_table(meta_dir, Table::SampleData, &self.sample_data)?;
+        write_table(meta_dir, Table::SampleAnnotation, &self.sample_annotations)?;
+        write_table(meta_dir, Table::Instance, &self.instances)?;
+        Ok(())
+    }
+}
+
+impl Dataset {
+    /// Split every scene into chunks spanning at most `duration`,
+    /// measured from the first sample of each chunk.
+    pub fn resplit_scenes(&self, duration: Duration) -> SceneTables {
+        assert!(duration > Duration::zero(), "duration must be positive");
+
+        // Assign samples to chunks
+        let mut scenes = vec![];
+        let mut sample_scene: HashMap<Token, Token> = HashMap::new();
+        let mut chunk_samples: Vec<Vec<Sample>> = vec![];
+
+        for scene in self.scene_iter() {
+            let mut chunks: Vec<Vec<Sample>> = vec![];
+            for sample in scene.sample_iter() {
+                let start_new = match chunks.last().and_then(|chunk| chunk.first()) {
+                    Some(first) => sample.timestamp - first.timestamp >= duration,
+                    None => true,
+                };
+                if start_new {
+                    chunks.push(vec![]);
+                }
+                chunks.last_mut().unwrap().push(sample.to_sample());
+            }
+
+            for (index, chunk) in chunks.into_iter().enumerate() {
+                let (token, name) = if index == 0 {
+                    (scene.token, scene.name.clone())
+                } else {
+                    (
+                        derive_token(scene.token, index),
+                        format!("{}-{index}", scene.name),
+                    )
+                };
+
+                scenes.push(Scene {
+                    token,
+                    name,
+                    description: scene.description.clone(),
+                    log_token: scene.log_token,
+                    nbr_samples: chunk.len(),
+                    first_sample_token: chunk[0].token,
+                    last_sample_token: chunk.last().unwrap().token,
+                });
+                for sample in &chunk {
+                    sample_scene.insert(sample.token, token);
+                }
+                chunk_samples.push(chunk);
+            }
+        }
+
+        // Cut sample chains at chunk boundaries
+        let mut samples = vec![];
+        for mut chunk in chunk_samples {
+            let scene_token = sample_scene[&chunk[0].token];
+            chunk.first_mut().unwrap().prev = None;
+            chunk.last_mut().unwrap().next = None;
+            for mut sample in chunk {
+                sample.scene_token = scene_token;
+                samples.push(sample);
+            }
+        }
+
+        // Cut sample data chains where neighbors belong to other
+        // chunks
+        let data_scene = |token: Token| sample_scene[&self.sample_data_map[&token].sample_token];
+        let sample_data = self
+            .sample_data_iter()
+            .map(|data| {
+                let mut record: SampleData = (*data).clone();
+                let scene_token = sample_scene[&record.sample_token];
+                record.prev = record.prev.filter(|&prev| data_scene(prev) == scene_token);
+                record.next = record.next.filter(|&next| data_scene(next) == scene_token);
+                record
+            })
+            .collect();
+
+        // Split instances by chunks
+        let mut instances = vec![];
+        let mut sample_annotations = vec![];
+        for instance in self.instance_iter() {
+            let mut groups: Vec<Vec<SampleAnnotation>> = vec![];
+            let mut prev_scene = None;
+            for annotation in instance.annotation_iter() {
+                let scene_token = sample_scene[&annotation.sample_token];
+                if prev_scene != Some(scene_token) {
+                    groups.push(vec![]);
+                    prev_scene = Some(scene_token);
+                }
+                groups.last_mut().unwrap().push((*annotation).clone());
+            }
+
+            for (index, mut group) in groups.into_iter().enumerate() {
+                let token = if index == 0 {
+                    instance.token
+                } else {
+                    derive_token(instance.token, index)
+                };
+                group.first_mut().unwrap().prev = None;
+                group.last_mut().unwrap().next = None;
+
+                instances.push(Instance {
+                    token,
+                    nbr_annotations: group.len(),
+                    category_token: instance.category_token,
+                    first_annotation_token: group[0].token,
+                    last_annotation_token: group.last().unwrap().token,
+                });
+                for mut annotation in group {
+                    annotation.instance_token = token;
+                    sample_annotations.push(annotation);
+                }
+            }
+        }
+
+        SceneTables {
+            scenes,
+            samples,
+            sample_data,
+            sample_annotations,
+            instances,
+        }
+    }
+
+    /// Concatenate consecutive scenes of the same log into one scene
+    /// if the gap between them is at most `max_gap`. The merged scene
+    /// keeps the token and name of its first scene. Sample and sample
+    /// data chains are joined, while instances are kept separate.
+    pub fn concat_scenes(&self, max_gap: Duration) -> SceneTables {
+        // Group consecutive scenes
+        let mut groups: Vec<Vec<SceneRef>> = vec![];
+        let mut last_by_log: HashMap<Token, usize> = HashMap::new();
+        for scene in self.scene_iter() {
+            let first = scene.sample_iter().next().unwrap();
+            let joinable = last_by_log.get(&scene.log_token).and_then(|&index| {
+                let prev = groups[index].last().unwrap();
+                let last = prev.sample_iter().last().unwrap();
+                (first.timestamp - last.timestamp <= max_gap).then_some(index)
+            });
+
+            match joinable {
+                Some(index) => groups[index].push(scene.clone()),
+                None => {
+                    last_by_log.insert(scene.log_token, groups.len());
+                    groups.push(vec![scene.clone()]);
+                }
+            }
+        }
+
+        // Find the ends of sample data chains in each scene
+        let mut chain_ends: HashMap<(Token, Token), (Option<Token>, Option<Token>)> =
+            HashMap::new();
+        for data in self.sample_data_iter() {
+            let scene_token = self.sample_map[&data.sample_token].scene_token;
+            let ends = chain_ends
+                .entry((scene_token, data.calibrated_sensor_token))
+                .or_default();
+            if data.prev.is_none() {
+                ends.0 = Some(data.token);
+            }
+            if data.next.is_none() {
+                ends.1 = Some(data.token);
+            }
+        }
+
+        let mut scenes = vec![];
+        let mut samples: HashMap<Token, Sample> = self
+            .sample_iter()
+            .map(|sample| (sample.token, sample.to_sample()))
+            .collect();
+        let mut sample_data: HashMap<Token, SampleData> = self
+            .sample_data_iter()
+            .map(|data| (data.token, (*data).clone()))
+            .collect();
+
+        for group in groups {
+            let head = &group[0];
+            let tail = group.last().unwrap();
+
+            // Join sample chains
+            for (prev, next) in group.iter().tuple_windows() {
+                let last = prev.sample_iter().last().unwrap().token;
+                let first = next.sample_iter().next().unwrap().token;
+                samples.get_mut(&last).unwrap().next = Some(first);
+                samples.get_mut(&first).unwrap().prev = Some(last);
+
+                // Join sample data chains of the same sensors
+                for (&(scene_token, sensor_token), &(_, last)) in &chain_ends {
+                    if scene_token != prev.token {
+                        continue;
+                    }
+                    let Some(&(first, _)) = chain_ends.get(&(next.token, sensor_token)) else {
+                        continue;
+                    };
+                    let (Some(last), Some(first)) = (last, first) else {
+                        continue;
+                    };
+                    sample_data.get_mut(&last).unwrap().next = Some(first);
+                    sample_data.get_mut(&first).unwrap().prev = Some(last);
+                }
+            }
+
+            let mut nbr_samples = 0;
+            for scene in &group {
+                for sample in scene.sample_iter() {
+                    samples.get_mut(&sample.token).unwrap().scene_token = head.token;
+                    nbr_samples += 1;
+                }
+            }
+
+            scenes.push(Scene {
+                token: head.token,
+                name: head.name.clone(),
+                description: group
+                    .iter()
+                    .map(|scene| scene.description.as_str())
+                    .join("; "),
+                log_token: head.log_token,
+                nbr_samples,
+                first_sample_token: head.sample_iter().next().unwrap().token,
+                last_sample_token: tail.sample_iter().last().unwrap().token,
+            });
+        }
+
+        SceneTables {
+            scenes,
+            samples: samples.into_values().collect(),
+            sample_data: sample_data.into_values().collect(),
+            sample_annotations: self
+                .sample_annotation_iter()
+                .map(|annotation| (*annotation).clone())
+                .collect(),
+            instances: self
+                .instance_iter()
+                .map(|instance| instance.to_instance())
+                .collect(),
+        }
+    }
+
+    /// Keep every `step`-th sample of each scene, starting from the
+    /// first one. Annotations of dropped samples are removed, and
+    /// their sample data are attached to the nearest kept samples as
+    /// sweeps.
+    pub fn thin_samples(&self, step: usize) -> SceneTables {
+        assert!(step > 0, "step must be positive");
+
+        let mut scenes = vec![];
+        let mut samples = vec![];
+        // Maps each sample to the kept sample taking over its data
+        let mut replacement: HashMap<Token, Token> = HashMap::new();
+
+        for scene in self.scene_iter() {
+            let all: Vec<_> = scene.sample_iter().collect();
+            let mut kept: Vec<Sample> = all
+                .iter()
+                .step_by(step)
+                .map(|sample| sample.to_sample())
+                .collect();
+
+            // Rechain kept samples
+            let tokens: Vec<Token> = kept.iter().map(|sample| sample.token).collect();
+            for (index, sample) in kept.iter_mut().enumerate() {
+                sample.prev = index.checked_sub(1).map(|prev| tokens[prev]);
+                sample.next = tokens.get(index + 1).copied();
+            }
+
+            for sample in &all {
+                let nearest = kept
+                    .iter()
+                    .min_by_key(|kept| (kept.timestamp - sample.timestamp).abs())
+                    .unwrap();
+                replacement.insert(sample.token, nearest.token);
+            }
+
+            scenes.push(Scene {
+                nbr_samples: kept.len(),
+                first_sample_token: tokens[0],
+                last_sample_token: *tokens.last().unwrap(),
+                ..scene.to_scene()
+            });
+            samples.extend(kept);
+        }
+
+        let sample_data = self
+            .sample_data_iter()
+            .map(|data| {
+                let mut record: SampleData = (*data).clone();
+                let sample_token = replacement[&record.sample_token];
+                if sample_token != record.sample_token {
+                    record.sample_token = sample_token;
+                    record.is_key_frame = false;
+                }
+                record
+            })
+            .collect();
+
+        // Drop annotations of removed samples and rechain the rest
+        let mut instances = vec![];
+        let mut sample_annotations = vec![];
+        for instance in self.instance_iter() {
+            let mut kept: Vec<SampleAnnotation> = instance
+                .annotation_iter()
+                .filter(|annotation| {
+                    replacement[&annotation.sample_token] == annotation.sample_token
+                })
+                .map(|annotation| (*annotation).clone())
+                .collect();
+            if kept.is_empty() {
+                continue;
+            }
+
+            let tokens: Vec<Token> = kept.iter().map(|annotation| annotation.token).collect();
+            for (index, annotation) in kept.iter_mut().enumerate() {
+                annotation.prev = index.checked_sub(1).map(|prev| tokens[prev]);
+                annotation.next = tokens.get(index + 1).copied();
+            }
+
+            instances.push(Instance {
+                nbr_annotations: kept.len(),
+                first_annotation_token: tokens[0],
+                last_annotation_token: *tokens.last().unwrap(),
+                ..instance.to_instance()
+            });
+            sample_annotations.extend(kept);
+        }
+
+        SceneTables {
+            scenes,
+            samples,
+            sample_data,
+            sample_annotations,
+            instances,
+        }
+    }
+}
+
+/// Derive a deterministic token from an existing token and an index,
+/// which is stable across Rust versions.
+fn derive_token(base: Token, index: usize) -> Token {
+    let mut state = splitmix64_bytes(index as u64, &base.0);
+    let mut bytes = [0; TOKEN_LENGTH];
+    for chunk in bytes.chunks_exact_mut(8) {
+        state = splitmix64(state);
+        chunk.copy_from_slice(&state.to_le_bytes());
+    }
+    Token(bytes)
+}
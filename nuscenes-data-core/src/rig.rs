@@ -0,0 +1,137 @@
+//! Sensor rig export for robotics stacks.
+//!
+//! The rig of a log is a static transform tree rooted at the ego
+//! vehicle frame. Each calibrated sensor is a child frame named after
+//! its channel, e.g. "CAM_FRONT".
+
+use crate::{
+    dataset::{LogRef, SampleDataRef},
+    geometry::Transform,
+    report::html_escape,
+    serializable::{Channel, Modality},
+};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, fmt::Write};
+
+/// The name of the ego vehicle frame.
+pub const EGO_FRAME: &str = "base_link";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SensorRig {
+    pub frames: Vec<SensorFrame>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SensorFrame {
+    pub channel: Channel,
+    pub modality: Modality,
+    /// The transform from the sensor frame to the ego frame.
+    pub sensor_to_ego: Transform,
+    pub camera_intrinsic: Option<[[f64; 3]; 3]>,
+}
+
+/// A parent-child edge in the static transform tree.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StaticTransform {
+    pub parent_frame: String,
+    pub child_frame: String,
+    pub transform: Transform,
+}
+
+impl SensorRig {
+    pub fn tf_tree(&self) -> Vec<StaticTransform> {
+        self.frames
+            .iter()
+            .map(|frame| StaticTransform {
+                parent_frame: EGO_FRAME.to_string(),
+                child_frame: frame.channel.to_string(),
+                transform: frame.sensor_to_ego,
+            })
+            .collect()
+    }
+
+    /// Render the rig as a URDF document. Special characters in the
+    /// names are escaped.
+    pub fn to_urdf(&self, robot_name: &str) -> String {
+        let robot_name = html_escape(robot_name);
+        let mut urdf = String::new();
+        writeln!(urdf, r#"<?xml version="1.0"?>"#).unwrap();
+        writeln!(urdf, r#"<robot name="{robot_name}">"#).unwrap();
+        writeln!(urdf, r#"  <link name="{EGO_FRAME}"/>"#).unwrap();
+
+        for frame in &self.frames {
+            let channel = html_escape(frame.channel.as_str());
+            let [x, y, z] = frame.sensor_to_ego.translation;
+            let [roll, pitch, yaw] = frame.sensor_to_ego.rpy();
+
+            writeln!(urdf, r#"  <link name="{channel}"/>"#).unwrap();
+            writeln!(
+                urdf,
+                r#"  <joint name="{EGO_FRAME}_to_{channel}" type="fixed">"#
+            )
+            .unwrap();
+            writeln!(urdf, r#"    <parent link="{EGO_FRAME}"/>"#).unwrap();
+            writeln!(urdf, r#"    <child link="{channel}"/>"#).unwrap();
+            writeln!(
+                urdf,
+                r#"    <origin xyz="{x} {y} {z}" rpy="{roll} {pitch} {yaw}"/>"#
+            )
+            .unwrap();
+            writeln!(urdf, "  </joint>").unwrap();
+        }
+
+        writeln!(urdf, "</robot>").unwrap();
+        urdf
+    }
+}
+
+impl LogRef {
+    /// Collect the calibrated sensors used in this log. If a channel
+    /// has several calibrations, the one of the earliest sample data
+    /// is used.
+    pub fn sensor_rig(&self) -> SensorRig {
+        let dataset = self.dataset();
+
+        // Find the earliest sample data of each channel
+        let mut earliest: HashMap<Channel, SampleDataRef> = HashMap::new();
+        let data_iter = dataset
+            .scene_iter()
+            .filter(|scene| scene.log_token == self.token)
+            .flat_map(|scene| {
+                scene
+                    .sample_iter()
+                    .flat_map(|sample| sample.sample_data_iter().collect::<Vec<_>>())
+                    .collect::<Vec<_>>()
+            });
+        for data in data_iter {
+            let channel = data.calibrated_sensor().sensor().channel;
+            let key = (data.timestamp, data.token);
+            match earliest.get(&channel) {
+                Some(prev) if (prev.timestamp, prev.token) <= key => {}
+                _ => {
+                    earliest.insert(channel, data);
+                }
+            }
+        }
+
+        let mut frames: Vec<_> = earliest
+            .into_values()
+            .map(|data| {
+                let calibrated_sensor = data.calibrated_sensor();
+                let sensor = calibrated_sensor.sensor();
+                SensorFrame {
+                    channel: sensor.channel,
+                    modality: sensor.modality,
+                    sensor_to_ego: Transform::new(
+                        calibrated_sensor.rotation,
+                        calibrated_sensor.translation,
+                    ),
+                    camera_intrinsic: calibrated_sensor.camera_intrinsic,
+                }
+            })
+            .collect();
+        frames.sort_by_key(|frame| frame.channel.as_str());
+
+        SensorRig { frames }
+    }
+}
@@ -0,0 +1,140 @@
+//! Sampling of samples stratified by ego speeds.
+
+use crate::{
+    dataset::{Dataset, SampleRef},
+    serializable::Channel,
+    utils::{splitmix64, splitmix64_bytes},
+};
+
+/// The default speed bin edges in m/s.
+pub const DEFAULT_SPEED_BIN_EDGES: [f64; 4] = [1.0, 5.0, 10.0, 15.0];
+
+impl SampleRef {
+    /// Estimate the ego speed in m/s from the ego poses of the
+    /// neighboring samples. It returns `None` for scenes with only
+    /// one sample.
+    pub fn ego_speed(&self) -> Option<f64> {
+        let prev = self.prev.and_then(|token| self.dataset().sample(token));
+        let next = self.next.and_then(|token| self.dataset().sample(token));
+        let (first, last) = match (&prev, &next) {
+            (Some(prev), Some(next)) => (prev, next),
+            (Some(prev), None) => (prev, self),
+            (None, Some(next)) => (self, next),
+            (None, None) => return None,
+        };
+
+        let [x1, y1, _] = ego_position(first)?;
+        let [x2, y2, _] = ego_position(last)?;
+        let time_diff = (last.timestamp - first.timestamp).num_microseconds()? as f64 / 1_000_000.0;
+        (time_diff > 0.0).then(|| (x2 - x1).hypot(y2 - y1) / time_diff)
+    }
+}
+
+/// A sampler yielding samples from speed bins in turn.
+///
+/// Bin `i` holds samples with speeds in `[edges[i - 1], edges[i])`,
+/// where the first and the last bins are unbounded.
+#[derive(Clone)]
+pub struct SpeedBinnedSampler {
+    edges: Vec<f64>,
+    bins: Vec<Vec<SampleRef>>,
+    seed: u64,
+}
+
+impl SpeedBinnedSampler {
+    /// Bin the samples by ego speeds. Samples without speeds are
+    /// dropped. The edges must be sorted in ascending order.
+    pub fn new<I>(samples: I, edges: &[f64], seed: u64) -> Self
+    where
+        I: IntoIterator<Item = SampleRef>,
+    {
+        assert!(
+            edges.windows(2).all(|pair| pair[0] < pair[1]),
+            "edges must be sorted in ascending order"
+        );
+
+        let mut bins = vec![vec![]; edges.len() + 1];
+        for sample in samples {
+            let Some(speed) = sample.ego_speed() else {
+                continue;
+            };
+            let index = edges.iter().take_while(|&&edge| edge <= speed).count();
+            bins[index].push(sample);
+        }
+
+        Self {
+            edges: edges.to_vec(),
+            bins,
+            seed,
+        }
+    }
+
+    pub fn edges(&self) -> &[f64] {
+        &self.edges
+    }
+
+    pub fn bins(&self) -> &[Vec<SampleRef>] {
+        &self.bins
+    }
+
+    /// Get the number of samples in each bin.
+    pub fn bin_counts(&self) -> Vec<usize> {
+        self.bins.iter().map(|bin| bin.len()).collect()
+    }
+
+    /// Iterate over samples endlessly, taking one sample from each
+    /// non-empty bin in turn. Each bin is reshuffled whenever it is
+    /// exhausted, so small bins repeat more often than large bins.
+    pub fn iter(&self) -> impl Iterator<Item = SampleRef> + '_ {
+        let bins: Vec<_> = self.bins.iter().filter(|bin| !bin.is_empty()).collect();
+        let mut orders: Vec<Vec<usize>> = vec![vec![]; bins.len()];
+        let mut step = 0;
+
+        std::iter::from_fn(move || {
+            if bins.is_empty() {
+                return None;
+            }
+
+            let bin_index = step % bins.len();
+            let pass = step / bins.len();
+            step += 1;
+
+            let bin = bins[bin_index];
+            let order = &mut orders[bin_index];
+            let offset = pass % bin.len();
+            if offset == 0 {
+                *order = self.shuffle(bin, pass / bin.len());
+            }
+            Some(bin[order[offset]].clone())
+        })
+    }
+
+    /// Get a permutation of the bin that depends only on the seed, the
+    /// epoch and the sample tokens.
+    fn shuffle(&self, bin: &[SampleRef], epoch: usize) -> Vec<usize> {
+        let mut keys: Vec<(u64, usize)> = bin
+            .iter()
+            .enumerate()
+            .map(|(index, sample)| {
+                let seed = splitmix64(self.seed ^ splitmix64(epoch as u64));
+                (splitmix64_bytes(seed, &sample.token.0), index)
+            })
+            .collect();
+        keys.sort_unstable();
+        keys.into_iter().map(|(_, index)| index).collect()
+    }
+}
+
+impl Dataset {
+    /// Create a sampler over all samples binned by ego speeds.
+    pub fn speed_binned_sampler(&self, edges: &[f64], seed: u64) -> SpeedBinnedSampler {
+        SpeedBinnedSampler::new(self.sample_iter(), edges, seed)
+    }
+}
+
+fn ego_position(sample: &SampleRef) -> Option<[f64; 3]> {
+    let data = sample
+        .sample_data_by_channel(Channel::LidarTop)
+        .or_else(|| sample.keyframe_data_iter().next())?;
+    Some(data.ego_pose().translation)
+}
@@ -0,0 +1,564 @@
+//! Machine-readable schemas of the dataset tables.
+//!
+//! The schemas describe the JSON files as this crate reads and writes
+//! them, so generators of nuScenes-format data can validate their
+//! output against [json_schema] or document it with [markdown].
+//! [validate_file_against_schema] checks a table file directly and
+//! reports the offending records and fields.
+
+use crate::{
+    error::{Error, Result},
+    serializable::{self, Token, TOKEN_LENGTH},
+    table::Table,
+};
+use chrono::NaiveDate;
+use serde::de::{DeserializeSeed, Deserializer, SeqAccess, Visitor};
+use serde_json::{json, Value};
+use std::{
+    fmt::{self, Display, Formatter, Write},
+    fs::File,
+    io::BufReader,
+    path::Path,
+    str::FromStr,
+};
+
+const MODALITIES: &[&str] = &["camera", "lidar", "radar"];
+const FILE_FORMATS: &[&str] = &["pcd", "jpg"];
+const VISIBILITY_LEVELS: &[&str] = &["v0-40", "v40-60", "v60-80", "v80-100"];
+const CHANNELS: &[&str] = &[
+    "CAM_BACK",
+    "CAM_BACK_LEFT",
+    "CAM_BACK_RIGHT",
+    "CAM_FRONT",
+    "CAM_FRONT_LEFT",
+    "CAM_FRONT_RIGHT",
+    "CAM_FRONT_ZOOMED",
+    "LIDAR_TOP",
+    "RADAR_FRONT",
+    "RADAR_FRONT_LEFT",
+    "RADAR_FRONT_RIGHT",
+    "RADAR_BACK_LEFT",
+    "RADAR_BACK_RIGHT",
+];
+
+/// The type of a table field.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldType {
+    /// A hex string of [TOKEN_LENGTH] bytes.
+    Token,
+    /// A list of tokens.
+    TokenList,
+    /// A decimal integer in a string.
+    VisibilityToken,
+    String,
+    /// A path relative to the dataset directory.
+    Path,
+    Integer,
+    UnsignedInteger,
+    Bool,
+    /// An integer timestamp in microseconds.
+    Timestamp,
+    /// A date in `YYYY-MM-DD` format.
+    Date,
+    /// A fixed-length list of numbers.
+    Vector(usize),
+    /// A 3x3 matrix in row-major nested lists.
+    Matrix3,
+    /// A string from a fixed set of values.
+    Enum(&'static [&'static str]),
+}
+
+impl FieldType {
+    pub fn name(&self) -> String {
+        match self {
+            Self::Token => "token".to_string(),
+            Self::TokenList => "token[]".to_string(),
+            Self::VisibilityToken => "visibility token".to_string(),
+            Self::String => "string".to_string(),
+            Self::Path => "path".to_string(),
+            Self::Integer => "integer".to_string(),
+            Self::UnsignedInteger => "unsigned integer".to_string(),
+            Self::Bool => "bool".to_string(),
+            Self::Timestamp => "timestamp (us)".to_string(),
+            Self::Date => "date".to_string(),
+            Self::Vector(len) => format!("number[{len}]"),
+            Self::Matrix3 => "number[3][3]".to_string(),
+            Self::Enum(values) => values.join(" | "),
+        }
+    }
+
+    /// Get the JSON Schema of a present value.
+    pub fn json_schema(&self) -> Value {
+        let token_pattern = format!("^[0-9a-f]{{{}}}$", TOKEN_LENGTH * 2);
+        match self {
+            Self::Token => json!({ "type": "string", "pattern": token_pattern }),
+            Self::TokenList => json!({
+                "type": "array",
+                "items": { "type": "string", "pattern": token_pattern },
+            }),
+            Self::VisibilityToken => json!({ "type": "string", "pattern": "^[0-9]+$" }),
+            Self::String | Self::Path => json!({ "type": "string" }),
+            Self::Integer => json!({ "type": "integer" }),
+            Self::UnsignedInteger | Self::Timestamp => json!({ "type": "integer", "minimum": 0 }),
+            Self::Bool => json!({ "type": "boolean" }),
+            Self::Date => json!({
+                "type": "string",
+                "pattern": "^[0-9]{4}-[0-9]{2}-[0-9]{2}$",
+            }),
+            Self::Vector(len) => json!({
+                "type": "array",
+                "items": { "type": "number" },
+                "minItems": len,
+                "maxItems": len,
+            }),
+            Self::Matrix3 => json!({
+                "type": "array",
+                "items": Self::Vector(3).json_schema(),
+                "minItems": 3,
+                "maxItems": 3,
+            }),
+            Self::Enum(values) => json!({ "type": "string", "enum": values }),
+        }
+    }
+
+    /// Check if a present value has this type.
+    pub fn accepts(&self, value: &Value) -> bool {
+        match self {
+            Self::Token => is_token(value),
+            Self::TokenList => value
+                .as_array()
+                .is_some_and(|items| items.iter().all(is_token)),
+            Self::VisibilityToken => value
+                .as_str()
+                .is_some_and(|text| text.parse::<u32>().is_ok()),
+            Self::String | Self::Path => value.is_string(),
+            Self::Integer | Self::Timestamp => value.is_i64(),
+            Self::UnsignedInteger => value.is_u64(),
+            Self::Bool => value.is_boolean(),
+            Self::Date => value
+                .as_str()
+                .is_some_and(|text| NaiveDate::parse_from_str(text, "%Y-%m-%d").is_ok()),
+            Self::Vector(len) => value
+                .as_array()
+                .is_some_and(|items| items.len() == *len && items.iter().all(Value::is_number)),
+            Self::Matrix3 => value.as_array().is_some_and(|rows| {
+                rows.len() == 3 && rows.iter().all(|row| Self::Vector(3).accepts(row))
+            }),
+            Self::Enum(values) => value.as_str().is_some_and(|text| values.contains(&text)),
+        }
+    }
+
+    /// Check if a value is the empty value of an optional field.
+    fn accepts_empty(&self, value: &Value) -> bool {
+        match self {
+            Self::Matrix3 => value.as_array().is_some_and(|items| items.is_empty()),
+            Self::VisibilityToken => value.is_null(),
+            _ => value.as_str() == Some(""),
+        }
+    }
+
+    /// Get the JSON Schema of the value written for a missing
+    /// optional field.
+    fn empty_json_schema(&self) -> Value {
+        match self {
+            Self::Matrix3 => json!({ "type": "array", "maxItems": 0 }),
+            Self::VisibilityToken => json!({ "type": "null" }),
+            _ => json!({ "const": "" }),
+        }
+    }
+}
+
+/// The schema of a table field.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FieldSchema {
+    pub name: &'static str,
+    pub ty: FieldType,
+    /// Whether the field can be empty. Empty tokens and paths are
+    /// written as `""`, an empty camera intrinsic as `[]` and an
+    /// empty visibility token as `null`.
+    pub optional: bool,
+}
+
+impl FieldSchema {
+    const fn new(name: &'static str, ty: FieldType) -> Self {
+        Self {
+            name,
+            ty,
+            optional: false,
+        }
+    }
+
+    const fn optional(name: &'static str, ty: FieldType) -> Self {
+        Self {
+            name,
+            ty,
+            optional: true,
+        }
+    }
+
+    /// Check if a value is valid for the field.
+    pub fn accepts(&self, value: &Value) -> bool {
+        self.ty.accepts(value) || (self.optional && self.ty.accepts_empty(value))
+    }
+
+    /// Whether the field may be absent from a record.
+    fn is_omittable(&self) -> bool {
+        self.optional && self.ty == FieldType::VisibilityToken
+    }
+
+    pub fn json_schema(&self) -> Value {
+        if self.optional {
+            json!({ "anyOf": [self.ty.json_schema(), self.ty.empty_json_schema()] })
+        } else {
+            self.ty.json_schema()
+        }
+    }
+}
+
+/// The schema of a table file.
+#[derive(Debug, Clone)]
+pub struct TableSchema {
+    pub table: Table,
+    pub fields: Vec<FieldSchema>,
+}
+
+impl TableSchema {
+    /// Get the JSON Schema of the table file, which is an array of
+    /// records.
+    pub fn json_schema(&self) -> Value {
+        let properties: serde_json::Map<String, Value> = self
+            .fields
+            .iter()
+            .map(|field| (field.name.to_string(), field.json_schema()))
+            .collect();
+        let required: Vec<&str> = self
+            .fields
+            .iter()
+            .filter(|field| !field.is_omittable())
+            .map(|field| field.name)
+            .collect();
+
+        json!({
+            "$schema": "https://json-schema.org/draft/2020-12/schema",
+            "title": self.table.file_name(),
+            "type": "array",
+            "items": {
+                "type": "object",
+                "properties": properties,
+                "required": required,
+            },
+        })
+    }
+
+    /// Render the schema as a markdown table.
+    pub fn markdown(&self) -> String {
+        let mut text = String::new();
+        writeln!(text, "## {}", self.table.file_name()).unwrap();
+        writeln!(text).unwrap();
+        writeln!(text, "| field | type | optional |").unwrap();
+        writeln!(text, "|---|---|---|").unwrap();
+        for field in &self.fields {
+            let optional = if field.optional { "yes" } else { "no" };
+            writeln!(
+                text,
+                "| {} | {} | {} |",
+                field.name,
+                field.ty.name().replace('|', "\\|"),
+                optional
+            )
+            .unwrap();
+        }
+        text
+    }
+
+    /// Check a record and push the found violations.
+    fn check_record(&self, index: usize, record: Value, violations: &mut Vec<SchemaViolation>) {
+        let Value::Object(fields) = &record else {
+            violations.push(SchemaViolation {
+                record_index: index,
+                field: None,
+                message: format!("expect an object, but found {record}"),
+            });
+            return;
+        };
+
+        let num_violations = violations.len();
+        for field in &self.fields {
+            match fields.get(field.name) {
+                Some(value) if !field.accepts(value) => violations.push(SchemaViolation {
+                    record_index: index,
+                    field: Some(field.name),
+                    message: format!("expect {}, but found {value}", field.ty.name()),
+                }),
+                None if !field.is_omittable() => violations.push(SchemaViolation {
+                    record_index: index,
+                    field: Some(field.name),
+                    message: "missing field".to_string(),
+                }),
+                _ => {}
+            }
+        }
+
+        // Catch what the field checks miss, such as out-of-range
+        // numbers.
+        if violations.len() == num_violations {
+            if let Err(err) = self.table.deserialize_record(record) {
+                violations.push(SchemaViolation {
+                    record_index: index,
+                    field: None,
+                    message: err.to_string(),
+                });
+            }
+        }
+    }
+}
+
+/// A record that does not match the table schema.
+#[derive(Debug, Clone)]
+pub struct SchemaViolation {
+    /// The index of the record in the table file.
+    pub record_index: usize,
+    /// The offending field, if the violation is specific to a field.
+    pub field: Option<&'static str>,
+    pub message: String,
+}
+
+impl Display for SchemaViolation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.field {
+            Some(field) => write!(
+                f,
+                "record {}, field \"{}\": {}",
+                self.record_index, field, self.message
+            ),
+            None => write!(f, "record {}: {}", self.record_index, self.message),
+        }
+    }
+}
+
+/// Validate a table file record by record. The file is streamed, so
+/// records are not held in memory at once.
+///
+/// It returns the violations of all records, or an error with the
+/// line and column if the file is not a JSON array.
+pub fn validate_file_against_schema<P>(path: P, table: Table) -> Result<Vec<SchemaViolation>>
+where
+    P: AsRef<Path>,
+{
+    let path = path.as_ref();
+    let schema = table.schema();
+    let mut violations = vec![];
+
+    let reader = BufReader::new(File::open(path)?);
+    let mut deserializer = serde_json::Deserializer::from_reader(reader);
+    RecordsSeed {
+        schema: &schema,
+        violations: &mut violations,
+    }
+    .deserialize(&mut deserializer)
+    .and_then(|()| deserializer.end())
+    .map_err(|err| {
+        // The message of the error includes the line and column.
+        Error::ParseError(format!("{}: {err}", path.display()))
+    })?;
+
+    Ok(violations)
+}
+
+struct RecordsSeed<'a> {
+    schema: &'a TableSchema,
+    violations: &'a mut Vec<SchemaViolation>,
+}
+
+impl<'de> DeserializeSeed<'de> for RecordsSeed<'_> {
+    type Value = ();
+
+    fn deserialize<D>(self, deserializer: D) -> std::result::Result<(), D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de> Visitor<'de> for RecordsSeed<'_> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str("an array of records")
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> std::result::Result<(), A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let mut index = 0;
+        while let Some(record) = seq.next_element::<Value>()? {
+            self.schema.check_record(index, record, self.violations);
+            index += 1;
+        }
+        Ok(())
+    }
+}
+
+fn is_token(value: &Value) -> bool {
+    value
+        .as_str()
+        .is_some_and(|text| Token::from_str(text).is_ok())
+}
+
+impl Table {
+    /// Deserialize a record into the type of the table.
+    fn deserialize_record(&self, record: Value) -> serde_json::Result<()> {
+        use serde_json::from_value;
+        use serializable as s;
+
+        match self {
+            Table::Attribute => from_value::<s::Attribute>(record).map(drop),
+            Table::CalibratedSensor => from_value::<s::CalibratedSensor>(record).map(drop),
+            Table::Category => from_value::<s::Category>(record).map(drop),
+            Table::EgoPose => from_value::<s::EgoPose>(record).map(drop),
+            Table::Instance => from_value::<s::Instance>(record).map(drop),
+            Table::Log => from_value::<s::Log>(record).map(drop),
+            Table::Map => from_value::<s::Map>(record).map(drop),
+            Table::Sample => from_value::<s::Sample>(record).map(drop),
+            Table::SampleAnnotation => from_value::<s::SampleAnnotation>(record).map(drop),
+            Table::SampleData => from_value::<s::SampleData>(record).map(drop),
+            Table::Scene => from_value::<s::Scene>(record).map(drop),
+            Table::Sensor => from_value::<s::Sensor>(record).map(drop),
+            Table::Visibility => from_value::<s::Visibility>(record).map(drop),
+        }
+    }
+
+    /// Get the schema of the table.
+    pub fn schema(&self) -> TableSchema {
+        use FieldSchema as F;
+        use FieldType::*;
+
+        let fields = match self {
+            Table::Attribute => vec![
+                F::new("token", Token),
+                F::new("description", String),
+                F::new("name", String),
+            ],
+            Table::CalibratedSensor => vec![
+                F::new("token", Token),
+                F::new("sensor_token", Token),
+                F::new("rotation", Vector(4)),
+                F::optional("camera_intrinsic", Matrix3),
+                F::new("translation", Vector(3)),
+            ],
+            Table::Category => vec![
+                F::new("token", Token),
+                F::new("description", String),
+                F::new("name", String),
+                F::optional("index", UnsignedInteger),
+            ],
+            Table::EgoPose => vec![
+                F::new("token", Token),
+                F::new("timestamp", Timestamp),
+                F::new("rotation", Vector(4)),
+                F::new("translation", Vector(3)),
+            ],
+            Table::Instance => vec![
+                F::new("token", Token),
+                F::new("nbr_annotations", UnsignedInteger),
+                F::new("category_token", Token),
+                F::new("first_annotation_token", Token),
+                F::new("last_annotation_token", Token),
+            ],
+            Table::Log => vec![
+                F::new("token", Token),
+                F::new("date_captured", Date),
+                F::new("location", String),
+                F::new("vehicle", String),
+                F::optional("logfile", Path),
+            ],
+            Table::Map => vec![
+                F::new("token", Token),
+                F::new("log_tokens", TokenList),
+                F::new("filename", Path),
+                F::new("category", String),
+            ],
+            Table::Sample => vec![
+                F::new("token", Token),
+                F::optional("next", Token),
+                F::optional("prev", Token),
+                F::new("scene_token", Token),
+                F::new("timestamp", Timestamp),
+            ],
+            Table::SampleAnnotation => vec![
+                F::new("token", Token),
+                F::new("num_lidar_pts", Integer),
+                F::new("num_radar_pts", Integer),
+                F::new("size", Vector(3)),
+                F::new("rotation", Vector(4)),
+                F::new("translation", Vector(3)),
+                F::new("sample_token", Token),
+                F::new("instance_token", Token),
+                F::new("attribute_tokens", TokenList),
+                F::optional("visibility_token", VisibilityToken),
+                F::optional("prev", Token),
+                F::optional("next", Token),
+            ],
+            Table::SampleData => vec![
+                F::new("token", Token),
+                F::new("fileformat", Enum(FILE_FORMATS)),
+                F::new("is_key_frame", Bool),
+                F::new("filename", Path),
+                F::new("width", UnsignedInteger),
+                F::new("height", UnsignedInteger),
+                F::new("timestamp", Timestamp),
+                F::new("sample_token", Token),
+                F::new("ego_pose_token", Token),
+                F::new("calibrated_sensor_token", Token),
+                F::optional("prev", Token),
+                F::optional("next", Token),
+            ],
+            Table::Scene => vec![
+                F::new("token", Token),
+                F::new("name", String),
+                F::new("description", String),
+                F::new("log_token", Token),
+                F::new("nbr_samples", UnsignedInteger),
+                F::new("first_sample_token", Token),
+                F::new("last_sample_token", Token),
+            ],
+            Table::Sensor => vec![
+                F::new("token", Token),
+                F::new("modality", Enum(MODALITIES)),
+                F::new("channel", Enum(CHANNELS)),
+            ],
+            Table::Visibility => vec![
+                F::new("token", VisibilityToken),
+                F::new("level", Enum(VISIBILITY_LEVELS)),
+                F::new("description", String),
+            ],
+        };
+
+        TableSchema {
+            table: *self,
+            fields,
+        }
+    }
+}
+
+/// Get the JSON Schemas of all tables, keyed by file names.
+pub fn json_schema() -> Value {
+    let tables: serde_json::Map<String, Value> = Table::ALL
+        .iter()
+        .map(|table| (table.file_name().to_string(), table.schema().json_schema()))
+        .collect();
+    Value::Object(tables)
+}
+
+/// Render the schemas of all tables as a markdown document.
+pub fn markdown() -> String {
+    let mut text = String::new();
+    writeln!(text, "# nuScenes table schemas").unwrap();
+    for table in Table::ALL {
+        writeln!(text).unwrap();
+        text.push_str(&table.schema().markdown());
+    }
+    text
+}
@@ -0,0 +1,129 @@
+//! Sets of selected samples, such as labeled pools in active learning
+//! loops.
+
+use crate::{
+    dataset::{Dataset, SampleRef},
+    error::{Error, Result},
+    serializable::Token,
+};
+use std::{
+    collections::BTreeSet,
+    fs::File,
+    io::{self, BufReader, BufWriter},
+    path::Path,
+};
+
+/// A set of sample tokens.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SampleSet {
+    tokens: BTreeSet<Token>,
+}
+
+impl SampleSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.tokens.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tokens.is_empty()
+    }
+
+    pub fn contains(&self, token: Token) -> bool {
+        self.tokens.contains(&token)
+    }
+
+    /// Add a sample. It returns false if the sample is already in the
+    /// set.
+    pub fn insert(&mut self, token: Token) -> bool {
+        self.tokens.insert(token)
+    }
+
+    /// Remove a sample. It returns false if the sample is not in the
+    /// set.
+    pub fn remove(&mut self, token: Token) -> bool {
+        self.tokens.remove(&token)
+    }
+
+    /// Iterate over the tokens in ascending order.
+    pub fn tokens(&self) -> impl Iterator<Item = Token> + '_ {
+        self.tokens.iter().copied()
+    }
+
+    pub fn union(&self, other: &SampleSet) -> SampleSet {
+        self.tokens.union(&other.tokens).copied().collect()
+    }
+
+    pub fn intersection(&self, other: &SampleSet) -> SampleSet {
+        self.tokens.intersection(&other.tokens).copied().collect()
+    }
+
+    pub fn difference(&self, other: &SampleSet) -> SampleSet {
+        self.tokens.difference(&other.tokens).copied().collect()
+    }
+
+    /// Iterate over the samples of the set in the dataset. Tokens not
+    /// in the dataset are skipped.
+    pub fn samples<'a>(&'a self, dataset: &'a Dataset) -> impl Iterator<Item = SampleRef> + 'a {
+        self.tokens().filter_map(|token| dataset.sample(token))
+    }
+
+    /// Save the set as a JSON array of tokens.
+    pub fn save<P>(&self, path: P) -> Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        let writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(writer, &self.tokens).map_err(io::Error::from)?;
+        Ok(())
+    }
+
+    /// Load a set saved by [SampleSet::save].
+    pub fn load<P>(path: P) -> Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let reader = BufReader::new(File::open(path)?);
+        let tokens = serde_json::from_reader(reader).map_err(|err| {
+            Error::ParseError(format!("failed to load {}: {err}", path.display()))
+        })?;
+        Ok(Self { tokens })
+    }
+}
+
+impl FromIterator<Token> for SampleSet {
+    fn from_iter<I>(iter: I) -> Self
+    where
+        I: IntoIterator<Item = Token>,
+    {
+        Self {
+            tokens: iter.into_iter().collect(),
+        }
+    }
+}
+
+impl Extend<Token> for SampleSet {
+    fn extend<I>(&mut self, iter: I)
+    where
+        I: IntoIterator<Item = Token>,
+    {
+        self.tokens.extend(iter);
+    }
+}
+
+impl Dataset {
+    /// Select the samples satisfying the predicate.
+    pub fn select_samples<F>(&self, mut predicate: F) -> SampleSet
+    where
+        F: FnMut(&SampleRef) -> bool,
+    {
+        self.sample_iter()
+            .filter(|sample| predicate(sample))
+            .map(|sample| sample.token)
+            .collect()
+    }
+}
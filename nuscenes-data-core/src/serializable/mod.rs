@@ -1,4 +1,4 @@
-mod serde_utils;
+pub(crate) mod serde_utils;
 mod token;
 mod types;
 
@@ -194,27 +194,68 @@ pub mod opt_token {
 //     }
 // }
 
+/// Timestamps in microseconds since the Unix epoch. They are stored as
+/// integers in the tables and written back as integers, so that tables
+/// written by [DatasetWriter](crate::writer::DatasetWriter) keep every
+/// digit and match the devkit.
 pub mod timestamp {
-    use chrono::NaiveDateTime;
-    use serde::{Deserialize, Deserializer, Serializer};
+    use chrono::{DateTime, NaiveDateTime};
+    use serde::{
+        de::{Error as DeserializeError, Visitor},
+        Deserializer, Serializer,
+    };
+    use std::fmt::{Formatter, Result as FormatResult};
+
+    struct TimestampVisitor;
+
+    impl<'de> Visitor<'de> for TimestampVisitor {
+        type Value = NaiveDateTime;
+
+        fn expecting(&self, formatter: &mut Formatter) -> FormatResult {
+            formatter.write_str("a timestamp in microseconds")
+        }
+
+        fn visit_i64<E>(self, value: i64) -> Result<Self::Value, E>
+        where
+            E: DeserializeError,
+        {
+            DateTime::from_timestamp_micros(value)
+                .map(|datetime| datetime.naive_utc())
+                .ok_or_else(|| E::custom(format!("timestamp {value} is out of range")))
+        }
+
+        fn visit_u64<E>(self, value: u64) -> Result<Self::Value, E>
+        where
+            E: DeserializeError,
+        {
+            let value = i64::try_from(value)
+                .map_err(|_| E::custom(format!("timestamp {value} is out of range")))?;
+            self.visit_i64(value)
+        }
+
+        fn visit_f64<E>(self, value: f64) -> Result<Self::Value, E>
+        where
+            E: DeserializeError,
+        {
+            // Fractional microseconds are rounded off
+            self.visit_i64(value.round() as i64)
+        }
+    }
 
     pub fn serialize<S>(value: &NaiveDateTime, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: Serializer,
     {
-        let timestamp = value.timestamp_nanos() as f64 / 1_000_000_000.0;
-        serializer.serialize_f64(timestamp)
+        // Round to the nearest microsecond
+        let datetime = value.and_utc();
+        let round_up = datetime.timestamp_subsec_nanos() % 1000 >= 500;
+        serializer.serialize_i64(datetime.timestamp_micros() + i64::from(round_up))
     }
 
     pub fn deserialize<'de, D>(deserializer: D) -> Result<NaiveDateTime, D::Error>
     where
         D: Deserializer<'de>,
     {
-        let timestamp_us = f64::deserialize(deserializer)?; // in us
-        let timestamp_ns = (timestamp_us * 1000.0) as u64; // in ns
-        let secs = timestamp_ns / 1_000_000_000;
-        let nsecs = timestamp_ns % 1_000_000_000;
-        let datetime = NaiveDateTime::from_timestamp_opt(secs as i64, nsecs as u32).unwrap();
-        Ok(datetime)
+        deserializer.deserialize_any(TimestampVisitor)
     }
 }
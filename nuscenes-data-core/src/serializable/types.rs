@@ -5,7 +5,10 @@ use crate::{
 };
 use chrono::naive::{NaiveDate, NaiveDateTime};
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::{
+    fmt::{self, Display, Formatter},
+    path::PathBuf,
+};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Attribute {
@@ -29,6 +32,10 @@ pub struct Category {
     pub token: Token,
     pub description: String,
     pub name: String,
+    /// The class index in lidarseg labels, which is only present in
+    /// datasets with lidarseg.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub index: Option<u8>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -67,6 +74,14 @@ pub struct Map {
     pub category: String,
 }
 
+/// A Panoptic nuScenes label file of a lidar sweep.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Panoptic {
+    pub token: Token,
+    pub sample_data_token: Token,
+    pub filename: PathBuf,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Sample {
     pub token: Token,
@@ -103,6 +118,10 @@ pub struct SampleData {
     pub fileformat: FileFormat,
     pub is_key_frame: bool,
     pub filename: PathBuf,
+    /// The image width in pixels, or zero for non-image data.
+    pub width: u32,
+    /// The image height in pixels, or zero for non-image data.
+    pub height: u32,
     #[serde(with = "serde_utils::timestamp")]
     pub timestamp: NaiveDateTime,
     pub sample_token: Token,
@@ -154,7 +173,7 @@ pub enum FileFormat {
     Jpg,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Serialize, Deserialize, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 #[serde(rename_all = "kebab-case")]
 pub enum VisibilityLevel {
     V0_40,
@@ -163,7 +182,7 @@ pub enum VisibilityLevel {
     V80_100,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Serialize, Deserialize, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 pub enum Channel {
     CamBack,
@@ -181,6 +200,83 @@ pub enum Channel {
     RadarBackRight,
 }
 
+impl Modality {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Camera => "camera",
+            Self::Lidar => "lidar",
+            Self::Radar => "radar",
+        }
+    }
+}
+
+impl Display for Modality {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+        formatter.write_str(self.as_str())
+    }
+}
+
+impl Modality {
+    /// Get the file format of the sample data of the modality.
+    pub fn file_format(&self) -> FileFormat {
+        match self {
+            Self::Camera => FileFormat::Jpg,
+            Self::Lidar | Self::Radar => FileFormat::Pcd,
+        }
+    }
+}
+
+impl FileFormat {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Pcd => "pcd",
+            Self::Jpg => "jpg",
+        }
+    }
+
+    /// Get the file extensions of the format. Lidar scans in the
+    /// `pcd` format are stored as `.pcd.bin` files, and camera frames
+    /// converted from other sources may be stored as `.png` files.
+    pub fn extensions(&self) -> &'static [&'static str] {
+        match self {
+            Self::Pcd => &["pcd", "bin"],
+            Self::Jpg => &["jpg", "jpeg", "png"],
+        }
+    }
+}
+
+impl Display for FileFormat {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+        formatter.write_str(self.as_str())
+    }
+}
+
+impl Channel {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::CamBack => "CAM_BACK",
+            Self::CamBackLeft => "CAM_BACK_LEFT",
+            Self::CamBackRight => "CAM_BACK_RIGHT",
+            Self::CamFront => "CAM_FRONT",
+            Self::CamFrontLeft => "CAM_FRONT_LEFT",
+            Self::CamFrontRight => "CAM_FRONT_RIGHT",
+            Self::CamFrontZoomed => "CAM_FRONT_ZOOMED",
+            Self::LidarTop => "LIDAR_TOP",
+            Self::RadarFront => "RADAR_FRONT",
+            Self::RadarFrontLeft => "RADAR_FRONT_LEFT",
+            Self::RadarFrontRight => "RADAR_FRONT_RIGHT",
+            Self::RadarBackLeft => "RADAR_BACK_LEFT",
+            Self::RadarBackRight => "RADAR_BACK_RIGHT",
+        }
+    }
+}
+
+impl Display for Channel {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+        formatter.write_str(self.as_str())
+    }
+}
+
 macro_rules! impl_with_token {
     ($name:path) => {
         impl WithToken for $name {
@@ -198,6 +294,7 @@ impl_with_token!(EgoPose);
 impl_with_token!(Instance);
 impl_with_token!(Log);
 impl_with_token!(Map);
+impl_with_token!(Panoptic);
 impl_with_token!(Sample);
 impl_with_token!(SampleAnnotation);
 impl_with_token!(SampleData);
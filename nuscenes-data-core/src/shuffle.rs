@@ -0,0 +1,191 @@
+//! Reproducible shuffling of scenes, samples and sample data over
+//! epochs.
+//!
+//! The order of an epoch depends only on the seed, the epoch number
+//! and the item tokens, so training can resume from any position.
+
+use crate::{
+    dataset::{Dataset, SampleDataRef, SampleRef, SceneRef},
+    serializable::{Channel, Token},
+    utils::splitmix64,
+};
+use std::collections::HashSet;
+
+/// Options to select items to shuffle.
+#[derive(Debug, Clone)]
+pub struct EpochOptions {
+    /// Restrict items to the scenes with these names, such as the
+    /// scenes of a split. All scenes are used if it is `None`.
+    pub scene_names: Option<HashSet<String>>,
+    /// Skip sweeps when shuffling sample data.
+    pub key_frames_only: bool,
+}
+
+impl Default for EpochOptions {
+    fn default() -> Self {
+        Self {
+            scene_names: None,
+            key_frames_only: true,
+        }
+    }
+}
+
+impl EpochOptions {
+    fn contains_scene(&self, scene: &SceneRef) -> bool {
+        match &self.scene_names {
+            Some(names) => names.contains(&scene.name),
+            None => true,
+        }
+    }
+}
+
+/// An endless iterator over shuffled items, epoch by epoch.
+#[derive(Clone)]
+pub struct EpochIter<T> {
+    items: Vec<(Token, T)>,
+    seed: u64,
+    epoch: usize,
+    index: usize,
+    order: Vec<usize>,
+}
+
+impl<T> EpochIter<T>
+where
+    T: Clone,
+{
+    /// Create the iterator. Items are sorted by tokens first, so the
+    /// order does not depend on the input order.
+    pub fn new<I>(items: I, seed: u64) -> Self
+    where
+        I: IntoIterator<Item = (Token, T)>,
+    {
+        let mut items: Vec<_> = items.into_iter().collect();
+        items.sort_by_key(|(token, _)| *token);
+
+        let mut iter = Self {
+            items,
+            seed,
+            epoch: 0,
+            index: 0,
+            order: vec![],
+        };
+        iter.order = iter.permutation(0);
+        iter
+    }
+
+    /// Continue from the index-th item of the epoch.
+    pub fn resume(mut self, epoch: usize, index: usize) -> Self {
+        assert!(
+            index <= self.items.len(),
+            "index must not exceed the epoch length"
+        );
+        self.epoch = epoch;
+        self.index = index;
+        self.order = self.permutation(epoch);
+        self
+    }
+
+    /// The number of items in each epoch.
+    pub fn epoch_len(&self) -> usize {
+        self.items.len()
+    }
+
+    /// The epoch of the next item.
+    pub fn epoch(&self) -> usize {
+        self.epoch
+    }
+
+    /// The index of the next item within the epoch.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Iterate over the remaining items of the current epoch.
+    pub fn current_epoch(&mut self) -> impl Iterator<Item = T> + '_ {
+        let remaining = self.items.len() - self.index;
+        self.take(remaining)
+    }
+
+    /// Get the order of items in the epoch, using a Fisher–Yates
+    /// shuffle.
+    fn permutation(&self, epoch: usize) -> Vec<usize> {
+        let mut state = splitmix64(self.seed ^ splitmix64(epoch as u64));
+        let mut order: Vec<usize> = (0..self.items.len()).collect();
+        for index in (1..order.len()).rev() {
+            state = splitmix64(state);
+            let other = (state % (index as u64 + 1)) as usize;
+            order.swap(index, other);
+        }
+        order
+    }
+}
+
+impl<T> Iterator for EpochIter<T>
+where
+    T: Clone,
+{
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.items.is_empty() {
+            return None;
+        }
+        if self.index == self.items.len() {
+            self.epoch += 1;
+            self.index = 0;
+            self.order = self.permutation(self.epoch);
+        }
+
+        let (_, item) = &self.items[self.order[self.index]];
+        self.index += 1;
+        Some(item.clone())
+    }
+}
+
+impl Dataset {
+    /// Shuffle all samples over epochs.
+    pub fn epoch_iter(&self, seed: u64) -> EpochIter<SampleRef> {
+        self.sample_epoch_iter(seed, &EpochOptions::default())
+    }
+
+    /// Shuffle samples of the selected scenes over epochs.
+    pub fn sample_epoch_iter(&self, seed: u64, options: &EpochOptions) -> EpochIter<SampleRef> {
+        let samples = self
+            .scene_iter()
+            .filter(|scene| options.contains_scene(scene))
+            .flat_map(|scene| scene.sample_iter().collect::<Vec<_>>())
+            .map(|sample| (sample.token, sample));
+        EpochIter::new(samples, seed)
+    }
+
+    /// Shuffle scenes over epochs.
+    pub fn scene_epoch_iter(&self, seed: u64, options: &EpochOptions) -> EpochIter<SceneRef> {
+        let scenes = self
+            .scene_iter()
+            .filter(|scene| options.contains_scene(scene))
+            .map(|scene| (scene.token, scene));
+        EpochIter::new(scenes, seed)
+    }
+
+    /// Shuffle sample data of the channel in the selected scenes over
+    /// epochs. Sweeps are included unless `key_frames_only` is set.
+    pub fn sample_data_epoch_iter(
+        &self,
+        seed: u64,
+        channel: Channel,
+        options: &EpochOptions,
+    ) -> EpochIter<SampleDataRef> {
+        let scene_tokens: HashSet<Token> = self
+            .scene_iter()
+            .filter(|scene| options.contains_scene(scene))
+            .map(|scene| scene.token)
+            .collect();
+        let sample_data = self
+            .sample_data_iter()
+            .filter(|data| !options.key_frames_only || data.is_key_frame)
+            .filter(|data| data.channel() == channel)
+            .filter(|data| scene_tokens.contains(&data.sample().scene_token))
+            .map(|data| (data.token, data));
+        EpochIter::new(sample_data, seed)
+    }
+}
@@ -0,0 +1,91 @@
+//! Spatial indexing of items on the xy plane.
+
+use crate::dataset::{SampleAnnotationRef, SampleRef};
+use std::collections::HashMap;
+
+/// The default cell size in meters of annotation indexes.
+pub const DEFAULT_CELL_SIZE: f64 = 10.0;
+
+/// A uniform grid index of items located on the xy plane.
+#[derive(Debug, Clone)]
+pub struct SpatialIndex<T> {
+    cell_size: f64,
+    items: Vec<([f64; 2], T)>,
+    cells: HashMap<(i64, i64), Vec<usize>>,
+}
+
+impl<T> SpatialIndex<T> {
+    pub fn new(cell_size: f64) -> Self {
+        assert!(cell_size > 0.0, "cell_size must be positive");
+        Self {
+            cell_size,
+            items: vec![],
+            cells: HashMap::new(),
+        }
+    }
+
+    pub fn insert(&mut self, position: [f64; 2], item: T) {
+        let cell = self.cell_of(position);
+        self.cells.entry(cell).or_default().push(self.items.len());
+        self.items.push((position, item));
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Find the items within the radius, sorted by distances in
+    /// ascending order.
+    pub fn within(&self, center: [f64; 2], radius: f64) -> Vec<(f64, &T)> {
+        let (min_col, min_row) = self.cell_of([center[0] - radius, center[1] - radius]);
+        let (max_col, max_row) = self.cell_of([center[0] + radius, center[1] + radius]);
+
+        let mut found = vec![];
+        for col in min_col..=max_col {
+            for row in min_row..=max_row {
+                let Some(indices) = self.cells.get(&(col, row)) else {
+                    continue;
+                };
+                for &index in indices {
+                    let ([x, y], item) = &self.items[index];
+                    let distance = (x - center[0]).hypot(y - center[1]);
+                    if distance <= radius {
+                        found.push((distance, item));
+                    }
+                }
+            }
+        }
+
+        found.sort_by(|(lhs, _), (rhs, _)| lhs.total_cmp(rhs));
+        found
+    }
+
+    /// Find the nearest item within the radius.
+    pub fn nearest(&self, center: [f64; 2], radius: f64) -> Option<(f64, &T)> {
+        self.within(center, radius).into_iter().next()
+    }
+
+    fn cell_of(&self, [x, y]: [f64; 2]) -> (i64, i64) {
+        (
+            (x / self.cell_size).floor() as i64,
+            (y / self.cell_size).floor() as i64,
+        )
+    }
+}
+
+impl SampleRef {
+    /// Index the annotations of the sample by box centers in the
+    /// global frame.
+    pub fn annotation_index(&self, cell_size: f64) -> SpatialIndex<SampleAnnotationRef> {
+        let mut index = SpatialIndex::new(cell_size);
+        for annotation in self.annotation_iter() {
+            let [x, y, _] = annotation.translation;
+            index.insert([x, y], annotation);
+        }
+        index
+    }
+}
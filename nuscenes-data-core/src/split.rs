@@ -0,0 +1,294 @@
+//! The train, validation, test and mini scene splits.
+//!
+//! The scene names of the validation and mini splits are embedded. The
+//! training split of `v1.0-trainval` is the complement of the
+//! validation split, and the test split is the whole `v1.0-test`
+//! version. Custom splits can be stored as
+//! [TokenSet](crate::token_set::TokenSet)s instead.
+//!
+//! Only these splits are provided. The devkit additionally defines the
+//! `train_detect` and `train_track` subsets of the training split,
+//! which are not embedded here. Filter the dataset by their scene
+//! names instead.
+//!
+//! ```ignore
+//! use std::collections::HashSet;
+//!
+//! // The scene names copied from nuscenes/utils/splits.py
+//! let train_detect: HashSet<&str> = TRAIN_DETECT.iter().copied().collect();
+//! let dataset = dataset.filter_scenes(|scene| train_detect.contains(scene.name.as_str()))?;
+//! ```
+
+use crate::{
+    dataset::{Dataset, SceneRef},
+    error::Result,
+};
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Display};
+
+/// The scenes of the `val` split.
+const VAL_SCENES: [&str; 150] = [
+    "scene-0003",
+    "scene-0012",
+    "scene-0013",
+    "scene-0014",
+    "scene-0015",
+    "scene-0016",
+    "scene-0017",
+    "scene-0018",
+    "scene-0035",
+    "scene-0036",
+    "scene-0038",
+    "scene-0039",
+    "scene-0092",
+    "scene-0093",
+    "scene-0094",
+    "scene-0095",
+    "scene-0096",
+    "scene-0097",
+    "scene-0098",
+    "scene-0099",
+    "scene-0100",
+    "scene-0101",
+    "scene-0102",
+    "scene-0103",
+    "scene-0104",
+    "scene-0105",
+    "scene-0106",
+    "scene-0107",
+    "scene-0108",
+    "scene-0109",
+    "scene-0110",
+    "scene-0221",
+    "scene-0268",
+    "scene-0269",
+    "scene-0270",
+    "scene-0271",
+    "scene-0272",
+    "scene-0273",
+    "scene-0274",
+    "scene-0275",
+    "scene-0276",
+    "scene-0277",
+    "scene-0278",
+    "scene-0329",
+    "scene-0330",
+    "scene-0331",
+    "scene-0332",
+    "scene-0344",
+    "scene-0345",
+    "scene-0346",
+    "scene-0519",
+    "scene-0520",
+    "scene-0521",
+    "scene-0522",
+    "scene-0523",
+    "scene-0524",
+    "scene-0552",
+    "scene-0553",
+    "scene-0554",
+    "scene-0555",
+    "scene-0556",
+    "scene-0557",
+    "scene-0558",
+    "scene-0559",
+    "scene-0560",
+    "scene-0561",
+    "scene-0562",
+    "scene-0563",
+    "scene-0564",
+    "scene-0565",
+    "scene-0625",
+    "scene-0626",
+    "scene-0627",
+    "scene-0629",
+    "scene-0630",
+    "scene-0632",
+    "scene-0633",
+    "scene-0634",
+    "scene-0635",
+    "scene-0636",
+    "scene-0637",
+    "scene-0638",
+    "scene-0770",
+    "scene-0771",
+    "scene-0775",
+    "scene-0777",
+    "scene-0778",
+    "scene-0780",
+    "scene-0781",
+    "scene-0782",
+    "scene-0783",
+    "scene-0784",
+    "scene-0794",
+    "scene-0795",
+    "scene-0796",
+    "scene-0797",
+    "scene-0798",
+    "scene-0799",
+    "scene-0800",
+    "scene-0802",
+    "scene-0904",
+    "scene-0905",
+    "scene-0906",
+    "scene-0907",
+    "scene-0908",
+    "scene-0909",
+    "scene-0910",
+    "scene-0911",
+    "scene-0912",
+    "scene-0913",
+    "scene-0914",
+    "scene-0915",
+    "scene-0916",
+    "scene-0917",
+    "scene-0919",
+    "scene-0920",
+    "scene-0921",
+    "scene-0922",
+    "scene-0923",
+    "scene-0924",
+    "scene-0925",
+    "scene-0926",
+    "scene-0927",
+    "scene-0928",
+    "scene-0929",
+    "scene-0930",
+    "scene-0931",
+    "scene-0962",
+    "scene-0963",
+    "scene-0966",
+    "scene-0967",
+    "scene-0968",
+    "scene-0969",
+    "scene-0971",
+    "scene-0972",
+    "scene-1059",
+    "scene-1060",
+    "scene-1061",
+    "scene-1062",
+    "scene-1063",
+    "scene-1064",
+    "scene-1065",
+    "scene-1066",
+    "scene-1067",
+    "scene-1068",
+    "scene-1069",
+    "scene-1070",
+    "scene-1071",
+    "scene-1072",
+    "scene-1073",
+];
+
+/// The scenes of the `mini_train` split.
+const MINI_TRAIN_SCENES: [&str; 8] = [
+    "scene-0061",
+    "scene-0553",
+    "scene-0655",
+    "scene-0757",
+    "scene-0796",
+    "scene-1077",
+    "scene-1094",
+    "scene-1100",
+];
+
+/// The scenes of the `mini_val` split.
+const MINI_VAL_SCENES: [&str; 2] = ["scene-0103", "scene-0916"];
+
+/// The suffix of the version names holding the training and
+/// validation splits.
+const TRAINVAL_SUFFIX: &str = "-trainval";
+
+/// The suffix of the version names holding the test split.
+const TEST_SUFFIX: &str = "-test";
+
+/// A scene split with the same scene names as the devkit split of
+/// the same name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum Split {
+    /// The scenes of `v1.0-trainval` not in the validation split.
+    Train,
+    Val,
+    /// The scenes of `v1.0-test`.
+    Test,
+    MiniTrain,
+    MiniVal,
+}
+
+impl Split {
+    pub const ALL: [Split; 5] = [
+        Split::Train,
+        Split::Val,
+        Split::Test,
+        Split::MiniTrain,
+        Split::MiniVal,
+    ];
+
+    /// Get the split name used by the devkit, such as `mini_val`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Split::Train => "train",
+            Split::Val => "val",
+            Split::Test => "test",
+            Split::MiniTrain => "mini_train",
+            Split::MiniVal => "mini_val",
+        }
+    }
+
+    /// Get the embedded scene names of the split. It returns `None`
+    /// for the training and test splits, which are determined by
+    /// versions.
+    pub fn scene_names(&self) -> Option<&'static [&'static str]> {
+        match self {
+            Split::Val => Some(&VAL_SCENES),
+            Split::MiniTrain => Some(&MINI_TRAIN_SCENES),
+            Split::MiniVal => Some(&MINI_VAL_SCENES),
+            Split::Train | Split::Test => None,
+        }
+    }
+
+    /// Check if the scene belongs to the split.
+    pub fn contains(&self, scene: &SceneRef) -> bool {
+        let name = scene.name.as_str();
+        match self {
+            Split::Train => {
+                scene.version().ends_with(TRAINVAL_SUFFIX) && !VAL_SCENES.contains(&name)
+            }
+            Split::Val => scene.version().ends_with(TRAINVAL_SUFFIX) && VAL_SCENES.contains(&name),
+            Split::Test => scene.version().ends_with(TEST_SUFFIX),
+            Split::MiniTrain => MINI_TRAIN_SCENES.contains(&name),
+            Split::MiniVal => MINI_VAL_SCENES.contains(&name),
+        }
+    }
+}
+
+impl Display for Split {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl Dataset {
+    /// Get the scenes of the split in time order.
+    ///
+    /// ```ignore
+    /// use nuscenes_data::split::Split;
+    ///
+    /// let val_scenes = dataset.split_scenes(Split::Val);
+    /// ```
+    pub fn split_scenes(&self, split: Split) -> Vec<SceneRef> {
+        self.sorted_scene_tokens
+            .iter()
+            .filter_map(|&token| self.scene(token))
+            .filter(|scene| split.contains(scene))
+            .collect()
+    }
+
+    /// Keep the scenes of the split and the records depending on them.
+    /// See [Dataset::filter_scenes].
+    pub fn split(&self, split: Split) -> Result<Dataset> {
+        self.filter_scenes(|scene| split.contains(scene))
+    }
+}
@@ -0,0 +1,174 @@
+//! Box statistics per detection class.
+//!
+//! The statistics are typically computed over the training samples to
+//! design anchors and choose normalization constants of detectors.
+//! Headings are measured relative to the ego vehicle at the top lidar
+//! sample data, which is what anchors are laid out against.
+
+use crate::{
+    dataset::{Dataset, SampleRef},
+    eval::detection::DetectionClass,
+    geometry::{quat_conj, quat_mul, quat_yaw},
+    serializable::Channel,
+};
+use itertools::Itertools;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, f64::consts::PI};
+
+/// The percentiles reported by [Distribution::percentiles].
+pub const BOX_STATISTICS_PERCENTILES: [f64; 5] = [5.0, 25.0, 50.0, 75.0, 95.0];
+
+/// The number of bins of [ClassBoxStatistics::yaw_histogram].
+pub const YAW_HISTOGRAM_BINS: usize = 12;
+
+/// Summary statistics of a set of values.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Distribution {
+    pub mean: f64,
+    pub std: f64,
+    pub min: f64,
+    pub max: f64,
+    /// The values at [BOX_STATISTICS_PERCENTILES], interpolated
+    /// linearly between the nearest ranks.
+    pub percentiles: [f64; 5],
+}
+
+impl Distribution {
+    /// Compute the statistics of the values. It returns `None` if the
+    /// values are empty.
+    pub fn from_values(mut values: Vec<f64>) -> Option<Self> {
+        if values.is_empty() {
+            return None;
+        }
+        values.sort_by(f64::total_cmp);
+
+        let len = values.len() as f64;
+        let mean = values.iter().sum::<f64>() / len;
+        let var = values
+            .iter()
+            .map(|value| (value - mean).powi(2))
+            .sum::<f64>()
+            / len;
+        let percentiles = BOX_STATISTICS_PERCENTILES.map(|percentile| {
+            let rank = percentile / 100.0 * (len - 1.0);
+            let lower = values[rank.floor() as usize];
+            let upper = values[rank.ceil() as usize];
+            lower + (upper - lower) * rank.fract()
+        });
+
+        Some(Self {
+            mean,
+            std: var.sqrt(),
+            min: values[0],
+            max: values[values.len() - 1],
+            percentiles,
+        })
+    }
+}
+
+/// Box statistics of a detection class.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ClassBoxStatistics {
+    pub count: usize,
+    pub width: Distribution,
+    pub length: Distribution,
+    pub height: Distribution,
+    /// The heading relative to the ego vehicle in radians within
+    /// `[-π, π]`.
+    pub yaw: Distribution,
+    /// The number of boxes whose relative heading falls into each of
+    /// equal bins over `[-π, π)`.
+    pub yaw_histogram: [usize; YAW_HISTOGRAM_BINS],
+}
+
+/// Box statistics of each detection class. Classes without
+/// annotations are absent.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct BoxStatistics {
+    pub classes: BTreeMap<DetectionClass, ClassBoxStatistics>,
+}
+
+impl BoxStatistics {
+    pub fn get(&self, class: DetectionClass) -> Option<&ClassBoxStatistics> {
+        self.classes.get(&class)
+    }
+
+    /// Get the median sizes `[width, length, height]` of the class,
+    /// which are common anchor sizes.
+    pub fn median_size(&self, class: DetectionClass) -> Option<[f64; 3]> {
+        let stats = self.classes.get(&class)?;
+        let median = |dist: &Distribution| dist.percentiles[2];
+        Some([
+            median(&stats.width),
+            median(&stats.length),
+            median(&stats.height),
+        ])
+    }
+}
+
+impl Dataset {
+    /// Compute the box statistics over all annotations of the dataset.
+    /// Compute them on [Dataset::split] of the training split, or use
+    /// [Dataset::box_statistics_of], to avoid leaking statistics of
+    /// evaluation samples.
+    pub fn box_statistics(&self) -> BoxStatistics {
+        self.box_statistics_of(self.sample_iter())
+    }
+
+    /// Compute the box statistics over the annotations of the samples.
+    pub fn box_statistics_of<I>(&self, samples: I) -> BoxStatistics
+    where
+        I: IntoIterator<Item = SampleRef>,
+    {
+        let samples: Vec<SampleRef> = samples.into_iter().collect();
+
+        // Collect [width, length, height, yaw] of each annotation
+        let boxes = samples
+            .par_iter()
+            .flat_map_iter(|sample| {
+                let ego_rotation = sample
+                    .sample_data_by_channel(Channel::LidarTop)
+                    .map(|data| data.ego_pose().rotation);
+                sample.annotation_iter().filter_map(move |annotation| {
+                    let class = annotation.detection_class()?;
+                    let rotation = match ego_rotation {
+                        Some(ego_rotation) => {
+                            quat_mul(quat_conj(ego_rotation), annotation.rotation)
+                        }
+                        None => annotation.rotation,
+                    };
+                    let [width, length, height] = annotation.size;
+                    Some((class, [width, length, height, quat_yaw(rotation)]))
+                })
+            })
+            .collect::<Vec<_>>()
+            .into_iter()
+            .into_group_map();
+
+        let classes = boxes
+            .into_iter()
+            .filter_map(|(class, values)| {
+                let column = |index: usize| values.iter().map(|value| value[index]).collect_vec();
+                let mut yaw_histogram = [0; YAW_HISTOGRAM_BINS];
+                for value in &values {
+                    let ratio = (value[3] + PI) / (2.0 * PI);
+                    let bin = (ratio * YAW_HISTOGRAM_BINS as f64) as usize;
+                    yaw_histogram[bin.min(YAW_HISTOGRAM_BINS - 1)] += 1;
+                }
+
+                let stats = ClassBoxStatistics {
+                    count: values.len(),
+                    width: Distribution::from_values(column(0))?,
+                    length: Distribution::from_values(column(1))?,
+                    height: Distribution::from_values(column(2))?,
+                    yaw: Distribution::from_values(column(3))?,
+                    yaw_histogram,
+                };
+                Some((class, stats))
+            })
+            .collect();
+
+        BoxStatistics { classes }
+    }
+}
@@ -0,0 +1,463 @@
+//! Storage backends to read sample files.
+//!
+//! The dataset reads sample files through the [Storage] set on
+//! [DatasetLoader](crate::DatasetLoader). It defaults to
+//! [LocalStorage], which reads from the local file system. Reads are
+//! retried and timed out according to [ReadOptions].
+
+use crate::utils::{splitmix64, splitmix64_bytes};
+use std::{
+    collections::HashMap,
+    ffi::OsStr,
+    fmt::{self, Debug, Display, Write},
+    fs, io,
+    path::{Path, PathBuf},
+    sync::{mpsc, Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+/// A backend to read files by paths.
+pub trait Storage: Debug + Send + Sync {
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>>;
+
+    /// Check if the file exists. The default implementation reads the
+    /// whole file, so backends should override it if possible.
+    fn exists(&self, path: &Path) -> bool {
+        self.read(path).is_ok()
+    }
+
+    /// Get the size of the file in bytes. The default implementation
+    /// reads the whole file, so backends should override it if
+    /// possible.
+    fn size(&self, path: &Path) -> io::Result<u64> {
+        self.read(path).map(|bytes| bytes.len() as u64)
+    }
+
+    /// Check if the paths are files on the local file system, which
+    /// are opened directly to map them into memory with the `mmap`
+    /// feature. Backends transforming or intercepting reads must
+    /// return false, which is the default.
+    fn is_local(&self) -> bool {
+        false
+    }
+
+    /// Read the file of a record. The default implementation joins the
+    /// path and calls [Storage::read]. Backends keyed by the relative
+    /// file names, such as object stores, override it to skip the
+    /// dataset directory.
+    fn read_data(&self, path: DataPath<'_>) -> io::Result<Vec<u8>> {
+        self.read(&path.to_path_buf())
+    }
+
+    /// Check if the file of a record exists. See [Storage::read_data].
+    fn data_exists(&self, path: DataPath<'_>) -> bool {
+        self.exists(&path.to_path_buf())
+    }
+
+    /// Get the size of the file of a record in bytes. See
+    /// [Storage::read_data].
+    fn data_size(&self, path: DataPath<'_>) -> io::Result<u64> {
+        self.size(&path.to_path_buf())
+    }
+}
+
+/// Read files from the local file system.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LocalStorage;
+
+impl Storage for LocalStorage {
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        fs::read(path)
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        path.is_file()
+    }
+
+    fn size(&self, path: &Path) -> io::Result<u64> {
+        let metadata = fs::metadata(path)?;
+        if !metadata.is_file() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{} is not a file", path.display()),
+            ));
+        }
+        Ok(metadata.len())
+    }
+
+    fn is_local(&self) -> bool {
+        true
+    }
+}
+
+/// The path of a file of a record, which borrows the dataset directory
+/// and the relative file name. Storages receive both parts by
+/// [Storage::read_data], so the full path is joined only by the
+/// storages needing it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DataPath<'a> {
+    root: &'a Path,
+    relative: &'a Path,
+}
+
+impl<'a> DataPath<'a> {
+    pub fn new(root: &'a Path, relative: &'a Path) -> Self {
+        Self { root, relative }
+    }
+
+    /// Get the dataset directory.
+    pub fn root(&self) -> &'a Path {
+        self.root
+    }
+
+    /// Get the file name relative to the dataset directory.
+    pub fn relative(&self) -> &'a Path {
+        self.relative
+    }
+
+    pub fn file_name(&self) -> Option<&'a OsStr> {
+        self.relative.file_name()
+    }
+
+    pub fn extension(&self) -> Option<&'a OsStr> {
+        self.relative.extension()
+    }
+
+    /// Join the dataset directory and the file name.
+    pub fn to_path_buf(&self) -> PathBuf {
+        self.root.join(self.relative)
+    }
+}
+
+impl From<DataPath<'_>> for PathBuf {
+    fn from(path: DataPath<'_>) -> Self {
+        path.to_path_buf()
+    }
+}
+
+impl Display for DataPath<'_> {
+    /// Display the path as [Path::join] would join it, without
+    /// allocating the joined path.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let root = self.root.as_os_str();
+        if self.relative.has_root() || root.is_empty() {
+            return Display::fmt(&self.relative.display(), f);
+        }
+
+        Display::fmt(&self.root.display(), f)?;
+        let has_separator = self
+            .root
+            .to_str()
+            .is_some_and(|root| root.ends_with(std::path::is_separator));
+        if !has_separator {
+            f.write_char(std::path::MAIN_SEPARATOR)?;
+        }
+        Display::fmt(&self.relative.display(), f)
+    }
+}
+
+/// Retry and timeout policies on file reads.
+#[derive(Debug, Clone)]
+pub struct ReadOptions {
+    /// The maximum number of retries after the first attempt.
+    pub max_retries: usize,
+    /// The delay before the first retry.
+    pub initial_backoff: Duration,
+    /// The upper bound of delays between retries.
+    pub max_backoff: Duration,
+    /// The factor multiplied to the delay after each retry.
+    pub backoff_multiplier: f64,
+    /// The time limit of each attempt. Reads run on the calling
+    /// thread if it is not set.
+    pub timeout: Option<Duration>,
+}
+
+impl ReadOptions {
+    /// Set the delays between retries, which start from `initial` and
+    /// grow by `multiplier` after each retry up to `max`. Multipliers
+    /// less than 1.0 or NaN are clamped to 1.0.
+    pub fn with_backoff(mut self, initial: Duration, max: Duration, multiplier: f64) -> Self {
+        self.initial_backoff = initial;
+        self.max_backoff = max;
+        self.backoff_multiplier = multiplier.max(1.0);
+        self
+    }
+
+    /// Get the delay before the n-th retry, starting from zero. Delays
+    /// too long to represent saturate to the maximum delay, and
+    /// multipliers less than 1.0 are taken as 1.0.
+    pub fn backoff(&self, retry: usize) -> Duration {
+        let factor = self
+            .backoff_multiplier
+            .max(1.0)
+            .powi(retry.min(i32::MAX as usize) as i32);
+        let secs = self.initial_backoff.as_secs_f64() * factor;
+        match Duration::try_from_secs_f64(secs) {
+            Ok(delay) => delay.min(self.max_backoff),
+            Err(_) => self.max_backoff,
+        }
+    }
+}
+
+impl Default for ReadOptions {
+    fn default() -> Self {
+        Self {
+            max_retries: 0,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(5),
+            backoff_multiplier: 2.0,
+            timeout: None,
+        }
+    }
+}
+
+/// Read a file with retries and timeouts.
+///
+/// Errors that are unlikely to go away, such as missing files, are
+/// returned without retries. A timed-out attempt is left running in
+/// the background and reported as [io::ErrorKind::TimedOut].
+pub fn read_with_options<P>(
+    storage: &Arc<dyn Storage>,
+    path: P,
+    options: &ReadOptions,
+) -> io::Result<Vec<u8>>
+where
+    P: AsRef<Path>,
+{
+    let path = path.as_ref();
+    retry_read(options, || match options.timeout {
+        Some(timeout) => {
+            let storage = storage.clone();
+            let owned_path = path.to_owned();
+            read_with_timeout(path.display(), timeout, move || storage.read(&owned_path))
+        }
+        None => storage.read(path),
+    })
+}
+
+/// Read the file of a record with retries and timeouts by
+/// [Storage::read_data]. See [read_with_options].
+pub fn read_data_with_options(
+    storage: &Arc<dyn Storage>,
+    path: DataPath<'_>,
+    options: &ReadOptions,
+) -> io::Result<Vec<u8>> {
+    retry_read(options, || match options.timeout {
+        Some(timeout) => {
+            let storage = storage.clone();
+            let root = path.root().to_owned();
+            let relative = path.relative().to_owned();
+            read_with_timeout(path, timeout, move || {
+                storage.read_data(DataPath::new(&root, &relative))
+            })
+        }
+        None => storage.read_data(path),
+    })
+}
+
+fn retry_read<F>(options: &ReadOptions, mut read: F) -> io::Result<Vec<u8>>
+where
+    F: FnMut() -> io::Result<Vec<u8>>,
+{
+    let mut retry = 0;
+
+    loop {
+        match read() {
+            Ok(bytes) => return Ok(bytes),
+            Err(err) if retry >= options.max_retries || !is_retryable(&err) => return Err(err),
+            Err(_) => {
+                thread::sleep(options.backoff(retry));
+                retry += 1;
+            }
+        }
+    }
+}
+
+/// Run the read on another thread and wait for it until the timeout.
+fn read_with_timeout<F>(path: impl Display, timeout: Duration, read: F) -> io::Result<Vec<u8>>
+where
+    F: FnOnce() -> io::Result<Vec<u8>> + Send + 'static,
+{
+    let (tx, rx) = mpsc::sync_channel(1);
+    thread::spawn(move || {
+        let _ = tx.send(read());
+    });
+
+    rx.recv_timeout(timeout).unwrap_or_else(|_| {
+        let msg = format!("timed out reading {path}");
+        Err(io::Error::new(io::ErrorKind::TimedOut, msg))
+    })
+}
+
+fn is_retryable(err: &io::Error) -> bool {
+    use io::ErrorKind as E;
+    !matches!(
+        err.kind(),
+        E::NotFound | E::PermissionDenied | E::InvalidInput | E::InvalidData | E::Unsupported
+    )
+}
+
+/// Configuration of injected faults.
+#[derive(Debug, Clone)]
+pub struct FaultConfig {
+    /// The seed to decide which reads fail.
+    pub seed: u64,
+    /// The probability that a read fails.
+    pub failure_rate: f64,
+    /// The number of first reads on each path that always fail.
+    pub fail_first: usize,
+    /// The kind of injected errors.
+    pub error_kind: io::ErrorKind,
+    /// The delay added to every read.
+    pub latency: Duration,
+    /// The maximum random delay added on top of `latency`.
+    pub latency_jitter: Duration,
+}
+
+impl Default for FaultConfig {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            failure_rate: 0.0,
+            fail_first: 0,
+            error_kind: io::ErrorKind::Other,
+            latency: Duration::ZERO,
+            latency_jitter: Duration::ZERO,
+        }
+    }
+}
+
+/// A storage decorator injecting IO failures and latencies.
+///
+/// Whether a read fails depends only on the seed, the path and the
+/// number of previous reads on the path, so the failures are
+/// reproducible regardless of the order of reads across threads.
+#[derive(Debug)]
+pub struct FaultInjectingStorage<S> {
+    inner: S,
+    config: FaultConfig,
+    state: Mutex<FaultState>,
+}
+
+#[derive(Debug, Default)]
+struct FaultState {
+    attempts: HashMap<PathBuf, usize>,
+    num_reads: usize,
+    num_failures: usize,
+}
+
+impl<S> FaultInjectingStorage<S>
+where
+    S: Storage,
+{
+    pub fn new(inner: S, config: FaultConfig) -> Self {
+        Self {
+            inner,
+            config,
+            state: Mutex::new(FaultState::default()),
+        }
+    }
+
+    pub fn inner(&self) -> &S {
+        &self.inner
+    }
+
+    pub fn config(&self) -> &FaultConfig {
+        &self.config
+    }
+
+    /// Get the total number of reads.
+    pub fn num_reads(&self) -> usize {
+        self.state.lock().unwrap().num_reads
+    }
+
+    /// Get the number of reads failed by injection.
+    pub fn num_failures(&self) -> usize {
+        self.state.lock().unwrap().num_failures
+    }
+
+    /// Forget past reads, so that faults repeat from the beginning.
+    pub fn reset(&self) {
+        *self.state.lock().unwrap() = FaultState::default();
+    }
+
+    /// Delay the read on the path and decide if it fails.
+    fn inject(&self, path: &Path) -> io::Result<()> {
+        let attempt = {
+            let mut state = self.state.lock().unwrap();
+            state.num_reads += 1;
+            let count = state.attempts.entry(path.to_owned()).or_default();
+            let attempt = *count;
+            *count += 1;
+            attempt
+        };
+
+        // Add latency
+        let FaultConfig {
+            latency,
+            latency_jitter,
+            ..
+        } = self.config;
+        let delay = latency + latency_jitter.mul_f64(self.random(path, attempt, 0));
+        if !delay.is_zero() {
+            thread::sleep(delay);
+        }
+
+        // Inject failures
+        let fail = attempt < self.config.fail_first
+            || self.random(path, attempt, 1) < self.config.failure_rate;
+        if fail {
+            self.state.lock().unwrap().num_failures += 1;
+            let msg = format!(
+                "injected failure on attempt {} reading {}",
+                attempt + 1,
+                path.display()
+            );
+            return Err(io::Error::new(self.config.error_kind, msg));
+        }
+
+        Ok(())
+    }
+
+    /// Get a pseudo-random number in `[0, 1)` for the n-th attempt on
+    /// the path.
+    fn random(&self, path: &Path, attempt: usize, salt: u64) -> f64 {
+        let path = path.to_string_lossy();
+        let mut state = splitmix64_bytes(self.config.seed, path.as_bytes());
+        state = splitmix64(state ^ attempt as u64);
+        state = splitmix64(state ^ salt);
+        (state >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+impl<S> Storage for FaultInjectingStorage<S>
+where
+    S: Storage,
+{
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        self.inject(path)?;
+        self.inner.read(path)
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        self.inner.exists(path)
+    }
+
+    fn size(&self, path: &Path) -> io::Result<u64> {
+        self.inner.size(path)
+    }
+
+    fn read_data(&self, path: DataPath<'_>) -> io::Result<Vec<u8>> {
+        self.inject(&path.to_path_buf())?;
+        self.inner.read_data(path)
+    }
+
+    fn data_exists(&self, path: DataPath<'_>) -> bool {
+        self.inner.data_exists(path)
+    }
+
+    fn data_size(&self, path: DataPath<'_>) -> io::Result<u64> {
+        self.inner.data_size(path)
+    }
+}
@@ -0,0 +1,88 @@
+//! Grouping of sweeps with their key frames.
+//!
+//! Sweeps preceding a key frame on the same channel are collected by
+//! following the `prev` chain until the previous key frame, and are
+//! numbered -1, -2, ... going back in time.
+
+use crate::{
+    dataset::{SampleDataRef, SampleRef},
+    serializable::Channel,
+};
+
+/// A non-key frame preceding a key frame.
+#[derive(Clone)]
+pub struct Sweep {
+    /// The relative index to the key frame, starting at -1.
+    pub index: isize,
+    /// The time in seconds from the sweep to the key frame.
+    pub time_lag: f64,
+    pub data: SampleDataRef,
+}
+
+/// A key frame with the sweeps since the previous key frame on the
+/// same channel.
+#[derive(Clone)]
+pub struct SweepGroup {
+    pub channel: Channel,
+    pub key_frame: SampleDataRef,
+    /// Sweeps ordered from the newest to the oldest.
+    pub sweeps: Vec<Sweep>,
+}
+
+impl SweepGroup {
+    /// Iterate over the key frame followed by the sweeps, which is
+    /// the order of relative indices 0, -1, -2, ...
+    pub fn frames(&self) -> impl Iterator<Item = (isize, &SampleDataRef)> + '_ {
+        std::iter::once((0, &self.key_frame))
+            .chain(self.sweeps.iter().map(|sweep| (sweep.index, &sweep.data)))
+    }
+}
+
+impl SampleRef {
+    /// Group the key frames of the sample with their preceding sweeps
+    /// by channel.
+    pub fn sweep_groups(&self) -> Vec<SweepGroup> {
+        self.keyframe_data_iter()
+            .map(|key_frame| {
+                let channel = key_frame.channel();
+                let sweeps = sweeps_before(&key_frame);
+                SweepGroup {
+                    channel,
+                    key_frame,
+                    sweeps,
+                }
+            })
+            .collect()
+    }
+
+    /// Get the sweep group of the channel.
+    pub fn sweep_group(&self, channel: Channel) -> Option<SweepGroup> {
+        let key_frame = self.sample_data_by_channel(channel)?;
+        let sweeps = sweeps_before(&key_frame);
+        Some(SweepGroup {
+            channel,
+            key_frame,
+            sweeps,
+        })
+    }
+}
+
+fn sweeps_before(key_frame: &SampleDataRef) -> Vec<Sweep> {
+    let mut sweeps = vec![];
+    let mut curr = key_frame.prev();
+
+    while let Some(data) = curr {
+        if data.is_key_frame {
+            break;
+        }
+        let lag = key_frame.timestamp - data.timestamp;
+        sweeps.push(Sweep {
+            index: -(sweeps.len() as isize + 1),
+            time_lag: lag.num_microseconds().unwrap() as f64 / 1_000_000.0,
+            data: data.clone(),
+        });
+        curr = data.prev();
+    }
+
+    sweeps
+}
@@ -0,0 +1,104 @@
+//! Metadata tables of the dataset.
+
+use crate::serializable::{
+    Attribute, CalibratedSensor, Category, EgoPose, Instance, Log, Map, Sample, SampleAnnotation,
+    SampleData, Scene, Sensor,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    any::Any,
+    fmt::{self, Display},
+};
+
+/// A metadata table, stored as a `.json` file in the version
+/// directory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Table {
+    Attribute,
+    CalibratedSensor,
+    Category,
+    EgoPose,
+    Instance,
+    Log,
+    Map,
+    Sample,
+    SampleAnnotation,
+    SampleData,
+    Scene,
+    Sensor,
+    Visibility,
+}
+
+impl Table {
+    pub const ALL: [Table; 13] = [
+        Table::Attribute,
+        Table::CalibratedSensor,
+        Table::Category,
+        Table::EgoPose,
+        Table::Instance,
+        Table::Log,
+        Table::Map,
+        Table::Sample,
+        Table::SampleAnnotation,
+        Table::SampleData,
+        Table::Scene,
+        Table::Sensor,
+        Table::Visibility,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Table::Attribute => "attribute",
+            Table::CalibratedSensor => "calibrated_sensor",
+            Table::Category => "category",
+            Table::EgoPose => "ego_pose",
+            Table::Instance => "instance",
+            Table::Log => "log",
+            Table::Map => "map",
+            Table::Sample => "sample",
+            Table::SampleAnnotation => "sample_annotation",
+            Table::SampleData => "sample_data",
+            Table::Scene => "scene",
+            Table::Sensor => "sensor",
+            Table::Visibility => "visibility",
+        }
+    }
+
+    /// Get the file name of the table, such as "sample.json".
+    pub fn file_name(&self) -> String {
+        format!("{}.json", self.as_str())
+    }
+}
+
+impl Display for Table {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A record type stored in a table with [Token](crate::Token) keys.
+pub trait TableRecord: Any + Send {
+    const TABLE: Table;
+}
+
+macro_rules! impl_table_record {
+    ($name:ident, $table:ident) => {
+        impl TableRecord for $name {
+            const TABLE: Table = Table::$table;
+        }
+    };
+}
+
+impl_table_record!(Attribute, Attribute);
+impl_table_record!(CalibratedSensor, CalibratedSensor);
+impl_table_record!(Category, Category);
+impl_table_record!(EgoPose, EgoPose);
+impl_table_record!(Instance, Instance);
+impl_table_record!(Log, Log);
+impl_table_record!(Map, Map);
+impl_table_record!(Sample, Sample);
+impl_table_record!(SampleAnnotation, SampleAnnotation);
+impl_table_record!(SampleData, SampleData);
+impl_table_record!(Scene, Scene);
+impl_table_record!(Sensor, Sensor);
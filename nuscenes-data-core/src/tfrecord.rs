@@ -0,0 +1,370 @@
+//! Export of samples into TFRecord files.
+//!
+//! Each sample is written as a `tf.train.Example` whose features are
+//! selected by a [TfRecordSpec]. Features whose sources are missing
+//! in a sample, such as a camera channel not in the sample, are
+//! omitted from the example.
+//!
+//! [Dataset::export_tfrecord_resumable] exports whole scenes and
+//! records its progress in a checkpoint next to the output file after
+//! each scene, so an interrupted export continues from the last
+//! completed scene.
+//!
+//! [Dataset::export_tfrecord_cached] exports all samples into the
+//! [TFRECORD_ARTIFACT] entry of a [DerivedCache], keyed by the spec,
+//! so that the file is written once per dataset and spec.
+
+use crate::{
+    cache::{ArtifactKey, DerivedCache},
+    dataset::{Dataset, SampleRef, SceneRef},
+    error::Result,
+    estimate::ExportEstimate,
+    features::FeatureLayout,
+    job::{self, ResumableJob},
+    serializable::Channel,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+/// The cache entry of TFRecord files written by
+/// [Dataset::export_tfrecord_cached].
+pub const TFRECORD_ARTIFACT: ArtifactKey = ArtifactKey::new("tfrecord", 1);
+
+/// The name of the TFRecord file in the cache entry.
+pub const TFRECORD_FILE_NAME: &str = "examples.tfrecord";
+
+/// The source of an example feature.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FeatureSource {
+    /// The sample token as bytes.
+    SampleToken,
+    /// The scene name as bytes.
+    SceneName,
+    /// The sample timestamp in microseconds as int64.
+    Timestamp,
+    /// The encoded JPEG file of the camera as bytes.
+    Image(Channel),
+    /// The lidar points flattened into floats.
+    LidarPoints(Channel, FeatureLayout),
+    /// The annotation boxes in the global frame flattened into floats.
+    /// Each box consists of the translation, the size and the rotation
+    /// quaternion, 10 floats in total.
+    Boxes,
+    /// The category names of the annotations as bytes, in the order
+    /// of [FeatureSource::Boxes].
+    BoxCategories,
+}
+
+/// A named feature of examples.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FeatureSpec {
+    pub key: String,
+    pub source: FeatureSource,
+}
+
+/// The features of each example.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TfRecordSpec {
+    pub features: Vec<FeatureSpec>,
+}
+
+impl TfRecordSpec {
+    pub fn new() -> Self {
+        Self { features: vec![] }
+    }
+
+    /// Add a feature.
+    pub fn feature(mut self, key: impl Into<String>, source: FeatureSource) -> Self {
+        self.features.push(FeatureSpec {
+            key: key.into(),
+            source,
+        });
+        self
+    }
+}
+
+impl Default for TfRecordSpec {
+    /// The sample token, the timestamp, the front camera image, the
+    /// top lidar points in (x, y, z, intensity) and the boxes.
+    fn default() -> Self {
+        Self::new()
+            .feature("sample/token", FeatureSource::SampleToken)
+            .feature("sample/timestamp", FeatureSource::Timestamp)
+            .feature("image/cam_front", FeatureSource::Image(Channel::CamFront))
+            .feature(
+                "lidar/lidar_top",
+                FeatureSource::LidarPoints(Channel::LidarTop, FeatureLayout::xyzi()),
+            )
+            .feature("boxes/boxes", FeatureSource::Boxes)
+            .feature("boxes/categories", FeatureSource::BoxCategories)
+    }
+}
+
+/// The value of a feature, which is one of the list types of
+/// `tf.train.Feature`.
+enum FeatureValue {
+    Bytes(Vec<Vec<u8>>),
+    Floats(Vec<f32>),
+    Int64s(Vec<i64>),
+}
+
+impl Dataset {
+    /// Write the samples into a TFRecord file. It returns the number
+    /// of written examples.
+    pub fn export_tfrecord<I, P>(&self, samples: I, path: P, spec: &TfRecordSpec) -> Result<usize>
+    where
+        I: IntoIterator<Item = SampleRef>,
+        P: AsRef<Path>,
+    {
+        let mut writer = BufWriter::new(File::create(path)?);
+        let mut num_examples = 0;
+
+        for sample in samples {
+            write_record(&mut writer, &encode_sample(&sample, spec)?)?;
+            num_examples += 1;
+        }
+
+        writer.flush()?;
+        Ok(num_examples)
+    }
+
+    /// Write the samples of the scenes into a TFRecord file like
+    /// [Dataset::export_tfrecord], recording the progress after each
+    /// scene in `<path>.checkpoint.json`. If a previous export into the
+    /// file was interrupted, it continues after the last completed
+    /// scene. The scenes and the spec must be the same as the
+    /// interrupted export.
+    pub fn export_tfrecord_resumable<I, P>(
+        &self,
+        scenes: I,
+        path: P,
+        spec: &TfRecordSpec,
+    ) -> Result<usize>
+    where
+        I: IntoIterator<Item = SceneRef>,
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let mut job: ResumableJob<TfRecordProgress> =
+            ResumableJob::open(job::checkpoint_path_of(path), "tfrecord")?;
+        let file = if job.is_resumed() {
+            job::reopen_truncated(path, job.state().file_len)?
+        } else {
+            File::create(path)?
+        };
+        let mut writer = BufWriter::new(file);
+
+        for scene in scenes {
+            if job.is_completed(scene.token) {
+                continue;
+            }
+            let mut num_examples = job.state().num_examples;
+            for sample in scene.sample_iter() {
+                write_record(&mut writer, &encode_sample(&sample, spec)?)?;
+                num_examples += 1;
+            }
+            let progress = TfRecordProgress {
+                num_examples,
+                file_len: job::flushed_len(&mut writer)?,
+            };
+            job.commit(scene.token, progress)?;
+        }
+
+        writer.flush()?;
+        Ok(job.finish()?.num_examples)
+    }
+
+    /// Write the samples of all scenes into [TFRECORD_FILE_NAME] in the
+    /// cache, unless it is cached for the spec already. Scenes are
+    /// written in the order of their tokens. It returns the path of
+    /// the file.
+    pub fn export_tfrecord_cached(
+        &self,
+        cache: &DerivedCache,
+        spec: &TfRecordSpec,
+    ) -> Result<PathBuf> {
+        let key = TFRECORD_ARTIFACT.with_params(spec);
+        let dir = cache.get_or_build(key, |dir| {
+            let mut scenes: Vec<_> = self.scene_iter().collect();
+            scenes.sort_by_key(|scene| scene.token);
+            let samples = scenes.iter().flat_map(|scene| scene.sample_iter());
+            self.export_tfrecord(samples, dir.join(TFRECORD_FILE_NAME), spec)?;
+            Ok(())
+        })?;
+        Ok(dir.join(TFRECORD_FILE_NAME))
+    }
+}
+
+impl Dataset {
+    /// Compute the size of the TFRecord file [Dataset::export_tfrecord]
+    /// would write, without writing it. The examples are encoded in
+    /// memory, so the sample files are still read.
+    pub fn estimate_tfrecord<I>(&self, samples: I, spec: &TfRecordSpec) -> Result<ExportEstimate>
+    where
+        I: IntoIterator<Item = SampleRef>,
+    {
+        let mut estimate = ExportEstimate {
+            num_files: 1,
+            ..Default::default()
+        };
+        for sample in samples {
+            estimate.num_bytes += record_size(encode_sample(&sample, spec)?.len());
+            estimate.num_samples += 1;
+        }
+        Ok(estimate)
+    }
+}
+
+/// The progress of an export recorded in checkpoints.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct TfRecordProgress {
+    num_examples: usize,
+    file_len: u64,
+}
+
+fn encode_sample(sample: &SampleRef, spec: &TfRecordSpec) -> Result<Vec<u8>> {
+    let mut features = vec![];
+    for spec in &spec.features {
+        if let Some(value) = feature_value(sample, &spec.source)? {
+            features.push((spec.key.as_str(), value));
+        }
+    }
+    Ok(encode_example(&features))
+}
+
+fn feature_value(sample: &SampleRef, source: &FeatureSource) -> Result<Option<FeatureValue>> {
+    let value = match source {
+        FeatureSource::SampleToken => FeatureValue::Bytes(vec![sample.token.to_string().into()]),
+        FeatureSource::SceneName => FeatureValue::Bytes(vec![sample.scene().name.clone().into()]),
+        FeatureSource::Timestamp => {
+            FeatureValue::Int64s(vec![sample.timestamp.and_utc().timestamp_micros()])
+        }
+        FeatureSource::Image(channel) => {
+            let Some(data) = sample.sample_data_by_channel(*channel) else {
+                return Ok(None);
+            };
+            FeatureValue::Bytes(vec![data.read_bytes()?])
+        }
+        FeatureSource::LidarPoints(channel, layout) => {
+            let Some(data) = sample.sample_data_by_channel(*channel) else {
+                return Ok(None);
+            };
+            FeatureValue::Floats(data.load_lidar_features(layout)?.values)
+        }
+        FeatureSource::Boxes => {
+            let values = sample
+                .annotation_iter()
+                .flat_map(|annotation| {
+                    let mut values = vec![];
+                    values.extend(annotation.translation);
+                    values.extend(annotation.size);
+                    values.extend(annotation.rotation);
+                    values
+                })
+                .map(|value| value as f32)
+                .collect();
+            FeatureValue::Floats(values)
+        }
+        FeatureSource::BoxCategories => {
+            let values = sample
+                .annotation_iter()
+                .map(|annotation| annotation.instance().category().name.clone().into())
+                .collect();
+            FeatureValue::Bytes(values)
+        }
+    };
+    Ok(Some(value))
+}
+
+/// Write a record framed by its length and masked CRC32C checksums.
+fn write_record<W>(writer: &mut W, data: &[u8]) -> Result<()>
+where
+    W: Write,
+{
+    let len = (data.len() as u64).to_le_bytes();
+    writer.write_all(&len)?;
+    writer.write_all(&masked_crc(&len).to_le_bytes())?;
+    writer.write_all(data)?;
+    writer.write_all(&masked_crc(data).to_le_bytes())?;
+    Ok(())
+}
+
+/// Get the size of a record written by [write_record], which frames
+/// the data with the length, the length checksum and the data checksum.
+fn record_size(len: usize) -> u64 {
+    len as u64 + 16
+}
+
+fn masked_crc(data: &[u8]) -> u32 {
+    let crc = crc32c::crc32c(data);
+    crc.rotate_right(15).wrapping_add(0xa282ead8)
+}
+
+/// Encode a `tf.train.Example` protobuf message.
+fn encode_example(features: &[(&str, FeatureValue)]) -> Vec<u8> {
+    // Features { map<string, Feature> feature = 1; }
+    let mut features_msg = vec![];
+    for (key, value) in features {
+        let mut entry = vec![];
+        encode_bytes_field(&mut entry, 1, key.as_bytes());
+        encode_bytes_field(&mut entry, 2, &encode_feature(value));
+        encode_bytes_field(&mut features_msg, 1, &entry);
+    }
+
+    // Example { Features features = 1; }
+    let mut example = vec![];
+    encode_bytes_field(&mut example, 1, &features_msg);
+    example
+}
+
+/// Encode a `tf.train.Feature` protobuf message.
+fn encode_feature(value: &FeatureValue) -> Vec<u8> {
+    let mut list = vec![];
+    let field = match value {
+        FeatureValue::Bytes(values) => {
+            for value in values {
+                encode_bytes_field(&mut list, 1, value);
+            }
+            1
+        }
+        FeatureValue::Floats(values) => {
+            let packed: Vec<u8> = values
+                .iter()
+                .flat_map(|value| value.to_le_bytes())
+                .collect();
+            encode_bytes_field(&mut list, 1, &packed);
+            2
+        }
+        FeatureValue::Int64s(values) => {
+            let mut packed = vec![];
+            for &value in values {
+                encode_varint(&mut packed, value as u64);
+            }
+            encode_bytes_field(&mut list, 1, &packed);
+            3
+        }
+    };
+
+    let mut feature = vec![];
+    encode_bytes_field(&mut feature, field, &list);
+    feature
+}
+
+/// Encode a length-delimited field.
+fn encode_bytes_field(buf: &mut Vec<u8>, field: u64, bytes: &[u8]) {
+    encode_varint(buf, (field << 3) | 2);
+    encode_varint(buf, bytes.len() as u64);
+    buf.extend_from_slice(bytes);
+}
+
+fn encode_varint(buf: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buf.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    buf.push(value as u8);
+}
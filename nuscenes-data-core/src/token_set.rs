@@ -0,0 +1,216 @@
+//! Named sets of tokens, such as custom splits.
+//!
+//! A set is stored as a JSON object with the name, the table and the
+//! tokens of the set:
+//!
+//! ```json
+//! {"name": "hard_samples", "table": "sample", "tokens": ["...", "..."]}
+//! ```
+//!
+//! Multiple sets are stored as NDJSON files with one object per line.
+
+use crate::{
+    dataset::Dataset,
+    error::{Error, Result},
+    selection::SampleSet,
+    serializable::Token,
+    table::Table,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeSet,
+    fs::File,
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    path::Path,
+};
+
+/// The maximum number of missing tokens listed in validation errors.
+const MAX_REPORTED_TOKENS: usize = 5;
+
+/// A named set of tokens of a table.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenSet {
+    pub name: String,
+    pub table: Table,
+    pub tokens: BTreeSet<Token>,
+}
+
+impl TokenSet {
+    pub fn new<I>(name: impl Into<String>, table: Table, tokens: I) -> Self
+    where
+        I: IntoIterator<Item = Token>,
+    {
+        Self {
+            name: name.into(),
+            table,
+            tokens: tokens.into_iter().collect(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.tokens.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tokens.is_empty()
+    }
+
+    pub fn contains(&self, token: Token) -> bool {
+        self.tokens.contains(&token)
+    }
+
+    /// Save the set as a JSON object.
+    pub fn save<P>(&self, path: P) -> Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        let writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(writer, self).map_err(io::Error::from)?;
+        Ok(())
+    }
+
+    /// Load a set saved by [TokenSet::save] without validating the
+    /// tokens.
+    pub fn load<P>(path: P) -> Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let reader = BufReader::new(File::open(path)?);
+        serde_json::from_reader(reader)
+            .map_err(|err| Error::ParseError(format!("failed to load {}: {err}", path.display())))
+    }
+
+    /// Save multiple sets as an NDJSON file.
+    pub fn save_many<P>(sets: &[TokenSet], path: P) -> Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        let mut writer = BufWriter::new(File::create(path)?);
+        for set in sets {
+            serde_json::to_writer(&mut writer, set).map_err(io::Error::from)?;
+            writer.write_all(b"\n")?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Load the sets of an NDJSON file without validating the tokens.
+    /// Blank lines are skipped.
+    pub fn load_many<P>(path: P) -> Result<Vec<Self>>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let reader = BufReader::new(File::open(path)?);
+        let mut sets = vec![];
+        for (index, line) in reader.lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let set = serde_json::from_str(&line).map_err(|err| {
+                Error::ParseError(format!(
+                    "failed to load {} at line {}: {err}",
+                    path.display(),
+                    index + 1
+                ))
+            })?;
+            sets.push(set);
+        }
+        Ok(sets)
+    }
+}
+
+impl SampleSet {
+    /// Name the set as a token set of the sample table.
+    pub fn to_token_set(&self, name: impl Into<String>) -> TokenSet {
+        TokenSet::new(name, Table::Sample, self.tokens())
+    }
+}
+
+impl Dataset {
+    /// Check that all tokens of the set exist in its table.
+    pub fn validate_token_set(&self, set: &TokenSet) -> Result<()> {
+        let missing: Vec<Token> = match set.table {
+            Table::Visibility => {
+                return Err(Error::CorruptedDataset(format!(
+                    "the token set {} refers to the visibility table, which has no tokens",
+                    set.name
+                )));
+            }
+            table => set
+                .tokens
+                .iter()
+                .copied()
+                .filter(|&token| !self.contains_token(table, token))
+                .collect(),
+        };
+
+        if missing.is_empty() {
+            return Ok(());
+        }
+        let examples: Vec<String> = missing
+            .iter()
+            .take(MAX_REPORTED_TOKENS)
+            .map(Token::to_string)
+            .collect();
+        Err(Error::CorruptedDataset(format!(
+            "{} tokens of the token set {} do not exist in the {} table, such as {}",
+            missing.len(),
+            set.name,
+            set.table,
+            examples.join(", ")
+        )))
+    }
+
+    /// Load a token set and check that its tokens exist.
+    pub fn load_token_set<P>(&self, path: P) -> Result<TokenSet>
+    where
+        P: AsRef<Path>,
+    {
+        let set = TokenSet::load(path)?;
+        self.validate_token_set(&set)?;
+        Ok(set)
+    }
+
+    /// Load the token sets of an NDJSON file and check that their
+    /// tokens exist.
+    pub fn load_token_sets<P>(&self, path: P) -> Result<Vec<TokenSet>>
+    where
+        P: AsRef<Path>,
+    {
+        let sets = TokenSet::load_many(path)?;
+        for set in &sets {
+            self.validate_token_set(set)?;
+        }
+        Ok(sets)
+    }
+
+    /// Check the tokens of the set and save it.
+    pub fn save_token_set<P>(&self, set: &TokenSet, path: P) -> Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        self.validate_token_set(set)?;
+        set.save(path)
+    }
+
+    fn contains_token(&self, table: Table, token: Token) -> bool {
+        match table {
+            Table::Attribute => self.attribute_map.contains_key(&token),
+            Table::CalibratedSensor => self.calibrated_sensor_map.contains_key(&token),
+            Table::Category => self.category_map.contains_key(&token),
+            Table::EgoPose => self.ego_pose_map.contains_key(&token),
+            Table::Instance => self.instance_map.contains_key(&token),
+            Table::Log => self.log_map.contains_key(&token),
+            Table::Map => self.map_map.contains_key(&token),
+            Table::Sample => self.sample_map.contains_key(&token),
+            Table::SampleAnnotation => self.sample_annotation_map.contains_key(&token),
+            Table::SampleData => self.sample_data_map.contains_key(&token),
+            Table::Scene => self.scene_map.contains_key(&token),
+            Table::Sensor => self.sensor_map.contains_key(&token),
+            Table::Visibility => false,
+        }
+    }
+}
@@ -0,0 +1,198 @@
+//! Per-instance track statistics.
+//!
+//! An annotation is hidden if its visibility is below the minimum
+//! level. Samples skipped by the annotation chain are also treated as
+//! hidden. A track is split into fragments by hidden annotations.
+
+use crate::{
+    dataset::{Dataset, InstanceRef},
+    serializable::{Token, VisibilityLevel},
+};
+use chrono::{Duration, NaiveDateTime};
+use itertools::Itertools;
+use std::fmt::Write;
+
+/// The statistics of an instance track.
+#[derive(Debug, Clone)]
+pub struct TrackStats {
+    pub instance_token: Token,
+    pub scene_token: Token,
+    pub category: String,
+    pub num_annotations: usize,
+    pub num_hidden_annotations: usize,
+    /// The number of samples between the first and last annotations
+    /// where the instance is not annotated.
+    pub num_skipped_samples: usize,
+    /// The number of runs of visible annotations.
+    pub num_fragments: usize,
+    pub duration: Duration,
+    /// The travel distance of the box center in meters.
+    pub distance: f64,
+    /// The longest time between two visible annotations with hidden
+    /// annotations or skipped samples in between.
+    pub max_occlusion_duration: Duration,
+}
+
+impl TrackStats {
+    pub fn is_fragmented(&self) -> bool {
+        self.num_fragments > 1
+    }
+}
+
+/// The track statistics of all instances.
+#[derive(Debug, Clone)]
+pub struct TrackStatsTable {
+    /// Tracks sorted by scene tokens and instance tokens.
+    pub tracks: Vec<TrackStats>,
+}
+
+impl TrackStatsTable {
+    pub fn fragmented(&self) -> impl Iterator<Item = &TrackStats> + '_ {
+        self.tracks.iter().filter(|track| track.is_fragmented())
+    }
+
+    /// Render the table as CSV with one row per track. Durations are
+    /// in seconds.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from(
+            "instance_token,scene_token,category,num_annotations,num_hidden_annotations,\
+             num_skipped_samples,num_fragments,duration,distance,max_occlusion_duration\n",
+        );
+
+        let seconds = |duration: Duration| duration.num_milliseconds() as f64 / 1000.0;
+        for track in &self.tracks {
+            writeln!(
+                csv,
+                "{},{},{},{},{},{},{},{},{},{}",
+                track.instance_token,
+                track.scene_token,
+                track.category,
+                track.num_annotations,
+                track.num_hidden_annotations,
+                track.num_skipped_samples,
+                track.num_fragments,
+                seconds(track.duration),
+                track.distance,
+                seconds(track.max_occlusion_duration),
+            )
+            .unwrap();
+        }
+        csv
+    }
+}
+
+impl InstanceRef {
+    /// Compute the track statistics. Annotations without visibility
+    /// levels are considered visible.
+    pub fn track_stats(&self, min_visibility: VisibilityLevel) -> TrackStats {
+        let annotations: Vec<_> = self.annotation_iter().collect();
+        let samples: Vec<_> = annotations
+            .iter()
+            .map(|annotation| annotation.sample())
+            .collect();
+
+        struct Frame {
+            timestamp: NaiveDateTime,
+            visible: bool,
+            num_skipped: usize,
+        }
+
+        let mut frames = vec![];
+        for (index, (annotation, sample)) in annotations.iter().zip(&samples).enumerate() {
+            let visible = match annotation.visibility() {
+                Some(visibility) => visibility.level >= min_visibility,
+                None => true,
+            };
+
+            // Count samples skipped since the previous annotation
+            let num_skipped = match index.checked_sub(1) {
+                Some(prev) => {
+                    let mut count = 0;
+                    let mut next = samples[prev].next;
+                    while let Some(token) = next {
+                        if token == sample.token {
+                            break;
+                        }
+                        count += 1;
+                        next = self.dataset().sample(token).and_then(|sample| sample.next);
+                    }
+                    count
+                }
+                None => 0,
+            };
+
+            frames.push(Frame {
+                timestamp: sample.timestamp,
+                visible,
+                num_skipped,
+            });
+        }
+
+        let mut num_fragments = 0;
+        let mut max_occlusion_duration = Duration::zero();
+        let mut last_visible: Option<NaiveDateTime> = None;
+        let mut prev_visible = false;
+
+        if let (Some(first), Some(last)) = (frames.first(), frames.last()) {
+            for frame in &frames {
+                if !frame.visible {
+                    prev_visible = false;
+                    continue;
+                }
+                if !prev_visible || frame.num_skipped > 0 {
+                    num_fragments += 1;
+                    let gap = frame.timestamp - last_visible.unwrap_or(first.timestamp);
+                    max_occlusion_duration = max_occlusion_duration.max(gap);
+                }
+                prev_visible = true;
+                last_visible = Some(frame.timestamp);
+            }
+
+            // Count hidden annotations at the end of the track
+            if !prev_visible {
+                let gap = last.timestamp - last_visible.unwrap_or(first.timestamp);
+                max_occlusion_duration = max_occlusion_duration.max(gap);
+            }
+        }
+
+        let duration = match (samples.first(), samples.last()) {
+            (Some(first), Some(last)) => last.timestamp - first.timestamp,
+            _ => Duration::zero(),
+        };
+        let distance = annotations
+            .iter()
+            .tuple_windows()
+            .map(|(from, to)| {
+                let [x1, y1, z1] = from.translation;
+                let [x2, y2, z2] = to.translation;
+                ((x2 - x1).powi(2) + (y2 - y1).powi(2) + (z2 - z1).powi(2)).sqrt()
+            })
+            .sum();
+
+        TrackStats {
+            instance_token: self.token,
+            // Instances always have at least one annotation
+            scene_token: samples[0].scene_token,
+            category: self.category().name.clone(),
+            num_annotations: frames.len(),
+            num_hidden_annotations: frames.iter().filter(|frame| !frame.visible).count(),
+            num_skipped_samples: frames.iter().map(|frame| frame.num_skipped).sum(),
+            num_fragments,
+            duration,
+            distance,
+            max_occlusion_duration,
+        }
+    }
+}
+
+impl Dataset {
+    /// Compute the track statistics of all instances.
+    pub fn track_stats(&self, min_visibility: VisibilityLevel) -> TrackStatsTable {
+        let tracks = self
+            .instance_iter()
+            .map(|instance| instance.track_stats(min_visibility))
+            .sorted_by_key(|track| (track.scene_token, track.instance_token))
+            .collect();
+        TrackStatsTable { tracks }
+    }
+}
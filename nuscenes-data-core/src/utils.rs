@@ -0,0 +1,48 @@
+use crate::serializable::Token;
+use rayon::prelude::{FromParallelIterator, ParallelIterator};
+
+pub(crate) trait WithToken {
+    fn token(&self) -> Token;
+}
+
+pub trait ParallelIteratorExt {
+    fn par_try_collect<C, T, E>(self) -> Result<C, E>
+    where
+        Self: ParallelIterator<Item = Result<T, E>>,
+        C: FromParallelIterator<T>,
+        T: Send,
+        E: Send;
+}
+
+impl<I> ParallelIteratorExt for I {
+    fn par_try_collect<C, T, E>(self) -> Result<C, E>
+    where
+        Self: ParallelIterator<Item = Result<T, E>>,
+        C: FromParallelIterator<T>,
+        T: Send,
+        E: Send,
+    {
+        let collection: Result<C, E> = self.collect();
+        collection
+    }
+}
+
+pub(crate) fn splitmix64(value: u64) -> u64 {
+    let mut z = value.wrapping_add(0x9E3779B97F4A7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+    z ^ (z >> 31)
+}
+
+/// Hash the bytes with [splitmix64] starting from the seed. Unlike
+/// `DefaultHasher`, the result is stable across Rust versions and
+/// platforms.
+pub(crate) fn splitmix64_bytes(seed: u64, bytes: &[u8]) -> u64 {
+    let mut state = splitmix64(seed ^ bytes.len() as u64);
+    for chunk in bytes.chunks(8) {
+        let mut word = [0; 8];
+        word[..chunk.len()].copy_from_slice(chunk);
+        state = splitmix64(state ^ u64::from_le_bytes(word));
+    }
+    state
+}
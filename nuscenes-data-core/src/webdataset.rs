@@ -0,0 +1,530 @@
+//! Export of samples into [WebDataset](https://github.com/webdataset/webdataset)
+//! tar shards.
+//!
+//! Each sample is stored as a group of files sharing the sample token
+//! as the key:
+//!
+//! - `<token>.json`: the labels and calibrations of the sample.
+//! - `<token>.<channel>.<ext>`: the key frame file of each channel,
+//!   such as `<token>.cam_front.jpg` and `<token>.lidar_top.bin`.
+//!
+//! A `manifest.json` listing the shards is written next to them.
+//!
+//! [Dataset::export_webdataset_resumable] exports whole scenes and
+//! records its progress in `checkpoint.json` after each scene, so an
+//! interrupted export continues from the last completed scene.
+//!
+//! [Dataset::export_webdataset_cached] exports all samples into the
+//! [WEBDATASET_ARTIFACT] entry of a [DerivedCache], keyed by the
+//! options, so that the shards are written once per dataset and
+//! options.
+
+use crate::{
+    cache::{ArtifactKey, DerivedCache},
+    dataset::{Dataset, SampleDataRef, SampleRef, SceneRef},
+    error::Result,
+    estimate::{CountingWriter, ExportEstimate},
+    geometry::Transform,
+    job::{self, ResumableJob},
+    serializable::{Channel, Token, VisibilityLevel},
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+/// The cache entry of shards written by
+/// [Dataset::export_webdataset_cached].
+pub const WEBDATASET_ARTIFACT: ArtifactKey = ArtifactKey::new("webdataset", 1);
+
+/// The directory of the shards in the cache entry, which keeps their
+/// `manifest.json` apart from that of the entry.
+pub const SHARDS_DIR_NAME: &str = "shards";
+
+/// The size of tar headers and the block size of tar entries.
+const TAR_BLOCK_SIZE: u64 = 512;
+
+/// The checkpoint file of resumable exports in the output directory.
+const CHECKPOINT_FILE_NAME: &str = "checkpoint.json";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WebDatasetOptions {
+    /// The prefix of shard file names, followed by the shard index.
+    pub shard_prefix: String,
+    /// Start a new shard before the shard exceeds this size in bytes.
+    /// A shard always holds at least one sample.
+    pub max_shard_bytes: u64,
+    /// The maximum number of samples per shard.
+    pub max_shard_samples: usize,
+    /// The channels to export. All channels are exported if it is
+    /// `None`.
+    pub channels: Option<Vec<Channel>>,
+}
+
+impl Default for WebDatasetOptions {
+    fn default() -> Self {
+        Self {
+            shard_prefix: "shard-".to_string(),
+            max_shard_bytes: 1 << 30,
+            max_shard_samples: 10_000,
+            channels: None,
+        }
+    }
+}
+
+/// The labels stored in `<token>.json`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SampleLabels {
+    pub sample_token: Token,
+    pub scene_token: Token,
+    /// The timestamp in microseconds.
+    pub timestamp: i64,
+    pub sensors: Vec<SensorLabel>,
+    pub annotations: Vec<AnnotationLabel>,
+}
+
+/// The calibration and ego pose of a key frame file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SensorLabel {
+    pub channel: Channel,
+    pub sample_data_token: Token,
+    /// The name of the file within the sample.
+    pub file_name: String,
+    pub sensor_to_ego: Transform,
+    pub ego_to_global: Transform,
+    pub camera_intrinsic: Option<[[f64; 3]; 3]>,
+}
+
+/// An annotation box in the global frame.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnnotationLabel {
+    pub token: Token,
+    pub instance_token: Token,
+    pub category: String,
+    pub attributes: Vec<String>,
+    pub visibility: Option<VisibilityLevel>,
+    pub translation: [f64; 3],
+    pub size: [f64; 3],
+    pub rotation: [f64; 4],
+    pub num_lidar_pts: isize,
+    pub num_radar_pts: isize,
+}
+
+/// The statistics of a written shard.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShardInfo {
+    pub file_name: String,
+    pub num_samples: usize,
+    pub num_bytes: u64,
+}
+
+/// The content of `manifest.json`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct WebDatasetManifest {
+    pub num_samples: usize,
+    pub shards: Vec<ShardInfo>,
+}
+
+impl Dataset {
+    /// Write the samples into tar shards in the output directory,
+    /// which is created if it does not exist.
+    pub fn export_webdataset<I, P>(
+        &self,
+        samples: I,
+        output_dir: P,
+        options: &WebDatasetOptions,
+    ) -> Result<WebDatasetManifest>
+    where
+        I: IntoIterator<Item = SampleRef>,
+        P: AsRef<Path>,
+    {
+        let output_dir = output_dir.as_ref();
+        fs::create_dir_all(output_dir)?;
+
+        let mut sink = ShardSink::resume(output_dir, options, ShardProgress::default())?;
+        for sample in samples {
+            sink.append(&sample)?;
+        }
+        sink.finish()
+    }
+
+    /// Write the samples of the scenes into tar shards like
+    /// [Dataset::export_webdataset], recording the progress after each
+    /// scene. If a previous export into the output directory was
+    /// interrupted, it continues after the last completed scene. The
+    /// scenes and options must be the same as the interrupted export.
+    pub fn export_webdataset_resumable<I, P>(
+        &self,
+        scenes: I,
+        output_dir: P,
+        options: &WebDatasetOptions,
+    ) -> Result<WebDatasetManifest>
+    where
+        I: IntoIterator<Item = SceneRef>,
+        P: AsRef<Path>,
+    {
+        let output_dir = output_dir.as_ref();
+        fs::create_dir_all(output_dir)?;
+
+        let mut job: ResumableJob<ShardProgress> =
+            ResumableJob::open(output_dir.join(CHECKPOINT_FILE_NAME), "webdataset")?;
+        let mut sink = ShardSink::resume(output_dir, options, job.state().clone())?;
+        for scene in scenes {
+            if job.is_completed(scene.token) {
+                continue;
+            }
+            for sample in scene.sample_iter() {
+                sink.append(&sample)?;
+            }
+            job.commit(scene.token, sink.progress()?)?;
+        }
+
+        let manifest = sink.finish()?;
+        job.finish()?;
+        Ok(manifest)
+    }
+
+    /// Write the samples of all scenes into shards under
+    /// [SHARDS_DIR_NAME] in the cache, unless they are cached for the
+    /// options already. Scenes are written in the order of their
+    /// tokens. It returns the directory of the shards.
+    pub fn export_webdataset_cached(
+        &self,
+        cache: &DerivedCache,
+        options: &WebDatasetOptions,
+    ) -> Result<PathBuf> {
+        let key = WEBDATASET_ARTIFACT.with_params(options);
+        let dir = cache.get_or_build(key, |dir| {
+            let mut scenes: Vec<_> = self.scene_iter().collect();
+            scenes.sort_by_key(|scene| scene.token);
+            let samples = scenes.iter().flat_map(|scene| scene.sample_iter());
+            self.export_webdataset(samples, dir.join(SHARDS_DIR_NAME), options)?;
+            Ok(())
+        })?;
+        Ok(dir.join(SHARDS_DIR_NAME))
+    }
+
+    /// Compute the shards and the manifest [Dataset::export_webdataset]
+    /// would write, without writing them. The sample files are not
+    /// read, but their sizes are queried from the storage.
+    pub fn estimate_webdataset<I>(
+        &self,
+        samples: I,
+        options: &WebDatasetOptions,
+    ) -> Result<ExportEstimate>
+    where
+        I: IntoIterator<Item = SampleRef>,
+    {
+        let mut manifest = WebDatasetManifest::default();
+        let mut shard: Option<ShardInfo> = None;
+
+        for sample in samples {
+            let SampleLayout { json, files } = sample_layout(&sample, options)?;
+            let mut num_bytes = tar_entry_size(json.1.len() as u64);
+            for (_, data) in &files {
+                num_bytes += tar_entry_size(data.file_size()?);
+            }
+
+            if shard
+                .as_ref()
+                .is_some_and(|shard| is_shard_full(shard, num_bytes, options))
+            {
+                let mut info = shard.take().unwrap();
+                info.num_bytes += 2 * TAR_BLOCK_SIZE;
+                manifest.shards.push(info);
+            }
+
+            let shard = shard.get_or_insert_with(|| ShardInfo {
+                file_name: shard_file_name(options, manifest.shards.len()),
+                num_samples: 0,
+                num_bytes: 0,
+            });
+            shard.num_samples += 1;
+            shard.num_bytes += num_bytes;
+            manifest.num_samples += 1;
+        }
+
+        if let Some(mut info) = shard {
+            info.num_bytes += 2 * TAR_BLOCK_SIZE;
+            manifest.shards.push(info);
+        }
+
+        let mut writer = CountingWriter::default();
+        serde_json::to_writer_pretty(&mut writer, &manifest).map_err(io::Error::from)?;
+        Ok(ExportEstimate {
+            num_samples: manifest.num_samples,
+            num_files: manifest.shards.len() + 1,
+            num_bytes: manifest
+                .shards
+                .iter()
+                .map(|shard| shard.num_bytes)
+                .sum::<u64>()
+                + writer.num_bytes,
+        })
+    }
+}
+
+/// The progress of an export recorded in checkpoints.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct ShardProgress {
+    manifest: WebDatasetManifest,
+    /// The unfinished shard and its file size.
+    open_shard: Option<(ShardInfo, u64)>,
+}
+
+/// Appends samples to shards, starting a new shard when one is full.
+struct ShardSink<'a> {
+    output_dir: &'a Path,
+    options: &'a WebDatasetOptions,
+    manifest: WebDatasetManifest,
+    shard: Option<ShardWriter>,
+}
+
+impl<'a> ShardSink<'a> {
+    /// Continue from the progress. The unfinished shard is truncated
+    /// to the recorded size.
+    fn resume(
+        output_dir: &'a Path,
+        options: &'a WebDatasetOptions,
+        progress: ShardProgress,
+    ) -> Result<Self> {
+        let ShardProgress {
+            manifest,
+            open_shard,
+        } = progress;
+        let shard = match open_shard {
+            Some((info, file_len)) => Some(ShardWriter::reopen(output_dir, info, file_len)?),
+            None => None,
+        };
+        Ok(Self {
+            output_dir,
+            options,
+            manifest,
+            shard,
+        })
+    }
+
+    fn append(&mut self, sample: &SampleRef) -> Result<()> {
+        let Self {
+            output_dir,
+            options,
+            manifest,
+            shard,
+        } = self;
+
+        let entries = sample_entries(sample, options)?;
+        let num_bytes: u64 = entries
+            .iter()
+            .map(|(_, bytes)| tar_entry_size(bytes.len() as u64))
+            .sum();
+
+        if shard
+            .as_ref()
+            .is_some_and(|shard| is_shard_full(&shard.info, num_bytes, options))
+        {
+            manifest.shards.push(shard.take().unwrap().finish()?);
+        }
+
+        let shard = match shard {
+            Some(shard) => shard,
+            None => {
+                let file_name = shard_file_name(options, manifest.shards.len());
+                shard.insert(ShardWriter::create(output_dir, file_name)?)
+            }
+        };
+        for (name, bytes) in &entries {
+            shard.append(name, bytes)?;
+        }
+        shard.info.num_samples += 1;
+        manifest.num_samples += 1;
+        Ok(())
+    }
+
+    /// Flush the unfinished shard and get the progress.
+    fn progress(&mut self) -> Result<ShardProgress> {
+        let open_shard = match &mut self.shard {
+            Some(shard) => Some((shard.info.clone(), shard.flushed_len()?)),
+            None => None,
+        };
+        Ok(ShardProgress {
+            manifest: self.manifest.clone(),
+            open_shard,
+        })
+    }
+
+    /// Finish the last shard and write the manifest.
+    fn finish(self) -> Result<WebDatasetManifest> {
+        let Self {
+            output_dir,
+            mut manifest,
+            shard,
+            ..
+        } = self;
+        if let Some(shard) = shard {
+            manifest.shards.push(shard.finish()?);
+        }
+
+        let writer = BufWriter::new(File::create(output_dir.join("manifest.json"))?);
+        serde_json::to_writer_pretty(writer, &manifest).map_err(io::Error::from)?;
+        Ok(manifest)
+    }
+}
+
+struct ShardWriter {
+    builder: tar::Builder<BufWriter<File>>,
+    info: ShardInfo,
+}
+
+impl ShardWriter {
+    fn create(dir: &Path, file_name: String) -> Result<Self> {
+        let writer = BufWriter::new(File::create(dir.join(&file_name))?);
+        Ok(Self {
+            builder: tar::Builder::new(writer),
+            info: ShardInfo {
+                file_name,
+                num_samples: 0,
+                num_bytes: 0,
+            },
+        })
+    }
+
+    /// Open an unfinished shard to append after the first `file_len`
+    /// bytes.
+    fn reopen(dir: &Path, info: ShardInfo, file_len: u64) -> Result<Self> {
+        let file = job::reopen_truncated(&dir.join(&info.file_name), file_len)?;
+        Ok(Self {
+            builder: tar::Builder::new(BufWriter::new(file)),
+            info,
+        })
+    }
+
+    fn flushed_len(&mut self) -> Result<u64> {
+        job::flushed_len(self.builder.get_mut())
+    }
+
+    fn append(&mut self, name: &str, bytes: &[u8]) -> Result<()> {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(bytes.len() as u64);
+        header.set_mode(0o644);
+        self.builder.append_data(&mut header, name, bytes)?;
+        self.info.num_bytes += tar_entry_size(bytes.len() as u64);
+        Ok(())
+    }
+
+    fn finish(self) -> Result<ShardInfo> {
+        let Self { builder, mut info } = self;
+        builder.into_inner()?.flush()?;
+        // The archive ends with two empty blocks.
+        info.num_bytes += 2 * TAR_BLOCK_SIZE;
+        Ok(info)
+    }
+}
+
+/// The entries of a sample, whose sample files are not read yet.
+struct SampleLayout {
+    /// The name and the content of the labels.
+    json: (String, Vec<u8>),
+    /// The names and the key frame data of the sample files.
+    files: Vec<(String, SampleDataRef)>,
+}
+
+/// Collect the file names and contents of a sample.
+fn sample_entries(
+    sample: &SampleRef,
+    options: &WebDatasetOptions,
+) -> Result<Vec<(String, Vec<u8>)>> {
+    let SampleLayout { json, files } = sample_layout(sample, options)?;
+    let mut entries = vec![json];
+    for (name, data) in files {
+        entries.push((name, data.read_bytes()?));
+    }
+    Ok(entries)
+}
+
+fn sample_layout(sample: &SampleRef, options: &WebDatasetOptions) -> Result<SampleLayout> {
+    let key = sample.token.to_string();
+    let mut files = vec![];
+    let mut sensors = vec![];
+
+    for data in sample.keyframe_data_iter() {
+        let channel = data.channel();
+        if let Some(channels) = &options.channels {
+            if !channels.contains(&channel) {
+                continue;
+            }
+        }
+
+        let file_name = format!(
+            "{}.{}",
+            channel.as_str().to_ascii_lowercase(),
+            file_extension(&data)
+        );
+        sensors.push(SensorLabel {
+            channel,
+            sample_data_token: data.token,
+            file_name: file_name.clone(),
+            sensor_to_ego: data.sensor_to_ego(),
+            ego_to_global: data.ego_to_global(),
+            camera_intrinsic: data.calibrated_sensor().camera_intrinsic,
+        });
+        files.push((format!("{key}.{file_name}"), data));
+    }
+
+    let annotations = sample
+        .annotation_iter()
+        .map(|annotation| AnnotationLabel {
+            token: annotation.token,
+            instance_token: annotation.instance_token,
+            category: annotation.instance().category().name.clone(),
+            attributes: annotation
+                .attribute_iter()
+                .map(|attribute| attribute.name.clone())
+                .collect(),
+            visibility: annotation.visibility().map(|visibility| visibility.level),
+            translation: annotation.translation,
+            size: annotation.size,
+            rotation: annotation.rotation,
+            num_lidar_pts: annotation.num_lidar_pts,
+            num_radar_pts: annotation.num_radar_pts,
+        })
+        .collect();
+
+    let labels = SampleLabels {
+        sample_token: sample.token,
+        scene_token: sample.scene_token,
+        timestamp: sample.timestamp.and_utc().timestamp_micros(),
+        sensors,
+        annotations,
+    };
+    let json = serde_json::to_vec(&labels).map_err(io::Error::from)?;
+
+    Ok(SampleLayout {
+        json: (format!("{key}.json"), json),
+        files,
+    })
+}
+
+fn is_shard_full(info: &ShardInfo, num_bytes: u64, options: &WebDatasetOptions) -> bool {
+    info.num_samples >= options.max_shard_samples
+        || info.num_bytes + num_bytes > options.max_shard_bytes
+}
+
+fn shard_file_name(options: &WebDatasetOptions, index: usize) -> String {
+    format!("{}{:06}.tar", options.shard_prefix, index)
+}
+
+/// Get the extension of the file, keeping only the last component of
+/// compound extensions such as `.pcd.bin`.
+fn file_extension(data: &SampleDataRef) -> String {
+    data.filename
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or("bin")
+        .to_ascii_lowercase()
+}
+
+fn tar_entry_size(len: u64) -> u64 {
+    TAR_BLOCK_SIZE + len.div_ceil(TAR_BLOCK_SIZE) * TAR_BLOCK_SIZE
+}
@@ -0,0 +1,394 @@
+//! Serialization of datasets into version directories.
+//!
+//! [DatasetWriter] writes the tables of a dataset, or of a subset of
+//! its scenes, as JSON files in the layout of the official releases,
+//! so the result can be loaded by this crate and by the Python
+//! devkit. Sample files are not copied.
+
+use crate::{
+    dataset::Dataset,
+    error::Result,
+    estimate::{CountingWriter, ExportEstimate},
+    serializable::{
+        Attribute, CalibratedSensor, Category, Channel, EgoPose, Instance, Log, Map, Panoptic,
+        Sample, SampleAnnotation, SampleData, Scene, Sensor, Token, Visibility,
+    },
+    table::Table,
+};
+use itertools::Itertools;
+use serde::Serialize;
+use std::{
+    collections::HashSet,
+    fs::{self, File},
+    io::{self, BufWriter},
+    path::Path,
+};
+
+/// The file name of the panoptic table, which is written only if the
+/// dataset has panoptic records.
+const PANOPTIC_FILE_NAME: &str = "panoptic.json";
+
+/// Writes datasets into version directories of JSON tables.
+///
+/// ```ignore
+/// use nuscenes_data::writer::DatasetWriter;
+///
+/// let scenes = dataset.scene_iter().take(10).map(|scene| scene.token);
+/// DatasetWriter::default()
+///     .scenes(scenes)
+///     .write(&dataset, "/path/to/your/dataset/v1.0-tiny")?;
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct DatasetWriter {
+    /// The scenes to write. All scenes are written if it is `None`.
+    pub scenes: Option<HashSet<Token>>,
+    /// The channels whose sample data are written. All channels are
+    /// written if it is `None`.
+    pub channels: Option<HashSet<Channel>>,
+}
+
+/// The records selected by a [DatasetWriter].
+pub(crate) struct SelectedTables {
+    pub attributes: Vec<Attribute>,
+    pub calibrated_sensors: Vec<CalibratedSensor>,
+    pub categories: Vec<Category>,
+    pub ego_poses: Vec<EgoPose>,
+    pub instances: Vec<Instance>,
+    pub logs: Vec<Log>,
+    pub maps: Vec<Map>,
+    pub samples: Vec<Sample>,
+    pub sample_annotations: Vec<SampleAnnotation>,
+    pub sample_data: Vec<SampleData>,
+    pub scenes: Vec<Scene>,
+    pub sensors: Vec<Sensor>,
+    pub visibility: Vec<Visibility>,
+    pub panoptic: Vec<Panoptic>,
+}
+
+impl DatasetWriter {
+    /// Write only the scenes with the tokens.
+    pub fn scenes<I>(mut self, tokens: I) -> Self
+    where
+        I: IntoIterator<Item = Token>,
+    {
+        self.scenes = Some(tokens.into_iter().collect());
+        self
+    }
+
+    /// Write only the sample data of the channels.
+    pub fn channels<I>(mut self, channels: I) -> Self
+    where
+        I: IntoIterator<Item = Channel>,
+    {
+        self.channels = Some(channels.into_iter().collect());
+        self
+    }
+
+    /// Write the tables into the version directory, which is created
+    /// if missing. Existing table files are overwritten.
+    ///
+    /// Records are kept if they are reachable from the selected
+    /// scenes and channels. Chains of sample data and annotations are
+    /// cut where they leave the selection, and the counts of scenes
+    /// and instances are recomputed. Categories, attributes and
+    /// visibility levels are kept entirely.
+    pub fn write<P>(&self, dataset: &Dataset, meta_dir: P) -> Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        let meta_dir = meta_dir.as_ref();
+        fs::create_dir_all(meta_dir)?;
+
+        let SelectedTables {
+            attributes,
+            calibrated_sensors,
+            categories,
+            ego_poses,
+            instances,
+            logs,
+            maps,
+            samples,
+            sample_annotations,
+            sample_data,
+            scenes,
+            sensors,
+            visibility,
+            panoptic,
+        } = self.select(dataset);
+
+        write_table(meta_dir, Table::Attribute, &attributes)?;
+        write_table(meta_dir, Table::CalibratedSensor, &calibrated_sensors)?;
+        write_table(meta_dir, Table::Category, &categories)?;
+        write_table(meta_dir, Table::EgoPose, &ego_poses)?;
+        write_table(meta_dir, Table::Instance, &instances)?;
+        write_table(meta_dir, Table::Log, &logs)?;
+        write_table(meta_dir, Table::Map, &maps)?;
+        write_table(meta_dir, Table::Sample, &samples)?;
+        write_table(meta_dir, Table::SampleAnnotation, &sample_annotations)?;
+        write_table(meta_dir, Table::SampleData, &sample_data)?;
+        write_table(meta_dir, Table::Scene, &scenes)?;
+        write_table(meta_dir, Table::Sensor, &sensors)?;
+        write_table(meta_dir, Table::Visibility, &visibility)?;
+        if !panoptic.is_empty() {
+            write_json(&meta_dir.join(PANOPTIC_FILE_NAME), &panoptic)?;
+        }
+
+        Ok(())
+    }
+
+    /// Compute the number and the total size of the table files
+    /// [DatasetWriter::write] would write, without writing them.
+    pub fn estimate(&self, dataset: &Dataset) -> Result<ExportEstimate> {
+        let SelectedTables {
+            attributes,
+            calibrated_sensors,
+            categories,
+            ego_poses,
+            instances,
+            logs,
+            maps,
+            samples,
+            sample_annotations,
+            sample_data,
+            scenes,
+            sensors,
+            visibility,
+            panoptic,
+        } = self.select(dataset);
+
+        let mut sizes = vec![
+            json_size(&attributes)?,
+            json_size(&calibrated_sensors)?,
+            json_size(&categories)?,
+            json_size(&ego_poses)?,
+            json_size(&instances)?,
+            json_size(&logs)?,
+            json_size(&maps)?,
+            json_size(&samples)?,
+            json_size(&sample_annotations)?,
+            json_size(&sample_data)?,
+            json_size(&scenes)?,
+            json_size(&sensors)?,
+            json_size(&visibility)?,
+        ];
+        if !panoptic.is_empty() {
+            sizes.push(json_size(&panoptic)?);
+        }
+
+        Ok(ExportEstimate {
+            num_samples: samples.len(),
+            num_files: sizes.len(),
+            num_bytes: sizes.iter().sum(),
+        })
+    }
+
+    /// Select the records reachable from the selected scenes and
+    /// channels. Records are sorted by token or time.
+    pub(crate) fn select(&self, dataset: &Dataset) -> SelectedTables {
+        let scenes: Vec<Scene> = dataset
+            .sorted_scene_tokens
+            .iter()
+            .filter(|token| match &self.scenes {
+                Some(selected) => selected.contains(token),
+                None => true,
+            })
+            .map(|token| dataset.scene_map[token].to_scene())
+            .collect();
+        let scene_tokens: HashSet<Token> = scenes.iter().map(|scene| scene.token).collect();
+
+        let samples: Vec<Sample> = dataset
+            .sorted_sample_tokens
+            .iter()
+            .map(|token| &dataset.sample_map[token])
+            .filter(|sample| scene_tokens.contains(&sample.scene_token))
+            .map(|sample| sample.to_sample())
+            .collect();
+        let sample_tokens: HashSet<Token> = samples.iter().map(|sample| sample.token).collect();
+
+        // Cut sample data chains leaving the selection
+        let sample_data: Vec<SampleData> = {
+            let kept = |token: &Token| {
+                dataset.sample_data_map.get(token).is_some_and(|data| {
+                    let (channel, _) =
+                        dataset.calibrated_sensor_channel_map[&data.calibrated_sensor_token];
+                    let channel_selected = match &self.channels {
+                        Some(selected) => selected.contains(&channel),
+                        None => true,
+                    };
+                    channel_selected && sample_tokens.contains(&data.sample_token)
+                })
+            };
+            dataset
+                .sorted_sample_data_tokens
+                .iter()
+                .filter(|token| kept(token))
+                .map(|token| {
+                    let mut data = dataset.sample_data_map[token].clone();
+                    data.prev = data.prev.filter(kept);
+                    data.next = data.next.filter(kept);
+                    data
+                })
+                .collect()
+        };
+
+        // Rechain annotations of each instance within the selection
+        let mut instances = vec![];
+        let mut sample_annotations = vec![];
+        for instance in dataset.instance_iter() {
+            let mut kept: Vec<SampleAnnotation> = instance
+                .annotation_iter()
+                .filter(|annotation| sample_tokens.contains(&annotation.sample_token))
+                .map(|annotation| (*annotation).clone())
+                .collect();
+            if kept.is_empty() {
+                continue;
+            }
+
+            let tokens: Vec<Token> = kept.iter().map(|annotation| annotation.token).collect();
+            for (index, annotation) in kept.iter_mut().enumerate() {
+                annotation.prev = index.checked_sub(1).map(|prev| tokens[prev]);
+                annotation.next = tokens.get(index + 1).copied();
+            }
+
+            instances.push(Instance {
+                nbr_annotations: kept.len(),
+                first_annotation_token: tokens[0],
+                last_annotation_token: *tokens.last().unwrap(),
+                ..instance.to_instance()
+            });
+            sample_annotations.extend(kept);
+        }
+        instances.sort_by_key(|instance| instance.token);
+        sample_annotations.sort_by_key(|annotation| annotation.token);
+
+        // Keep records referred to by the selection
+        let ego_pose_tokens: HashSet<Token> =
+            sample_data.iter().map(|data| data.ego_pose_token).collect();
+        let ego_poses: Vec<EgoPose> = dataset
+            .sorted_ego_pose_tokens
+            .iter()
+            .filter(|token| ego_pose_tokens.contains(token))
+            .map(|token| dataset.ego_pose_map[token].clone())
+            .collect();
+
+        let calibrated_sensor_tokens: HashSet<Token> = sample_data
+            .iter()
+            .map(|data| data.calibrated_sensor_token)
+            .collect();
+        let calibrated_sensors: Vec<CalibratedSensor> = dataset
+            .calibrated_sensor_iter()
+            .filter(|sensor| calibrated_sensor_tokens.contains(&sensor.token))
+            .map(|sensor| (*sensor).clone())
+            .sorted_by_key(|sensor| sensor.token)
+            .collect();
+
+        let sensor_tokens: HashSet<Token> = calibrated_sensors
+            .iter()
+            .map(|sensor| sensor.sensor_token)
+            .collect();
+        let sensors: Vec<Sensor> = dataset
+            .sensor_iter()
+            .filter(|sensor| sensor_tokens.contains(&sensor.token))
+            .map(|sensor| (*sensor).clone())
+            .sorted_by_key(|sensor| sensor.token)
+            .collect();
+
+        let log_tokens: HashSet<Token> = scenes.iter().map(|scene| scene.log_token).collect();
+        let logs: Vec<Log> = dataset
+            .log_iter()
+            .filter(|log| log_tokens.contains(&log.token))
+            .map(|log| (*log).clone())
+            .sorted_by_key(|log| log.token)
+            .collect();
+        let maps: Vec<Map> = dataset
+            .map_iter()
+            .filter_map(|map| {
+                let mut map: Map = (*map).clone();
+                map.log_tokens.retain(|token| log_tokens.contains(token));
+                (!map.log_tokens.is_empty()).then_some(map)
+            })
+            .sorted_by_key(|map| map.token)
+            .collect();
+
+        let sample_data_tokens: HashSet<Token> =
+            sample_data.iter().map(|data| data.token).collect();
+        let panoptic: Vec<Panoptic> = dataset
+            .panoptic_map
+            .values()
+            .filter(|panoptic| sample_data_tokens.contains(&panoptic.sample_data_token))
+            .cloned()
+            .sorted_by_key(|panoptic| panoptic.token)
+            .collect();
+
+        let categories: Vec<Category> = dataset
+            .category_iter()
+            .map(|category| (*category).clone())
+            .sorted_by_key(|category| category.token)
+            .collect();
+        let attributes: Vec<Attribute> = dataset
+            .attribute_iter()
+            .map(|attribute| (*attribute).clone())
+            .sorted_by_key(|attribute| attribute.token)
+            .collect();
+        let visibility: Vec<Visibility> = dataset
+            .visibility_iter()
+            .map(|visibility| (*visibility).clone())
+            .sorted_by_key(|visibility| visibility.token)
+            .collect();
+
+        SelectedTables {
+            attributes,
+            calibrated_sensors,
+            categories,
+            ego_poses,
+            instances,
+            logs,
+            maps,
+            samples,
+            sample_annotations,
+            sample_data,
+            scenes,
+            sensors,
+            visibility,
+            panoptic,
+        }
+    }
+}
+
+impl Dataset {
+    /// Write all tables into the version directory. See
+    /// [DatasetWriter::write].
+    pub fn write_to<P>(&self, meta_dir: P) -> Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        DatasetWriter::default().write(self, meta_dir)
+    }
+}
+
+/// Write the records into the table file of the version directory.
+pub(crate) fn write_table<T>(meta_dir: &Path, table: Table, records: &[T]) -> Result<()>
+where
+    T: Serialize,
+{
+    write_json(&meta_dir.join(table.file_name()), records)
+}
+
+/// Get the size of the records written by [write_json].
+fn json_size<T>(records: &[T]) -> Result<u64>
+where
+    T: Serialize,
+{
+    let mut writer = CountingWriter::default();
+    serde_json::to_writer_pretty(&mut writer, records).map_err(io::Error::from)?;
+    Ok(writer.num_bytes)
+}
+
+fn write_json<T>(path: &Path, records: &[T]) -> Result<()>
+where
+    T: Serialize,
+{
+    let writer = BufWriter::new(File::create(path)?);
+    serde_json::to_writer_pretty(writer, records).map_err(io::Error::from)?;
+    Ok(())
+}
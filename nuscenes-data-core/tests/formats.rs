@@ -0,0 +1,55 @@
+//! File format checks on camera frames stored in other formats.
+
+#![cfg(feature = "test-fixtures")]
+
+use nuscenes_data_core::{
+    fixtures::{self, VERSION},
+    loader::{IssueKind, Rule},
+    table::Table,
+    DatasetLoader,
+};
+use std::{env, fs, path::PathBuf, process};
+
+/// Write the fixture with the CAM_FRONT file names renamed to the
+/// extension.
+fn fixture_with_camera_extension(extension: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!(
+        "nuscenes-data-formats-{extension}-{}",
+        process::id()
+    ));
+    fixtures::write_to(&dir).unwrap();
+
+    let path = dir.join(VERSION).join(Table::SampleData.file_name());
+    let json =
+        fixtures::table_json(Table::SampleData).replace(".jpg\"", &format!(".{extension}\""));
+    fs::write(path, json).unwrap();
+    dir
+}
+
+#[test]
+fn png_camera_frames_pass_file_format_checks() {
+    let dir = fixture_with_camera_extension("png");
+
+    let dataset = DatasetLoader::default().load(VERSION, &dir).unwrap();
+    assert!(dataset
+        .sample_data_iter()
+        .filter(|data| data.filename.starts_with("samples/CAM_FRONT"))
+        .all(|data| data.filename.extension().is_some_and(|ext| ext == "png")));
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn unknown_camera_extensions_fail_file_format_checks() {
+    let dir = fixture_with_camera_extension("bmp");
+
+    let loader = DatasetLoader::default().checks(&[Rule::FileFormats]);
+    let report = loader.validate(VERSION, &dir).unwrap();
+    assert_eq!(
+        report.issues_of_kind(IssueKind::FileFormatMismatch).count(),
+        2
+    );
+    assert!(loader.load(VERSION, &dir).is_err());
+
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,84 @@
+//! Indexing of table files by the lazy loader.
+
+use nuscenes_data_core::{lazy::LazyTable, serializable::Attribute, Token};
+use std::{env, fs, process};
+
+const TOKEN_A: &str = "0123456789abcdef0123456789abcdef";
+const TOKEN_B: &str = "fedcba9876543210fedcba9876543210";
+
+/// Write the text to a table file and index it.
+fn open(name: &str, text: &str) -> nuscenes_data_core::error::Result<LazyTable<Attribute>> {
+    let path = env::temp_dir().join(format!("nuscenes-data-lazy-{name}-{}.json", process::id()));
+    fs::write(&path, text).unwrap();
+    let table = LazyTable::open(&path);
+    fs::remove_file(path).unwrap();
+    table
+}
+
+fn attribute(token: &str, name: &str) -> String {
+    format!(r#"{{"token": "{token}", "name": "{name}", "description": ""}}"#)
+}
+
+#[test]
+fn index_records_with_escaped_quotes() {
+    // Brackets and escaped quotes in strings do not end the record
+    let name = r#"a \"}]\" b \\"#;
+    let text = format!(
+        "[{}, {}]",
+        attribute(TOKEN_A, name),
+        attribute(TOKEN_B, "b")
+    );
+    let table = open("escaped", &text).unwrap();
+    assert_eq!(table.len(), 2);
+
+    let token: Token = TOKEN_A.parse().unwrap();
+    let record = table.get(token).unwrap().unwrap();
+    assert_eq!(record.name, r#"a "}]" b \"#);
+}
+
+#[test]
+fn index_records_with_nested_values() {
+    let nested = format!(
+        r#"{{"token": "{TOKEN_A}", "name": "a", "description": "", "extra": {{"list": [[1, 2], {{"key": []}}]}}}}"#
+    );
+    let text = format!("\n[\n  {nested},\n  {}\n]\n", attribute(TOKEN_B, "b"));
+    let table = open("nested", &text).unwrap();
+
+    let tokens: Vec<_> = table.tokens().map(|token| token.to_string()).collect();
+    assert_eq!(tokens, [TOKEN_A, TOKEN_B]);
+    let token: Token = TOKEN_A.parse().unwrap();
+    assert_eq!(table.raw(token).unwrap(), nested.as_bytes());
+}
+
+#[test]
+fn index_empty_array() {
+    let table = open("empty", " [ ] ").unwrap();
+    assert!(table.is_empty());
+}
+
+#[test]
+fn reject_malformed_arrays() {
+    let a = attribute(TOKEN_A, "a");
+    let b = attribute(TOKEN_B, "b");
+    let cases = [
+        ("no-array", String::new()),
+        ("object", a.clone()),
+        ("unclosed", format!("[{a}")),
+        ("unclosed-record", format!("[{}", &a[..a.len() - 1])),
+        (
+            "record-closed-by-bracket",
+            format!("[{}]]", &a[..a.len() - 1]),
+        ),
+        ("trailing-array", format!("[{a}] [{b}]")),
+        ("trailing-text", format!("[{a}] x")),
+        ("missing-comma", format!("[{a} {b}]")),
+        ("leading-comma", format!("[, {a}]")),
+        ("trailing-comma", format!("[{a},]")),
+        ("number", format!("[{a}, 1]")),
+        ("string", format!("[{a}, \"{TOKEN_B}\"]")),
+    ];
+
+    for (name, text) in cases {
+        assert!(open(name, &text).is_err(), "{name} is accepted: {text}");
+    }
+}
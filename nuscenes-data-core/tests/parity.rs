@@ -0,0 +1,405 @@
+//! Numeric parity with the Python devkit.
+//!
+//! The tests are enabled by the `parity-tests` feature. They need the
+//! official `v1.0-mini` split under the directory in the
+//! `NUSCENES_MINI_DIR` environment variable, and a Python interpreter
+//! with the `nuscenes-devkit` package, which is `python3` unless
+//! `NUSCENES_PYTHON` is set. The tests are skipped if either is
+//! missing.
+//!
+//! The devkit values are dumped once by `tests/parity/devkit_dump.py`
+//! and compared against this crate within tolerances.
+
+#![cfg(feature = "parity-tests")]
+
+use nuscenes_data_core::{
+    eval::{
+        detection::DetectionClass,
+        prediction::{is_miss_k, min_ade_k, min_fde_k, Prediction, MISS_THRESHOLD},
+    },
+    geometry::Box3D,
+    prediction::helper::PredictHelper,
+    projection::{BoxVisibility, ProjectionOptions},
+    serializable::Channel,
+    Dataset, DatasetLoader, Token,
+};
+use serde::Deserialize;
+use std::{
+    collections::HashMap,
+    env,
+    path::{Path, PathBuf},
+    process::Command,
+    sync::OnceLock,
+};
+
+const VERSION: &str = "v1.0-mini";
+
+/// The tolerance of positions in meters.
+const POSITION_TOLERANCE: f64 = 1e-6;
+/// The tolerance of projected pixels.
+const PIXEL_TOLERANCE: f64 = 1e-3;
+/// The tolerance of velocities in m/s.
+const VELOCITY_TOLERANCE: f64 = 1e-6;
+/// The tolerance of prediction metrics.
+const METRIC_TOLERANCE: f64 = 1e-9;
+/// The tolerance of agent speeds, accelerations and heading change
+/// rates.
+const AGENT_STATE_TOLERANCE: f64 = 1e-6;
+/// The depth in meters below which projected corners are not compared.
+const MIN_DEPTH: f64 = 0.1;
+
+/// The agent state query parameters, which must match
+/// `tests/parity/devkit_dump.py`.
+const PAST_SECONDS: f64 = 2.0;
+const FUTURE_SECONDS: f64 = 6.0;
+const MAX_TIME_DIFF: f64 = 1.5;
+
+/// The exit code of the dump script if the devkit is not installed.
+const DEVKIT_MISSING: i32 = 3;
+
+#[derive(Deserialize)]
+struct DevkitDump {
+    annotations: Vec<AnnotationDump>,
+    projections: Vec<ProjectionDump>,
+    prediction_metrics: Vec<PredictionMetricsDump>,
+    agent_states: Vec<AgentStateDump>,
+    eval_boxes: HashMap<Token, Vec<EvalBoxDump>>,
+}
+
+#[derive(Deserialize)]
+struct AnnotationDump {
+    token: Token,
+    corners: Vec<[f64; 3]>,
+    velocity: Option<[f64; 2]>,
+}
+
+#[derive(Deserialize)]
+struct EvalBoxDump {
+    translation: [f64; 3],
+    detection_name: DetectionClass,
+}
+
+#[derive(Deserialize)]
+struct ProjectionDump {
+    sample_data_token: Token,
+    sample_annotation_token: Token,
+    corners: Vec<[f64; 3]>,
+}
+
+#[derive(Deserialize)]
+struct PredictionMetricsDump {
+    prediction: Prediction,
+    ground_truth: Vec<[f64; 2]>,
+    k: Vec<usize>,
+    min_ade: Vec<f64>,
+    min_fde: Vec<f64>,
+    miss_rate: Vec<f64>,
+}
+
+#[derive(Deserialize)]
+struct AgentStateDump {
+    instance_token: Token,
+    sample_token: Token,
+    past: Vec<[f64; 2]>,
+    past_local: Vec<[f64; 2]>,
+    future: Vec<[f64; 2]>,
+    future_local: Vec<[f64; 2]>,
+    velocity: Option<f64>,
+    acceleration: Option<f64>,
+    heading_change_rate: Option<f64>,
+}
+
+struct Fixture {
+    dataset: Dataset,
+    dump: DevkitDump,
+}
+
+/// Load the dataset and run the devkit once for all tests. It returns
+/// `None` if the tests should be skipped.
+fn fixture() -> Option<&'static Fixture> {
+    static FIXTURE: OnceLock<Option<Fixture>> = OnceLock::new();
+    FIXTURE.get_or_init(load_fixture).as_ref()
+}
+
+fn load_fixture() -> Option<Fixture> {
+    let Some(dataset_dir) = env::var_os("NUSCENES_MINI_DIR").map(PathBuf::from) else {
+        eprintln!("NUSCENES_MINI_DIR is not set, skipping parity tests");
+        return None;
+    };
+    let python = env::var("NUSCENES_PYTHON").unwrap_or_else(|_| "python3".to_string());
+    let script = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/parity/devkit_dump.py");
+    let output = env::temp_dir().join(format!("nuscenes-parity-{}.json", std::process::id()));
+
+    let status = Command::new(&python)
+        .arg(&script)
+        .arg(&dataset_dir)
+        .arg(VERSION)
+        .arg(&output)
+        .status();
+    match status {
+        Ok(status) if status.success() => {}
+        Ok(status) if status.code() == Some(DEVKIT_MISSING) => {
+            eprintln!("nuscenes-devkit is not installed for {python}, skipping parity tests");
+            return None;
+        }
+        Ok(status) => panic!("{} failed with {status}", script.display()),
+        Err(err) => {
+            eprintln!("failed to run {python}: {err}, skipping parity tests");
+            return None;
+        }
+    }
+
+    let dump = serde_json::from_slice(&std::fs::read(&output).unwrap()).unwrap();
+    std::fs::remove_file(&output).unwrap();
+    let dataset = DatasetLoader::default()
+        .load(VERSION, &dataset_dir)
+        .unwrap();
+    Some(Fixture { dataset, dump })
+}
+
+fn assert_close(actual: f64, expected: f64, tolerance: f64, what: &str) {
+    assert!(
+        (actual - expected).abs() <= tolerance,
+        "{what}: expected {expected}, found {actual}"
+    );
+}
+
+#[test]
+fn box_corners() {
+    let Some(Fixture { dataset, dump }) = fixture() else {
+        return;
+    };
+
+    for expected in &dump.annotations {
+        let annotation = dataset.sample_annotation(expected.token).unwrap();
+        let corners = Box3D::from(&*annotation).corners();
+        for (index, (actual, expected)) in corners.iter().zip(&expected.corners).enumerate() {
+            for axis in 0..3 {
+                let what = format!("corner {index} of annotation {}", annotation.token);
+                assert_close(actual[axis], expected[axis], POSITION_TOLERANCE, &what);
+            }
+        }
+    }
+}
+
+#[test]
+fn camera_projection() {
+    let Some(Fixture { dataset, dump }) = fixture() else {
+        return;
+    };
+    let options = ProjectionOptions {
+        visibility: BoxVisibility::None,
+        clip_to_image: false,
+        ..Default::default()
+    };
+
+    let expected: HashMap<(Token, Token), &ProjectionDump> = dump
+        .projections
+        .iter()
+        .map(|entry| {
+            (
+                (entry.sample_data_token, entry.sample_annotation_token),
+                entry,
+            )
+        })
+        .collect();
+
+    for sample in dataset.sample_iter() {
+        let data = sample.sample_data_by_channel(Channel::CamFront).unwrap();
+        let boxes = data.project_boxes(&options).unwrap();
+
+        // Boxes fully in front of the camera are always projected
+        let num_in_front = dump
+            .projections
+            .iter()
+            .filter(|entry| entry.sample_data_token == data.token)
+            .filter(|entry| entry.corners.iter().all(|corner| corner[2] > MIN_DEPTH))
+            .count();
+        let num_projected = boxes
+            .iter()
+            .filter(|projected| projected.corners.iter().all(|corner| corner[2] > MIN_DEPTH))
+            .count();
+        assert_eq!(num_projected, num_in_front, "boxes of {}", data.token);
+
+        for projected in &boxes {
+            let entry = expected[&(data.token, projected.sample_annotation_token)];
+            for (actual, expected) in projected.corners.iter().zip(&entry.corners) {
+                let what = format!(
+                    "projection of annotation {} onto {}",
+                    projected.sample_annotation_token, data.token
+                );
+                assert_close(actual[2], expected[2], POSITION_TOLERANCE, &what);
+                if expected[2] > MIN_DEPTH {
+                    assert_close(actual[0], expected[0], PIXEL_TOLERANCE, &what);
+                    assert_close(actual[1], expected[1], PIXEL_TOLERANCE, &what);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn box_velocity() {
+    let Some(Fixture { dataset, dump }) = fixture() else {
+        return;
+    };
+
+    // The devkit gives up on large gaps between annotations, so only
+    // its finite velocities are compared.
+    for expected in &dump.annotations {
+        let Some(expected_velocity) = expected.velocity else {
+            continue;
+        };
+        let annotation = dataset.sample_annotation(expected.token).unwrap();
+        let velocity = annotation.velocity().unwrap();
+        for axis in 0..2 {
+            let what = format!("velocity of annotation {}", annotation.token);
+            assert_close(
+                velocity[axis],
+                expected_velocity[axis],
+                VELOCITY_TOLERANCE,
+                &what,
+            );
+        }
+    }
+}
+
+#[test]
+fn prediction_metrics() {
+    let Some(Fixture { dump, .. }) = fixture() else {
+        return;
+    };
+
+    for case in &dump.prediction_metrics {
+        let prediction = &case.prediction;
+        let ground_truth = &case.ground_truth;
+        for (index, &k) in case.k.iter().enumerate() {
+            let what = |metric: &str| format!("{metric} of {} at k={k}", prediction.instance_token);
+            assert_close(
+                min_ade_k(prediction, ground_truth, k).unwrap(),
+                case.min_ade[index],
+                METRIC_TOLERANCE,
+                &what("minADE"),
+            );
+            assert_close(
+                min_fde_k(prediction, ground_truth, k).unwrap(),
+                case.min_fde[index],
+                METRIC_TOLERANCE,
+                &what("minFDE"),
+            );
+            let miss = is_miss_k(prediction, ground_truth, k, MISS_THRESHOLD);
+            assert_close(
+                if miss { 1.0 } else { 0.0 },
+                case.miss_rate[index],
+                METRIC_TOLERANCE,
+                &what("miss rate"),
+            );
+        }
+    }
+}
+
+fn assert_coords_close(actual: &[[f64; 2]], expected: &[[f64; 2]], what: &str) {
+    assert_eq!(actual.len(), expected.len(), "number of {what}");
+    for (actual, expected) in actual.iter().zip(expected) {
+        for axis in 0..2 {
+            assert_close(actual[axis], expected[axis], POSITION_TOLERANCE, what);
+        }
+    }
+}
+
+fn assert_option_close(actual: Option<f64>, expected: Option<f64>, what: &str) {
+    match (actual, expected) {
+        (Some(actual), Some(expected)) => {
+            assert_close(actual, expected, AGENT_STATE_TOLERANCE, what)
+        }
+        (None, None) => {}
+        _ => panic!("{what}: expected {expected:?}, found {actual:?}"),
+    }
+}
+
+#[test]
+fn predict_helper() {
+    let Some(Fixture { dataset, dump }) = fixture() else {
+        return;
+    };
+    let helper = PredictHelper::new(dataset.clone());
+
+    for expected in &dump.agent_states {
+        let instance = expected.instance_token;
+        let sample = expected.sample_token;
+        let what = |query: &str| format!("{query} of instance {instance} at sample {sample}");
+
+        let queries = [
+            (&expected.past, PAST_SECONDS, false, "past"),
+            (&expected.past_local, PAST_SECONDS, true, "local past"),
+        ];
+        for (coords, seconds, in_agent_frame, query) in queries {
+            let actual = helper
+                .get_past_for_agent(instance, sample, seconds, in_agent_frame)
+                .unwrap();
+            assert_coords_close(&actual, coords, &what(query));
+        }
+
+        let queries = [
+            (&expected.future, FUTURE_SECONDS, false, "future"),
+            (&expected.future_local, FUTURE_SECONDS, true, "local future"),
+        ];
+        for (coords, seconds, in_agent_frame, query) in queries {
+            let actual = helper
+                .get_future_for_agent(instance, sample, seconds, in_agent_frame)
+                .unwrap();
+            assert_coords_close(&actual, coords, &what(query));
+        }
+
+        assert_option_close(
+            helper.get_velocity_for_agent(instance, sample, MAX_TIME_DIFF),
+            expected.velocity,
+            &what("velocity"),
+        );
+        assert_option_close(
+            helper.get_acceleration_for_agent(instance, sample, MAX_TIME_DIFF),
+            expected.acceleration,
+            &what("acceleration"),
+        );
+        assert_option_close(
+            helper.get_heading_change_rate_for_agent(instance, sample, MAX_TIME_DIFF),
+            expected.heading_change_rate,
+            &what("heading change rate"),
+        );
+    }
+}
+
+#[test]
+fn detection_eval_filtering() {
+    let Some(Fixture { dataset, dump }) = fixture() else {
+        return;
+    };
+
+    // The devkit boxes have no annotation tokens, so they are matched
+    // by centers and classes
+    for (&sample_token, expected) in &dump.eval_boxes {
+        let sample = dataset.sample(sample_token).unwrap();
+        let annotations = dataset.annotations_in_eval_range(&sample);
+        assert_eq!(
+            annotations.len(),
+            expected.len(),
+            "evaluated annotations of sample {sample_token}"
+        );
+
+        for annotation in &annotations {
+            let class = annotation.detection_class();
+            let found = expected.iter().any(|expected| {
+                Some(expected.detection_name) == class
+                    && (0..3).all(|axis| {
+                        (annotation.translation[axis] - expected.translation[axis]).abs()
+                            <= POSITION_TOLERANCE
+                    })
+            });
+            assert!(
+                found,
+                "annotation {} is not evaluated by the devkit",
+                annotation.token
+            );
+        }
+    }
+}
@@ -0,0 +1,191 @@
+#!/usr/bin/env python3
+"""Dump reference values of the nuScenes devkit for tests/parity.rs.
+
+Usage: devkit_dump.py DATAROOT VERSION OUTPUT
+
+It exits with code 3 if the devkit is not installed, so that the
+parity tests are skipped instead of failing.
+"""
+
+import json
+import math
+import sys
+
+try:
+    import numpy as np
+    from nuscenes import NuScenes
+    from nuscenes.eval.common.config import config_factory
+    from nuscenes.eval.common.loaders import add_center_dist, filter_eval_boxes, load_gt
+    from nuscenes.eval.detection.data_classes import DetectionBox
+    from nuscenes.eval.prediction.data_classes import Prediction
+    from nuscenes.eval.prediction.metrics import MinADEK, MinFDEK, MissRateTopK, RowMean
+    from nuscenes.prediction import PredictHelper
+    from nuscenes.utils.geometry_utils import BoxVisibility, view_points
+except ImportError as err:
+    print(f"nuscenes-devkit is not available: {err}", file=sys.stderr)
+    sys.exit(3)
+
+# The camera whose key frames are projected
+PROJECTION_CHANNEL = "CAM_FRONT"
+
+# The synthetic prediction cases
+NUM_PREDICTIONS = 20
+NUM_MODES = 5
+HORIZON = 12
+K_TO_REPORT = [1, 5]
+
+# The agent state queries, made on every AGENT_STRIDE-th annotation
+AGENT_STRIDE = 10
+PAST_SECONDS = 2.0
+FUTURE_SECONDS = 6.0
+MAX_TIME_DIFF = 1.5
+
+
+def float_or_none(value):
+    value = float(value)
+    if math.isnan(value):
+        return None
+    return value
+
+
+def finite_or_none(values):
+    values = [float(value) for value in values]
+    if any(math.isnan(value) for value in values):
+        return None
+    return values
+
+
+def dump_annotations(nusc):
+    annotations = []
+    for record in nusc.sample_annotation:
+        box = nusc.get_box(record["token"])
+        velocity = nusc.box_velocity(record["token"])
+        annotations.append(
+            {
+                "token": record["token"],
+                "corners": box.corners().T.tolist(),
+                "velocity": finite_or_none(velocity[:2]),
+            }
+        )
+    return annotations
+
+
+def dump_projections(nusc):
+    projections = []
+    for sample in nusc.sample:
+        data_token = sample["data"][PROJECTION_CHANNEL]
+        _, boxes, intrinsic = nusc.get_sample_data(data_token, box_vis_level=BoxVisibility.NONE)
+        for box in boxes:
+            corners = box.corners()
+            pixels = view_points(corners, np.array(intrinsic), normalize=True)
+            projections.append(
+                {
+                    "sample_data_token": data_token,
+                    "sample_annotation_token": box.token,
+                    "corners": [
+                        [float(pixels[0, i]), float(pixels[1, i]), float(corners[2, i])]
+                        for i in range(corners.shape[1])
+                    ],
+                }
+            )
+    return projections
+
+
+def dump_prediction_metrics():
+    rng = np.random.RandomState(0)
+    min_ade = MinADEK(K_TO_REPORT, [RowMean()])
+    min_fde = MinFDEK(K_TO_REPORT, [RowMean()])
+    miss_rate = MissRateTopK(K_TO_REPORT, [RowMean()], tolerance=2.0)
+
+    cases = []
+    for index in range(NUM_PREDICTIONS):
+        ground_truth = np.cumsum(rng.normal(size=(HORIZON, 2)), axis=0)
+        trajectories = ground_truth[None] + rng.normal(scale=2.0, size=(NUM_MODES, HORIZON, 2))
+        probabilities = rng.dirichlet(np.ones(NUM_MODES))
+        instance = f"{index:032x}"
+        sample = f"{index + NUM_PREDICTIONS:032x}"
+        prediction = Prediction(instance, sample, trajectories, probabilities)
+
+        cases.append(
+            {
+                "prediction": {
+                    "instance": instance,
+                    "sample": sample,
+                    "prediction": trajectories.tolist(),
+                    "probabilities": probabilities.tolist(),
+                },
+                "ground_truth": ground_truth.tolist(),
+                "k": K_TO_REPORT,
+                "min_ade": min_ade(ground_truth, prediction).tolist(),
+                "min_fde": min_fde(ground_truth, prediction).tolist(),
+                "miss_rate": miss_rate(ground_truth, prediction).tolist(),
+            }
+        )
+    return cases
+
+
+def dump_agent_states(nusc):
+    helper = PredictHelper(nusc)
+    states = []
+    for record in nusc.sample_annotation[::AGENT_STRIDE]:
+        instance, sample = record["instance_token"], record["sample_token"]
+
+        def coords(method, seconds, in_agent_frame):
+            # The devkit fails to convert an empty array to the agent frame
+            if in_agent_frame and len(method(instance, sample, seconds, False)) == 0:
+                return []
+            return method(instance, sample, seconds, in_agent_frame).reshape(-1, 2).tolist()
+
+        states.append(
+            {
+                "instance_token": instance,
+                "sample_token": sample,
+                "past": coords(helper.get_past_for_agent, PAST_SECONDS, False),
+                "past_local": coords(helper.get_past_for_agent, PAST_SECONDS, True),
+                "future": coords(helper.get_future_for_agent, FUTURE_SECONDS, False),
+                "future_local": coords(helper.get_future_for_agent, FUTURE_SECONDS, True),
+                "velocity": float_or_none(
+                    helper.get_velocity_for_agent(instance, sample, MAX_TIME_DIFF)
+                ),
+                "acceleration": float_or_none(
+                    helper.get_acceleration_for_agent(instance, sample, MAX_TIME_DIFF)
+                ),
+                "heading_change_rate": float_or_none(
+                    helper.get_heading_change_rate_for_agent(instance, sample, MAX_TIME_DIFF)
+                ),
+            }
+        )
+    return states
+
+
+def dump_eval_boxes(nusc, version):
+    split = "mini_val" if version == "v1.0-mini" else "val"
+    config = config_factory("detection_cvpr_2019")
+    boxes = load_gt(nusc, split, DetectionBox, verbose=False)
+    boxes = add_center_dist(nusc, boxes)
+    boxes = filter_eval_boxes(nusc, boxes, config.class_range, verbose=False)
+    return {
+        sample_token: [
+            {"translation": list(box.translation), "detection_name": box.detection_name}
+            for box in boxes[sample_token]
+        ]
+        for sample_token in boxes.sample_tokens
+    }
+
+
+def main():
+    dataroot, version, output = sys.argv[1:4]
+    nusc = NuScenes(version=version, dataroot=dataroot, verbose=False)
+    dump = {
+        "annotations": dump_annotations(nusc),
+        "projections": dump_projections(nusc),
+        "prediction_metrics": dump_prediction_metrics(),
+        "agent_states": dump_agent_states(nusc),
+        "eval_boxes": dump_eval_boxes(nusc, version),
+    }
+    with open(output, "w") as file:
+        json.dump(dump, file)
+
+
+if __name__ == "__main__":
+    main()
@@ -0,0 +1,63 @@
+//! Reloading tables edited after the dataset is loaded.
+
+#![cfg(feature = "test-fixtures")]
+
+use nuscenes_data_core::{
+    fixtures::{self, VERSION},
+    table::Table,
+    DatasetLoader, Token,
+};
+use serde_json::Value;
+use std::{env, fs, path::PathBuf, process};
+
+const ANNOTATION_TOKEN: &str = "c36ec1cbabd42d6d375ad87a2640e322";
+
+/// Write the fixture to a directory owned by the test.
+fn fixture_dir(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("nuscenes-data-reload-{name}-{}", process::id()));
+    fixtures::write_to(&dir).unwrap();
+    dir
+}
+
+/// Edit the first sample annotation in the table file.
+fn edit_annotation<F>(dir: &PathBuf, edit: F)
+where
+    F: FnOnce(&mut Value),
+{
+    let path = dir.join(VERSION).join(Table::SampleAnnotation.file_name());
+    let mut records: Value = serde_json::from_slice(&fs::read(&path).unwrap()).unwrap();
+    edit(&mut records[0]);
+    fs::write(path, serde_json::to_vec(&records).unwrap()).unwrap();
+}
+
+#[test]
+fn reload_picks_up_edits() {
+    let dir = fixture_dir("edit");
+    let mut dataset = DatasetLoader::default().load(VERSION, &dir).unwrap();
+
+    edit_annotation(&dir, |record| record["num_lidar_pts"] = 7.into());
+    dataset.reload_table(Table::SampleAnnotation).unwrap();
+
+    let token: Token = ANNOTATION_TOKEN.parse().unwrap();
+    assert_eq!(dataset.sample_annotation(token).unwrap().num_lidar_pts, 7);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn reload_rejects_dangling_references() {
+    let dir = fixture_dir("dangling");
+    let mut dataset = DatasetLoader::default().load(VERSION, &dir).unwrap();
+
+    let missing = "0123456789abcdef0123456789abcdef";
+    edit_annotation(&dir, |record| record["instance_token"] = missing.into());
+    assert!(dataset.reload_table(Table::SampleAnnotation).is_err());
+
+    // The dataset keeps the records before the reload
+    let token: Token = ANNOTATION_TOKEN.parse().unwrap();
+    let annotation = dataset.sample_annotation(token).unwrap();
+    assert_ne!(annotation.instance_token.to_string(), missing);
+    assert_eq!(annotation.instance().token, annotation.instance_token);
+
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,110 @@
+//! Consistency of the table schemas with the serializable types.
+//!
+//! A record with every field of [Table::schema] is deserialized into
+//! the type of the table and serialized back. A schema field missing
+//! in the type is dropped, and a type field missing in the schema
+//! fails the deserialization or is added, so both show up as a
+//! difference in the field names.
+
+use nuscenes_data_core::{
+    schema::{FieldSchema, FieldType},
+    serializable as s,
+    table::Table,
+};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::{json, Map, Value};
+use std::collections::BTreeSet;
+
+/// Get a valid value of a field type.
+fn example_value(ty: FieldType) -> Value {
+    let token = "0123456789abcdef0123456789abcdef";
+    match ty {
+        FieldType::Token => json!(token),
+        FieldType::TokenList => json!([token]),
+        FieldType::VisibilityToken => json!("1"),
+        FieldType::String => json!("text"),
+        FieldType::Path => json!("samples/CAM_FRONT/example.jpg"),
+        FieldType::Integer => json!(-1),
+        FieldType::UnsignedInteger => json!(1),
+        FieldType::Bool => json!(true),
+        FieldType::Timestamp => json!(1532402927647951u64),
+        FieldType::Date => json!("2018-07-24"),
+        FieldType::Vector(len) => Value::Array(vec![json!(1.0); len]),
+        FieldType::Matrix3 => json!([[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]]),
+        FieldType::Enum(values) => json!(values[0]),
+    }
+}
+
+fn roundtrip_as<T>(record: Value) -> Result<Value, String>
+where
+    T: Serialize + DeserializeOwned,
+{
+    let value: T = serde_json::from_value(record).map_err(|err| err.to_string())?;
+    serde_json::to_value(value).map_err(|err| err.to_string())
+}
+
+fn roundtrip(table: Table, record: Value) -> Result<Value, String> {
+    match table {
+        Table::Attribute => roundtrip_as::<s::Attribute>(record),
+        Table::CalibratedSensor => roundtrip_as::<s::CalibratedSensor>(record),
+        Table::Category => roundtrip_as::<s::Category>(record),
+        Table::EgoPose => roundtrip_as::<s::EgoPose>(record),
+        Table::Instance => roundtrip_as::<s::Instance>(record),
+        Table::Log => roundtrip_as::<s::Log>(record),
+        Table::Map => roundtrip_as::<s::Map>(record),
+        Table::Sample => roundtrip_as::<s::Sample>(record),
+        Table::SampleAnnotation => roundtrip_as::<s::SampleAnnotation>(record),
+        Table::SampleData => roundtrip_as::<s::SampleData>(record),
+        Table::Scene => roundtrip_as::<s::Scene>(record),
+        Table::Sensor => roundtrip_as::<s::Sensor>(record),
+        Table::Visibility => roundtrip_as::<s::Visibility>(record),
+    }
+}
+
+fn field_names(value: &Value) -> BTreeSet<String> {
+    value
+        .as_object()
+        .expect("a record is not an object")
+        .keys()
+        .cloned()
+        .collect()
+}
+
+#[test]
+fn schema_matches_serializable_types() {
+    for table in Table::ALL {
+        let schema = table.schema();
+        let record: Map<String, Value> = schema
+            .fields
+            .iter()
+            .map(|field: &FieldSchema| (field.name.to_string(), example_value(field.ty)))
+            .collect();
+        let record = Value::Object(record);
+
+        let output = roundtrip(table, record.clone()).unwrap_or_else(|err| {
+            panic!(
+                "{} does not deserialize a record of its schema: {err}",
+                table.file_name()
+            )
+        });
+
+        let expect = field_names(&record);
+        let actual = field_names(&output);
+        assert_eq!(
+            actual,
+            expect,
+            "the fields of {} differ from its schema",
+            table.file_name()
+        );
+
+        for field in &schema.fields {
+            assert!(
+                field.accepts(&output[field.name]),
+                "{}.{} is written as {}, which the schema rejects",
+                table.file_name(),
+                field.name,
+                output[field.name]
+            );
+        }
+    }
+}
@@ -0,0 +1,71 @@
+//! Fault injection on storages keyed by relative file names.
+
+use nuscenes_data_core::storage::{
+    read_data_with_options, DataPath, FaultConfig, FaultInjectingStorage, ReadOptions, Storage,
+};
+use std::{
+    collections::HashMap,
+    io,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+/// An object store answering only reads by relative file names.
+#[derive(Debug)]
+struct KeyedStorage {
+    objects: HashMap<PathBuf, Vec<u8>>,
+}
+
+impl Storage for KeyedStorage {
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        let msg = format!("{} is not keyed by a relative file name", path.display());
+        Err(io::Error::new(io::ErrorKind::Unsupported, msg))
+    }
+
+    fn read_data(&self, path: DataPath<'_>) -> io::Result<Vec<u8>> {
+        self.objects
+            .get(path.relative())
+            .cloned()
+            .ok_or_else(|| io::Error::from(io::ErrorKind::NotFound))
+    }
+
+    fn data_exists(&self, path: DataPath<'_>) -> bool {
+        self.objects.contains_key(path.relative())
+    }
+
+    fn data_size(&self, path: DataPath<'_>) -> io::Result<u64> {
+        self.read_data(path).map(|bytes| bytes.len() as u64)
+    }
+}
+
+#[test]
+fn fault_injection_forwards_data_reads() {
+    let relative = Path::new("samples/LIDAR_TOP/example.pcd.bin");
+    let inner = KeyedStorage {
+        objects: HashMap::from([(relative.to_owned(), vec![1, 2, 3])]),
+    };
+    let config = FaultConfig {
+        fail_first: 1,
+        error_kind: io::ErrorKind::Interrupted,
+        ..Default::default()
+    };
+    let storage = Arc::new(FaultInjectingStorage::new(inner, config));
+    let path = DataPath::new(Path::new("/data/nuscenes"), relative);
+
+    assert!(storage.data_exists(path));
+    assert_eq!(storage.data_size(path).unwrap(), 3);
+
+    // The first read fails by injection and the retry reaches the
+    // inner storage by the relative file name
+    let options = ReadOptions {
+        max_retries: 1,
+        ..Default::default()
+    }
+    .with_backoff(Duration::ZERO, Duration::ZERO, 1.0);
+    let dyn_storage: Arc<dyn Storage> = storage.clone();
+    let bytes = read_data_with_options(&dyn_storage, path, &options).unwrap();
+    assert_eq!(bytes, [1, 2, 3]);
+    assert_eq!(storage.num_reads(), 2);
+    assert_eq!(storage.num_failures(), 1);
+}
@@ -0,0 +1,273 @@
+//! Pack scenes into HDF5 files.
+//!
+//! Each scene is written into one file to reduce small-file IO on
+//! network file systems. The file layout is as follows.
+//!
+//! - `/` has the `scene_token`, `name` and `description` attributes.
+//! - `/samples` has `tokens` and `timestamps` datasets.
+//! - `/<CHANNEL>` groups, such as `/CAM_FRONT`, hold the sample data
+//!   of each channel sorted by timestamps, including sweeps.
+//!   - The `sensor_to_ego_translation`, `sensor_to_ego_rotation` and
+//!     `camera_intrinsic` attributes store the calibration.
+//!   - `tokens`, `sample_tokens`, `timestamps`, `is_key_frame`,
+//!     `ego_translation` and `ego_rotation` datasets are per file.
+//!   - `data` concatenates the raw file contents, and the i-th file is
+//!     `data[offsets[i]..offsets[i + 1]]`.
+//! - `/annotations` has `tokens`, `sample_tokens`, `instance_tokens`,
+//!   `categories`, `translation`, `size` and `rotation` datasets.
+
+use hdf5::{types::VarLenUnicode, Group, H5Type, Location};
+use nuscenes_data::{
+    dataset::{SampleDataRef, SceneRef},
+    error::Result,
+    estimate::ExportEstimate,
+    serializable::{Channel, TOKEN_LENGTH},
+};
+use std::{collections::BTreeMap, path::Path};
+
+/// The length of tokens written as hex strings.
+const TOKEN_STR_LEN: u64 = 2 * TOKEN_LENGTH as u64;
+
+pub mod prelude {
+    pub use super::SceneRefHdf5Ext;
+}
+
+pub trait SceneRefHdf5Ext {
+    /// Write the sensor data and annotations of the scene into an
+    /// HDF5 file.
+    fn export_hdf5<P>(&self, path: P) -> hdf5::Result<()>
+    where
+        P: AsRef<Path>;
+
+    /// Estimate the file written by [SceneRefHdf5Ext::export_hdf5]
+    /// without writing it. The sizes of sample files are queried from
+    /// the dataset storage.
+    ///
+    /// It counts the bytes of the datasets and attributes, but not the
+    /// HDF5 metadata and string heaps, so the written file is slightly
+    /// larger.
+    fn estimate_hdf5(&self) -> Result<ExportEstimate>;
+}
+
+impl SceneRefHdf5Ext for SceneRef {
+    fn export_hdf5<P>(&self, path: P) -> hdf5::Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        let file = hdf5::File::create(path)?;
+        write_str_attr(&file, "scene_token", &self.token.to_string())?;
+        write_str_attr(&file, "name", &self.name)?;
+        write_str_attr(&file, "description", &self.description)?;
+
+        let samples: Vec<_> = self.sample_iter().collect();
+        let group = file.create_group("samples")?;
+        let tokens: Vec<String> = samples
+            .iter()
+            .map(|sample| sample.token.to_string())
+            .collect();
+        let timestamps: Vec<i64> = samples
+            .iter()
+            .map(|sample| sample.timestamp.timestamp_micros())
+            .collect();
+        write_strings(&group, "tokens", &tokens)?;
+        write_dataset(&group, "timestamps", &[timestamps.len()], &timestamps)?;
+
+        let mut channels: BTreeMap<&'static str, (Channel, Vec<SampleDataRef>)> = BTreeMap::new();
+        for data in samples.iter().flat_map(|sample| sample.sample_data_iter()) {
+            let channel = data.channel();
+            channels
+                .entry(channel.as_str())
+                .or_insert_with(|| (channel, vec![]))
+                .1
+                .push(data);
+        }
+        for (channel, mut data) in channels.into_values() {
+            data.sort_by_key(|data| data.timestamp);
+            let group = file.create_group(channel.as_str())?;
+            write_channel(&group, &data)?;
+        }
+
+        write_annotations(&file.create_group("annotations")?, self)?;
+        Ok(())
+    }
+
+    fn estimate_hdf5(&self) -> Result<ExportEstimate> {
+        let samples: Vec<_> = self.sample_iter().collect();
+        let mut num_bytes = TOKEN_STR_LEN + self.name.len() as u64 + self.description.len() as u64;
+
+        // Sample tokens and timestamps
+        num_bytes += samples.len() as u64 * (TOKEN_STR_LEN + 8);
+
+        // Per-file datasets, the data and offsets of each channel
+        let mut channels: BTreeMap<&'static str, SampleDataRef> = BTreeMap::new();
+        for data in samples.iter().flat_map(|sample| sample.sample_data_iter()) {
+            num_bytes += 2 * TOKEN_STR_LEN + 8 + 1 + 3 * 8 + 4 * 8 + 8;
+            num_bytes += data.file_size()?;
+            channels.entry(data.channel().as_str()).or_insert(data);
+        }
+
+        // Calibration attributes and the first offset of each channel
+        for data in channels.values() {
+            let calibrated_sensor = data.calibrated_sensor();
+            num_bytes += 3 * 8 + 4 * 8 + 8;
+            if calibrated_sensor.camera_intrinsic.is_some() {
+                num_bytes += 9 * 8;
+            }
+        }
+
+        // Annotation tokens, categories and boxes
+        for annotation in samples.iter().flat_map(|sample| sample.annotation_iter()) {
+            num_bytes += 3 * TOKEN_STR_LEN + 3 * 8 + 3 * 8 + 4 * 8;
+            num_bytes += annotation.instance().category().name.len() as u64;
+        }
+
+        Ok(ExportEstimate {
+            num_samples: samples.len(),
+            num_files: 1,
+            num_bytes,
+        })
+    }
+}
+
+fn write_channel(group: &Group, data: &[SampleDataRef]) -> hdf5::Result<()> {
+    let calibrated_sensor = data[0].calibrated_sensor();
+    write_f64_attr(
+        group,
+        "sensor_to_ego_translation",
+        &calibrated_sensor.translation,
+    )?;
+    write_f64_attr(group, "sensor_to_ego_rotation", &calibrated_sensor.rotation)?;
+    if let Some(intrinsic) = calibrated_sensor.camera_intrinsic {
+        write_f64_attr(group, "camera_intrinsic", &intrinsic.concat())?;
+    }
+
+    let len = data.len();
+    let tokens: Vec<String> = data.iter().map(|data| data.token.to_string()).collect();
+    let sample_tokens: Vec<String> = data
+        .iter()
+        .map(|data| data.sample_token.to_string())
+        .collect();
+    let timestamps: Vec<i64> = data
+        .iter()
+        .map(|data| data.timestamp.timestamp_micros())
+        .collect();
+    let is_key_frame: Vec<u8> = data.iter().map(|data| data.is_key_frame as u8).collect();
+    let ego_translation: Vec<f64> = data
+        .iter()
+        .flat_map(|data| data.ego_pose().translation)
+        .collect();
+    let ego_rotation: Vec<f64> = data
+        .iter()
+        .flat_map(|data| data.ego_pose().rotation)
+        .collect();
+
+    write_strings(group, "tokens", &tokens)?;
+    write_strings(group, "sample_tokens", &sample_tokens)?;
+    write_dataset(group, "timestamps", &[len], &timestamps)?;
+    write_dataset(group, "is_key_frame", &[len], &is_key_frame)?;
+    write_dataset(group, "ego_translation", &[len, 3], &ego_translation)?;
+    write_dataset(group, "ego_rotation", &[len, 4], &ego_rotation)?;
+
+    let mut bytes = vec![];
+    let mut offsets = vec![0u64];
+    for data in data {
+        let content = data
+            .read_bytes()
+            .map_err(|err| hdf5::Error::from(format!("{}: {err}", data.path())))?;
+        bytes.extend_from_slice(&content);
+        offsets.push(bytes.len() as u64);
+    }
+    write_dataset(group, "data", &[bytes.len()], &bytes)?;
+    write_dataset(group, "offsets", &[offsets.len()], &offsets)?;
+    Ok(())
+}
+
+fn write_annotations(group: &Group, scene: &SceneRef) -> hdf5::Result<()> {
+    let annotations: Vec<_> = scene
+        .sample_iter()
+        .flat_map(|sample| sample.annotation_iter().collect::<Vec<_>>())
+        .collect();
+    let len = annotations.len();
+
+    let tokens: Vec<String> = annotations
+        .iter()
+        .map(|annotation| annotation.token.to_string())
+        .collect();
+    let sample_tokens: Vec<String> = annotations
+        .iter()
+        .map(|annotation| annotation.sample_token.to_string())
+        .collect();
+    let instance_tokens: Vec<String> = annotations
+        .iter()
+        .map(|annotation| annotation.instance_token.to_string())
+        .collect();
+    let categories: Vec<String> = annotations
+        .iter()
+        .map(|annotation| annotation.instance().category().name.clone())
+        .collect();
+    let translation: Vec<f64> = annotations
+        .iter()
+        .flat_map(|annotation| annotation.translation)
+        .collect();
+    let size: Vec<f64> = annotations
+        .iter()
+        .flat_map(|annotation| annotation.size)
+        .collect();
+    let rotation: Vec<f64> = annotations
+        .iter()
+        .flat_map(|annotation| annotation.rotation)
+        .collect();
+
+    write_strings(group, "tokens", &tokens)?;
+    write_strings(group, "sample_tokens", &sample_tokens)?;
+    write_strings(group, "instance_tokens", &instance_tokens)?;
+    write_strings(group, "categories", &categories)?;
+    write_dataset(group, "translation", &[len, 3], &translation)?;
+    write_dataset(group, "size", &[len, 3], &size)?;
+    write_dataset(group, "rotation", &[len, 4], &rotation)?;
+    Ok(())
+}
+
+fn write_dataset<T>(group: &Group, name: &str, shape: &[usize], values: &[T]) -> hdf5::Result<()>
+where
+    T: H5Type,
+{
+    let dataset = group
+        .new_dataset::<T>()
+        .shape(shape.to_vec())
+        .create(name)?;
+    dataset.write_raw(values)?;
+    Ok(())
+}
+
+fn write_strings(group: &Group, name: &str, values: &[String]) -> hdf5::Result<()> {
+    let values = values
+        .iter()
+        .map(|value| to_var_len_unicode(value))
+        .collect::<hdf5::Result<Vec<_>>>()?;
+    write_dataset(group, name, &[values.len()], &values)
+}
+
+fn write_str_attr(location: &Location, name: &str, value: &str) -> hdf5::Result<()> {
+    let attr = location
+        .new_attr::<VarLenUnicode>()
+        .shape(())
+        .create(name)?;
+    attr.write_scalar(&to_var_len_unicode(value)?)?;
+    Ok(())
+}
+
+fn write_f64_attr(location: &Location, name: &str, values: &[f64]) -> hdf5::Result<()> {
+    let attr = location
+        .new_attr::<f64>()
+        .shape(values.len())
+        .create(name)?;
+    attr.write_raw(values)?;
+    Ok(())
+}
+
+fn to_var_len_unicode(value: &str) -> hdf5::Result<VarLenUnicode> {
+    value
+        .parse()
+        .map_err(|err| hdf5::Error::from(format!("invalid string {value:?}: {err}")))
+}
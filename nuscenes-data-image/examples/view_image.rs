@@ -45,9 +45,9 @@ fn main() -> Result<()> {
             .ok_or_else(|| anyhow!("no image data found"))?;
 
         match first.load_dynamic_image() {
-            Ok(image) => image,
+            Ok(image) => Some(image),
             Err(err) => {
-                eprintln!("unable to load {}: {err}", first.path().display());
+                eprintln!("unable to load {}: {err}", first.path());
                 None
             }
         }
@@ -87,9 +87,9 @@ fn main() -> Result<()> {
             image = {
                 let record = &records[index];
                 match record.load_dynamic_image() {
-                    Ok(image) => image,
+                    Ok(image) => Some(image),
                     Err(err) => {
-                        eprintln!("unable to load {}: {err}", record.path().display());
+                        eprintln!("unable to load {}: {err}", record.path());
                         None
                     }
                 }
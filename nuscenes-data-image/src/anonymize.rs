@@ -0,0 +1,175 @@
+//! Offline anonymization of camera images.
+//!
+//! The pipeline runs a user-provided detector on every camera frame,
+//! blurs the detected regions and writes the blurred copies to a
+//! parallel data root. The other sample files and the map masks are
+//! hard-linked into the output root if possible, or copied otherwise,
+//! and the tables are written to a new metadata directory. The output
+//! root keeps the relative file names of the source, so it can be
+//! moved or uploaded as a dataset directory. Lidarseg and panoptic
+//! label files are not copied.
+
+use crate::{from_error, SampleDataRefImageExt};
+use image::{
+    error::{ParameterError, ParameterErrorKind},
+    imageops, DynamicImage, ImageError, ImageResult,
+};
+use nuscenes_data::{
+    dataset::{Dataset, SampleDataRef},
+    serializable::{FileFormat, SampleData},
+    storage::DataPath,
+};
+use rayon::prelude::*;
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter},
+    path::Path,
+};
+
+/// A rectangle region on an image in pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Region {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+#[derive(Debug, Clone)]
+pub struct AnonymizeOptions {
+    /// The standard deviation of the Gaussian blur.
+    pub blur_sigma: f32,
+}
+
+impl Default for AnonymizeOptions {
+    fn default() -> Self {
+        Self { blur_sigma: 15.0 }
+    }
+}
+
+/// Blur the regions on the image.
+pub fn blur_regions(image: &DynamicImage, regions: &[Region], sigma: f32) -> DynamicImage {
+    let mut rgb = image.to_rgb8();
+
+    for region in regions {
+        let x = region.x.min(rgb.width());
+        let y = region.y.min(rgb.height());
+        let width = region.width.min(rgb.width() - x);
+        let height = region.height.min(rgb.height() - y);
+        if width == 0 || height == 0 {
+            continue;
+        }
+
+        let patch = imageops::crop_imm(&rgb, x, y, width, height).to_image();
+        let blurred = imageops::blur(&patch, sigma);
+        imageops::replace(&mut rgb, &blurred, x as i64, y as i64);
+    }
+
+    DynamicImage::ImageRgb8(rgb)
+}
+
+/// Anonymize all camera images in the dataset and write the results
+/// to `out_root`.
+///
+/// The `detector` is called on every camera frame and returns the
+/// regions to be blurred. The dataset with anonymized images can be
+/// loaded from `out_root` with the same version name. Datasets merged
+/// from multiple versions are rejected, since the tables of each
+/// version are written separately.
+pub fn anonymize_dataset<P, F>(
+    dataset: &Dataset,
+    out_root: P,
+    options: &AnonymizeOptions,
+    detector: F,
+) -> ImageResult<()>
+where
+    P: AsRef<Path>,
+    F: Fn(&SampleDataRef, &DynamicImage) -> Vec<Region> + Sync,
+{
+    if dataset.versions.len() != 1 {
+        let msg = format!(
+            "cannot anonymize the dataset merged from versions {}",
+            dataset.versions.join(", ")
+        );
+        return Err(ImageError::Parameter(ParameterError::from_kind(
+            ParameterErrorKind::Generic(msg),
+        )));
+    }
+
+    let out_root = out_root.as_ref();
+    let out_meta_dir = out_root.join(&dataset.version);
+    let meta_dir = dataset.dataset_dir.join(&dataset.version);
+    fs::create_dir_all(&out_meta_dir)?;
+
+    // Blur camera frames
+    let records: Vec<_> = dataset.sample_data_iter().collect();
+    let sample_data: Vec<SampleData> = records
+        .par_iter()
+        .map(|record| -> ImageResult<_> {
+            let sample_data: SampleData = (**record).clone();
+            let out_path = out_root.join(&record.filename);
+            if let Some(parent) = out_path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+
+            if record.fileformat == FileFormat::Jpg {
+                let image = record.load_dynamic_image().map_err(from_error)?;
+                let regions = detector(record, &image);
+                let blurred = blur_regions(&image, &regions, options.blur_sigma);
+                blurred.save(&out_path)?;
+                return Ok(sample_data);
+            }
+
+            link_or_copy(dataset, record.path(), &out_path, || record.read_bytes())?;
+            Ok(sample_data)
+        })
+        .collect::<ImageResult<_>>()?;
+
+    // Write the new sample_data table
+    {
+        let writer = BufWriter::new(File::create(out_meta_dir.join("sample_data.json"))?);
+        serde_json::to_writer(writer, &sample_data).map_err(io::Error::from)?;
+    }
+
+    // Copy remaining tables
+    for entry in fs::read_dir(&meta_dir)? {
+        let path = entry?.path();
+        let Some(file_name) = path.file_name() else {
+            continue;
+        };
+        let is_json = path.extension().map(|ext| ext == "json").unwrap_or(false);
+        if !is_json || file_name == "sample_data.json" {
+            continue;
+        }
+        fs::copy(&path, out_meta_dir.join(file_name))?;
+    }
+
+    // Copy map masks, which are resolved against the output root
+    for map in dataset.map_iter() {
+        let out_path = out_root.join(&map.filename);
+        if let Some(parent) = out_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        link_or_copy(dataset, map.path(), &out_path, || map.read_bytes())?;
+    }
+
+    Ok(())
+}
+
+/// Hard-link the file to the output path if the storage is local, or
+/// write the bytes read from the storage otherwise.
+fn link_or_copy<F>(
+    dataset: &Dataset,
+    path: DataPath<'_>,
+    out_path: &Path,
+    read: F,
+) -> io::Result<()>
+where
+    F: FnOnce() -> io::Result<Vec<u8>>,
+{
+    // Linking fails if the output exists, such as in a rerun
+    if dataset.storage.is_local() && fs::hard_link(path.to_path_buf(), out_path).is_ok() {
+        return Ok(());
+    }
+    fs::write(out_path, read()?)
+}
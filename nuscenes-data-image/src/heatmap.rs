@@ -0,0 +1,50 @@
+//! Rendering of annotation heatmaps over map masks.
+
+use crate::{report::MAP_RESOLUTION, MapRefImageExt};
+use image::{imageops, ImageResult, Rgb, RgbImage};
+use nuscenes_data::{dataset::MapRef, heatmap::Heatmap};
+
+/// Render the heatmap over the map mask with one pixel per cell.
+/// Cell colors go from blue to red by the counts relative to the
+/// maximum count, and empty cells show the map mask.
+pub fn render_heatmap(map: &MapRef, heatmap: &Heatmap) -> ImageResult<RgbImage> {
+    let mask = map.load_dynamic_image()?.to_luma8();
+    let (mask_width, mask_height) = mask.dimensions();
+
+    // Crop the mask to the grid extent, which starts at the
+    // bottom-left corner of the mask.
+    let scale = heatmap.cell_size / MAP_RESOLUTION;
+    let crop_width = ((heatmap.width as f64 * scale) as u32).clamp(1, mask_width);
+    let crop_height = ((heatmap.height as f64 * scale) as u32).clamp(1, mask_height);
+    let crop =
+        imageops::crop_imm(&mask, 0, mask_height - crop_height, crop_width, crop_height).to_image();
+    let background = imageops::resize(
+        &crop,
+        heatmap.width as u32,
+        heatmap.height as u32,
+        imageops::FilterType::Triangle,
+    );
+
+    let values = heatmap.normalized();
+    let canvas = RgbImage::from_fn(heatmap.width as u32, heatmap.height as u32, |x, y| {
+        // Image rows go downward while grid rows go upward
+        let row = heatmap.height - 1 - y as usize;
+        let index = row * heatmap.width + x as usize;
+
+        if heatmap.counts[index] == 0 {
+            let value = background.get_pixel(x, y)[0] / 2;
+            Rgb([value, value, value])
+        } else {
+            heat_color(values[index])
+        }
+    });
+    Ok(canvas)
+}
+
+fn heat_color(value: f32) -> Rgb<u8> {
+    // Use a square root scale to reveal sparse cells
+    let value = value.clamp(0.0, 1.0).sqrt();
+    let red = (255.0 * value) as u8;
+    let blue = (255.0 * (1.0 - value)) as u8;
+    Rgb([red, 64, blue])
+}
@@ -1,34 +1,149 @@
+pub mod anonymize;
+pub mod heatmap;
+pub mod render;
+pub mod report;
+pub mod thumbnail;
+pub mod transform;
+
 pub use image;
-use image::{DynamicImage, ImageResult};
+
+use crate::{
+    report::MAP_RESOLUTION,
+    transform::{apply_transforms, ImageTransform},
+};
+use image::{
+    error::{DecodingError, ImageFormatHint},
+    DynamicImage, ImageError, ImageResult,
+};
 use nuscenes_data::{
+    bundle::MapCrop,
+    data_loader::{self, Payload, SampleDataLoader},
     dataset::{MapRef, SampleDataRef},
-    serializable::FileFormat,
+    error::{Error, Result, UnsupportedFormat},
+    serializable::{FileFormat, SampleData},
+    storage::ReadOptions,
 };
 
 pub mod prelude {
-    pub use super::{MapRefImageExt, SampleDataRefImageExt};
+    pub use super::{
+        render::SampleDataRefRenderExt,
+        report::SceneRefReportExt,
+        thumbnail::{SampleRefThumbnailExt, SceneRefThumbnailExt},
+        transform::ImageTransform,
+        MapRefImageExt, SampleDataRefImageExt,
+    };
 }
 
 pub trait MapRefImageExt {
     fn load_dynamic_image(&self) -> ImageResult<DynamicImage>;
+
+    /// Load the image with custom retry and timeout options.
+    fn load_dynamic_image_with_options(&self, options: &ReadOptions) -> ImageResult<DynamicImage>;
+
+    /// Load the map region of a sample bundle.
+    fn load_map_crop(&self, crop: &MapCrop) -> ImageResult<DynamicImage>;
 }
 
 impl MapRefImageExt for MapRef {
     fn load_dynamic_image(&self) -> ImageResult<DynamicImage> {
-        image::open(self.path())
+        self.load_dynamic_image_with_options(&self.dataset().read_options)
+    }
+
+    fn load_dynamic_image_with_options(&self, options: &ReadOptions) -> ImageResult<DynamicImage> {
+        image::load_from_memory(&self.read_bytes_with_options(options)?)
+    }
+
+    fn load_map_crop(&self, crop: &MapCrop) -> ImageResult<DynamicImage> {
+        let image = self.load_dynamic_image()?;
+        let (width, height) = (image.width() as f64, image.height() as f64);
+
+        let [x, y] = crop.center;
+        let center_x = x / MAP_RESOLUTION;
+        let center_y = height - y / MAP_RESOLUTION;
+        let half = crop.size / MAP_RESOLUTION / 2.0;
+
+        let left = (center_x - half).clamp(0.0, width) as u32;
+        let right = (center_x + half).clamp(0.0, width) as u32;
+        let top = (center_y - half).clamp(0.0, height) as u32;
+        let bottom = (center_y + half).clamp(0.0, height) as u32;
+        Ok(image.crop_imm(left, top, right - left, bottom - top))
     }
 }
 
 pub trait SampleDataRefImageExt {
-    fn load_dynamic_image(&self) -> ImageResult<Option<DynamicImage>>;
+    /// Load the camera image. It fails with
+    /// [UnsupportedFormat](Error::UnsupportedFormat) if the sample
+    /// data is not a JPEG image.
+    fn load_dynamic_image(&self) -> Result<DynamicImage>;
+
+    /// Load the image with custom retry and timeout options.
+    fn load_dynamic_image_with_options(&self, options: &ReadOptions) -> Result<DynamicImage>;
+
+    /// Load the image and apply the transforms in order.
+    fn load_dynamic_image_with(
+        &self,
+        transforms: &[Box<dyn ImageTransform>],
+    ) -> Result<DynamicImage>;
 }
 
 impl SampleDataRefImageExt for SampleDataRef {
-    fn load_dynamic_image(&self) -> ImageResult<Option<DynamicImage>> {
+    fn load_dynamic_image(&self) -> Result<DynamicImage> {
+        self.load_dynamic_image_with_options(&self.dataset().read_options)
+    }
+
+    fn load_dynamic_image_with_options(&self, options: &ReadOptions) -> Result<DynamicImage> {
         if self.fileformat != FileFormat::Jpg {
-            return Ok(None);
+            return Err(UnsupportedFormat::of_sample_data(self, "jpg").into());
         }
 
-        Ok(Some(image::open(self.path())?))
+        let bytes = self.read_bytes_with_options(options)?;
+        image::load_from_memory(&bytes).map_err(to_error)
+    }
+
+    fn load_dynamic_image_with(
+        &self,
+        transforms: &[Box<dyn ImageTransform>],
+    ) -> Result<DynamicImage> {
+        let image = self.load_dynamic_image()?;
+        Ok(apply_transforms(image, transforms))
+    }
+}
+
+/// Load camera images as [DynamicImage].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DynamicImageLoader;
+
+impl SampleDataLoader for DynamicImageLoader {
+    fn name(&self) -> &str {
+        "image"
+    }
+
+    fn supports(&self, data: &SampleData) -> bool {
+        data.fileformat == FileFormat::Jpg
+    }
+
+    fn load(&self, data: &SampleDataRef) -> Result<Payload> {
+        let image = data.load_dynamic_image()?;
+        Ok(Payload::new(self.name(), image))
+    }
+}
+
+/// Register the loaders of this crate to
+/// [SampleDataRef::load_any](nuscenes_data::dataset::SampleDataRef::load_any).
+pub fn register_loaders() {
+    data_loader::register_loader(DynamicImageLoader);
+}
+
+pub(crate) fn to_error(err: ImageError) -> Error {
+    match err {
+        ImageError::IoError(err) => Error::IoError(err),
+        err => Error::ParseError(err.to_string()),
+    }
+}
+
+pub(crate) fn from_error(err: Error) -> ImageError {
+    match err {
+        Error::IoError(err) => ImageError::IoError(err),
+        err => ImageError::Decoding(DecodingError::new(ImageFormatHint::Unknown, err)),
     }
 }
@@ -0,0 +1,86 @@
+//! Rendering of annotation boxes on camera images.
+
+use crate::SampleDataRefImageExt;
+use image::{Rgb, RgbImage};
+use nuscenes_data::{
+    dataset::SampleDataRef,
+    error::Result,
+    projection::{ProjectedBox, ProjectionOptions},
+};
+
+const FRONT_COLOR: Rgb<u8> = Rgb([255, 0, 0]);
+const BOX_COLOR: Rgb<u8> = Rgb([0, 0, 255]);
+
+/// Box edges as pairs of corner indices. The first four edges
+/// outline the front face.
+const EDGES: [(usize, usize); 12] = [
+    (0, 1),
+    (1, 2),
+    (2, 3),
+    (3, 0),
+    (4, 5),
+    (5, 6),
+    (6, 7),
+    (7, 4),
+    (0, 4),
+    (1, 5),
+    (2, 6),
+    (3, 7),
+];
+
+pub trait SampleDataRefRenderExt {
+    /// Load the camera image and draw the annotation boxes selected
+    /// by the projection options on it.
+    fn render_boxes(&self, options: &ProjectionOptions) -> Result<Option<RgbImage>>;
+}
+
+impl SampleDataRefRenderExt for SampleDataRef {
+    fn render_boxes(&self, options: &ProjectionOptions) -> Result<Option<RgbImage>> {
+        let Some(boxes) = self.project_boxes(options) else {
+            return Ok(None);
+        };
+        let image = self.load_dynamic_image()?;
+
+        let mut canvas = image.to_rgb8();
+        for projected in &boxes {
+            draw_box(&mut canvas, projected);
+        }
+        Ok(Some(canvas))
+    }
+}
+
+/// Draw the wireframe of a projected box. Edges with a corner behind
+/// the camera are skipped.
+pub fn draw_box(canvas: &mut RgbImage, projected: &ProjectedBox) {
+    for (index, &(i, j)) in EDGES.iter().enumerate() {
+        let [u1, v1, d1] = projected.corners[i];
+        let [u2, v2, d2] = projected.corners[j];
+        if d1 <= 0.0 || d2 <= 0.0 {
+            continue;
+        }
+
+        let color = if index < 4 { FRONT_COLOR } else { BOX_COLOR };
+        draw_line(canvas, (u1, v1), (u2, v2), color);
+    }
+}
+
+fn draw_line(canvas: &mut RgbImage, from: (f64, f64), to: (f64, f64), color: Rgb<u8>) {
+    let (width, height) = canvas.dimensions();
+    let ((x1, y1), (x2, y2)) = (from, to);
+
+    // Limit the number of steps for lines far beyond the image
+    let steps = (x2 - x1)
+        .abs()
+        .max((y2 - y1).abs())
+        .ceil()
+        .clamp(1.0, (width + height) as f64 * 4.0) as usize;
+
+    for step in 0..=steps {
+        let ratio = step as f64 / steps as f64;
+        let x = (x1 + (x2 - x1) * ratio).round();
+        let y = (y1 + (y2 - y1) * ratio).round();
+        if (0.0..width as f64).contains(&x) && (0.0..height as f64).contains(&y) {
+            canvas.put_pixel(x as u32, y as u32, color);
+        }
+    }
+}
@@ -0,0 +1,221 @@
+//! Scene summary card rendering.
+
+use crate::{from_error, MapRefImageExt, SampleDataRefImageExt};
+use image::{imageops, GrayImage, ImageResult, Rgb, RgbImage};
+use nuscenes_data::{
+    dataset::{Dataset, MapRef, SceneRef},
+    error::Error,
+    serializable::{Channel, Token},
+};
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+/// The resolution of map masks in meters per pixel.
+pub const MAP_RESOLUTION: f64 = 0.1;
+
+/// The margin in meters around the trajectory on the cropped map.
+pub const MAP_CROP_MARGIN: f64 = 20.0;
+
+pub const THUMBNAIL_WIDTH: u32 = 320;
+
+const TRAJECTORY_COLOR: Rgb<u8> = Rgb([255, 0, 0]);
+
+/// Decoded map masks shared by the summary cards of scenes in the
+/// same map. A mask takes up to hundreds of megabytes, so the cache
+/// should be dropped after rendering.
+#[derive(Debug, Default)]
+pub struct MapMaskCache {
+    masks: Mutex<HashMap<Token, Arc<GrayImage>>>,
+}
+
+impl MapMaskCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get the mask of the map, which is read through the dataset
+    /// storage on the first access.
+    pub fn get(&self, map: &MapRef) -> ImageResult<Arc<GrayImage>> {
+        if let Some(mask) = self.masks.lock().unwrap().get(&map.token) {
+            return Ok(mask.clone());
+        }
+
+        // Decode without the lock, so that masks of different maps
+        // are decoded concurrently
+        let mask = Arc::new(map.load_dynamic_image()?.to_luma8());
+        let mut masks = self.masks.lock().unwrap();
+        Ok(masks.entry(map.token).or_insert(mask).clone())
+    }
+}
+
+pub trait SceneRefReportExt {
+    /// Render the summary card of the scene into the directory,
+    /// including a map crop with the ego trajectory and CAM_FRONT
+    /// thumbnails. It returns the path to the HTML page.
+    fn render_summary_card<P>(&self, out_dir: P) -> ImageResult<PathBuf>
+    where
+        P: AsRef<Path>;
+
+    /// Render the summary card with map masks from the cache. See
+    /// [render_summary_cards] to render all scenes of a dataset.
+    fn render_summary_card_with_masks<P>(
+        &self,
+        out_dir: P,
+        masks: &MapMaskCache,
+    ) -> ImageResult<PathBuf>
+    where
+        P: AsRef<Path>;
+}
+
+impl SceneRefReportExt for SceneRef {
+    fn render_summary_card<P>(&self, out_dir: P) -> ImageResult<PathBuf>
+    where
+        P: AsRef<Path>,
+    {
+        self.render_summary_card_with_masks(out_dir, &MapMaskCache::new())
+    }
+
+    fn render_summary_card_with_masks<P>(
+        &self,
+        out_dir: P,
+        masks: &MapMaskCache,
+    ) -> ImageResult<PathBuf>
+    where
+        P: AsRef<Path>,
+    {
+        let out_dir = out_dir.as_ref();
+        fs::create_dir_all(out_dir)?;
+
+        let summary = self.summary();
+        let name = &summary.name;
+
+        // Render the trajectory on the map
+        let map_image = match self.log().map() {
+            Some(map) => {
+                let file_name = format!("{name}_map.png");
+                let mask = masks.get(&map)?;
+                render_trajectory_map(&mask, &summary.ego_trajectory)?
+                    .save(out_dir.join(&file_name))?;
+                Some(file_name)
+            }
+            None => None,
+        };
+
+        // Save thumbnails of the first, middle and last samples
+        let samples: Vec<_> = self.sample_iter().collect();
+        let mut indices = vec![0, samples.len() / 2, samples.len().saturating_sub(1)];
+        indices.dedup();
+
+        let mut thumbnails = vec![];
+        for index in indices {
+            let Some(sample) = samples.get(index) else {
+                continue;
+            };
+            let Some(data) = sample.sample_data_by_channel(Channel::CamFront) else {
+                continue;
+            };
+            let image = match data.load_dynamic_image() {
+                Ok(image) => image,
+                Err(Error::UnsupportedFormat(_)) => continue,
+                Err(err) => return Err(from_error(err)),
+            };
+
+            let file_name = format!("{name}_{index:03}.jpg");
+            image
+                .thumbnail(THUMBNAIL_WIDTH, THUMBNAIL_WIDTH)
+                .to_rgb8()
+                .save(out_dir.join(&file_name))?;
+            thumbnails.push(file_name);
+        }
+
+        let path = out_dir.join(format!("{name}.html"));
+        fs::write(&path, summary.to_html(map_image.as_deref(), &thumbnails))?;
+        Ok(path)
+    }
+}
+
+/// Render the summary cards of all scenes into the directory in the
+/// order of scene names. Each map mask is decoded once. It returns
+/// the paths to the HTML pages.
+pub fn render_summary_cards<P>(dataset: &Dataset, out_dir: P) -> ImageResult<Vec<PathBuf>>
+where
+    P: AsRef<Path>,
+{
+    let out_dir = out_dir.as_ref();
+    let masks = MapMaskCache::new();
+    let mut scenes: Vec<_> = dataset.scene_iter().collect();
+    scenes.sort_by(|lhs, rhs| lhs.name.cmp(&rhs.name));
+    scenes
+        .iter()
+        .map(|scene| scene.render_summary_card_with_masks(out_dir, &masks))
+        .collect()
+}
+
+/// Crop the map mask around the trajectory and draw the trajectory
+/// on it. Trajectory points are in the global frame.
+pub fn render_trajectory_map(mask: &GrayImage, trajectory: &[[f64; 3]]) -> ImageResult<RgbImage> {
+    let (width, height) = mask.dimensions();
+
+    let pixels: Vec<(f64, f64)> = trajectory
+        .iter()
+        .map(|[x, y, _]| (x / MAP_RESOLUTION, height as f64 - y / MAP_RESOLUTION))
+        .collect();
+
+    // Compute the crop region
+    let margin = MAP_CROP_MARGIN / MAP_RESOLUTION;
+    let (left, top, right, bottom) = if pixels.is_empty() {
+        (0, 0, width, height)
+    } else {
+        let min_x = pixels.iter().map(|p| p.0).fold(f64::INFINITY, f64::min);
+        let max_x = pixels.iter().map(|p| p.0).fold(f64::NEG_INFINITY, f64::max);
+        let min_y = pixels.iter().map(|p| p.1).fold(f64::INFINITY, f64::min);
+        let max_y = pixels.iter().map(|p| p.1).fold(f64::NEG_INFINITY, f64::max);
+
+        let left = (min_x - margin).clamp(0.0, width as f64) as u32;
+        let right = (max_x + margin).clamp(0.0, width as f64) as u32;
+        let top = (min_y - margin).clamp(0.0, height as f64) as u32;
+        let bottom = (max_y + margin).clamp(0.0, height as f64) as u32;
+        (left, top, right.max(left + 1), bottom.max(top + 1))
+    };
+
+    let crop = imageops::crop_imm(mask, left, top, right - left, bottom - top).to_image();
+    let mut canvas = RgbImage::from_fn(crop.width(), crop.height(), |x, y| {
+        let value = crop.get_pixel(x, y)[0];
+        Rgb([value, value, value])
+    });
+
+    // Draw the trajectory
+    let mut draw_point = |x: f64, y: f64| {
+        let x = x - left as f64;
+        let y = y - top as f64;
+        for dx in -1..=1 {
+            for dy in -1..=1 {
+                let px = x.round() as i64 + dx;
+                let py = y.round() as i64 + dy;
+                if (0..canvas.width() as i64).contains(&px)
+                    && (0..canvas.height() as i64).contains(&py)
+                {
+                    canvas.put_pixel(px as u32, py as u32, TRAJECTORY_COLOR);
+                }
+            }
+        }
+    };
+
+    pixels.windows(2).for_each(|window| {
+        let [(x1, y1), (x2, y2)] = [window[0], window[1]];
+        let steps = (x2 - x1).abs().max((y2 - y1).abs()).ceil().max(1.0) as usize;
+        for step in 0..=steps {
+            let ratio = step as f64 / steps as f64;
+            draw_point(x1 + (x2 - x1) * ratio, y1 + (y2 - y1) * ratio);
+        }
+    });
+    if let [(x, y)] = pixels.as_slice() {
+        draw_point(*x, *y);
+    }
+
+    Ok(canvas)
+}
@@ -0,0 +1,135 @@
+//! Cached thumbnails of scenes and samples for browsing UIs.
+//!
+//! A thumbnail is the CAM_FRONT frame of a sample downscaled to fit
+//! in [THUMBNAIL_SIZE] pixels. [generate_thumbnails] stores the
+//! thumbnails of all samples as `<sample_token>.jpg` in the
+//! [THUMBNAIL_ARTIFACT] entry of a [DerivedCache], whose directory is
+//! chosen by the caller, so that listings do not decode
+//! full-resolution images again. Thumbnails are rendered in memory
+//! until the entry is built.
+//!
+//! ```ignore
+//! use nuscenes_data::cache::DerivedCache;
+//! use nuscenes_data_image::thumbnail::{generate_thumbnails, SampleRefThumbnailExt};
+//!
+//! let cache = DerivedCache::new(&dataset, "/path/to/cache");
+//! generate_thumbnails(&dataset, &cache)?;
+//! let thumbnail = sample.thumbnail(&cache)?;
+//! ```
+
+use crate::{from_error, to_error, SampleDataRefImageExt};
+use image::{DynamicImage, ImageResult};
+use nuscenes_data::{
+    browser,
+    cache::{ArtifactKey, DerivedCache},
+    dataset::{Dataset, SampleRef, SceneRef},
+    error::{Error, Result},
+    serializable::Channel,
+};
+use rayon::prelude::*;
+use std::path::{Path, PathBuf};
+
+/// The maximum width and height of thumbnails in pixels.
+pub const THUMBNAIL_SIZE: u32 = 160;
+
+/// The cache entry of the thumbnails of a dataset.
+pub const THUMBNAIL_ARTIFACT: ArtifactKey = ArtifactKey::new("thumbnails", 1);
+
+pub trait SampleRefThumbnailExt {
+    /// Get the thumbnail of the CAM_FRONT frame. It is read from the
+    /// cache if the thumbnails are generated, or rendered otherwise.
+    /// It returns `None` if the sample has no CAM_FRONT image.
+    fn thumbnail(&self, cache: &DerivedCache) -> ImageResult<Option<DynamicImage>>;
+
+    /// Get the path of the cached thumbnail. It returns `None` if the
+    /// thumbnails are not generated or the sample has no CAM_FRONT
+    /// image.
+    fn thumbnail_path(&self, cache: &DerivedCache) -> Option<PathBuf>;
+}
+
+impl SampleRefThumbnailExt for SampleRef {
+    fn thumbnail(&self, cache: &DerivedCache) -> ImageResult<Option<DynamicImage>> {
+        if cache.get(THUMBNAIL_ARTIFACT).is_none() {
+            return render_thumbnail(self).map_err(from_error);
+        }
+        match self.thumbnail_path(cache) {
+            Some(path) => Ok(Some(image::open(path)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn thumbnail_path(&self, cache: &DerivedCache) -> Option<PathBuf> {
+        let path = cache
+            .get(THUMBNAIL_ARTIFACT)?
+            .join(thumbnail_file_name(self));
+        path.is_file().then_some(path)
+    }
+}
+
+pub trait SceneRefThumbnailExt {
+    /// Get the thumbnail of the first sample of the scene.
+    fn thumbnail(&self, cache: &DerivedCache) -> ImageResult<Option<DynamicImage>>;
+}
+
+impl SceneRefThumbnailExt for SceneRef {
+    fn thumbnail(&self, cache: &DerivedCache) -> ImageResult<Option<DynamicImage>> {
+        match self.sample_iter().next() {
+            Some(sample) => sample.thumbnail(cache),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Generate the thumbnails of all samples in parallel into the cache,
+/// unless they are cached already. It returns the directory of the
+/// thumbnails.
+pub fn generate_thumbnails(dataset: &Dataset, cache: &DerivedCache) -> Result<PathBuf> {
+    cache.get_or_build(THUMBNAIL_ARTIFACT, |dir| {
+        let samples: Vec<_> = dataset.sample_iter().collect();
+        samples.par_iter().try_for_each(|sample| {
+            if let Some(thumbnail) = render_thumbnail(sample)? {
+                thumbnail
+                    .save_with_format(
+                        dir.join(thumbnail_file_name(sample)),
+                        image::ImageFormat::Jpeg,
+                    )
+                    .map_err(to_error)?;
+            }
+            Ok(())
+        })
+    })
+}
+
+/// Generate the static HTML browser of the dataset with downscaled
+/// camera thumbnails. See
+/// [Dataset::generate_browser_with](nuscenes_data::dataset::Dataset::generate_browser_with).
+pub fn generate_browser<P>(dataset: &Dataset, out_dir: P) -> Result<()>
+where
+    P: AsRef<Path>,
+{
+    dataset.generate_browser_with(out_dir, |data, path| {
+        let image = data.load_dynamic_image()?;
+        let size = browser::THUMBNAIL_SIZE;
+        DynamicImage::ImageRgb8(image.thumbnail(size, size).into_rgb8())
+            .save_with_format(path, image::ImageFormat::Jpeg)
+            .map_err(to_error)
+    })
+}
+
+fn render_thumbnail(sample: &SampleRef) -> Result<Option<DynamicImage>> {
+    let Some(data) = sample.sample_data_by_channel(Channel::CamFront) else {
+        return Ok(None);
+    };
+    let image = match data.load_dynamic_image() {
+        Ok(image) => image,
+        Err(Error::UnsupportedFormat(_)) => return Ok(None),
+        Err(err) => return Err(err),
+    };
+    let thumbnail =
+        DynamicImage::ImageRgb8(image.thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE).into_rgb8());
+    Ok(Some(thumbnail))
+}
+
+fn thumbnail_file_name(sample: &SampleRef) -> String {
+    format!("{}.jpg", sample.token)
+}
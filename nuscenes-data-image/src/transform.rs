@@ -0,0 +1,107 @@
+//! Image transforms applied at load time.
+
+use image::{imageops::FilterType, DynamicImage};
+use rand::Rng;
+
+/// An image transform applied by
+/// [load_dynamic_image_with](crate::SampleDataRefImageExt::load_dynamic_image_with).
+///
+/// Closures of type `Fn(DynamicImage) -> DynamicImage` are also
+/// image transforms.
+pub trait ImageTransform: Send + Sync {
+    fn apply(&self, image: DynamicImage) -> DynamicImage;
+}
+
+impl<F> ImageTransform for F
+where
+    F: Fn(DynamicImage) -> DynamicImage + Send + Sync,
+{
+    fn apply(&self, image: DynamicImage) -> DynamicImage {
+        self(image)
+    }
+}
+
+/// Apply the transforms in order.
+pub fn apply_transforms(
+    image: DynamicImage,
+    transforms: &[Box<dyn ImageTransform>],
+) -> DynamicImage {
+    transforms
+        .iter()
+        .fold(image, |image, transform| transform.apply(image))
+}
+
+/// Randomly brighten or darken the image by a value in `[-max_delta,
+/// max_delta]`.
+#[derive(Debug, Clone)]
+pub struct BrightnessJitter {
+    pub max_delta: i32,
+}
+
+impl ImageTransform for BrightnessJitter {
+    fn apply(&self, image: DynamicImage) -> DynamicImage {
+        let max_delta = self.max_delta.abs();
+        let delta = rand::thread_rng().gen_range(-max_delta..=max_delta);
+        image.brighten(delta)
+    }
+}
+
+/// Randomly adjust the contrast by a percentage in `[-max_delta,
+/// max_delta]`.
+#[derive(Debug, Clone)]
+pub struct ContrastJitter {
+    pub max_delta: f32,
+}
+
+impl ImageTransform for ContrastJitter {
+    fn apply(&self, image: DynamicImage) -> DynamicImage {
+        let max_delta = self.max_delta.abs();
+        let delta = rand::thread_rng().gen_range(-max_delta..=max_delta);
+        image.adjust_contrast(delta)
+    }
+}
+
+/// Crop a fixed region of the image.
+#[derive(Debug, Clone)]
+pub struct Crop {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl ImageTransform for Crop {
+    fn apply(&self, image: DynamicImage) -> DynamicImage {
+        image.crop_imm(self.x, self.y, self.width, self.height)
+    }
+}
+
+/// Crop a region of fixed size at a random location.
+#[derive(Debug, Clone)]
+pub struct RandomCrop {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl ImageTransform for RandomCrop {
+    fn apply(&self, image: DynamicImage) -> DynamicImage {
+        let mut rng = rand::thread_rng();
+        let x = rng.gen_range(0..=image.width().saturating_sub(self.width));
+        let y = rng.gen_range(0..=image.height().saturating_sub(self.height));
+        image.crop_imm(x, y, self.width, self.height)
+    }
+}
+
+/// Resize the image to the exact size, ignoring the aspect ratio.
+#[derive(Debug, Clone)]
+pub struct Resize {
+    pub width: u32,
+    pub height: u32,
+    pub filter: FilterType,
+}
+
+impl ImageTransform for Resize {
+    fn apply(&self, image: DynamicImage) -> DynamicImage {
+        image.resize_exact(self.width, self.height, self.filter)
+    }
+}
@@ -0,0 +1,409 @@
+//! The map expansion of a location with geometric queries.
+
+use crate::types::{
+    ArclinePath, DrivableArea, Lane, LaneConnectivity, LaneConnector, PedCrossing, RawExpansion,
+    RoadSegment, StopLine, Walkway,
+};
+use nuscenes_data::error::{Error, Result};
+use std::{
+    collections::HashMap,
+    fmt::{self, Display},
+    fs::File,
+    io::BufReader,
+    path::Path,
+};
+
+/// A polygonal layer of the map expansion.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Layer {
+    DrivableArea,
+    RoadSegment,
+    Lane,
+    LaneConnector,
+    PedCrossing,
+    Walkway,
+    StopLine,
+}
+
+impl Layer {
+    pub const ALL: [Layer; 7] = [
+        Layer::DrivableArea,
+        Layer::RoadSegment,
+        Layer::Lane,
+        Layer::LaneConnector,
+        Layer::PedCrossing,
+        Layer::Walkway,
+        Layer::StopLine,
+    ];
+
+    /// Get the layer name used by the devkit, such as `ped_crossing`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Layer::DrivableArea => "drivable_area",
+            Layer::RoadSegment => "road_segment",
+            Layer::Lane => "lane",
+            Layer::LaneConnector => "lane_connector",
+            Layer::PedCrossing => "ped_crossing",
+            Layer::Walkway => "walkway",
+            Layer::StopLine => "stop_line",
+        }
+    }
+}
+
+impl Display for Layer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A polygon with holes in the global frame.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PolygonShape {
+    pub exterior: Vec<[f64; 2]>,
+    pub holes: Vec<Vec<[f64; 2]>>,
+    /// The bounding box `[min_x, min_y, max_x, max_y]`.
+    pub bounds: [f64; 4],
+}
+
+impl PolygonShape {
+    fn new(exterior: Vec<[f64; 2]>, holes: Vec<Vec<[f64; 2]>>) -> Self {
+        let bounds = exterior.iter().fold(
+            [
+                f64::INFINITY,
+                f64::INFINITY,
+                f64::NEG_INFINITY,
+                f64::NEG_INFINITY,
+            ],
+            |[min_x, min_y, max_x, max_y], &[x, y]| {
+                [min_x.min(x), min_y.min(y), max_x.max(x), max_y.max(y)]
+            },
+        );
+        Self {
+            exterior,
+            holes,
+            bounds,
+        }
+    }
+
+    /// Check if the point is inside the exterior and outside the
+    /// holes.
+    pub fn contains(&self, point: [f64; 2]) -> bool {
+        let [min_x, min_y, max_x, max_y] = self.bounds;
+        let [x, y] = point;
+        if x < min_x || x > max_x || y < min_y || y > max_y {
+            return false;
+        }
+        ring_contains(&self.exterior, point)
+            && !self.holes.iter().any(|hole| ring_contains(hole, point))
+    }
+
+    /// Get the distance from the point to the polygon, which is zero
+    /// if the point is inside.
+    pub fn distance(&self, point: [f64; 2]) -> f64 {
+        if self.contains(point) {
+            return 0.0;
+        }
+        std::iter::once(&self.exterior)
+            .chain(&self.holes)
+            .map(|ring| ring_distance(ring, point))
+            .fold(f64::INFINITY, f64::min)
+    }
+
+    /// Get the distance from the point to the bounding box, which is a
+    /// lower bound of [PolygonShape::distance].
+    pub(crate) fn bounds_distance(&self, [x, y]: [f64; 2]) -> f64 {
+        let [min_x, min_y, max_x, max_y] = self.bounds;
+        let dx = (min_x - x).max(x - max_x).max(0.0);
+        let dy = (min_y - y).max(y - max_y).max(0.0);
+        dx.hypot(dy)
+    }
+}
+
+/// The map expansion of a location.
+#[derive(Debug, Clone)]
+pub struct MapExpansion {
+    pub version: String,
+    pub drivable_areas: Vec<DrivableArea>,
+    pub road_segments: Vec<RoadSegment>,
+    pub lanes: Vec<Lane>,
+    pub lane_connectors: Vec<LaneConnector>,
+    pub ped_crossings: Vec<PedCrossing>,
+    pub walkways: Vec<Walkway>,
+    pub stop_lines: Vec<StopLine>,
+    /// The connectivity of each lane or lane connector token.
+    pub connectivity: HashMap<String, LaneConnectivity>,
+    /// The centerline of each lane or lane connector token.
+    pub arcline_paths: HashMap<String, Vec<ArclinePath>>,
+    polygons: HashMap<String, PolygonShape>,
+    lines: HashMap<String, Vec<[f64; 2]>>,
+}
+
+impl MapExpansion {
+    /// Load a map expansion file, such as
+    /// `maps/expansion/boston-seaport.json`.
+    pub fn load<P>(path: P) -> Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let reader = BufReader::new(File::open(path)?);
+        let raw: RawExpansion = serde_json::from_reader(reader).map_err(|err| {
+            Error::ParseError(format!("failed to load {}: {err}", path.display()))
+        })?;
+        Self::from_raw(raw)
+    }
+
+    fn from_raw(raw: RawExpansion) -> Result<Self> {
+        let RawExpansion {
+            version,
+            node,
+            line,
+            polygon,
+            drivable_area,
+            road_segment,
+            lane,
+            lane_connector,
+            ped_crossing,
+            walkway,
+            stop_line,
+            connectivity,
+            arcline_path_3,
+        } = raw;
+
+        // Resolve node tokens into coordinates
+        let nodes: HashMap<String, [f64; 2]> = node
+            .into_iter()
+            .map(|node| (node.token, [node.x, node.y]))
+            .collect();
+        let resolve = |tokens: &[String]| -> Result<Vec<[f64; 2]>> {
+            tokens
+                .iter()
+                .map(|token| {
+                    nodes.get(token).copied().ok_or_else(|| {
+                        Error::CorruptedDataset(format!(
+                            "the token {token} does not refer to any map node"
+                        ))
+                    })
+                })
+                .collect()
+        };
+
+        let lines = line
+            .into_iter()
+            .map(|line| Ok((line.token, resolve(&line.node_tokens)?)))
+            .collect::<Result<_>>()?;
+        let polygons = polygon
+            .into_iter()
+            .map(|polygon| {
+                let exterior = resolve(&polygon.exterior_node_tokens)?;
+                let holes = polygon
+                    .holes
+                    .iter()
+                    .map(|hole| resolve(&hole.node_tokens))
+                    .collect::<Result<_>>()?;
+                Ok((polygon.token, PolygonShape::new(exterior, holes)))
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self {
+            version,
+            drivable_areas: drivable_area,
+            road_segments: road_segment,
+            lanes: lane,
+            lane_connectors: lane_connector,
+            ped_crossings: ped_crossing,
+            walkways: walkway,
+            stop_lines: stop_line,
+            connectivity,
+            arcline_paths: arcline_path_3,
+            polygons,
+            lines,
+        })
+    }
+
+    /// Get the polygon with the token.
+    pub fn polygon(&self, token: &str) -> Option<&PolygonShape> {
+        self.polygons.get(token)
+    }
+
+    /// Get the points of the line with the token.
+    pub fn line(&self, token: &str) -> Option<&[[f64; 2]]> {
+        Some(self.lines.get(token)?)
+    }
+
+    /// Iterate over the records of the layer as `(token, polygon)`
+    /// pairs. Drivable areas yield one pair per polygon.
+    pub fn layer_polygons(
+        &self,
+        layer: Layer,
+    ) -> Box<dyn Iterator<Item = (&str, &PolygonShape)> + '_> {
+        match layer {
+            Layer::DrivableArea => Box::new(self.drivable_areas.iter().flat_map(move |area| {
+                area.polygon_tokens.iter().filter_map(move |polygon_token| {
+                    self.record_polygon(&area.token, polygon_token)
+                })
+            })),
+            Layer::RoadSegment => Box::new(self.road_segments.iter().filter_map(move |record| {
+                self.record_polygon(&record.token, &record.polygon_token)
+            })),
+            Layer::Lane => Box::new(self.lanes.iter().filter_map(move |record| {
+                self.record_polygon(&record.token, &record.polygon_token)
+            })),
+            Layer::LaneConnector => {
+                Box::new(self.lane_connectors.iter().filter_map(move |record| {
+                    self.record_polygon(&record.token, record.polygon_token.as_deref()?)
+                }))
+            }
+            Layer::PedCrossing => Box::new(self.ped_crossings.iter().filter_map(move |record| {
+                self.record_polygon(&record.token, &record.polygon_token)
+            })),
+            Layer::Walkway => Box::new(self.walkways.iter().filter_map(move |record| {
+                self.record_polygon(&record.token, &record.polygon_token)
+            })),
+            Layer::StopLine => Box::new(self.stop_lines.iter().filter_map(move |record| {
+                self.record_polygon(&record.token, &record.polygon_token)
+            })),
+        }
+    }
+
+    fn record_polygon<'a>(
+        &'a self,
+        token: &'a str,
+        polygon_token: &str,
+    ) -> Option<(&'a str, &'a PolygonShape)> {
+        Some((token, self.polygons.get(polygon_token)?))
+    }
+
+    /// Sample the centerline of the lane or lane connector into poses
+    /// `[x, y, yaw]` at most `resolution` meters apart, like
+    /// `discretize_lane` of the devkit. It returns `None` if the
+    /// expansion has no centerline for the token, and an error if the
+    /// resolution is not positive.
+    pub fn discretize_lane(&self, token: &str, resolution: f64) -> Result<Option<Vec<[f64; 3]>>> {
+        check_lane_resolution(resolution)?;
+        let Some(paths) = self.arcline_paths.get(token) else {
+            return Ok(None);
+        };
+        let poses = paths
+            .iter()
+            .flat_map(|path| {
+                let length: f64 = path.segment_length.iter().sum();
+                let num_points = ((length / resolution).ceil() as usize + 1).max(2);
+                let step = length / (num_points - 1) as f64;
+                (0..num_points).map(move |index| pose_at_length(path, index as f64 * step))
+            })
+            .collect();
+        Ok(Some(poses))
+    }
+
+    /// Find the records of all layers containing the point in the
+    /// global frame, as `(layer, token)` pairs.
+    pub fn layers_at_point(&self, x: f64, y: f64) -> Vec<(Layer, &str)> {
+        let mut records: Vec<(Layer, &str)> = Layer::ALL
+            .iter()
+            .flat_map(|&layer| {
+                self.layer_polygons(layer)
+                    .filter(|(_, polygon)| polygon.contains([x, y]))
+                    .map(move |(token, _)| (layer, token))
+            })
+            .collect();
+        records.dedup();
+        records
+    }
+
+    /// Find the lanes within the radius in meters of the point in the
+    /// global frame, sorted by the distances. Lane connectors are not
+    /// included.
+    pub fn lanes_within_radius(&self, x: f64, y: f64, radius: f64) -> Vec<(&Lane, f64)> {
+        let mut lanes: Vec<(&Lane, f64)> = self
+            .lanes
+            .iter()
+            .filter_map(|lane| {
+                let polygon = self.polygons.get(&lane.polygon_token)?;
+                if polygon.bounds_distance([x, y]) > radius {
+                    return None;
+                }
+                let distance = polygon.distance([x, y]);
+                (distance <= radius).then_some((lane, distance))
+            })
+            .collect();
+        lanes.sort_by(|(_, lhs), (_, rhs)| lhs.total_cmp(rhs));
+        lanes
+    }
+}
+
+/// Get the pose at the distance along the arcline path.
+/// Check that the lane resolution is a positive finite distance.
+pub(crate) fn check_lane_resolution(resolution: f64) -> Result<()> {
+    if resolution > 0.0 && resolution.is_finite() {
+        Ok(())
+    } else {
+        Err(Error::InvalidArgument(format!(
+            "the lane resolution must be positive and finite, but got {resolution}"
+        )))
+    }
+}
+
+fn pose_at_length(path: &ArclinePath, length: f64) -> [f64; 3] {
+    let mut pose = path.start_pose;
+    let mut remaining = length.max(0.0);
+
+    for (primitive, &segment_length) in path.shape.chars().zip(&path.segment_length) {
+        let distance = remaining.min(segment_length);
+        let [x, y, yaw] = pose;
+        pose = match primitive {
+            'L' | 'R' => {
+                let direction = if primitive == 'L' { 1.0 } else { -1.0 };
+                let curvature = direction / path.radius;
+                let end_yaw = yaw + curvature * distance;
+                [
+                    x + (end_yaw.sin() - yaw.sin()) / curvature,
+                    y + (yaw.cos() - end_yaw.cos()) / curvature,
+                    end_yaw,
+                ]
+            }
+            _ => [x + distance * yaw.cos(), y + distance * yaw.sin(), yaw],
+        };
+
+        remaining -= distance;
+        if remaining <= 0.0 {
+            break;
+        }
+    }
+
+    pose
+}
+
+/// Test the point against the ring by ray casting.
+fn ring_contains(ring: &[[f64; 2]], [x, y]: [f64; 2]) -> bool {
+    let mut inside = false;
+    for (index, &[xi, yi]) in ring.iter().enumerate() {
+        let [xj, yj] = ring[(index + ring.len() - 1) % ring.len()];
+        if (yi > y) != (yj > y) && x < (xj - xi) * (y - yi) / (yj - yi) + xi {
+            inside = !inside;
+        }
+    }
+    inside
+}
+
+fn ring_distance(ring: &[[f64; 2]], point: [f64; 2]) -> f64 {
+    ring.iter()
+        .enumerate()
+        .map(|(index, &start)| {
+            let end = ring[(index + 1) % ring.len()];
+            segment_distance(start, end, point)
+        })
+        .fold(f64::INFINITY, f64::min)
+}
+
+fn segment_distance(start: [f64; 2], end: [f64; 2], point: [f64; 2]) -> f64 {
+    let [dx, dy] = [end[0] - start[0], end[1] - start[1]];
+    let len2 = dx * dx + dy * dy;
+    let ratio = if len2 > 0.0 {
+        (((point[0] - start[0]) * dx + (point[1] - start[1]) * dy) / len2).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    let [px, py] = [start[0] + dx * ratio, start[1] + dy * ratio];
+    (point[0] - px).hypot(point[1] - py)
+}
@@ -0,0 +1,61 @@
+//! Support of the nuScenes map expansion.
+//!
+//! The expansion is installed as `maps/expansion/<location>.json`
+//! files under the dataset directory, one per log location. Use
+//! [MapRefExpansionExt::expansion] to load the expansion of a map.
+
+mod expansion;
+pub mod prediction;
+pub mod types;
+pub mod vectors;
+
+pub use expansion::*;
+
+use nuscenes_data::{dataset::MapRef, error::Result};
+use std::{io, path::PathBuf};
+
+pub mod prelude {
+    pub use super::MapRefExpansionExt;
+}
+
+/// The directory of the map expansion files under the dataset
+/// directory.
+pub const EXPANSION_DIR: &str = "maps/expansion";
+
+pub trait MapRefExpansionExt {
+    /// Get the path of the expansion file of the map location. It
+    /// returns `None` if the map is not used by any log.
+    fn expansion_path(&self) -> Option<PathBuf>;
+
+    /// Load the expansion of the map location. It returns `None` if
+    /// the expansion is not installed. Loading parses the whole file,
+    /// so the result should be kept for repeated queries.
+    fn expansion(&self) -> Result<Option<MapExpansion>>;
+}
+
+impl MapRefExpansionExt for MapRef {
+    fn expansion_path(&self) -> Option<PathBuf> {
+        let log = self.log_iter().next()?;
+        let path = self
+            .dataset()
+            .dataset_dir
+            .join(EXPANSION_DIR)
+            .join(format!("{}.json", log.location));
+        Some(path)
+    }
+
+    fn expansion(&self) -> Result<Option<MapExpansion>> {
+        let Some(path) = self.expansion_path() else {
+            return Ok(None);
+        };
+        match MapExpansion::load(path) {
+            Ok(expansion) => Ok(Some(expansion)),
+            Err(nuscenes_data::error::Error::IoError(err))
+                if err.kind() == io::ErrorKind::NotFound =>
+            {
+                Ok(None)
+            }
+            Err(err) => Err(err),
+        }
+    }
+}
@@ -0,0 +1,153 @@
+//! Map inputs of the prediction metrics and features.
+//!
+//! The map expansions implement
+//! [DrivableArea](nuscenes_data::eval::prediction::DrivableArea) for
+//! the off-road rate, and [LaneFeatureExtractor] implements
+//! [MapFeatureExtractor] with lane centerlines around agents.
+//!
+//! ```ignore
+//! use nuscenes_data::{
+//!     eval::prediction::evaluate_predictions, prediction::features::extract_all_features,
+//! };
+//! use nuscenes_data_map::prediction::{LaneFeatureExtractor, MapExpansions};
+//!
+//! let expansions = MapExpansions::load(&dataset)?;
+//! let metrics = evaluate_predictions(&helper, &predictions, &eval_config, Some(&expansions))?;
+//! let extractor = LaneFeatureExtractor::new(expansions).with_lane_resolution(0.5)?;
+//! let features = extract_all_features(&dataset, &config, Some(&extractor));
+//! ```
+
+use crate::{expansion::check_lane_resolution, Layer, MapExpansion, MapRefExpansionExt};
+use nuscenes_data::{
+    dataset::Dataset,
+    error::Result,
+    eval::prediction::DrivableArea,
+    prediction::features::{MapFeatureExtractor, MapFeatures},
+};
+use std::{collections::HashMap, f64::consts::PI};
+
+/// The map expansions of the locations of a dataset.
+#[derive(Debug, Clone, Default)]
+pub struct MapExpansions {
+    expansions: HashMap<String, MapExpansion>,
+}
+
+impl MapExpansions {
+    /// Load the map expansions of the locations in the dataset.
+    /// Locations without installed expansions are skipped.
+    pub fn load(dataset: &Dataset) -> Result<Self> {
+        let mut expansions = HashMap::new();
+        for map in dataset.map_iter() {
+            let Some(log) = map.log_iter().next() else {
+                continue;
+            };
+            if let Some(expansion) = map.expansion()? {
+                expansions.insert(log.location.clone(), expansion);
+            }
+        }
+        Ok(Self { expansions })
+    }
+
+    /// Get the map expansion of the location.
+    pub fn get(&self, location: &str) -> Option<&MapExpansion> {
+        self.expansions.get(location)
+    }
+
+    pub fn insert(&mut self, location: String, expansion: MapExpansion) {
+        self.expansions.insert(location, expansion);
+    }
+
+    /// Iterate over the locations with map expansions.
+    pub fn locations(&self) -> impl Iterator<Item = &str> + '_ {
+        self.expansions.keys().map(String::as_str)
+    }
+}
+
+/// The drivable area of a single location. The `location` argument is
+/// not checked, so use [MapExpansions] for datasets with multiple
+/// locations.
+impl DrivableArea for MapExpansion {
+    fn is_drivable(&self, _location: &str, position: [f64; 2]) -> bool {
+        self.layer_polygons(Layer::DrivableArea)
+            .any(|(_, polygon)| polygon.contains(position))
+    }
+}
+
+/// Positions at locations without map expansions are not drivable.
+impl DrivableArea for MapExpansions {
+    fn is_drivable(&self, location: &str, position: [f64; 2]) -> bool {
+        self.get(location)
+            .is_some_and(|expansion| expansion.is_drivable(location, position))
+    }
+}
+
+/// Extracts the centerlines of lanes and lane connectors around agents
+/// as [MapFeatures::Vectors].
+///
+/// The centerlines are sampled by [MapExpansion::discretize_lane] and
+/// expressed in the agent frame, in which the agent is located at the
+/// origin and heads to the +y direction. Agents at locations without
+/// map expansions get no polylines.
+#[derive(Debug, Clone)]
+pub struct LaneFeatureExtractor {
+    pub expansions: MapExpansions,
+    /// Lanes within this distance in meters from the agent are
+    /// included.
+    pub radius: f64,
+    lane_resolution: f64,
+}
+
+impl LaneFeatureExtractor {
+    /// Create an extractor with a radius of 50 meters and a resolution
+    /// of 1 meter.
+    pub fn new(expansions: MapExpansions) -> Self {
+        Self {
+            expansions,
+            radius: 50.0,
+            lane_resolution: 1.0,
+        }
+    }
+
+    /// Set the maximum distance in meters between points of
+    /// centerlines. It is an error if the resolution is not positive.
+    pub fn with_lane_resolution(mut self, lane_resolution: f64) -> Result<Self> {
+        check_lane_resolution(lane_resolution)?;
+        self.lane_resolution = lane_resolution;
+        Ok(self)
+    }
+
+    pub fn lane_resolution(&self) -> f64 {
+        self.lane_resolution
+    }
+}
+
+impl MapFeatureExtractor for LaneFeatureExtractor {
+    fn extract(&self, location: &str, position: [f64; 2], heading: f64) -> MapFeatures {
+        let Some(expansion) = self.expansions.get(location) else {
+            return MapFeatures::Vectors(vec![]);
+        };
+
+        // Rotate the heading to the +y direction
+        let (sin, cos) = (PI / 2.0 - heading).sin_cos();
+        let to_local = |[x, y, _]: [f64; 3]| {
+            let [dx, dy] = [x - position[0], y - position[1]];
+            [cos * dx - sin * dy, sin * dx + cos * dy]
+        };
+
+        let polylines = [Layer::Lane, Layer::LaneConnector]
+            .into_iter()
+            .flat_map(|layer| expansion.layer_polygons(layer))
+            .filter(|(_, polygon)| {
+                polygon.bounds_distance(position) <= self.radius
+                    && polygon.distance(position) <= self.radius
+            })
+            .filter_map(|(token, _)| {
+                let poses = expansion
+                    .discretize_lane(token, self.lane_resolution)
+                    .expect("the lane resolution is checked")?;
+                Some(poses.into_iter().map(to_local).collect())
+            })
+            .collect();
+        MapFeatures::Vectors(polylines)
+    }
+}
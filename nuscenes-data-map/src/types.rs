@@ -0,0 +1,150 @@
+//! Records of the map expansion JSON files.
+//!
+//! Map expansion tokens are UUID strings rather than the hex tokens of
+//! the dataset tables, so they are kept as strings. Geometry records,
+//! namely nodes, lines and polygons, are resolved by [MapExpansion]
+//! and not exposed as records.
+//!
+//! [MapExpansion]: crate::MapExpansion
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DrivableArea {
+    pub token: String,
+    pub polygon_tokens: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RoadSegment {
+    pub token: String,
+    pub polygon_token: String,
+    pub is_intersection: bool,
+    #[serde(default)]
+    pub drivable_area_token: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Lane {
+    pub token: String,
+    pub polygon_token: String,
+    /// The lane type, such as `CAR`.
+    pub lane_type: String,
+    #[serde(default)]
+    pub from_edge_line_token: Option<String>,
+    #[serde(default)]
+    pub to_edge_line_token: Option<String>,
+}
+
+/// A lane connecting lanes through an intersection.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LaneConnector {
+    pub token: String,
+    #[serde(default)]
+    pub polygon_token: Option<String>,
+}
+
+/// A pedestrian crossing.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PedCrossing {
+    pub token: String,
+    pub polygon_token: String,
+    #[serde(default)]
+    pub road_segment_token: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Walkway {
+    pub token: String,
+    pub polygon_token: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StopLine {
+    pub token: String,
+    pub polygon_token: String,
+    /// The reason to stop, such as `PED_CROSSING` or `TRAFFIC_LIGHT`.
+    pub stop_line_type: String,
+    #[serde(default)]
+    pub ped_crossing_tokens: Vec<String>,
+    #[serde(default)]
+    pub traffic_light_tokens: Vec<String>,
+    #[serde(default)]
+    pub road_block_token: Option<String>,
+}
+
+/// The lanes and lane connectors entering and leaving a lane.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LaneConnectivity {
+    pub incoming: Vec<String>,
+    pub outgoing: Vec<String>,
+}
+
+/// A segment of a lane centerline made of three primitives, which
+/// are left turns, straight lines or right turns.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArclinePath {
+    /// The start pose `[x, y, yaw]` in the global frame.
+    pub start_pose: [f64; 3],
+    /// The end pose `[x, y, yaw]` in the global frame.
+    pub end_pose: [f64; 3],
+    /// The primitives, such as `LSR`, where `L`, `S` and `R` are a
+    /// left turn, a straight line and a right turn.
+    pub shape: String,
+    /// The turning radius in meters.
+    pub radius: f64,
+    /// The length of each primitive in meters.
+    pub segment_length: [f64; 3],
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct Node {
+    pub token: String,
+    pub x: f64,
+    pub y: f64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct Line {
+    pub token: String,
+    pub node_tokens: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct Polygon {
+    pub token: String,
+    pub exterior_node_tokens: Vec<String>,
+    #[serde(default)]
+    pub holes: Vec<Hole>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct Hole {
+    pub node_tokens: Vec<String>,
+}
+
+/// The layout of a map expansion file. Layers not supported are
+/// ignored.
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct RawExpansion {
+    #[serde(default)]
+    pub version: String,
+    pub node: Vec<Node>,
+    pub line: Vec<Line>,
+    pub polygon: Vec<Polygon>,
+    pub drivable_area: Vec<DrivableArea>,
+    pub road_segment: Vec<RoadSegment>,
+    pub lane: Vec<Lane>,
+    #[serde(default)]
+    pub lane_connector: Vec<LaneConnector>,
+    pub ped_crossing: Vec<PedCrossing>,
+    pub walkway: Vec<Walkway>,
+    pub stop_line: Vec<StopLine>,
+    #[serde(default)]
+    pub connectivity: HashMap<String, LaneConnectivity>,
+    /// The centerlines of lanes and lane connectors, which are
+    /// available since expansion v1.2.
+    #[serde(default)]
+    pub arcline_path_3: HashMap<String, Vec<ArclinePath>>,
+}
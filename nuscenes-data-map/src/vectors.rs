@@ -0,0 +1,309 @@
+//! Vectorized scene graphs for trajectory prediction, in the style of
+//! [VectorNet](https://arxiv.org/abs/2005.04259).
+//!
+//! A [SceneGraph] describes the surroundings of an agent at a
+//! prediction instant as polylines in the agent frame, in which the
+//! agent is located at the origin and heads to the +y direction:
+//!
+//! - `agent`: the past positions of each agent within the radius,
+//!   including the focal agent, in chronological order and ending at
+//!   the current position.
+//! - `lane` and `lane_connector`: the centerlines of lanes and lane
+//!   connectors sampled by [MapExpansion::discretize_lane].
+//! - `ped_crossing`: the closed exterior of pedestrian crossings, whose
+//!   last point repeats the first one.
+//!
+//! [SceneGraphExtractor::export] writes one scene graph per line as
+//! JSON, such as
+//!
+//! ```json
+//! {"instance_token": "...", "sample_token": "...", "location": "boston-seaport",
+//!  "translation": [x, y, z], "rotation": [w, x, y, z],
+//!  "polylines": [{"kind": "agent", "token": "...", "category": "vehicle.car",
+//!                 "points": [[x, y], ...]}, ...],
+//!  "future": [[x, y], ...]}
+//! ```
+//!
+//! where `translation` and `rotation` are the global pose of the focal
+//! agent and `future` is its future trajectory in the agent frame.
+
+use crate::{expansion::check_lane_resolution, prediction::MapExpansions, Layer};
+use nuscenes_data::{
+    dataset::{Dataset, SampleAnnotationRef},
+    error::Result,
+    estimate::{CountingWriter, ExportEstimate},
+    prediction::{convert_global_coords_to_local, helper::PredictHelper},
+    serializable::Token,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+#[derive(Debug, Clone)]
+pub struct VectorConfig {
+    /// The length of past agent trajectories in seconds.
+    pub past_seconds: f64,
+    /// The length of the future trajectory of the focal agent in
+    /// seconds.
+    pub future_seconds: f64,
+    /// Agents and map elements within this distance in meters from the
+    /// focal agent are included.
+    pub radius: f64,
+    /// The maximum distance in meters between points of lane
+    /// centerlines.
+    pub lane_resolution: f64,
+}
+
+impl Default for VectorConfig {
+    fn default() -> Self {
+        Self {
+            past_seconds: 2.0,
+            future_seconds: 6.0,
+            radius: 50.0,
+            lane_resolution: 1.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PolylineKind {
+    Agent,
+    Lane,
+    LaneConnector,
+    PedCrossing,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Polyline {
+    pub kind: PolylineKind,
+    /// The instance token of agents or the record token of map
+    /// elements.
+    pub token: String,
+    /// The category name of agents.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub category: Option<String>,
+    /// The points in the agent frame.
+    pub points: Vec<[f64; 2]>,
+}
+
+/// A segment of a polyline, which is the input unit of VectorNet.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Vector {
+    /// The index of the polyline in [SceneGraph::polylines].
+    pub polyline: usize,
+    pub kind: PolylineKind,
+    pub start: [f64; 2],
+    pub end: [f64; 2],
+}
+
+/// The polylines around an agent at a prediction instant.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SceneGraph {
+    pub instance_token: Token,
+    pub sample_token: Token,
+    pub location: String,
+    /// The global position of the focal agent.
+    pub translation: [f64; 3],
+    /// The global orientation of the focal agent.
+    pub rotation: [f64; 4],
+    pub polylines: Vec<Polyline>,
+    /// The future positions of the focal agent in the agent frame.
+    pub future: Vec<[f64; 2]>,
+}
+
+impl SceneGraph {
+    /// Split the polylines into vectors between consecutive points.
+    pub fn vectors(&self) -> impl Iterator<Item = Vector> + '_ {
+        self.polylines
+            .iter()
+            .enumerate()
+            .flat_map(|(index, polyline)| {
+                polyline.points.windows(2).map(move |pair| Vector {
+                    polyline: index,
+                    kind: polyline.kind,
+                    start: pair[0],
+                    end: pair[1],
+                })
+            })
+    }
+}
+
+/// Builds scene graphs from a dataset and the map expansions of its
+/// locations.
+pub struct SceneGraphExtractor {
+    config: VectorConfig,
+    helper: PredictHelper,
+    expansions: MapExpansions,
+}
+
+impl SceneGraphExtractor {
+    /// Load the map expansions of the locations in the dataset.
+    /// Locations without installed expansions are skipped. It is an
+    /// error if the lane resolution of the config is not positive.
+    pub fn new(dataset: &Dataset, config: VectorConfig) -> Result<Self> {
+        check_lane_resolution(config.lane_resolution)?;
+        Ok(Self {
+            config,
+            helper: PredictHelper::new(dataset.clone()),
+            expansions: MapExpansions::load(dataset)?,
+        })
+    }
+
+    /// Build the scene graph of the agent at the sample of the
+    /// annotation. It returns `None` if the map expansion of the
+    /// location is not installed.
+    pub fn extract(&self, annotation: &SampleAnnotationRef) -> Result<Option<SceneGraph>> {
+        let VectorConfig {
+            past_seconds,
+            future_seconds,
+            radius,
+            lane_resolution,
+        } = self.config;
+        let sample = annotation.sample();
+        let location = sample.scene().log().location.clone();
+        let Some(expansion) = self.expansions.get(&location) else {
+            return Ok(None);
+        };
+
+        let translation = annotation.translation;
+        let rotation = annotation.rotation;
+        let center = [translation[0], translation[1]];
+        let to_local =
+            |point: [f64; 2]| convert_global_coords_to_local(point, translation, rotation);
+        let in_range = |[x, y]: [f64; 2]| (x - center[0]).hypot(y - center[1]) <= radius;
+
+        // Agent polylines
+        let mut pasts = self
+            .helper
+            .get_past_for_sample(sample.token, past_seconds, false)
+            .unwrap_or_default();
+        let agents = sample.annotation_iter().filter_map(|other| {
+            let [x, y, _] = other.translation;
+            if !in_range([x, y]) {
+                return None;
+            }
+            let mut points = pasts.remove(&other.instance_token).unwrap_or_default();
+            points.reverse();
+            points.push([x, y]);
+            Some(Polyline {
+                kind: PolylineKind::Agent,
+                token: other.instance_token.to_string(),
+                category: Some(other.instance().category().name.clone()),
+                points: points.into_iter().map(to_local).collect(),
+            })
+        });
+
+        // Lane centerlines and crosswalks
+        let near = |layer: Layer| {
+            expansion.layer_polygons(layer).filter(move |(_, polygon)| {
+                polygon.bounds_distance(center) <= radius && polygon.distance(center) <= radius
+            })
+        };
+        let mut centerlines = vec![];
+        for (layer, kind) in [
+            (Layer::Lane, PolylineKind::Lane),
+            (Layer::LaneConnector, PolylineKind::LaneConnector),
+        ] {
+            for (token, _) in near(layer) {
+                let Some(poses) = expansion.discretize_lane(token, lane_resolution)? else {
+                    continue;
+                };
+                centerlines.push(Polyline {
+                    kind,
+                    token: token.to_string(),
+                    category: None,
+                    points: poses.iter().map(|&[x, y, _]| to_local([x, y])).collect(),
+                });
+            }
+        }
+        let crossings = near(Layer::PedCrossing).map(|(token, polygon)| {
+            let closing = polygon.exterior.first().copied();
+            Polyline {
+                kind: PolylineKind::PedCrossing,
+                token: token.to_string(),
+                category: None,
+                points: polygon
+                    .exterior
+                    .iter()
+                    .copied()
+                    .chain(closing)
+                    .map(to_local)
+                    .collect(),
+            }
+        });
+
+        let polylines = agents.chain(centerlines).chain(crossings).collect();
+        let future = self
+            .helper
+            .get_future_for_agent(
+                annotation.instance_token,
+                sample.token,
+                future_seconds,
+                true,
+            )
+            .unwrap_or_default();
+
+        Ok(Some(SceneGraph {
+            instance_token: annotation.instance_token,
+            sample_token: sample.token,
+            location,
+            translation,
+            rotation,
+            polylines,
+            future,
+        }))
+    }
+
+    /// Write the scene graphs of the annotations into a file, one JSON
+    /// object per line. Annotations at locations without map
+    /// expansions are skipped. It returns the number of written scene
+    /// graphs.
+    pub fn export<I, P>(&self, annotations: I, path: P) -> Result<usize>
+    where
+        I: IntoIterator<Item = SampleAnnotationRef>,
+        P: AsRef<Path>,
+    {
+        let mut writer = BufWriter::new(File::create(path)?);
+        let mut count = 0;
+        for annotation in annotations {
+            let Some(graph) = self.extract(&annotation)? else {
+                continue;
+            };
+            serde_json::to_writer(&mut writer, &graph).map_err(io::Error::from)?;
+            writer.write_all(b"\n")?;
+            count += 1;
+        }
+        writer.flush()?;
+        Ok(count)
+    }
+
+    /// Estimate the file written by [SceneGraphExtractor::export]
+    /// without writing it. Each scene graph is extracted and
+    /// serialized to measure its size, and the number of samples is
+    /// the number of scene graphs.
+    pub fn estimate<I>(&self, annotations: I) -> Result<ExportEstimate>
+    where
+        I: IntoIterator<Item = SampleAnnotationRef>,
+    {
+        let mut writer = CountingWriter::default();
+        let mut count = 0;
+        for annotation in annotations {
+            let Some(graph) = self.extract(&annotation)? else {
+                continue;
+            };
+            serde_json::to_writer(&mut writer, &graph).map_err(io::Error::from)?;
+            writer.write_all(b"\n")?;
+            count += 1;
+        }
+
+        Ok(ExportEstimate {
+            num_samples: count,
+            num_files: 1,
+            num_bytes: writer.num_bytes,
+        })
+    }
+}
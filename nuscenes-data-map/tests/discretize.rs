@@ -0,0 +1,116 @@
+//! Discretization of lane centerlines on a synthetic map expansion.
+
+use nuscenes_data::error::Error;
+use nuscenes_data_map::MapExpansion;
+use std::{
+    f64::consts::{FRAC_PI_2, PI},
+    fs,
+    path::Path,
+};
+
+/// A 10 m square drivable area with a straight lane `straight` from
+/// (1, 5) to (9, 5) and a left quarter turn `turn` of radius 2 m from
+/// the origin to (2, 2).
+const EXPANSION: &str = r#"{
+    "version": "1.3",
+    "node": [
+        {"token": "n0", "x": 0, "y": 0},
+        {"token": "n1", "x": 10, "y": 0},
+        {"token": "n2", "x": 10, "y": 10},
+        {"token": "n3", "x": 0, "y": 10}
+    ],
+    "line": [],
+    "polygon": [{"token": "p0", "exterior_node_tokens": ["n0", "n1", "n2", "n3"]}],
+    "drivable_area": [{"token": "d0", "polygon_tokens": ["p0"]}],
+    "road_segment": [],
+    "lane": [
+        {"token": "straight", "polygon_token": "p0", "lane_type": "CAR"},
+        {"token": "turn", "polygon_token": "p0", "lane_type": "CAR"}
+    ],
+    "ped_crossing": [],
+    "walkway": [],
+    "stop_line": [],
+    "connectivity": {},
+    "arcline_path_3": {
+        "straight": [{
+            "start_pose": [1, 5, 0], "end_pose": [9, 5, 0], "shape": "SSS",
+            "radius": 999, "segment_length": [8, 0, 0]
+        }],
+        "turn": [{
+            "start_pose": [0, 0, 0], "end_pose": [2, 2, 1.5707963267948966], "shape": "LSS",
+            "radius": 2, "segment_length": [3.141592653589793, 0, 0]
+        }]
+    }
+}"#;
+
+fn load_expansion(name: &str) -> MapExpansion {
+    let path = Path::new(env!("CARGO_TARGET_TMPDIR")).join(format!("{name}.json"));
+    fs::write(&path, EXPANSION).unwrap();
+    MapExpansion::load(&path).unwrap()
+}
+
+fn assert_close(actual: [f64; 3], expect: [f64; 3]) {
+    let error = actual
+        .iter()
+        .zip(&expect)
+        .map(|(lhs, rhs)| (lhs - rhs).abs())
+        .fold(0.0, f64::max);
+    assert!(error < 1e-9, "expect {expect:?}, but got {actual:?}");
+}
+
+#[test]
+fn discretize_straight_lane() {
+    let expansion = load_expansion("discretize_straight_lane");
+
+    let poses = expansion.discretize_lane("straight", 1.0).unwrap().unwrap();
+    assert_eq!(poses.len(), 9);
+    for (index, &pose) in poses.iter().enumerate() {
+        assert_close(pose, [1.0 + index as f64, 5.0, 0.0]);
+    }
+
+    // The points are evenly spaced when the resolution does not divide
+    // the length
+    let poses = expansion.discretize_lane("straight", 3.0).unwrap().unwrap();
+    assert_eq!(poses.len(), 4);
+    for (index, &pose) in poses.iter().enumerate() {
+        assert_close(pose, [1.0 + index as f64 * 8.0 / 3.0, 5.0, 0.0]);
+    }
+}
+
+#[test]
+fn discretize_arc_lane() {
+    let expansion = load_expansion("discretize_arc_lane");
+    let resolution = 0.5;
+
+    let poses = expansion
+        .discretize_lane("turn", resolution)
+        .unwrap()
+        .unwrap();
+    assert_eq!(poses.len(), (PI / resolution).ceil() as usize + 1);
+    assert_close(poses[0], [0.0, 0.0, 0.0]);
+    assert_close(*poses.last().unwrap(), [2.0, 2.0, FRAC_PI_2]);
+
+    // The points lie on the circle around (0, 2) and head along it
+    for &[x, y, yaw] in &poses {
+        assert!((x.hypot(y - 2.0) - 2.0).abs() < 1e-9);
+        assert_close([x, y, yaw], [2.0 * yaw.sin(), 2.0 - 2.0 * yaw.cos(), yaw]);
+    }
+    for pair in poses.windows(2) {
+        let [x0, y0, _] = pair[0];
+        let [x1, y1, _] = pair[1];
+        assert!((x1 - x0).hypot(y1 - y0) <= resolution);
+    }
+}
+
+#[test]
+fn discretize_lane_rejects_invalid_resolutions() {
+    let expansion = load_expansion("discretize_lane_rejects_invalid_resolutions");
+
+    for resolution in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+        assert!(matches!(
+            expansion.discretize_lane("straight", resolution),
+            Err(Error::InvalidArgument(_))
+        ));
+    }
+    assert!(expansion.discretize_lane("missing", 1.0).unwrap().is_none());
+}
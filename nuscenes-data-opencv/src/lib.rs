@@ -1,40 +1,100 @@
 use nuscenes_data::{
+    data_loader::{self, Payload, SampleDataLoader},
     dataset::{MapRef, SampleDataRef},
-    serializable::FileFormat,
+    error::{Error, Result, UnsupportedFormat},
+    serializable::{FileFormat, SampleData},
+    storage::ReadOptions,
 };
 use opencv::{
     self as cv,
-    imgcodecs::{imread, IMREAD_COLOR},
+    core::Vector,
+    imgcodecs::{imdecode, IMREAD_COLOR},
     prelude::*,
 };
+use std::io;
+
+pub mod panorama;
 
 pub mod prelude {
-    pub use super::{MapRefImageExt, SampleDataRefImageExt};
+    pub use super::{panorama::SampleRefPanoramaExt, MapRefImageExt, SampleDataRefImageExt};
 }
 
 pub trait MapRefImageExt {
     fn load_opencv_mat(&self) -> cv::Result<Mat>;
+
+    /// Load the image with custom retry and timeout options.
+    fn load_opencv_mat_with_options(&self, options: &ReadOptions) -> cv::Result<Mat>;
 }
 
 impl MapRefImageExt for MapRef {
     fn load_opencv_mat(&self) -> cv::Result<Mat> {
-        let path = format!("{}", self.path().display());
-        imread(&path, IMREAD_COLOR)
+        self.load_opencv_mat_with_options(&self.dataset().read_options)
+    }
+
+    fn load_opencv_mat_with_options(&self, options: &ReadOptions) -> cv::Result<Mat> {
+        let bytes = self.read_bytes_with_options(options).map_err(io_error)?;
+        imdecode(&Vector::from_slice(&bytes), IMREAD_COLOR)
     }
 }
 
 pub trait SampleDataRefImageExt {
-    fn load_opencv_mat(&self) -> cv::Result<Option<Mat>>;
+    /// Load the camera image. It fails with
+    /// [UnsupportedFormat](Error::UnsupportedFormat) if the sample
+    /// data is not a JPEG image.
+    fn load_opencv_mat(&self) -> Result<Mat>;
+
+    /// Load the image with custom retry and timeout options.
+    fn load_opencv_mat_with_options(&self, options: &ReadOptions) -> Result<Mat>;
 }
 
 impl SampleDataRefImageExt for SampleDataRef {
-    fn load_opencv_mat(&self) -> cv::Result<Option<Mat>> {
+    fn load_opencv_mat(&self) -> Result<Mat> {
+        self.load_opencv_mat_with_options(&self.dataset().read_options)
+    }
+
+    fn load_opencv_mat_with_options(&self, options: &ReadOptions) -> Result<Mat> {
         if self.fileformat != FileFormat::Jpg {
-            return Ok(None);
+            return Err(UnsupportedFormat::of_sample_data(self, "jpg").into());
         }
 
-        let path = format!("{}", self.path().display());
-        let mat = imread(&path, IMREAD_COLOR)?;
-        Ok(Some(mat))
+        let bytes = self.read_bytes_with_options(options)?;
+        imdecode(&Vector::from_slice(&bytes), IMREAD_COLOR).map_err(to_error)
+    }
+}
+
+/// Load camera images as [Mat].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MatLoader;
+
+impl SampleDataLoader for MatLoader {
+    fn name(&self) -> &str {
+        "opencv"
     }
+
+    fn supports(&self, data: &SampleData) -> bool {
+        data.fileformat == FileFormat::Jpg
+    }
+
+    fn load(&self, data: &SampleDataRef) -> Result<Payload> {
+        let mat = data.load_opencv_mat()?;
+        Ok(Payload::new(self.name(), mat))
+    }
+}
+
+/// Register the loaders of this crate to
+/// [SampleDataRef::load_any](nuscenes_data::dataset::SampleDataRef::load_any).
+pub fn register_loaders() {
+    data_loader::register_loader(MatLoader);
+}
+
+fn io_error(err: io::Error) -> cv::Error {
+    cv::Error::new(cv::core::StsError, err.to_string())
+}
+
+fn to_error(err: cv::Error) -> Error {
+    Error::ParseError(err.to_string())
+}
+
+pub(crate) fn from_error(err: Error) -> cv::Error {
+    cv::Error::new(cv::core::StsError, err.to_string())
 }
@@ -0,0 +1,145 @@
+//! Approximate surround-view panoramas.
+//!
+//! Camera images of a sample are projected on a unit cylinder around
+//! the ego vehicle using the camera calibrations. Camera translations
+//! are ignored, so nearby objects may appear misaligned at image
+//! seams.
+
+use crate::{from_error, SampleDataRefImageExt};
+use nuscenes_data::{
+    dataset::SampleRef,
+    error::Result,
+    geometry::{quat_conj, quat_rotate},
+    serializable::Modality,
+};
+use opencv::{
+    self as cv,
+    core::{self, Scalar},
+    imgproc,
+    prelude::*,
+};
+use std::f64::consts::PI;
+
+#[derive(Debug, Clone)]
+pub struct PanoramaOptions {
+    /// The width of the panorama in pixels, covering 360 degrees.
+    pub width: i32,
+    /// The vertical field of view in radians.
+    pub vertical_fov: f64,
+}
+
+impl Default for PanoramaOptions {
+    fn default() -> Self {
+        Self {
+            width: 3600,
+            vertical_fov: 40f64.to_radians(),
+        }
+    }
+}
+
+pub trait SampleRefPanoramaExt {
+    /// Stitch camera images of the sample into a cylindrical panorama.
+    ///
+    /// The ego forward direction is at the center column and the left
+    /// side is on the left half. It returns `None` if the sample has
+    /// no camera data.
+    fn render_panorama(&self, options: &PanoramaOptions) -> cv::Result<Option<Mat>>;
+}
+
+impl SampleRefPanoramaExt for SampleRef {
+    fn render_panorama(&self, options: &PanoramaOptions) -> cv::Result<Option<Mat>> {
+        let cameras: Vec<_> = self
+            .keyframe_data_iter()
+            .filter_map(|data| {
+                let calibrated_sensor = data.calibrated_sensor();
+                if calibrated_sensor.sensor().modality != Modality::Camera {
+                    return None;
+                }
+                let intrinsic = calibrated_sensor.intrinsic()?;
+                let ego_to_camera = quat_conj(calibrated_sensor.rotation);
+                Some((data, ego_to_camera, intrinsic))
+            })
+            .collect();
+        if cameras.is_empty() {
+            return Ok(None);
+        }
+
+        let images = cameras
+            .iter()
+            .map(|(data, _, _)| data.load_opencv_mat())
+            .collect::<Result<Vec<_>>>()
+            .map_err(from_error)?;
+        let sizes: Vec<_> = images
+            .iter()
+            .map(|image| image.size())
+            .collect::<cv::Result<_>>()?;
+
+        let width = options.width;
+        let pixel_angle = 2.0 * PI / width as f64;
+        let height = ((2.0 * (options.vertical_fov / 2.0).tan()) / pixel_angle).round() as i32;
+
+        // Pick the camera with the ray closest to its optical axis for
+        // each pixel
+        let new_map =
+            || Mat::new_rows_cols_with_default(height, width, core::CV_32FC1, Scalar::all(-1.0));
+        let mut maps: Vec<(Mat, Mat)> = (0..cameras.len())
+            .map(|_| Ok((new_map()?, new_map()?)))
+            .collect::<cv::Result<_>>()?;
+        let mut masks: Vec<Mat> = (0..cameras.len())
+            .map(|_| {
+                Mat::new_rows_cols_with_default(height, width, core::CV_8UC1, Scalar::all(0.0))
+            })
+            .collect::<cv::Result<_>>()?;
+
+        for row in 0..height {
+            let elevation = (height as f64 / 2.0 - (row as f64 + 0.5)) * pixel_angle;
+
+            for col in 0..width {
+                let theta = PI - (col as f64 + 0.5) * pixel_angle;
+                let ray = [theta.cos(), theta.sin(), elevation];
+
+                let best = cameras
+                    .iter()
+                    .zip(&sizes)
+                    .enumerate()
+                    .filter_map(|(index, ((_, ego_to_camera, intrinsic), size))| {
+                        let [x, y, z] = quat_rotate(*ego_to_camera, ray);
+                        let [u, v] = intrinsic.project([x, y, z])?;
+                        let inside = (0.0..size.width as f64).contains(&u)
+                            && (0.0..size.height as f64).contains(&v);
+                        inside.then(|| {
+                            let score = z / (x * x + y * y + z * z).sqrt();
+                            (index, u, v, score)
+                        })
+                    })
+                    .max_by(|lhs, rhs| lhs.3.total_cmp(&rhs.3));
+
+                if let Some((index, u, v, _)) = best {
+                    let (map_x, map_y) = &mut maps[index];
+                    *map_x.at_2d_mut::<f32>(row, col)? = u as f32;
+                    *map_y.at_2d_mut::<f32>(row, col)? = v as f32;
+                    *masks[index].at_2d_mut::<u8>(row, col)? = 255;
+                }
+            }
+        }
+
+        // Warp each image and paste the chosen pixels
+        let mut panorama =
+            Mat::new_rows_cols_with_default(height, width, core::CV_8UC3, Scalar::all(0.0))?;
+        for ((image, (map_x, map_y)), mask) in images.iter().zip(&maps).zip(&masks) {
+            let mut warped = Mat::default();
+            imgproc::remap(
+                image,
+                &mut warped,
+                map_x,
+                map_y,
+                imgproc::INTER_LINEAR,
+                core::BORDER_CONSTANT,
+                Scalar::all(0.0),
+            )?;
+            warped.copy_to_masked(&mut panorama, mask)?;
+        }
+
+        Ok(Some(panorama))
+    }
+}
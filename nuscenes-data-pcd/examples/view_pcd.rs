@@ -48,7 +48,7 @@ fn main() -> Result<()> {
             Err(err) => {
                 eprintln!(
                     "Unable to load the file {} for sample data {}: {err}",
-                    record.path().display(),
+                    record.path(),
                     record.token
                 );
                 vec![]
@@ -127,7 +127,7 @@ impl State for Gui {
                 Err(err) => {
                     eprintln!(
                         "Unable to load the file {} for sample data {}: {err}",
-                        record.path().display(),
+                        record.path(),
                         record.token
                     );
                     vec![]
@@ -153,7 +153,7 @@ fn load_pcd(record: &SampleDataRef) -> Result<Vec<na::Point3<f32>>> {
             .into_iter()
             .map(|p| na::Point3::new(p.x, p.y, p.z))
             .collect(),
-        PointCloud::NotSupported => bail!("file format not supported"),
+        PointCloud::NotSupported(reason) => bail!(reason),
     };
     Ok(points)
 }
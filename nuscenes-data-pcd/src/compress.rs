@@ -0,0 +1,239 @@
+//! Compression of lidar `.bin` files with zstd.
+//!
+//! [compress_lidar_bins] replaces each `.pcd.bin` file of the dataset
+//! with a `.pcd.bin.zst` file next to it, while the metadata keeps
+//! referring to the original file names. The point cloud loader of
+//! this crate falls back to the compressed file when the original is
+//! missing. Wrap the storage with [ZstdStorage] to make other readers,
+//! such as `load_lidar_bin_points()` and file checks, aware of
+//! compressed files too.
+//!
+//! Files are read through the storage of the dataset, while the
+//! transcoded files are written to the local file system. Transcoding
+//! therefore requires a [local](Storage::is_local) storage.
+
+use anyhow::{ensure, Result};
+use nuscenes_data::{
+    dataset::{Dataset, SampleDataRef},
+    storage::{read_data_with_options, DataPath, ReadOptions, Storage},
+};
+use std::{
+    ffi::OsString,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// The extension appended to compressed files.
+pub const COMPRESSED_EXTENSION: &str = "zst";
+
+/// The default zstd compression level.
+pub const DEFAULT_COMPRESSION_LEVEL: i32 = 9;
+
+/// Get the path of the compressed file, such as `a.pcd.bin.zst` for
+/// `a.pcd.bin`.
+pub fn compressed_path(path: &Path) -> PathBuf {
+    let mut path = OsString::from(path);
+    path.push(".");
+    path.push(COMPRESSED_EXTENSION);
+    PathBuf::from(path)
+}
+
+/// The file sizes before and after transcoding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CompressionStats {
+    pub num_files: usize,
+    pub original_bytes: u64,
+    pub compressed_bytes: u64,
+}
+
+impl CompressionStats {
+    /// Get the compressed size relative to the original size. It
+    /// returns `None` if no bytes are transcoded.
+    pub fn ratio(&self) -> Option<f64> {
+        (self.original_bytes > 0).then(|| self.compressed_bytes as f64 / self.original_bytes as f64)
+    }
+}
+
+/// Compress the lidar `.bin` files of the dataset in place. Each file
+/// is replaced by its compressed file only after the compressed file
+/// is completely written. Files already compressed are skipped.
+pub fn compress_lidar_bins(dataset: &Dataset, level: i32) -> Result<CompressionStats> {
+    ensure_local(dataset)?;
+    let mut stats = CompressionStats::default();
+
+    for data in dataset.sample_data_iter().filter(is_lidar_bin) {
+        let path = data.path().to_path_buf();
+        if !path.is_file() {
+            continue;
+        }
+
+        let bytes = data.read_bytes()?;
+        let compressed = zstd::encode_all(bytes.as_slice(), level)?;
+        write_atomic(&compressed_path(&path), &compressed)?;
+        fs::remove_file(&path)?;
+
+        stats.num_files += 1;
+        stats.original_bytes += bytes.len() as u64;
+        stats.compressed_bytes += compressed.len() as u64;
+    }
+
+    Ok(stats)
+}
+
+/// Restore the lidar `.bin` files compressed by
+/// [compress_lidar_bins].
+pub fn decompress_lidar_bins(dataset: &Dataset) -> Result<CompressionStats> {
+    ensure_local(dataset)?;
+    let mut stats = CompressionStats::default();
+
+    for data in dataset.sample_data_iter().filter(is_lidar_bin) {
+        let path = data.path().to_path_buf();
+        let src = compressed_path(&path);
+        if path.is_file() || !src.is_file() {
+            continue;
+        }
+
+        let relative = compressed_path(&data.filename);
+        let src_path = DataPath::new(&dataset.dataset_dir, &relative);
+        let compressed = dataset.storage.read_data(src_path)?;
+        let bytes = zstd::decode_all(compressed.as_slice())?;
+        write_atomic(&path, &bytes)?;
+        fs::remove_file(&src)?;
+
+        stats.num_files += 1;
+        stats.original_bytes += bytes.len() as u64;
+        stats.compressed_bytes += compressed.len() as u64;
+    }
+
+    Ok(stats)
+}
+
+/// A storage decorator reading compressed files in place of missing
+/// files.
+#[derive(Debug)]
+pub struct ZstdStorage<S> {
+    inner: S,
+}
+
+impl<S> ZstdStorage<S>
+where
+    S: Storage,
+{
+    pub fn new(inner: S) -> Self {
+        Self { inner }
+    }
+
+    pub fn inner(&self) -> &S {
+        &self.inner
+    }
+}
+
+impl<S> Storage for ZstdStorage<S>
+where
+    S: Storage,
+{
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        match self.inner.read(path) {
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                let compressed = self.inner.read(&compressed_path(path)).map_err(|_| err)?;
+                zstd::decode_all(compressed.as_slice())
+            }
+            result => result,
+        }
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        self.inner.exists(path) || self.inner.exists(&compressed_path(path))
+    }
+
+    fn is_local(&self) -> bool {
+        self.inner.is_local()
+    }
+
+    /// Get the size of the file, or of the compressed file if the file
+    /// is missing.
+    fn size(&self, path: &Path) -> io::Result<u64> {
+        match self.inner.size(path) {
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                self.inner.size(&compressed_path(path)).map_err(|_| err)
+            }
+            result => result,
+        }
+    }
+
+    fn read_data(&self, path: DataPath<'_>) -> io::Result<Vec<u8>> {
+        match self.inner.read_data(path) {
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                let relative = compressed_path(path.relative());
+                let compressed = self
+                    .inner
+                    .read_data(DataPath::new(path.root(), &relative))
+                    .map_err(|_| err)?;
+                zstd::decode_all(compressed.as_slice())
+            }
+            result => result,
+        }
+    }
+
+    fn data_exists(&self, path: DataPath<'_>) -> bool {
+        let relative = compressed_path(path.relative());
+        self.inner.data_exists(path)
+            || self
+                .inner
+                .data_exists(DataPath::new(path.root(), &relative))
+    }
+
+    fn data_size(&self, path: DataPath<'_>) -> io::Result<u64> {
+        match self.inner.data_size(path) {
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                let relative = compressed_path(path.relative());
+                self.inner
+                    .data_size(DataPath::new(path.root(), &relative))
+                    .map_err(|_| err)
+            }
+            result => result,
+        }
+    }
+}
+
+/// Read a sample data file, falling back to the compressed file if
+/// the file is missing.
+pub(crate) fn read_bytes_or_compressed(
+    data: &SampleDataRef,
+    options: &ReadOptions,
+) -> io::Result<Vec<u8>> {
+    match data.read_bytes_with_options(options) {
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {
+            let dataset = data.dataset();
+            let relative = compressed_path(&data.filename);
+            let path = DataPath::new(&dataset.dataset_dir, &relative);
+            let compressed =
+                read_data_with_options(&dataset.storage, path, options).map_err(|_| err)?;
+            zstd::decode_all(compressed.as_slice())
+        }
+        result => result,
+    }
+}
+
+/// Check that the transcoded files can be written next to the files
+/// read from the storage.
+fn ensure_local(dataset: &Dataset) -> Result<()> {
+    ensure!(
+        dataset.storage.is_local(),
+        "lidar files can only be transcoded in place on a local storage"
+    );
+    Ok(())
+}
+
+fn is_lidar_bin(data: &SampleDataRef) -> bool {
+    data.filename.extension().is_some_and(|ext| ext == "bin")
+}
+
+/// Write to a temporary file and rename it, so that an interrupted
+/// write does not leave a truncated file.
+fn write_atomic(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    let mut tmp = OsString::from(path);
+    tmp.push(".tmp");
+    fs::write(&tmp, bytes)?;
+    fs::rename(&tmp, path)
+}
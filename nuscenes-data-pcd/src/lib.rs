@@ -1,22 +1,29 @@
-use anyhow::{ensure, Result};
-use nuscenes_data::{dataset::SampleDataRef, serializable::FileFormat};
+use anyhow::{bail, ensure, Result};
+use nuscenes_data::{
+    data_loader::{self, Payload, SampleDataLoader},
+    dataset::SampleDataRef,
+    error::{Error, UnsupportedFormat},
+    pointcloud::{self as core_pcd, PointAttribute},
+    serializable::{FileFormat, SampleData},
+    storage::ReadOptions,
+};
 use pcd_rs::{PcdDeserialize, PcdSerialize};
 use raw_parts::RawParts;
-use std::{
-    fs::File,
-    io::{prelude::*, BufReader},
-    mem,
-};
+use std::mem;
+
+pub mod compress;
+pub mod point_counts;
 
 pub mod prelude {
-    pub use super::SampleDataRefPcdExt;
+    pub use super::{point_counts::DatasetPointCountExt, SampleDataRefPcdExt};
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum PointCloud {
     Pcd(Vec<PcdPoint>),
     Bin(Vec<BinPoint>),
-    NotSupported,
+    /// The file is not a point cloud file.
+    NotSupported(UnsupportedFormat),
 }
 
 #[derive(Debug, Clone, PartialEq, PcdSerialize, PcdDeserialize)]
@@ -51,33 +58,106 @@ pub struct BinPoint {
     pub ring_index: i32,
 }
 
+impl TryFrom<PointCloud> for core_pcd::PointCloud {
+    type Error = anyhow::Error;
+
+    fn try_from(from: PointCloud) -> Result<Self> {
+        let pcd = match from {
+            PointCloud::Pcd(points) => {
+                let positions = points.iter().map(|p| [p.x, p.y, p.z]).collect();
+                let mut pcd = core_pcd::PointCloud::new(positions);
+
+                macro_rules! insert {
+                    ($field:ident, $variant:ident) => {
+                        pcd.insert_attribute(
+                            stringify!($field),
+                            PointAttribute::$variant(points.iter().map(|p| p.$field).collect()),
+                        )?;
+                    };
+                }
+
+                insert!(dyn_prop, I8);
+                insert!(id, I16);
+                insert!(rcs, F32);
+                insert!(vx, F32);
+                insert!(vy, F32);
+                insert!(vx_comp, F32);
+                insert!(vy_comp, F32);
+                insert!(is_quality_valid, I8);
+                insert!(ambig_state, I8);
+                insert!(x_rms, I8);
+                insert!(y_rms, I8);
+                insert!(invalid_state, I8);
+                insert!(pdh0, I8);
+                insert!(vx_rms, I8);
+                insert!(vy_rms, I8);
+                pcd
+            }
+            PointCloud::Bin(points) => {
+                let positions = points.iter().map(|p| [p.x, p.y, p.z]).collect();
+                let intensities = points.iter().map(|p| p.intensity).collect();
+                let ring_indices = points.iter().map(|p| p.ring_index).collect();
+
+                let mut pcd = core_pcd::PointCloud::new(positions);
+                pcd.insert_attribute(core_pcd::INTENSITY, PointAttribute::F32(intensities))?;
+                pcd.insert_attribute(core_pcd::RING_INDEX, PointAttribute::I32(ring_indices))?;
+                pcd
+            }
+            PointCloud::NotSupported(reason) => bail!(reason),
+        };
+        Ok(pcd)
+    }
+}
+
 pub trait SampleDataRefPcdExt {
     fn load_pcd(&self) -> Result<PointCloud>;
+
+    /// Load the file with custom retry and timeout options.
+    fn load_pcd_with_options(&self, options: &ReadOptions) -> Result<PointCloud>;
+
+    /// Load the file as a modality-independent point cloud. It returns
+    /// `None` if the file format is not supported.
+    fn load_point_cloud(&self) -> Result<Option<core_pcd::PointCloud>>;
+
+    fn load_point_cloud_with_options(
+        &self,
+        options: &ReadOptions,
+    ) -> Result<Option<core_pcd::PointCloud>>;
 }
 
 impl SampleDataRefPcdExt for SampleDataRef {
     fn load_pcd(&self) -> Result<PointCloud> {
+        self.load_pcd_with_options(&self.dataset().read_options)
+    }
+
+    fn load_pcd_with_options(&self, options: &ReadOptions) -> Result<PointCloud> {
         if self.fileformat != FileFormat::Pcd {
-            return Ok(PointCloud::NotSupported);
+            return Ok(PointCloud::NotSupported(UnsupportedFormat::of_sample_data(
+                self,
+                "pcd or bin",
+            )));
         }
 
         let Some(ext) = self.filename.extension() else {
-            return Ok(PointCloud::NotSupported)
+            return Ok(PointCloud::NotSupported(UnsupportedFormat::of_sample_data(
+                self,
+                "pcd or bin",
+            )));
         };
         let path = self.path();
 
         let pcd = if ext == "pcd" {
-            let reader = pcd_rs::Reader::open(path)?;
+            let bytes = self.read_bytes_with_options(options)?;
+            let reader = pcd_rs::Reader::from_bytes(&bytes)?;
             let points: Result<Vec<_>> = reader.collect();
             PointCloud::Pcd(points?)
         } else if ext == "bin" {
             let point_len = mem::size_of::<BinPoint>();
 
             let buf = {
-                let mut reader = BufReader::new(File::open(&path)?);
-                let mut buf = vec![];
-                let buf_len = reader.read_to_end(&mut buf)?;
-                ensure!(buf_len % point_len == 0, "Unable to load this file {}. The file size is {buf_len}, which is not multiple of {point_len}", path.display());
+                let buf = compress::read_bytes_or_compressed(self, options)?;
+                let buf_len = buf.len();
+                ensure!(buf_len % point_len == 0, "Unable to load this file {}. The file size is {buf_len}, which is not multiple of {point_len}", path);
                 buf
             };
 
@@ -104,9 +184,54 @@ impl SampleDataRefPcdExt for SampleDataRef {
 
             PointCloud::Bin(points)
         } else {
-            PointCloud::NotSupported
+            PointCloud::NotSupported(UnsupportedFormat::of_sample_data(self, "pcd or bin"))
         };
 
         Ok(pcd)
     }
+
+    fn load_point_cloud(&self) -> Result<Option<core_pcd::PointCloud>> {
+        self.load_point_cloud_with_options(&self.dataset().read_options)
+    }
+
+    fn load_point_cloud_with_options(
+        &self,
+        options: &ReadOptions,
+    ) -> Result<Option<core_pcd::PointCloud>> {
+        let pcd = match self.load_pcd_with_options(options)? {
+            PointCloud::NotSupported(_) => return Ok(None),
+            pcd => pcd.try_into()?,
+        };
+        Ok(Some(pcd))
+    }
+}
+
+/// Load radar `.pcd` files as modality-independent point clouds.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PcdLoader;
+
+impl SampleDataLoader for PcdLoader {
+    fn name(&self) -> &str {
+        "pcd"
+    }
+
+    fn supports(&self, data: &SampleData) -> bool {
+        data.fileformat == FileFormat::Pcd
+            && data.filename.extension().is_some_and(|ext| ext == "pcd")
+    }
+
+    fn load(&self, data: &SampleDataRef) -> nuscenes_data::error::Result<Payload> {
+        let parse_error = |err: anyhow::Error| Error::ParseError(format!("{err:#}"));
+        let pcd: core_pcd::PointCloud = match data.load_pcd().map_err(parse_error)? {
+            PointCloud::NotSupported(reason) => return Err(reason.into()),
+            pcd => pcd.try_into().map_err(parse_error)?,
+        };
+        Ok(Payload::new(self.name(), pcd))
+    }
+}
+
+/// Register the loaders of this crate to
+/// [SampleDataRef::load_any](nuscenes_data::dataset::SampleDataRef::load_any).
+pub fn register_loaders() {
+    data_loader::register_loader(PcdLoader);
 }
@@ -0,0 +1,84 @@
+//! Recomputation of the point counts of annotations.
+//!
+//! Following the devkit, `num_lidar_pts` counts the points of the key
+//! frame lidar sweep inside the annotation box, and `num_radar_pts`
+//! counts the points of the key frames of all radars.
+
+use crate::SampleDataRefPcdExt;
+use anyhow::Result;
+use nuscenes_data::{
+    dataset::{Dataset, SampleRef},
+    geometry::{Box3D, Transform},
+    serializable::SampleAnnotation,
+};
+use rayon::prelude::*;
+
+pub trait DatasetPointCountExt {
+    /// Count the lidar and radar points inside every annotation box
+    /// in parallel. It returns the annotation records with
+    /// `num_lidar_pts` and `num_radar_pts` replaced, which can be
+    /// written back to `sample_annotation.json`.
+    fn recompute_point_counts(&self) -> Result<Vec<SampleAnnotation>>;
+}
+
+impl DatasetPointCountExt for Dataset {
+    fn recompute_point_counts(&self) -> Result<Vec<SampleAnnotation>> {
+        let samples: Vec<_> = self.sample_iter().collect();
+        let annotations: Vec<Vec<SampleAnnotation>> = samples
+            .par_iter()
+            .map(count_sample_points)
+            .collect::<Result<_>>()?;
+        Ok(annotations.into_iter().flatten().collect())
+    }
+}
+
+fn count_sample_points(sample: &SampleRef) -> Result<Vec<SampleAnnotation>> {
+    let mut annotations: Vec<SampleAnnotation> = sample
+        .annotation_iter()
+        .map(|annotation| {
+            let mut annotation = (*annotation).clone();
+            annotation.num_lidar_pts = 0;
+            annotation.num_radar_pts = 0;
+            annotation
+        })
+        .collect();
+
+    for data in sample.keyframe_data_iter() {
+        if !data.is_lidar() && !data.is_radar() {
+            continue;
+        }
+        let Some(pcd) = data.load_point_cloud()? else {
+            continue;
+        };
+
+        let sensor_to_global = data.sensor_to_global();
+        for annotation in &mut annotations {
+            let count = count_points_in_box(&pcd.positions, &sensor_to_global, annotation);
+            if data.is_lidar() {
+                annotation.num_lidar_pts += count as isize;
+            } else {
+                annotation.num_radar_pts += count as isize;
+            }
+        }
+    }
+
+    Ok(annotations)
+}
+
+fn count_points_in_box(
+    positions: &[[f32; 3]],
+    sensor_to_global: &Transform,
+    annotation: &SampleAnnotation,
+) -> usize {
+    let bbox = Box3D::from(annotation);
+    let sensor_to_box = bbox.to_transform().inverse().compose(sensor_to_global);
+    let [width, length, height] = bbox.size;
+
+    positions
+        .iter()
+        .filter(|&&[x, y, z]| {
+            let [x, y, z] = sensor_to_box.apply([x as f64, y as f64, z as f64]);
+            x.abs() <= length / 2.0 && y.abs() <= width / 2.0 && z.abs() <= height / 2.0
+        })
+        .count()
+}
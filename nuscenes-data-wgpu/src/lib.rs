@@ -0,0 +1,197 @@
+//! Upload point clouds and images to GPU buffers and textures.
+//!
+//! Points are uploaded as `array<vec4<f32>>` storing (x, y, z,
+//! intensity), whose stride is [POINT_STRIDE] bytes. Images are
+//! uploaded as [IMAGE_FORMAT] textures, since wgpu has no 3-channel
+//! formats.
+
+use image::DynamicImage;
+use nuscenes_data::{
+    dataset::SampleDataRef,
+    error::{Error, Result},
+    pointcloud::{LidarBinPoint, PointCloud, INTENSITY},
+};
+use nuscenes_data_image::SampleDataRefImageExt;
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+
+pub use wgpu;
+
+pub mod prelude {
+    pub use super::SampleDataRefWgpuExt;
+}
+
+/// The size in bytes of an uploaded point.
+pub const POINT_STRIDE: u64 = 16;
+
+/// The texture format of uploaded images.
+pub const IMAGE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+/// A point buffer on the GPU.
+#[derive(Debug)]
+pub struct GpuPointCloud {
+    pub buffer: wgpu::Buffer,
+    pub num_points: u32,
+}
+
+/// Upload the points of a point cloud. The intensity is set to zero
+/// if the point cloud has no intensity attribute.
+pub fn upload_point_cloud(
+    device: &wgpu::Device,
+    pcd: &PointCloud,
+    usage: wgpu::BufferUsages,
+) -> GpuPointCloud {
+    let intensity = pcd.attribute(INTENSITY);
+    let points: Vec<[f32; 4]> = pcd
+        .positions
+        .iter()
+        .enumerate()
+        .map(|(index, &[x, y, z])| {
+            let value = intensity
+                .and_then(|values| values.get_f64(index))
+                .unwrap_or(0.0);
+            [x, y, z, value as f32]
+        })
+        .collect();
+    upload_points(device, &points, usage)
+}
+
+/// Upload raw points of a lidar `.bin` file. The ring indices are
+/// dropped.
+pub fn upload_lidar_bin_points(
+    device: &wgpu::Device,
+    points: &[LidarBinPoint],
+    usage: wgpu::BufferUsages,
+) -> GpuPointCloud {
+    let points: Vec<[f32; 4]> = points
+        .iter()
+        .map(|&[x, y, z, intensity, _]| [x, y, z, intensity])
+        .collect();
+    upload_points(device, &points, usage)
+}
+
+/// Upload a per-point attribute as `array<f32>`.
+pub fn upload_attribute(
+    device: &wgpu::Device,
+    pcd: &PointCloud,
+    name: &str,
+    usage: wgpu::BufferUsages,
+) -> Option<wgpu::Buffer> {
+    let attribute = pcd.attribute(name)?;
+    let values: Vec<f32> = (0..attribute.len())
+        .map(|index| attribute.get_f64(index).unwrap() as f32)
+        .collect();
+
+    let buffer = device.create_buffer_init(&BufferInitDescriptor {
+        label: Some(name),
+        contents: bytemuck::cast_slice(&values),
+        usage,
+    });
+    Some(buffer)
+}
+
+/// Upload an image as a 2D texture with a single mip level.
+pub fn upload_image(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    image: &DynamicImage,
+    usage: wgpu::TextureUsages,
+) -> wgpu::Texture {
+    let rgba = image.to_rgba8();
+    let (width, height) = rgba.dimensions();
+    let size = wgpu::Extent3d {
+        width,
+        height,
+        depth_or_array_layers: 1,
+    };
+
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("nuscenes image"),
+        size,
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: IMAGE_FORMAT,
+        usage: usage | wgpu::TextureUsages::COPY_DST,
+        view_formats: &[],
+    });
+    queue.write_texture(
+        wgpu::ImageCopyTexture {
+            texture: &texture,
+            mip_level: 0,
+            origin: wgpu::Origin3d::ZERO,
+            aspect: wgpu::TextureAspect::All,
+        },
+        &rgba,
+        wgpu::ImageDataLayout {
+            offset: 0,
+            bytes_per_row: Some(4 * width),
+            rows_per_image: Some(height),
+        },
+        size,
+    );
+    texture
+}
+
+pub trait SampleDataRefWgpuExt {
+    /// Load a lidar `.bin` file into a point buffer. It returns
+    /// `None` for other files.
+    fn load_gpu_points(
+        &self,
+        device: &wgpu::Device,
+        usage: wgpu::BufferUsages,
+    ) -> Result<Option<GpuPointCloud>>;
+
+    /// Load the image into a texture. It returns `None` if the sample
+    /// data is not an image.
+    fn load_gpu_texture(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        usage: wgpu::TextureUsages,
+    ) -> Result<Option<wgpu::Texture>>;
+}
+
+impl SampleDataRefWgpuExt for SampleDataRef {
+    fn load_gpu_points(
+        &self,
+        device: &wgpu::Device,
+        usage: wgpu::BufferUsages,
+    ) -> Result<Option<GpuPointCloud>> {
+        let is_bin = self.filename.extension().is_some_and(|ext| ext == "bin");
+        if !is_bin {
+            return Ok(None);
+        }
+        let points = self.load_lidar_bin_points()?;
+        Ok(Some(upload_lidar_bin_points(device, &points, usage)))
+    }
+
+    fn load_gpu_texture(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        usage: wgpu::TextureUsages,
+    ) -> Result<Option<wgpu::Texture>> {
+        let image = match self.load_dynamic_image() {
+            Ok(image) => image,
+            Err(Error::UnsupportedFormat(_)) => return Ok(None),
+            Err(err) => return Err(err),
+        };
+        Ok(Some(upload_image(device, queue, &image, usage)))
+    }
+}
+
+fn upload_points(
+    device: &wgpu::Device,
+    points: &[[f32; 4]],
+    usage: wgpu::BufferUsages,
+) -> GpuPointCloud {
+    let buffer = device.create_buffer_init(&BufferInitDescriptor {
+        label: Some("nuscenes points"),
+        contents: bytemuck::cast_slice(points),
+        usage,
+    });
+    GpuPointCloud {
+        buffer,
+        num_points: points.len() as u32,
+    }
+}
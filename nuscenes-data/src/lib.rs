@@ -12,64 +12,87 @@
 //! dataset version is "v1.0-trainval" in this example. You should
 //! able to find the "/path/to/dataset/v1.0-trainval" directory.
 //!
-//! ```ignore
+//! ```no_run
 //! use nuscenes_data::Dataset;
 //!
 //! let dataset = Dataset::load("v1.0-trainval", "/path/to/dataset")?;
+//! # Ok::<(), nuscenes_data::error::Error>(())
 //! ```
 //!
+//! The examples below run against a tiny dataset embedded in the
+//! crate, which is available with the `test-fixtures` feature.
+//!
 //! ## Traverse Scenes and Samples in the Dataset
 //!
 //! The dataset contains many scenes. Use `dataset.scene_iter()` to
 //! iterate over scenes in the dataset. Scenes contain samples. Use
 //! `scene.sample_iter()` to iterate them.
 //!
-//! ```ignore
-//! for scene dataset.scene_iter() {
+//! ```
+//! # #[cfg(feature = "test-fixtures")]
+//! # {
+//! # let dataset = nuscenes_data::fixtures::load()?;
+//! for scene in dataset.scene_iter() {
 //!     for sample in scene.sample_iter() {
-//!         for annotation in sample.annotatoin_iter() { /* omit */ }
+//!         for annotation in sample.annotation_iter() { /* omit */ }
 //!         for data in sample.sample_data_iter() { /* omit */ }
 //!     }
 //! }
+//! # }
+//! # Ok::<(), nuscenes_data::error::Error>(())
 //! ```
 //!
 //! ## Look-up Samples using Tokens
 //!
 //! It supports data query using tokens. The usage is straightforward.
 //!
-//! ```ignore
-//! let sample_token = Token::from_str("24fa5b014824491f9bd2f9e4a1a1b5b8").unwrap();
+//! ```
+//! # #[cfg(feature = "test-fixtures")]
+//! # {
+//! # let dataset = nuscenes_data::fixtures::load()?;
+//! use nuscenes_data::Token;
+//! use std::str::FromStr;
+//!
+//! let sample_token = Token::from_str("dc8408b2861e12618292b58dfa4fb551").unwrap();
 //! let sample = dataset.sample(sample_token).unwrap();
 //!
-//! let sensor_token = Token::from_str("1f69f87a4e175e5ba1d03e2e6d9bcd27").unwrap();
+//! let sensor_token = Token::from_str("42a88be181aee8126b6d68310b2ecd01").unwrap();
 //! let sensor = dataset.sensor(sensor_token).unwrap();
+//! # }
+//! # Ok::<(), nuscenes_data::error::Error>(())
 //! ```
 //!
 //! ## Associated Data Query
 //!
 //! It's easy to search to associated data in the dataset.
 //!
-//! ```ignore
-//! let scene = dataset.scene_iter().first().unwrap();
-//! let sample = scene.sample_iter().first().unwrap();
-//! let data = sample.sample_data_iter().first().unwrap();
+//! ```
+//! # #[cfg(feature = "test-fixtures")]
+//! # {
+//! # let dataset = nuscenes_data::fixtures::load()?;
+//! let scene = dataset.scene_iter().next().unwrap();
+//! let sample = scene.sample_iter().next().unwrap();
+//! let data = sample.sample_data_iter().next().unwrap();
 //! let ego_pose = data.ego_pose();
 //! let calibrated_sensor = data.calibrated_sensor();
+//! # }
+//! # Ok::<(), nuscenes_data::error::Error>(())
 //! ```
 //!
 //! ## Integration with [nalgebra](https://docs.rs/nalgebra)
 //!
-//! Add this extension crate to enable [nalgebra](https://docs.rs/nalgebra) support.
+//! Enable the `nalgebra` feature to enable
+//! [nalgebra](https://docs.rs/nalgebra) support.
 //!
 //! ```sh
-//! cargo add nuscenes-data-nalgebra
+//! cargo add nuscenes-data --features nalgebra
 //! ```
 //!
 //! It add extra methods to existing types. This example obtains an
 //! nalgebra transformation from an ego\_pose object.
 //!
 //! ```ignore
-//! use nuscenes_data_nalgebra::prelude::*;
+//! use nuscenes_data::prelude::*;
 //! use nalgebra as na;
 //!
 //! let ego_pose = dataset.ego_pose(token).unwrap();
@@ -83,43 +106,137 @@
 //!
 //! This crate supports integration with
 //! [opencv](https://docs.rs/opencv), [image](https://docs.rs/image)
-//! and [pcd-rs](https://docs.rs/pcd-rs) crates. Add these extension
-//! crates to enable this.
+//! and [pcd-rs](https://docs.rs/pcd-rs) crates. Enable the `opencv`,
+//! `image` and `pcd` features respectively to enable this.
 //!
 //! ```sh
-//! cargo add nuscenes-data-opencv
-//! cargo add nuscenes-data-image
-//! cargo add nuscenes-data-pcd
+//! cargo add nuscenes-data --features image,opencv,pcd
 //! ```
 //!
-//! It adds data loading methods on sample data objects.
+//! It adds data loading methods on sample data objects. All extension
+//! traits are imported by `nuscenes_data::prelude::*`.
 //!
 //! ```ignore
+//! use nuscenes_data::prelude::*;
+//!
 //! // image
-//! use nuscenes_data_image::prelude::*;
 //! let image_sample = dataset.sample_data(token).unwrap();
-//! let image: image::DynamicImage = image_sample.load_dynamic_image()?.unwrap();
+//! let image: image::DynamicImage = image_sample.load_dynamic_image()?;
 //!
 //! // opencv
-//! use nuscenes_data_opencv::prelude::*;
 //! let image_sample = dataset.sample_data(token).unwrap();
-//! let image: opencv::core::Mat = image_sample.load_opencv_mat()?.unwrap();
+//! let image: opencv::core::Mat = image_sample.load_opencv_mat()?;
 //!
 //! // pcd-rs
-//! use nuscenes_data_pcd::{prelude::*, PointCloud};
+//! use nuscenes_data::pcd::PointCloud;
 //! let pcd_sample = dataset.sample_data(token).unwrap();
-//! let pcd: PointCloud = pcd_sample.load_pcd()?.unwrap();
+//! let pcd: PointCloud = pcd_sample.load_pcd()?;
 //! match pcd {
 //!     PointCloud::Pcd(points) => { /* Loaded from a .pcd file */ }
 //!     PointCloud::Bin(points) => { /* Loaded from a .bin file */  }
-//!     PointCloud::NotSupported => {}
+//!     PointCloud::NotSupported(reason) => { /* Not a point cloud file */ }
+//! }
+//! ```
+//!
+//! Alternatively, [register_loaders] registers the loaders of enabled
+//! features, so that `load_any()` decodes any supported file.
+//!
+//! ```ignore
+//! nuscenes_data::register_loaders();
+//! let payload = sample_data.load_any()?;
+//! ```
+//!
+//! ## Upload to GPU
+//!
+//! Enable the `wgpu` feature to upload point clouds and images into
+//! [wgpu](https://docs.rs/wgpu) buffers and textures.
+//!
+//! ```ignore
+//! use nuscenes_data::prelude::*;
+//! use nuscenes_data::wgpu::wgpu::{BufferUsages, TextureUsages};
+//!
+//! let points = lidar_sample.load_gpu_points(&device, BufferUsages::STORAGE)?.unwrap();
+//! let texture = camera_sample
+//!     .load_gpu_texture(&device, &queue, TextureUsages::TEXTURE_BINDING)?
+//!     .unwrap();
+//! ```
+//!
+//! ## Pack scenes into HDF5 files
+//!
+//! Enable the `hdf5` feature to write each scene into a single HDF5
+//! file. It requires the HDF5 library installed on the system.
+//!
+//! ```ignore
+//! use nuscenes_data::prelude::*;
+//!
+//! scene.export_hdf5(format!("{}.h5", scene.name))?;
+//! ```
+//!
+//! ## Query the map expansion
+//!
+//! Enable the `map` feature to load the map expansion of a map
+//! location, which is installed under `maps/expansion` in the dataset
+//! directory.
+//!
+//! ```ignore
+//! use nuscenes_data::prelude::*;
+//!
+//! let expansion = map.expansion()?.unwrap();
+//! for (layer, token) in expansion.layers_at_point(x, y) {
+//!     println!("{layer} {token}");
 //! }
+//! let nearby_lanes = expansion.lanes_within_radius(x, y, 5.0);
 //! ```
 
-pub mod dataset;
-pub mod error;
-pub mod loader;
-pub mod serializable;
-pub mod utils;
+pub use nuscenes_data_core::*;
+
+#[cfg(feature = "hdf5")]
+pub use nuscenes_data_hdf5 as hdf5;
+#[cfg(feature = "image")]
+pub use nuscenes_data_image as image;
+#[cfg(feature = "map")]
+pub use nuscenes_data_map as map;
+#[cfg(feature = "nalgebra")]
+pub use nuscenes_data_nalgebra as nalgebra;
+#[cfg(feature = "opencv")]
+pub use nuscenes_data_opencv as opencv;
+#[cfg(feature = "pcd")]
+pub use nuscenes_data_pcd as pcd;
+#[cfg(feature = "wgpu")]
+pub use nuscenes_data_wgpu as wgpu;
+
+/// Extension traits of enabled features.
+pub mod prelude {
+    #[cfg(feature = "hdf5")]
+    pub use nuscenes_data_hdf5::prelude::*;
+    #[cfg(feature = "image")]
+    pub use nuscenes_data_image::prelude::*;
+    #[cfg(feature = "map")]
+    pub use nuscenes_data_map::prelude::*;
+    #[cfg(feature = "nalgebra")]
+    pub use nuscenes_data_nalgebra::prelude::*;
+    #[cfg(feature = "opencv")]
+    pub use nuscenes_data_opencv::{
+        panorama::SampleRefPanoramaExt, MapRefImageExt as MapRefOpencvExt,
+        SampleDataRefImageExt as SampleDataRefOpencvExt,
+    };
+    #[cfg(feature = "pcd")]
+    pub use nuscenes_data_pcd::prelude::*;
+    #[cfg(feature = "wgpu")]
+    pub use nuscenes_data_wgpu::prelude::*;
+}
+
+/// Register the sample data loaders of enabled features. See
+/// [data_loader] for details.
+pub fn register_loaders() {
+    #[cfg(feature = "image")]
+    nuscenes_data_image::register_loaders();
+    #[cfg(feature = "opencv")]
+    nuscenes_data_opencv::register_loaders();
+    #[cfg(feature = "pcd")]
+    nuscenes_data_pcd::register_loaders();
+}
 
-pub use crate::{dataset::Dataset, loader::DatasetLoader, serializable::Token};
+#[cfg(doctest)]
+#[doc = include_str!("../README.md")]
+struct ReadmeDoctests;